@@ -111,18 +111,26 @@ impl Context {
         // Since the array is sorted, this removes all duplicate accounts, which shrinks the array.
         user_accounts.dedup();
 
+        let has_liquidity_metrics = market_state.liquidity_metrics != Pubkey::default();
         let consume_events_instruction = consume_events(
             self.program_id,
             Accounts {
                 orderbook: &market_state.orderbook,
                 market: &self.market,
                 event_queue: &Pubkey::new(&orderbook.event_queue),
+                bids: &Pubkey::new(&orderbook.bids),
+                asks: &Pubkey::new(&orderbook.asks),
+                trade_log: &market_state.trade_log,
                 reward_target: &self.reward_target,
+                liquidity_metrics: has_liquidity_metrics.then(|| &market_state.liquidity_metrics),
+                market_stats: None,
                 user_accounts: &user_accounts,
             },
             consume_events::Params {
                 max_iterations: MAX_ITERATIONS,
                 no_op_err: 1,
+                has_liquidity_metrics_account: has_liquidity_metrics as u64,
+                has_market_stats_account: 0,
             },
         );
 
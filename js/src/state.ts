@@ -2,7 +2,7 @@ import { Connection, PublicKey } from "@solana/web3.js";
 import BN from "bn.js";
 import { deserialize, deserializeUnchecked, Schema } from "borsh";
 
-export const CALLBACK_INFO_LEN = 33;
+export const CALLBACK_INFO_LEN = 64;
 
 export enum AccountTag {
   Initialized = 0,
@@ -57,6 +57,94 @@ pub enum DexError {
     EventQueueMustBeEmpty,
     #[error("Event queue mismatch")]
     EventQueueMismatch,
+    #[error("The post-only order would cross the spread")]
+    PostOnlyWouldCross,
+    #[error("The fill-or-kill order could not be entirely filled")]
+    FillOrKillNotFilled,
+    #[error("The order's expiry timestamp has already passed")]
+    OrderExpired,
+    #[error("The reduce-only order would lock more tokens than the user account holds free")]
+    ReduceOnlyViolation,
+    #[error("A user account cannot be referred by its own owner")]
+    SelfReferral,
+    #[error("The fee referral account is missing or isn't owned by the user account's referrer")]
+    InvalidReferralAccount,
+    #[error("The user account data is too short to hold its header and orders")]
+    CorruptUserAccount,
+    #[error("The user account still holds open orders or unsettled funds")]
+    AccountNotEmpty,
+    #[error("The signer isn't the admin currently nominated for this market")]
+    InvalidPendingAdmin,
+    #[error("The market's current status doesn't allow this operation")]
+    MarketPaused,
+    #[error("The order's limit price isn't a multiple of the market's tick size")]
+    InvalidTickSize,
+    #[error("The order's base quantity isn't a multiple of the market's lot size")]
+    InvalidLotSize,
+    #[error("The provided trade log account doesn't match the market's")]
+    InvalidTradeLogAccount,
+    #[error("The mint uses a Token-2022 extension which isn't supported")]
+    UnsupportedMintExtension,
+    #[error("The provided mint account doesn't match the market's")]
+    InvalidMintAccount,
+    #[error("The order's limit price deviates too much from the oracle price")]
+    PriceOutsideBand,
+    #[error("The provided oracle account doesn't match the market's")]
+    InvalidOracleAccount,
+    #[error("The base and quote mints of a market should differ")]
+    InvalidMarketMints,
+    #[error("The order's quote notional is below the market's minimum")]
+    OrderTooSmall,
+    #[error("The order's taker fee exceeds the specified maximum")]
+    FeeExceedsLimit,
+    #[error("The event queue is full, the market should be cranked")]
+    EventQueueFull,
+    #[error("The order would self-trade and its self-trade behavior aborts the transaction")]
+    SelfTradeAborted,
+    #[error("The user account's free balance is insufficient")]
+    InsufficientFunds,
+    #[error("The provided token accounts alias each other or a market vault")]
+    InvalidAccountAliasing,
+    #[error("The provided liquidity metrics account doesn't match the market's")]
+    InvalidLiquidityMetricsAccount,
+    #[error("The fee recipients are invalid")]
+    InvalidFeeRecipients,
+    #[error("The provided fee recipients account doesn't match the market's")]
+    InvalidFeeRecipientsAccount,
+    #[error("New orders are halted while the oracle price diverges from the market's mid price")]
+    OracleDivergenceHalt,
+    #[error("The user account already has a referrer")]
+    ReferrerAlreadySet,
+    #[error("The referrer registration window of the user account is closed")]
+    ReferrerRegistrationClosed,
+    #[error("The user account's size doesn't match its number of order slots")]
+    InvalidUserAccountSize,
+    #[error("The user account already holds an order with the same id")]
+    DuplicateOrder,
+    #[error("The provided market stats account doesn't match the market")]
+    InvalidMarketStatsAccount,
+    #[error("The market state was written by a newer version of the program")]
+    UnsupportedStateVersion,
+    #[error("The heartbeat of the user account hasn't expired")]
+    HeartbeatNotExpired,
+    #[error("The consumed events weren't popped from the head of the event queue")]
+    EventQueueOutOfOrder,
+    #[error("The wallet isn't allowed to trade this permissioned market")]
+    Unauthorized,
+    #[error("The market creation fee should be paid to the program's treasury")]
+    MarketCreationFeeNotPaid,
+    #[error("The user account has reached the market's limit of resting orders per account")]
+    TooManyOrders,
+    #[error("The tokens released from the user account exceed its locked balance")]
+    LockedBalanceUnderflow,
+    #[error("The accumulated fees can't cover the maker rebates")]
+    InsufficientFeeReserve,
+    #[error("The limit price exceeds the market's maximum price")]
+    PriceTooLarge,
+    #[error("The order's callback information has a layout which this version of the program doesn't support")]
+    UnsupportedCallbackInfoVersion,
+    #[error("The provided program config account isn't the one derived from the program id")]
+    InvalidProgramConfigAccount,
 }
 
 impl From<DexError> for ProgramError {
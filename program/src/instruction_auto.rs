@@ -1,8 +1,15 @@
 #![allow(clippy::too_many_arguments)]
 use crate::processor::close_account;
 pub use crate::processor::{
-    cancel_order, close_market, consume_events, create_market, initialize_account, new_order,
-    settle, swap, sweep_fees, update_royalties,
+    accept_admin, cancel_all, cancel_order, cancel_stale_account, close_market, consume_events,
+    consume_events_for_user, create_account, create_market, create_market_stats,
+    create_market_with_orderbook, deposit, extract_fees, get_depth, grant_access, grow_account,
+    heartbeat, initialize_account, migrate_account, migrate_market, new_order, new_order_batch,
+    nominate_admin, prune_expired, reconcile, reduce_order, register_referrer, replace_order,
+    reset_liquidity_metrics, revoke_access, revoke_delegate, set_access_authority, set_delegate,
+    set_fee_bps, set_market_status, set_oracle, set_program_config, set_rebate_boost, settle,
+    settle_multi, simulate_order, swap, sweep_fees, update_fee_schedule, update_market_params,
+    update_royalties, withdraw,
 };
 use bonfida_utils::InstructionsAccount;
 use num_derive::{FromPrimitive, ToPrimitive};
@@ -12,91 +19,118 @@ use solana_program::{instruction::Instruction, pubkey::Pubkey};
 pub enum DexInstruction {
     /// Creates a new DEX market
     ///
-    /// | Index | Writable | Signer | Description                 |
-    /// | ------------------------------------------------------- |
-    /// | 0     | ✅        | ❌      | The market account          |
-    /// | 1     | ✅        | ❌      | The orderbook account       |
-    /// | 2     | ❌        | ❌      | The base vault account      |
-    /// | 3     | ❌        | ❌      | The quote vault account     |
-    /// | 4     | ❌        | ❌      | The market admin account    |
-    /// | 5     | ✅        | ❌      | The AOB event queue account |
-    /// | 6     | ✅        | ❌      | The AOB asks account        |
-    /// | 7     | ✅        | ❌      | The AOB bids account        |
-    /// | 8     | ❌        | ❌      | The metaplex token metadata |
+    /// | Index | Writable | Signer | Description                                                                                              |
+    /// | ------------------------------------------------------------------------------------------------------------------------------------ |
+    /// | 0     | ✅        | ❌      | The market account                                                                                       |
+    /// | 1     | ✅        | ❌      | The orderbook account                                                                                    |
+    /// | 2     | ❌        | ❌      | The base vault account                                                                                   |
+    /// | 3     | ❌        | ❌      | The quote vault account                                                                                  |
+    /// | 4     | ❌        | ❌      | The market admin account                                                                                 |
+    /// | 5     | ✅        | ❌      | The AOB event queue account                                                                              |
+    /// | 6     | ✅        | ❌      | The AOB asks account                                                                                     |
+    /// | 7     | ✅        | ❌      | The AOB bids account                                                                                     |
+    /// | 8     | ❌        | ❌      | The metaplex token metadata                                                                              |
+    /// | 9     | ✅        | ❌      | The trade log account which the market's fills will be recorded to                                       |
+    /// | 10    | ❌        | ❌      | The base mint, whose decimals are recorded in the market                                                 |
+    /// | 11    | ❌        | ❌      | The quote mint, whose decimals are recorded in the market                                                |
+    /// | 12    | ❌        | ❌      | The program config account, which sets the market creation fee. No fee is charged while it doesn't exist |
+    /// | 13    | ❌        | ❌      | The system program, which is required when the program charges a market creation fee                     |
+    /// | 14    | ✅        | ✅      | The account which pays the market creation fee, required along with the system program                   |
+    /// | 15    | ✅        | ❌      | The program's treasury, which collects the market creation fee                                           |
     CreateMarket,
-    /// Execute a new order instruction. Supported types include Limit, IOC, FOK, or Post only.
-    ///
-    /// | Index | Writable | Signer | Description                                                                        |
-    /// | -------------------------------------------------------------------------------------------------------------- |
-    /// | 0     | ❌        | ❌      | The SPL token program                                                              |
-    /// | 1     | ❌        | ❌      | The system program                                                                 |
-    /// | 2     | ✅        | ❌      | The DEX market                                                                     |
-    /// | 3     | ✅        | ❌      | The orderbook                                                                      |
-    /// | 4     | ✅        | ❌      | The AOB event queue                                                                |
-    /// | 5     | ✅        | ❌      | The AOB bids shared memory                                                         |
-    /// | 6     | ✅        | ❌      | The AOB asks shared memory                                                         |
-    /// | 7     | ✅        | ❌      | The base token vault                                                               |
-    /// | 8     | ✅        | ❌      | The quote token vault                                                              |
-    /// | 9     | ✅        | ❌      | The DEX user account                                                               |
-    /// | 10    | ✅        | ❌      | The user source token account                                                      |
-    /// | 11    | ✅        | ✅      | The user wallet                                                                    |
-    /// | 12    | ❌        | ❌      | The optional SRM or MSRM discount token account (must be owned by the user wallet) |
-    /// | 13    | ✅        | ❌      | The optional referrer's token account which will receive a 20% cut of the fees     |
+    /// Execute a new order instruction. Supported types include Limit, IOC, FOK, Post only or Market.
+    ///
+    /// | Index | Writable | Signer | Description                                                                                                         |
+    /// | ----------------------------------------------------------------------------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The SPL token program                                                                                               |
+    /// | 1     | ❌        | ❌      | The system program                                                                                                  |
+    /// | 2     | ✅        | ❌      | The DEX market                                                                                                      |
+    /// | 3     | ✅        | ❌      | The orderbook                                                                                                       |
+    /// | 4     | ✅        | ❌      | The AOB event queue                                                                                                 |
+    /// | 5     | ✅        | ❌      | The AOB bids shared memory                                                                                          |
+    /// | 6     | ✅        | ❌      | The AOB asks shared memory                                                                                          |
+    /// | 7     | ✅        | ❌      | The base token vault                                                                                                |
+    /// | 8     | ✅        | ❌      | The quote token vault                                                                                               |
+    /// | 9     | ❌        | ❌      | The DEX market signer, which authorizes the referral fee transfers out of the quote vault                           |
+    /// | 10    | ❌        | ❌      | The base token mint                                                                                                 |
+    /// | 11    | ❌        | ❌      | The quote token mint                                                                                                |
+    /// | 12    | ✅        | ❌      | The DEX user account                                                                                                |
+    /// | 13    | ✅        | ❌      | The user source token account                                                                                       |
+    /// | 14    | ✅        | ✅      | The user wallet, or the user account's delegate                                                                     |
+    /// | 15    | ❌        | ❌      | The optional SRM or MSRM discount token account (must be owned by the user wallet)                                  |
+    /// | 16    | ❌        | ❌      | The market's oracle price account, which is required when the market has an oracle                                  |
+    /// | 17    | ✅        | ❌      | The market's optional liquidity metrics account, which records the user account as a maker when its order is posted |
+    /// | 18    | ✅        | ❌      | The market's optional stats account, which is refreshed once the order is placed                                    |
+    /// | 19    | ❌        | ❌      | The user wallet's access grant, which is required when the market has an access authority                           |
+    /// | 20    | ✅        | ❌      | The optional referrer's token account which will receive its share of the fees                                      |
     NewOrder,
+    /// Swap an exact input amount against the orderbook, with a minimum output amount as slippage protection.
+    ///
+    /// The output is transferred straight to the user's token account and the `SwapOutcome` is emitted as return data.
     ///
-    /// | Index | Writable | Signer | Description                                                                        |
-    /// | -------------------------------------------------------------------------------------------------------------- |
-    /// | 0     | ❌        | ❌      | The SPL token program                                                              |
-    /// | 1     | ❌        | ❌      | The system program                                                                 |
-    /// | 2     | ✅        | ❌      | The DEX market                                                                     |
-    /// | 3     | ✅        | ❌      | The orderbook                                                                      |
-    /// | 4     | ✅        | ❌      | The AOB event queue                                                                |
-    /// | 5     | ✅        | ❌      | The AOB bids shared memory                                                         |
-    /// | 6     | ✅        | ❌      | The AOB asks shared memory                                                         |
-    /// | 7     | ✅        | ❌      | The base token vault                                                               |
-    /// | 8     | ✅        | ❌      | The quote token vault                                                              |
-    /// | 9     | ❌        | ❌      | The DEX market signer                                                              |
-    /// | 10    | ✅        | ❌      | The user base token account                                                        |
-    /// | 11    | ✅        | ❌      | The user quote token account                                                       |
-    /// | 12    | ✅        | ✅      | The user wallet                                                                    |
-    /// | 13    | ❌        | ❌      | The optional SRM or MSRM discount token account (must be owned by the user wallet) |
-    /// | 14    | ✅        | ❌      | The optional referrer's token account which will receive a 20% cut of the fees     |
+    /// | Index | Writable | Signer | Description                                                                               |
+    /// | --------------------------------------------------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The SPL token program                                                                     |
+    /// | 1     | ❌        | ❌      | The system program                                                                        |
+    /// | 2     | ✅        | ❌      | The DEX market                                                                            |
+    /// | 3     | ✅        | ❌      | The orderbook                                                                             |
+    /// | 4     | ✅        | ❌      | The AOB event queue                                                                       |
+    /// | 5     | ✅        | ❌      | The AOB bids shared memory                                                                |
+    /// | 6     | ✅        | ❌      | The AOB asks shared memory                                                                |
+    /// | 7     | ✅        | ❌      | The base token vault                                                                      |
+    /// | 8     | ✅        | ❌      | The quote token vault                                                                     |
+    /// | 9     | ❌        | ❌      | The DEX market signer                                                                     |
+    /// | 10    | ❌        | ❌      | The base token mint                                                                       |
+    /// | 11    | ❌        | ❌      | The quote token mint                                                                      |
+    /// | 12    | ✅        | ❌      | The user base token account                                                               |
+    /// | 13    | ✅        | ❌      | The user quote token account                                                              |
+    /// | 14    | ✅        | ✅      | The user wallet                                                                           |
+    /// | 15    | ❌        | ❌      | The optional SRM or MSRM discount token account (must be owned by the user wallet)        |
+    /// | 16    | ❌        | ❌      | The user wallet's access grant, which is required when the market has an access authority |
+    /// | 17    | ✅        | ❌      | The optional referrer's token account which will receive its share of the fees            |
     Swap,
     /// Cancel an existing order and remove it from the orderbook.
     ///
-    /// | Index | Writable | Signer | Description                |
-    /// | ------------------------------------------------------ |
-    /// | 0     | ❌        | ❌      | The DEX market             |
-    /// | 1     | ✅        | ❌      | The orderbook              |
-    /// | 2     | ✅        | ❌      | The AOB event queue        |
-    /// | 3     | ✅        | ❌      | The AOB bids shared memory |
-    /// | 4     | ✅        | ❌      | The AOB asks shared memory |
-    /// | 5     | ✅        | ❌      | The DEX user account       |
-    /// | 6     | ❌        | ✅      | The user wallet            |
+    /// | Index | Writable | Signer | Description                                     |
+    /// | --------------------------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market                                  |
+    /// | 1     | ✅        | ❌      | The orderbook                                   |
+    /// | 2     | ✅        | ❌      | The AOB event queue                             |
+    /// | 3     | ✅        | ❌      | The AOB bids shared memory                      |
+    /// | 4     | ✅        | ❌      | The AOB asks shared memory                      |
+    /// | 5     | ✅        | ❌      | The DEX user account                            |
+    /// | 6     | ❌        | ✅      | The user wallet, or the user account's delegate |
     CancelOrder,
     /// Crank the processing of DEX events.
     ///
-    /// | Index    | Writable | Signer | Description                |
-    /// | --------------------------------------------------------- |
-    /// | 0        | ✅        | ❌      | The DEX market             |
-    /// | 1        | ✅        | ❌      | The orderbook              |
-    /// | 2        | ✅        | ❌      | The AOB event queue        |
-    /// | 3        | ✅        | ❌      | The reward target          |
-    /// | 4..4 + N | ✅        | ❌      | The relevant user accounts |
+    /// | Index    | Writable | Signer | Description                                                                                     |
+    /// | ------------------------------------------------------------------------------------------------------------------------------ |
+    /// | 0        | ✅        | ❌      | The DEX market                                                                                  |
+    /// | 1        | ✅        | ❌      | The orderbook                                                                                   |
+    /// | 2        | ✅        | ❌      | The AOB event queue                                                                             |
+    /// | 3        | ✅        | ❌      | The AOB bids shared memory                                                                      |
+    /// | 4        | ✅        | ❌      | The AOB asks shared memory                                                                      |
+    /// | 5        | ✅        | ❌      | The market's trade log                                                                          |
+    /// | 6        | ✅        | ❌      | The user account which receives the crank reward, if any                                        |
+    /// | 7        | ✅        | ❌      | The market's optional liquidity metrics account, which records the makers of the consumed fills |
+    /// | 8        | ✅        | ❌      | The market's optional stats account, which is refreshed once the events are consumed            |
+    /// | 9..9 + N | ✅        | ❌      | The relevant user accounts                                                                      |
     ConsumeEvents,
     /// Extract available base and quote token assets from a user account
     ///
     /// | Index | Writable | Signer | Description                         |
     /// | --------------------------------------------------------------- |
     /// | 0     | ❌        | ❌      | The spl token program               |
-    /// | 1     | ❌        | ❌      | The DEX market                      |
+    /// | 1     | ✅        | ❌      | The DEX market                      |
     /// | 2     | ✅        | ❌      | The base token vault                |
     /// | 3     | ✅        | ❌      | The quote token vault               |
     /// | 4     | ❌        | ❌      | The DEX market signer account       |
     /// | 5     | ✅        | ❌      | The DEX user account                |
-    /// | 6     | ❌        | ✅      | The DEX user account owner wallet   |
+    /// | 6     | ✅        | ✅      | The DEX user account owner wallet   |
     /// | 7     | ✅        | ❌      | The destination base token account  |
     /// | 8     | ✅        | ❌      | The destination quote token account |
+    /// | 9     | ❌        | ❌      | The base token mint                 |
+    /// | 10    | ❌        | ❌      | The quote token mint                |
     Settle,
     /// Initialize a new user account
     ///
@@ -109,15 +143,18 @@ pub enum DexInstruction {
     InitializeAccount,
     /// Extract accumulated fees from the market. This is an admin instruction
     ///
-    /// | Index    | Writable | Signer | Description                   |
-    /// | ------------------------------------------------------------ |
-    /// | 0        | ✅        | ❌      | The DEX market                |
-    /// | 1        | ❌        | ❌      | The DEX market signer         |
-    /// | 2        | ✅        | ❌      | The market quote token vault  |
-    /// | 3        | ✅        | ❌      | The destination token account |
-    /// | 4        | ❌        | ❌      | The spl token program         |
-    /// | 5        | ❌        | ❌      | The metadata account          |
-    /// | 6..6 + N | ✅        | ❌      | The creator token account     |
+    /// | Index            | Writable | Signer | Description                                                                                          |
+    /// | ------------------------------------------------------------------------------------------------------------------------------------------- |
+    /// | 0                | ✅        | ❌      | The DEX market                                                                                       |
+    /// | 1                | ❌        | ❌      | The DEX market signer                                                                                |
+    /// | 2                | ✅        | ❌      | The market quote token vault                                                                         |
+    /// | 3                | ❌        | ❌      | The quote token mint                                                                                 |
+    /// | 4                | ✅        | ❌      | The destination token account, which receives the fees when the market has no fee recipients account |
+    /// | 5                | ❌        | ❌      | The spl token program                                                                                |
+    /// | 6                | ❌        | ❌      | The metadata account                                                                                 |
+    /// | 7                | ❌        | ❌      | The market's fee recipients account                                                                  |
+    /// | 8..8 + N         | ✅        | ❌      | The token accounts of the fee recipients, in the order of the fee recipients account                 |
+    /// | 8 + N..8 + N + M | ✅        | ❌      | The creator token account                                                                            |
     SweepFees,
     /// Close an inactive and empty user account
     ///
@@ -152,6 +189,426 @@ pub enum DexInstruction {
     /// | 2     | ❌        | ❌      | The AOB market account  |
     /// | 3     | ❌        | ❌      | The token metadata      |
     UpdateRoyalties,
+    /// Update the market's fee schedule. This is an admin instruction
+    ///
+    /// | Index | Writable | Signer | Description              |
+    /// | ---------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market           |
+    /// | 1     | ❌        | ✅      | The market admin account |
+    UpdateFeeSchedule,
+    /// Nominate a new market admin, which has to accept the role before it takes over. This is an admin instruction
+    ///
+    /// | Index | Writable | Signer | Description                      |
+    /// | ------------------------------------------------------------ |
+    /// | 0     | ✅        | ❌      | The DEX market                   |
+    /// | 1     | ❌        | ✅      | The current market admin account |
+    NominateAdmin,
+    /// Accept the market admin role after being nominated by the current admin
+    ///
+    /// | Index | Writable | Signer | Description                        |
+    /// | -------------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market                     |
+    /// | 1     | ❌        | ✅      | The nominated market admin account |
+    AcceptAdmin,
+    /// Set the market's trading status. This is an admin instruction
+    ///
+    /// | Index | Writable | Signer | Description              |
+    /// | ---------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market           |
+    /// | 1     | ❌        | ✅      | The market admin account |
+    SetMarketStatus,
+    /// Update the market's parameters. This is an admin instruction
+    ///
+    /// | Index | Writable | Signer | Description              |
+    /// | ---------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market           |
+    /// | 1     | ✅        | ❌      | The orderbook            |
+    /// | 2     | ❌        | ✅      | The market admin account |
+    UpdateMarketParams,
+    /// Cancel all of a user's orders which are still resting on the orderbook.
+    ///
+    /// | Index | Writable | Signer | Description                                     |
+    /// | --------------------------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market                                  |
+    /// | 1     | ✅        | ❌      | The orderbook                                   |
+    /// | 2     | ✅        | ❌      | The AOB event queue                             |
+    /// | 3     | ✅        | ❌      | The AOB bids shared memory                      |
+    /// | 4     | ✅        | ❌      | The AOB asks shared memory                      |
+    /// | 5     | ✅        | ❌      | The DEX user account                            |
+    /// | 6     | ❌        | ✅      | The user wallet, or the user account's delegate |
+    CancelAll,
+    /// Execute several new orders atomically on behalf of a single user account.
+    ///
+    /// The whole batch fails if any of its orders fails. A batch contains at most `MAX_BATCH_SIZE` orders.
+    ///
+    /// | Index | Writable | Signer | Description                                                                                                           |
+    /// | ------------------------------------------------------------------------------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The SPL token program                                                                                                 |
+    /// | 1     | ❌        | ❌      | The system program                                                                                                    |
+    /// | 2     | ✅        | ❌      | The DEX market                                                                                                        |
+    /// | 3     | ✅        | ❌      | The orderbook                                                                                                         |
+    /// | 4     | ✅        | ❌      | The AOB event queue                                                                                                   |
+    /// | 5     | ✅        | ❌      | The AOB bids shared memory                                                                                            |
+    /// | 6     | ✅        | ❌      | The AOB asks shared memory                                                                                            |
+    /// | 7     | ✅        | ❌      | The base token vault                                                                                                  |
+    /// | 8     | ✅        | ❌      | The quote token vault                                                                                                 |
+    /// | 9     | ❌        | ❌      | The DEX market signer, which authorizes the referral fee transfers out of the quote vault                             |
+    /// | 10    | ❌        | ❌      | The base token mint                                                                                                   |
+    /// | 11    | ❌        | ❌      | The quote token mint                                                                                                  |
+    /// | 12    | ✅        | ❌      | The DEX user account                                                                                                  |
+    /// | 13    | ✅        | ❌      | The user base token account, which funds the asks                                                                     |
+    /// | 14    | ✅        | ❌      | The user quote token account, which funds the bids                                                                    |
+    /// | 15    | ✅        | ✅      | The user wallet, or the user account's delegate                                                                       |
+    /// | 16    | ❌        | ❌      | The optional SRM or MSRM discount token account (must be owned by the user wallet)                                    |
+    /// | 17    | ❌        | ❌      | The market's oracle price account, which is required when the market has an oracle                                    |
+    /// | 18    | ✅        | ❌      | The market's optional liquidity metrics account, which records the user account as a maker when its orders are posted |
+    /// | 19    | ❌        | ❌      | The user wallet's access grant, which is required when the market has an access authority                             |
+    /// | 20    | ✅        | ❌      | The optional referrer's token account which will receive its share of the fees                                        |
+    NewOrderBatch,
+    /// Set or clear the oracle which the market's limit prices are checked against. This is an admin instruction
+    ///
+    /// | Index | Writable | Signer | Description                                 |
+    /// | ----------------------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market                              |
+    /// | 1     | ❌        | ✅      | The market admin account                    |
+    /// | 2     | ❌        | ❌      | The Pyth price account of the market's pair |
+    /// | 3     | ❌        | ❌      | The base token mint                         |
+    /// | 4     | ❌        | ❌      | The quote token mint                        |
+    SetOracle,
+    /// Resize a user account to change the maximum number of orders it can hold
+    ///
+    /// | Index | Writable | Signer | Description                                    |
+    /// | -------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The system program                             |
+    /// | 1     | ✅        | ❌      | The user account to resize                     |
+    /// | 2     | ❌        | ✅      | The owner of the user account                  |
+    /// | 3     | ✅        | ✅      | The fee payer, which funds the additional rent |
+    GrowAccount,
+    /// Let a delegate wallet post and cancel orders on behalf of a user account's owner
+    ///
+    /// | Index | Writable | Signer | Description                   |
+    /// | --------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX user account          |
+    /// | 1     | ❌        | ✅      | The owner of the user account |
+    SetDelegate,
+    /// Remove the delegate of a user account
+    ///
+    /// | Index | Writable | Signer | Description                   |
+    /// | --------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX user account          |
+    /// | 1     | ❌        | ✅      | The owner of the user account |
+    RevokeDelegate,
+    /// Extract some or all of the accumulated fees to any quote token account. This is an admin instruction
+    ///
+    /// | Index | Writable | Signer | Description                         |
+    /// | --------------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market                      |
+    /// | 1     | ❌        | ✅      | The market admin account            |
+    /// | 2     | ❌        | ❌      | The DEX market signer               |
+    /// | 3     | ✅        | ❌      | The market quote token vault        |
+    /// | 4     | ❌        | ❌      | The quote token mint                |
+    /// | 5     | ✅        | ❌      | The destination quote token account |
+    /// | 6     | ❌        | ❌      | The spl token program               |
+    ExtractFees,
+    /// Extract available base and quote token assets from the user accounts of a wallet on several markets at once
+    ///
+    /// | Index    | Writable | Signer | Description                                                                                               |
+    /// | ---------------------------------------------------------------------------------------------------------------------------------------- |
+    /// | 0        | ❌        | ❌      | The spl token program                                                                                     |
+    /// | 1        | ❌        | ✅      | The DEX user accounts owner wallet                                                                        |
+    /// | 2..2 + N | ✅        | ❌      | The accounts of each market, ordered as in a settle instruction without the token program and user wallet |
+    SettleMulti,
+    /// Create a new DEX market along with its orderbook account, which is allocated at a program derived address
+    ///
+    /// | Index | Writable | Signer | Description                                                                                                    |
+    /// | ------------------------------------------------------------------------------------------------------------------------------------------ |
+    /// | 0     | ❌        | ❌      | The system program                                                                                             |
+    /// | 1     | ✅        | ✅      | The fee payer, which funds the orderbook account                                                               |
+    /// | 2     | ✅        | ❌      | The market account                                                                                             |
+    /// | 3     | ✅        | ❌      | The orderbook account to allocate                                                                              |
+    /// | 4     | ❌        | ❌      | The base vault account                                                                                         |
+    /// | 5     | ❌        | ❌      | The quote vault account                                                                                        |
+    /// | 6     | ❌        | ❌      | The market admin account                                                                                       |
+    /// | 7     | ✅        | ❌      | The AOB event queue account                                                                                    |
+    /// | 8     | ✅        | ❌      | The AOB asks account                                                                                           |
+    /// | 9     | ✅        | ❌      | The AOB bids account                                                                                           |
+    /// | 10    | ❌        | ❌      | The metaplex token metadata                                                                                    |
+    /// | 11    | ✅        | ❌      | The trade log account which the market's fills will be recorded to                                             |
+    /// | 12    | ❌        | ❌      | The base mint, whose decimals are recorded in the market                                                       |
+    /// | 13    | ❌        | ❌      | The quote mint, whose decimals are recorded in the market                                                      |
+    /// | 14    | ❌        | ❌      | The program config account, which sets the market creation fee                                                 |
+    /// | 15    | ✅        | ❌      | The program's treasury, which collects the market creation fee from the fee payer when the program charges one |
+    CreateMarketWithOrderbook,
+    /// Reduce the size of an existing order without losing its place in the queue.
+    ///
+    /// | Index | Writable | Signer | Description                                     |
+    /// | --------------------------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market                                  |
+    /// | 1     | ❌        | ❌      | The orderbook                                   |
+    /// | 2     | ✅        | ❌      | The AOB bids shared memory                      |
+    /// | 3     | ✅        | ❌      | The AOB asks shared memory                      |
+    /// | 4     | ✅        | ❌      | The DEX user account                            |
+    /// | 5     | ❌        | ✅      | The user wallet, or the user account's delegate |
+    ReduceOrder,
+    /// Atomically cancel an existing order and place a new one.
+    ///
+    /// | Index | Writable | Signer | Description                                                                                                         |
+    /// | ----------------------------------------------------------------------------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The SPL token program                                                                                               |
+    /// | 1     | ❌        | ❌      | The system program                                                                                                  |
+    /// | 2     | ✅        | ❌      | The DEX market                                                                                                      |
+    /// | 3     | ✅        | ❌      | The orderbook                                                                                                       |
+    /// | 4     | ✅        | ❌      | The AOB event queue                                                                                                 |
+    /// | 5     | ✅        | ❌      | The AOB bids shared memory                                                                                          |
+    /// | 6     | ✅        | ❌      | The AOB asks shared memory                                                                                          |
+    /// | 7     | ✅        | ❌      | The base token vault                                                                                                |
+    /// | 8     | ✅        | ❌      | The quote token vault                                                                                               |
+    /// | 9     | ❌        | ❌      | The DEX market signer, which authorizes the referral fee transfers out of the quote vault                           |
+    /// | 10    | ❌        | ❌      | The base token mint                                                                                                 |
+    /// | 11    | ❌        | ❌      | The quote token mint                                                                                                |
+    /// | 12    | ✅        | ❌      | The DEX user account                                                                                                |
+    /// | 13    | ✅        | ❌      | The user source token account                                                                                       |
+    /// | 14    | ✅        | ✅      | The user wallet, or the user account's delegate                                                                     |
+    /// | 15    | ❌        | ❌      | The optional SRM or MSRM discount token account (must be owned by the user wallet)                                  |
+    /// | 16    | ❌        | ❌      | The market's oracle price account, which is required when the market has an oracle                                  |
+    /// | 17    | ✅        | ❌      | The market's optional liquidity metrics account, which records the user account as a maker when its order is posted |
+    /// | 18    | ✅        | ❌      | The market's optional stats account, which is refreshed once the order is placed                                    |
+    /// | 19    | ❌        | ❌      | The user wallet's access grant, which is required when the market has an access authority                           |
+    /// | 20    | ✅        | ❌      | The optional referrer's token account which will receive its share of the fees                                      |
+    ReplaceOrder,
+    /// Set the market's base taker fee and maker rebate rates in basis points, along with the rounding of its taker fees.
+    /// This is an admin instruction
+    ///
+    /// | Index | Writable | Signer | Description              |
+    /// | ---------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market           |
+    /// | 1     | ❌        | ✅      | The market admin account |
+    SetFeeBps,
+    /// Compute how much an order would fill against the current book, without modifying any account.
+    ///
+    /// | Index | Writable | Signer | Description                |
+    /// | ------------------------------------------------------ |
+    /// | 0     | ❌        | ❌      | The DEX market             |
+    /// | 1     | ❌        | ❌      | The orderbook              |
+    /// | 2     | ❌        | ❌      | The AOB bids shared memory |
+    /// | 3     | ❌        | ❌      | The AOB asks shared memory |
+    SimulateOrder,
+    /// Deposit base and quote tokens into the free balances of a user account, ahead of placing orders
+    ///
+    /// | Index | Writable | Signer | Description                       |
+    /// | ------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The spl token program             |
+    /// | 1     | ✅        | ❌      | The DEX market                    |
+    /// | 2     | ✅        | ❌      | The base token vault              |
+    /// | 3     | ✅        | ❌      | The quote token vault             |
+    /// | 4     | ✅        | ❌      | The DEX user account              |
+    /// | 5     | ❌        | ✅      | The DEX user account owner wallet |
+    /// | 6     | ✅        | ❌      | The source base token account     |
+    /// | 7     | ✅        | ❌      | The source quote token account    |
+    /// | 8     | ❌        | ❌      | The base token mint               |
+    /// | 9     | ❌        | ❌      | The quote token mint              |
+    Deposit,
+    /// Withdraw part of the free base and quote token balances of a user account
+    ///
+    /// | Index | Writable | Signer | Description                         |
+    /// | --------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The spl token program               |
+    /// | 1     | ✅        | ❌      | The DEX market                      |
+    /// | 2     | ✅        | ❌      | The base token vault                |
+    /// | 3     | ✅        | ❌      | The quote token vault               |
+    /// | 4     | ❌        | ❌      | The DEX market signer account       |
+    /// | 5     | ✅        | ❌      | The DEX user account                |
+    /// | 6     | ❌        | ✅      | The DEX user account owner wallet   |
+    /// | 7     | ✅        | ❌      | The destination base token account  |
+    /// | 8     | ✅        | ❌      | The destination quote token account |
+    /// | 9     | ❌        | ❌      | The base token mint                 |
+    /// | 10    | ❌        | ❌      | The quote token mint                |
+    Withdraw,
+    /// Start, update or stop the market's maker rebate boost. This is an admin instruction
+    ///
+    /// | Index | Writable | Signer | Description              |
+    /// | ---------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market           |
+    /// | 1     | ❌        | ✅      | The market admin account |
+    SetRebateBoost,
+    /// Cancel the expired orders resting on the book. This instruction is permissionless.
+    ///
+    /// | Index | Writable | Signer | Description                                              |
+    /// | ------------------------------------------------------------------------------------ |
+    /// | 0     | ✅        | ❌      | The DEX market                                           |
+    /// | 1     | ✅        | ❌      | The orderbook                                            |
+    /// | 2     | ✅        | ❌      | The AOB event queue                                      |
+    /// | 3     | ✅        | ❌      | The AOB bids shared memory                               |
+    /// | 4     | ✅        | ❌      | The AOB asks shared memory                               |
+    /// | 5     | ✅        | ❌      | The user account which receives the crank reward, if any |
+    PruneExpired,
+    /// Start a new liquidity metrics epoch, clearing the market's maker bitmap and unique maker count. This is an admin
+    /// instruction.
+    ///
+    /// | Index | Writable | Signer | Description                                                                                                  |
+    /// | ---------------------------------------------------------------------------------------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market                                                                                               |
+    /// | 1     | ✅        | ❌      | The market's liquidity metrics account, or a zeroed account owned by the DEX program to attach to the market |
+    /// | 2     | ❌        | ✅      | The market admin account                                                                                     |
+    ResetLiquidityMetrics,
+    /// Crank the processing of the DEX events which credit a single user account.
+    ///
+    /// | Index | Writable | Signer | Description                                                                                     |
+    /// | --------------------------------------------------------------------------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market                                                                                  |
+    /// | 1     | ✅        | ❌      | The orderbook                                                                                   |
+    /// | 2     | ✅        | ❌      | The AOB event queue                                                                             |
+    /// | 3     | ✅        | ❌      | The AOB bids shared memory                                                                      |
+    /// | 4     | ✅        | ❌      | The AOB asks shared memory                                                                      |
+    /// | 5     | ✅        | ❌      | The market's trade log                                                                          |
+    /// | 6     | ✅        | ❌      | The user account which receives the crank reward, if any                                        |
+    /// | 7     | ✅        | ❌      | The market's optional liquidity metrics account, which records the makers of the consumed fills |
+    /// | 8     | ✅        | ❌      | The user account whose events should be consumed                                                |
+    /// | 9     | ❌        | ✅      | The owner of the user account, or its delegate                                                  |
+    ConsumeEventsForUser,
+    /// Set the token accounts between which the market's accumulated fees are split when swept. This is an admin
+    /// instruction.
+    ///
+    /// | Index    | Writable | Signer | Description                                                                                               |
+    /// | ---------------------------------------------------------------------------------------------------------------------------------------- |
+    /// | 0        | ✅        | ❌      | The DEX market                                                                                            |
+    /// | 1        | ✅        | ❌      | The market's fee recipients account, or a zeroed account owned by the DEX program to attach to the market |
+    /// | 2        | ❌        | ✅      | The market admin account                                                                                  |
+    /// | 3..3 + N | ❌        | ❌      | The recipient token accounts, in the order of the instruction's parameters                                |
+    SetFeeRecipients,
+    /// Register the referrer of a user account which has none, within a window after the account's initialization.
+    ///
+    /// | Index | Writable | Signer | Description                   |
+    /// | --------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX user account          |
+    /// | 1     | ❌        | ✅      | The owner of the user account |
+    RegisterReferrer,
+    /// Initialize a user account which the fee payer has already allocated with a system program create_account
+    /// instruction, sized for an explicit number of order slots.
+    ///
+    /// | Index | Writable | Signer | Description                                                                       |
+    /// | ------------------------------------------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The user account's parent market                                                  |
+    /// | 1     | ✅        | ✅      | The user account to initialize, allocated beforehand and owned by the DEX program |
+    /// | 2     | ❌        | ✅      | The owner of the user account                                                     |
+    CreateAccount,
+    /// Create the account mirroring a market's trading statistics. This instruction is permissionless.
+    ///
+    /// | Index | Writable | Signer | Description                                                 |
+    /// | --------------------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The system program                                          |
+    /// | 1     | ❌        | ❌      | The DEX market                                              |
+    /// | 2     | ✅        | ❌      | The market stats account to create, derived from the market |
+    /// | 3     | ✅        | ✅      | The fee payer                                               |
+    CreateMarketStats,
+    /// Upgrade a market state created by the initial program version to the current layout. This is an admin instruction
+    ///
+    /// | Index | Writable | Signer | Description                                                                                       |
+    /// | ----------------------------------------------------------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The system program                                                                                |
+    /// | 1     | ✅        | ❌      | The DEX market                                                                                    |
+    /// | 2     | ❌        | ❌      | The base token vault                                                                              |
+    /// | 3     | ❌        | ❌      | The quote token vault                                                                             |
+    /// | 4     | ✅        | ✅      | The fee payer, which funds the additional rent                                                    |
+    /// | 5     | ❌        | ✅      | The market admin account                                                                          |
+    /// | 6     | ✅        | ❌      | The legacy orderbook                                                                              |
+    /// | 7     | ✅        | ❌      | The legacy AOB event queue                                                                        |
+    /// | 8     | ✅        | ❌      | The legacy AOB bids shared memory                                                                 |
+    /// | 9     | ✅        | ❌      | The legacy AOB asks shared memory                                                                 |
+    /// | 10    | ✅        | ❌      | The new orderbook account, which is created by the first migrate_market instruction of the market |
+    /// | 11    | ✅        | ❌      | The new AOB event queue account                                                                   |
+    /// | 12    | ✅        | ❌      | The new AOB bids account                                                                          |
+    /// | 13    | ✅        | ❌      | The new AOB asks account                                                                          |
+    /// | 14    | ✅        | ❌      | The trade log account, which is initialized by the first migrate_market instruction of the market |
+    MigrateMarket,
+    /// Arm, refresh or disarm the dead-man switch of a user account, after whose heartbeat expiry anyone may cancel
+    /// all of the account's orders.
+    ///
+    /// | Index | Writable | Signer | Description                                     |
+    /// | --------------------------------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX user account                            |
+    /// | 1     | ❌        | ✅      | The user wallet, or the user account's delegate |
+    Heartbeat,
+    /// Cancel all of a user's orders once the heartbeat of its dead-man switch has expired. This instruction is
+    /// permissionless.
+    ///
+    /// | Index | Writable | Signer | Description                                              |
+    /// | ------------------------------------------------------------------------------------ |
+    /// | 0     | ✅        | ❌      | The DEX market                                           |
+    /// | 1     | ✅        | ❌      | The orderbook                                            |
+    /// | 2     | ✅        | ❌      | The AOB event queue                                      |
+    /// | 3     | ✅        | ❌      | The AOB bids shared memory                               |
+    /// | 4     | ✅        | ❌      | The AOB asks shared memory                               |
+    /// | 5     | ✅        | ❌      | The DEX user account whose heartbeat has expired         |
+    /// | 6     | ✅        | ❌      | The user account which receives the crank reward, if any |
+    CancelStaleAccount,
+    /// Compare the balances of a market's vaults with the token amounts which the program tracks for them, and emit the
+    /// discrepancy as return data. This instruction doesn't modify any account.
+    ///
+    /// | Index | Writable | Signer | Description           |
+    /// | ------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The DEX market        |
+    /// | 1     | ❌        | ❌      | The base token vault  |
+    /// | 2     | ❌        | ❌      | The quote token vault |
+    Reconcile,
+    /// Set or clear the authority which restricts trading on the market to the wallets it grants access to. This is an
+    /// admin instruction.
+    ///
+    /// | Index | Writable | Signer | Description              |
+    /// | ---------------------------------------------------- |
+    /// | 0     | ✅        | ❌      | The DEX market           |
+    /// | 1     | ❌        | ✅      | The market admin account |
+    SetAccessAuthority,
+    /// Grant a wallet access to a permissioned market by creating its access grant account. This instruction is signed by
+    /// the market's access authority.
+    ///
+    /// | Index | Writable | Signer | Description                                                                |
+    /// | ------------------------------------------------------------------------------------------------------ |
+    /// | 0     | ❌        | ❌      | The system program                                                         |
+    /// | 1     | ❌        | ❌      | The DEX market                                                             |
+    /// | 2     | ❌        | ✅      | The market's access authority                                              |
+    /// | 3     | ✅        | ❌      | The access grant account to create, derived from the market and the wallet |
+    /// | 4     | ✅        | ✅      | The fee payer                                                              |
+    GrantAccess,
+    /// Revoke a wallet's access to a permissioned market by closing its access grant account. This instruction is signed
+    /// by the market's access authority.
+    ///
+    /// | Index | Writable | Signer | Description                                            |
+    /// | ---------------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The DEX market                                         |
+    /// | 1     | ❌        | ✅      | The market's access authority                          |
+    /// | 2     | ✅        | ❌      | The access grant account to close                      |
+    /// | 3     | ✅        | ❌      | The account which receives the access grant's lamports |
+    RevokeAccess,
+    /// Upgrade a user account created by the initial program version to the current layout. This instruction is
+    /// permissionless.
+    ///
+    /// | Index | Writable | Signer | Description                                    |
+    /// | -------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The system program                             |
+    /// | 1     | ✅        | ❌      | The user account to migrate                    |
+    /// | 2     | ✅        | ✅      | The fee payer, which funds the additional rent |
+    MigrateAccount,
+    /// Aggregate the resting orders of a market into price levels, and emit them as return data. This instruction
+    /// doesn't modify any account.
+    ///
+    /// | Index | Writable | Signer | Description                |
+    /// | ------------------------------------------------------ |
+    /// | 0     | ❌        | ❌      | The DEX market             |
+    /// | 1     | ❌        | ❌      | The orderbook              |
+    /// | 2     | ❌        | ❌      | The AOB bids shared memory |
+    /// | 3     | ❌        | ❌      | The AOB asks shared memory |
+    GetDepth,
+    /// Create or update the program config, which holds the market creation fee and the treasury collecting it.
+    ///
+    /// | Index | Writable | Signer | Description                                                                           |
+    /// | ----------------------------------------------------------------------------------------------------------------- |
+    /// | 0     | ❌        | ❌      | The system program                                                                    |
+    /// | 1     | ✅        | ❌      | The program config account                                                            |
+    /// | 2     | ❌        | ✅      | The config's authority, or the program's upgrade authority when the config is created |
+    /// | 3     | ✅        | ✅      | The fee payer, which funds the program config account                                 |
+    /// | 4     | ❌        | ❌      | The program data account of the DEX program, which is required to create the config   |
+    SetProgramConfig,
 }
 ///          Create a new DEX market
 ///         
@@ -171,7 +628,9 @@ pub fn new_order(
 ) -> Instruction {
     accounts.get_instruction_cast(program_id, DexInstruction::NewOrder as u8, params)
 }
-///         Execute a swap on the orderbook.
+///          Swap an exact input amount against the orderbook, with a minimum output amount as slippage protection.
+///         
+///          The output is transferred straight to the user's token account and the `SwapOutcome` is emitted as return data.
 pub fn swap(
     program_id: Pubkey,
     accounts: swap::Accounts<Pubkey>,
@@ -243,3 +702,331 @@ pub fn update_royalties(
 ) -> Instruction {
     accounts.get_instruction_cast(program_id, DexInstruction::UpdateRoyalties as u8, params)
 }
+///          Update the market's fee schedule. This is an admin instruction
+pub fn update_fee_schedule(
+    program_id: Pubkey,
+    accounts: update_fee_schedule::Accounts<Pubkey>,
+    params: update_fee_schedule::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::UpdateFeeSchedule as u8, params)
+}
+///          Nominate a new market admin, which has to accept the role before it takes over. This is an admin instruction
+pub fn nominate_admin(
+    program_id: Pubkey,
+    accounts: nominate_admin::Accounts<Pubkey>,
+    params: nominate_admin::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::NominateAdmin as u8, params)
+}
+///          Accept the market admin role after being nominated by the current admin
+pub fn accept_admin(
+    program_id: Pubkey,
+    accounts: accept_admin::Accounts<Pubkey>,
+    params: accept_admin::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::AcceptAdmin as u8, params)
+}
+///          Set the market's trading status. This is an admin instruction
+pub fn set_market_status(
+    program_id: Pubkey,
+    accounts: set_market_status::Accounts<Pubkey>,
+    params: set_market_status::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::SetMarketStatus as u8, params)
+}
+///          Update the market's parameters. This is an admin instruction
+pub fn update_market_params(
+    program_id: Pubkey,
+    accounts: update_market_params::Accounts<Pubkey>,
+    params: update_market_params::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::UpdateMarketParams as u8, params)
+}
+///          Cancel all of a user's orders which are still resting on the orderbook.
+pub fn cancel_all(
+    program_id: Pubkey,
+    accounts: cancel_all::Accounts<Pubkey>,
+    params: cancel_all::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::CancelAll as u8, params)
+}
+///          Execute several new orders atomically on behalf of a single user account.
+pub fn new_order_batch(
+    program_id: Pubkey,
+    accounts: new_order_batch::Accounts<Pubkey>,
+    params: new_order_batch::Params,
+) -> Instruction {
+    accounts.get_instruction(program_id, DexInstruction::NewOrderBatch as u8, params)
+}
+///          Set or clear the oracle which the market's limit prices are checked against. This is an admin instruction
+pub fn set_oracle(
+    program_id: Pubkey,
+    accounts: set_oracle::Accounts<Pubkey>,
+    params: set_oracle::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::SetOracle as u8, params)
+}
+///          Resize a user account to change the maximum number of orders it can hold
+pub fn grow_account(
+    program_id: Pubkey,
+    accounts: grow_account::Accounts<Pubkey>,
+    params: grow_account::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::GrowAccount as u8, params)
+}
+///          Let a delegate wallet post and cancel orders on behalf of a user account's owner
+pub fn set_delegate(
+    program_id: Pubkey,
+    accounts: set_delegate::Accounts<Pubkey>,
+    params: set_delegate::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::SetDelegate as u8, params)
+}
+///          Remove the delegate of a user account
+pub fn revoke_delegate(
+    program_id: Pubkey,
+    accounts: revoke_delegate::Accounts<Pubkey>,
+    params: revoke_delegate::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::RevokeDelegate as u8, params)
+}
+///          Extract some or all of the accumulated fees to any quote token account. This is an admin instruction
+pub fn extract_fees(
+    program_id: Pubkey,
+    accounts: extract_fees::Accounts<Pubkey>,
+    params: extract_fees::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::ExtractFees as u8, params)
+}
+///          Extract available base and quote token assets from the user accounts of a wallet on several markets at once
+pub fn settle_multi(
+    program_id: Pubkey,
+    accounts: settle_multi::Accounts<Pubkey>,
+    params: settle_multi::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::SettleMulti as u8, params)
+}
+///          Create a new DEX market along with its orderbook account, which is allocated at a program derived address
+pub fn create_market_with_orderbook(
+    program_id: Pubkey,
+    accounts: create_market_with_orderbook::Accounts<Pubkey>,
+    params: create_market_with_orderbook::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(
+        program_id,
+        DexInstruction::CreateMarketWithOrderbook as u8,
+        params,
+    )
+}
+///          Reduce the size of an existing order without losing its place in the queue.
+pub fn reduce_order(
+    program_id: Pubkey,
+    accounts: reduce_order::Accounts<Pubkey>,
+    params: reduce_order::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::ReduceOrder as u8, params)
+}
+///          Atomically cancel an existing order and place a new one.
+pub fn replace_order(
+    program_id: Pubkey,
+    accounts: replace_order::Accounts<Pubkey>,
+    params: replace_order::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::ReplaceOrder as u8, params)
+}
+///          Set the market's base taker fee and maker rebate rates in basis points, along with the rounding of its taker fees.
+///          This is an admin instruction
+pub fn set_fee_bps(
+    program_id: Pubkey,
+    accounts: set_fee_bps::Accounts<Pubkey>,
+    params: set_fee_bps::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::SetFeeBps as u8, params)
+}
+///          Compute how much an order would fill against the current book, without modifying any account.
+pub fn simulate_order(
+    program_id: Pubkey,
+    accounts: simulate_order::Accounts<Pubkey>,
+    params: simulate_order::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::SimulateOrder as u8, params)
+}
+///          Deposit base and quote tokens into the free balances of a user account, ahead of placing orders
+pub fn deposit(
+    program_id: Pubkey,
+    accounts: deposit::Accounts<Pubkey>,
+    params: deposit::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::Deposit as u8, params)
+}
+///          Withdraw part of the free base and quote token balances of a user account
+pub fn withdraw(
+    program_id: Pubkey,
+    accounts: withdraw::Accounts<Pubkey>,
+    params: withdraw::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::Withdraw as u8, params)
+}
+///          Start, update or stop the market's maker rebate boost. This is an admin instruction
+pub fn set_rebate_boost(
+    program_id: Pubkey,
+    accounts: set_rebate_boost::Accounts<Pubkey>,
+    params: set_rebate_boost::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::SetRebateBoost as u8, params)
+}
+///          Cancel the expired orders resting on the book. This instruction is permissionless.
+pub fn prune_expired(
+    program_id: Pubkey,
+    accounts: prune_expired::Accounts<Pubkey>,
+    params: prune_expired::Params,
+) -> Instruction {
+    accounts.get_instruction(program_id, DexInstruction::PruneExpired as u8, params)
+}
+///          Start a new liquidity metrics epoch, clearing the market's maker bitmap and unique maker count. This is an admin
+///          instruction.
+pub fn reset_liquidity_metrics(
+    program_id: Pubkey,
+    accounts: reset_liquidity_metrics::Accounts<Pubkey>,
+    params: reset_liquidity_metrics::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(
+        program_id,
+        DexInstruction::ResetLiquidityMetrics as u8,
+        params,
+    )
+}
+///          Crank the processing of the DEX events which credit a single user account.
+pub fn consume_events_for_user(
+    program_id: Pubkey,
+    accounts: consume_events_for_user::Accounts<Pubkey>,
+    params: consume_events_for_user::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(
+        program_id,
+        DexInstruction::ConsumeEventsForUser as u8,
+        params,
+    )
+}
+///          Set the token accounts between which the market's accumulated fees are split when swept. This is an admin
+///          instruction.
+pub fn set_fee_recipients(
+    program_id: Pubkey,
+    accounts: set_fee_recipients::Accounts<Pubkey>,
+    params: set_fee_recipients::Params,
+) -> Instruction {
+    accounts.get_instruction(program_id, DexInstruction::SetFeeRecipients as u8, params)
+}
+///          Register the referrer of a user account which has none, within a window after the account's initialization.
+pub fn register_referrer(
+    program_id: Pubkey,
+    accounts: register_referrer::Accounts<Pubkey>,
+    params: register_referrer::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::RegisterReferrer as u8, params)
+}
+///          Initialize a user account which the fee payer has already allocated with a system program create_account
+///          instruction, sized for an explicit number of order slots.
+pub fn create_account(
+    program_id: Pubkey,
+    accounts: create_account::Accounts<Pubkey>,
+    params: create_account::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::CreateAccount as u8, params)
+}
+///          Create the account mirroring a market's trading statistics. This instruction is permissionless.
+pub fn create_market_stats(
+    program_id: Pubkey,
+    accounts: create_market_stats::Accounts<Pubkey>,
+    params: create_market_stats::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::CreateMarketStats as u8, params)
+}
+///          Upgrade a market state created by the initial program version to the current layout. This is an admin instruction
+pub fn migrate_market(
+    program_id: Pubkey,
+    accounts: migrate_market::Accounts<Pubkey>,
+    params: migrate_market::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::MigrateMarket as u8, params)
+}
+///          Arm, refresh or disarm the dead-man switch of a user account, after whose heartbeat expiry anyone may cancel
+///          all of the account's orders.
+pub fn heartbeat(
+    program_id: Pubkey,
+    accounts: heartbeat::Accounts<Pubkey>,
+    params: heartbeat::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::Heartbeat as u8, params)
+}
+///          Cancel all of a user's orders once the heartbeat of its dead-man switch has expired. This instruction is
+///          permissionless.
+pub fn cancel_stale_account(
+    program_id: Pubkey,
+    accounts: cancel_stale_account::Accounts<Pubkey>,
+    params: cancel_stale_account::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::CancelStaleAccount as u8, params)
+}
+///          Compare the balances of a market's vaults with the token amounts which the program tracks for them, and emit the
+///          discrepancy as return data. This instruction doesn't modify any account.
+pub fn reconcile(
+    program_id: Pubkey,
+    accounts: reconcile::Accounts<Pubkey>,
+    params: reconcile::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::Reconcile as u8, params)
+}
+///          Set or clear the authority which restricts trading on the market to the wallets it grants access to. This is an
+///          admin instruction.
+pub fn set_access_authority(
+    program_id: Pubkey,
+    accounts: set_access_authority::Accounts<Pubkey>,
+    params: set_access_authority::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::SetAccessAuthority as u8, params)
+}
+///          Grant a wallet access to a permissioned market by creating its access grant account. This instruction is signed by
+///          the market's access authority.
+pub fn grant_access(
+    program_id: Pubkey,
+    accounts: grant_access::Accounts<Pubkey>,
+    params: grant_access::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::GrantAccess as u8, params)
+}
+///          Revoke a wallet's access to a permissioned market by closing its access grant account. This instruction is signed
+///          by the market's access authority.
+pub fn revoke_access(
+    program_id: Pubkey,
+    accounts: revoke_access::Accounts<Pubkey>,
+    params: revoke_access::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::RevokeAccess as u8, params)
+}
+///          Upgrade a user account created by the initial program version to the current layout. This instruction is
+///          permissionless.
+pub fn migrate_account(
+    program_id: Pubkey,
+    accounts: migrate_account::Accounts<Pubkey>,
+    params: migrate_account::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::MigrateAccount as u8, params)
+}
+///          Aggregate the resting orders of a market into price levels, and emit them as return data. This instruction
+///          doesn't modify any account.
+pub fn get_depth(
+    program_id: Pubkey,
+    accounts: get_depth::Accounts<Pubkey>,
+    params: get_depth::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::GetDepth as u8, params)
+}
+///          Create or update the program config, which holds the market creation fee and the treasury collecting it.
+pub fn set_program_config(
+    program_id: Pubkey,
+    accounts: set_program_config::Accounts<Pubkey>,
+    params: set_program_config::Params,
+) -> Instruction {
+    accounts.get_instruction_cast(program_id, DexInstruction::SetProgramConfig as u8, params)
+}
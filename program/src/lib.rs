@@ -13,11 +13,13 @@ pub mod error;
 pub mod instruction_auto;
 /// Describes the different data structres that the program uses to encode state
 pub mod state;
+/// The source of the current time, which tests can replace with a fixed timestamp
+pub mod time;
 
 pub(crate) mod processor;
 pub(crate) mod utils;
 
-pub use processor::{CALLBACK_ID_LEN, CALLBACK_INFO_LEN};
+pub use processor::{CALLBACK_ID_LEN, CALLBACK_INFO_LEN, MSRM_MINT, REFERRAL_MASK, SRM_MINT};
 use solana_program::declare_id;
 
 declare_id!("SerumSqm3PWpKcHva3sxfUPXsYaE53czAbWtgAaisCf");
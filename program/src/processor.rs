@@ -8,8 +8,10 @@ use crate::instruction_auto::DexInstruction;
 
 ////////////////////////////////////////////////////////////
 // Constants
+/// The mint of the SRM tokens which grant fee tier discounts when held in a discount token account
 pub static SRM_MINT: Pubkey =
     solana_program::pubkey!("SRMuApVNdxXokk5GT7XD5cUUgXMBCoAz2LHeuAoKWRt");
+/// The mint of the MSRM tokens which grant the best fee tier when held in a discount token account
 pub static MSRM_MINT: Pubkey =
     solana_program::pubkey!("MSRMcoVyrFxnSgo5uXwone5SKcGhT1KEJMFEkMEWf9L");
 
@@ -18,7 +20,10 @@ pub static SWEEP_AUTHORITY: Pubkey =
     solana_program::pubkey!("DjXsn34uz8hnC4KLiSkEVNmzqX5ZFP2Q7aErTBH8LWxe");
 
 /// The length in bytes of the callback information in the associated asset agnostic orderbook
-pub static CALLBACK_INFO_LEN: u64 = 33;
+///
+/// Orderbooks created with the former 33 byte layout (before order expiry was introduced) are replaced with a new
+/// orderbook by the migrate_market instruction, which moves their orders and events over.
+pub static CALLBACK_INFO_LEN: u64 = 64;
 /// The length in bytes of the callback identifer prefix in the associated asset agnostic orderbook
 pub static CALLBACK_ID_LEN: u64 = 32;
 /// The most significant bit of the fee tier field in CallBack Info indicates if the transaction is referred
@@ -51,6 +56,99 @@ pub mod close_market;
 #[allow(missing_docs)]
 pub mod update_royalties;
 
+#[allow(missing_docs)]
+pub mod update_fee_schedule;
+
+#[allow(missing_docs)]
+pub mod nominate_admin;
+
+#[allow(missing_docs)]
+pub mod accept_admin;
+
+#[allow(missing_docs)]
+pub mod set_market_status;
+
+#[allow(missing_docs)]
+pub mod update_market_params;
+
+#[allow(missing_docs)]
+pub mod cancel_all;
+
+#[allow(missing_docs)]
+pub mod new_order_batch;
+
+#[allow(missing_docs)]
+pub mod set_oracle;
+
+#[allow(missing_docs)]
+pub mod grow_account;
+
+#[allow(missing_docs)]
+pub mod set_delegate;
+
+#[allow(missing_docs)]
+pub mod revoke_delegate;
+
+#[allow(missing_docs)]
+pub mod extract_fees;
+
+#[allow(missing_docs)]
+pub mod settle_multi;
+
+#[allow(missing_docs)]
+pub mod create_market_with_orderbook;
+
+#[allow(missing_docs)]
+pub mod reduce_order;
+
+#[allow(missing_docs)]
+pub mod replace_order;
+
+#[allow(missing_docs)]
+pub mod cancel_stale_account;
+#[allow(missing_docs)]
+pub mod consume_events_for_user;
+#[allow(missing_docs)]
+pub mod create_account;
+#[allow(missing_docs)]
+pub mod create_market_stats;
+#[allow(missing_docs)]
+pub mod deposit;
+#[allow(missing_docs)]
+pub mod get_depth;
+#[allow(missing_docs)]
+pub mod grant_access;
+#[allow(missing_docs)]
+pub mod heartbeat;
+#[allow(missing_docs)]
+pub mod migrate_account;
+#[allow(missing_docs)]
+pub mod migrate_market;
+#[allow(missing_docs)]
+pub mod prune_expired;
+#[allow(missing_docs)]
+pub mod reconcile;
+#[allow(missing_docs)]
+pub mod register_referrer;
+#[allow(missing_docs)]
+pub mod reset_liquidity_metrics;
+#[allow(missing_docs)]
+pub mod revoke_access;
+#[allow(missing_docs)]
+pub mod set_access_authority;
+#[allow(missing_docs)]
+pub mod set_fee_bps;
+#[allow(missing_docs)]
+pub mod set_fee_recipients;
+#[allow(missing_docs)]
+pub mod set_program_config;
+#[allow(missing_docs)]
+pub mod set_rebate_boost;
+#[allow(missing_docs)]
+pub mod simulate_order;
+#[allow(missing_docs)]
+pub mod withdraw;
+
 pub struct Processor {}
 
 // We add an offset larger than 1 to keep the instruction's internal arguments aligned
@@ -90,7 +188,7 @@ impl Processor {
             }
             DexInstruction::Settle => {
                 msg!("Instruction: Settle");
-                settle::process(program_id, accounts)?;
+                settle::process(program_id, accounts, instruction_data)?;
             }
             DexInstruction::InitializeAccount => {
                 msg!("Instruction: Initialize account");
@@ -98,7 +196,7 @@ impl Processor {
             }
             DexInstruction::SweepFees => {
                 msg!("Instruction: Sweep fees");
-                sweep_fees::process(program_id, accounts)?;
+                sweep_fees::process(program_id, accounts, instruction_data)?;
             }
             DexInstruction::CloseAccount => {
                 msg!("Instruction: Close Account");
@@ -112,6 +210,158 @@ impl Processor {
                 msg!("Instruction: Update royalties");
                 update_royalties::process(program_id, accounts)?
             }
+            DexInstruction::UpdateFeeSchedule => {
+                msg!("Instruction: Update fee schedule");
+                update_fee_schedule::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::NominateAdmin => {
+                msg!("Instruction: Nominate admin");
+                nominate_admin::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::AcceptAdmin => {
+                msg!("Instruction: Accept admin");
+                accept_admin::process(program_id, accounts)?
+            }
+            DexInstruction::SetMarketStatus => {
+                msg!("Instruction: Set market status");
+                set_market_status::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::UpdateMarketParams => {
+                msg!("Instruction: Update market params");
+                update_market_params::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::CancelAll => {
+                msg!("Instruction: Cancel all");
+                cancel_all::process(program_id, accounts)?
+            }
+            DexInstruction::NewOrderBatch => {
+                msg!("Instruction: New Order Batch");
+                new_order_batch::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::SetOracle => {
+                msg!("Instruction: Set Oracle");
+                set_oracle::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::GrowAccount => {
+                msg!("Instruction: Grow Account");
+                grow_account::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::SetDelegate => {
+                msg!("Instruction: Set Delegate");
+                set_delegate::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::RevokeDelegate => {
+                msg!("Instruction: Revoke Delegate");
+                revoke_delegate::process(program_id, accounts)?
+            }
+            DexInstruction::ExtractFees => {
+                msg!("Instruction: Extract Fees");
+                extract_fees::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::SettleMulti => {
+                msg!("Instruction: Settle Multi");
+                settle_multi::process(program_id, accounts)?
+            }
+            DexInstruction::CreateMarketWithOrderbook => {
+                msg!("Instruction: Create Market With Orderbook");
+                create_market_with_orderbook::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::ReduceOrder => {
+                msg!("Instruction: Reduce Order");
+                reduce_order::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::ReplaceOrder => {
+                msg!("Instruction: Replace Order");
+                replace_order::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::SetFeeBps => {
+                msg!("Instruction: Set Fee Bps");
+                set_fee_bps::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::SimulateOrder => {
+                msg!("Instruction: Simulate Order");
+                simulate_order::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::Deposit => {
+                msg!("Instruction: Deposit");
+                deposit::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::Withdraw => {
+                msg!("Instruction: Withdraw");
+                withdraw::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::SetRebateBoost => {
+                msg!("Instruction: Set Rebate Boost");
+                set_rebate_boost::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::PruneExpired => {
+                msg!("Instruction: Prune Expired");
+                prune_expired::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::ResetLiquidityMetrics => {
+                msg!("Instruction: Reset Liquidity Metrics");
+                reset_liquidity_metrics::process(program_id, accounts)?
+            }
+            DexInstruction::ConsumeEventsForUser => {
+                msg!("Instruction: Consume Events For User");
+                consume_events_for_user::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::SetFeeRecipients => {
+                msg!("Instruction: Set Fee Recipients");
+                set_fee_recipients::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::RegisterReferrer => {
+                msg!("Instruction: Register Referrer");
+                register_referrer::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::CreateAccount => {
+                msg!("Instruction: Create Account");
+                create_account::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::CreateMarketStats => {
+                msg!("Instruction: Create Market Stats");
+                create_market_stats::process(program_id, accounts)?
+            }
+            DexInstruction::MigrateMarket => {
+                msg!("Instruction: Migrate Market");
+                migrate_market::process(program_id, accounts)?
+            }
+            DexInstruction::Heartbeat => {
+                msg!("Instruction: Heartbeat");
+                heartbeat::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::CancelStaleAccount => {
+                msg!("Instruction: Cancel Stale Account");
+                cancel_stale_account::process(program_id, accounts)?
+            }
+            DexInstruction::Reconcile => {
+                msg!("Instruction: Reconcile");
+                reconcile::process(program_id, accounts)?
+            }
+            DexInstruction::SetAccessAuthority => {
+                msg!("Instruction: Set Access Authority");
+                set_access_authority::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::GrantAccess => {
+                msg!("Instruction: Grant Access");
+                grant_access::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::RevokeAccess => {
+                msg!("Instruction: Revoke Access");
+                revoke_access::process(program_id, accounts)?
+            }
+            DexInstruction::MigrateAccount => {
+                msg!("Instruction: Migrate Account");
+                migrate_account::process(program_id, accounts)?
+            }
+            DexInstruction::GetDepth => {
+                msg!("Instruction: Get Depth");
+                get_depth::process(program_id, accounts, instruction_data)?
+            }
+            DexInstruction::SetProgramConfig => {
+                msg!("Instruction: Set Program Config");
+                set_program_config::process(program_id, accounts, instruction_data)?
+            }
         }
         Ok(())
     }
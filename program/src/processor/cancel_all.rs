@@ -0,0 +1,212 @@
+//! Cancel all of a user's orders which are still resting on the orderbook.
+use crate::{
+    error::DexError,
+    state::{CallBackInfo, DexState, UserAccount},
+    utils::{check_account_key, check_account_owner, check_signer, get_order_callback_info},
+};
+use asset_agnostic_orderbook::{
+    error::AoError,
+    state::{get_side_from_order_id, Side},
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+    pubkey::Pubkey,
+};
+
+#[derive(Clone, Copy, BorshDeserialize, BorshSerialize, BorshSize, Pod, Zeroable)]
+#[repr(C)]
+pub struct Params {}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The orderbook
+    #[cons(writable)]
+    pub orderbook: &'a T,
+
+    /// The AOB event queue
+    #[cons(writable)]
+    pub event_queue: &'a T,
+
+    /// The AOB bids shared memory
+    #[cons(writable)]
+    pub bids: &'a T,
+
+    /// The AOB asks shared memory
+    #[cons(writable)]
+    pub asks: &'a T,
+
+    /// The DEX user account
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The user wallet, or the user account's delegate
+    #[cons(signer)]
+    pub user_owner: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            event_queue: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            user: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+
+    pub fn load_user_account(
+        &self,
+        user_account_data: &'a mut [u8],
+    ) -> Result<UserAccount<'a>, ProgramError> {
+        let user_account = UserAccount::from_buffer(user_account_data)?;
+        if !user_account.header.is_order_authority(self.user_owner.key) {
+            msg!("Invalid user account owner or delegate provided!");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if &user_account.header.market != self.market.key {
+            msg!("The provided user account doesn't match the current market");
+            return Err(ProgramError::InvalidArgument);
+        };
+        Ok(user_account)
+    }
+}
+
+pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let mut user_account = accounts.load_user_account(&mut user_account_data)?;
+
+    check_accounts(&market_state, &accounts).unwrap();
+
+    let number_of_cancelled_orders = cancel_user_orders(
+        program_id,
+        accounts.orderbook,
+        accounts.event_queue,
+        accounts.bids,
+        accounts.asks,
+        &mut market_state,
+        &mut user_account,
+    )?;
+
+    msg!("Cancelled {} orders", number_of_cancelled_orders);
+
+    Ok(())
+}
+
+/// Cancels all of a user account's orders which are still resting on the book and releases their locked tokens to
+/// the user account's free balances. Returns the number of cancelled orders.
+pub(crate) fn cancel_user_orders(
+    program_id: &Pubkey,
+    orderbook: &AccountInfo,
+    event_queue: &AccountInfo,
+    bids: &AccountInfo,
+    asks: &AccountInfo,
+    market_state: &mut DexState,
+    user_account: &mut UserAccount,
+) -> Result<u64, ProgramError> {
+    let mut number_of_cancelled_orders = 0;
+
+    // Iterating backwards keeps the indices of the orders left to visit stable as orders are removed
+    for order_index in (0..user_account.header.number_of_orders as usize).rev() {
+        let order_id = user_account.read_order(order_index)?.id;
+        let side = get_side_from_order_id(order_id);
+        let slab_account = match side {
+            Side::Bid => bids,
+            Side::Ask => asks,
+        };
+        // Orders which already left the book are removed from the user account once their out event is consumed
+        let hidden_base_qty = match get_order_callback_info(slab_account, side, order_id)? {
+            Some(c) => c.hidden_base_qty,
+            None => continue,
+        };
+
+        let invoke_params =
+            asset_agnostic_orderbook::instruction::cancel_order::Params { order_id };
+        let invoke_accounts = asset_agnostic_orderbook::instruction::cancel_order::Accounts {
+            market: orderbook,
+            event_queue,
+            bids,
+            asks,
+        };
+
+        let mut order_summary = match asset_agnostic_orderbook::instruction::cancel_order::process::<
+            CallBackInfo,
+        >(program_id, invoke_accounts, invoke_params)
+        {
+            Err(error) => {
+                error.print::<AoError>();
+                return Err(DexError::AOBError.into());
+            }
+            Ok(s) => s,
+        };
+
+        market_state
+            .unscale_order_summary(&mut order_summary)
+            .unwrap();
+        market_state.remove_resting_base(side, order_summary.total_base_qty);
+
+        if hidden_base_qty != 0 {
+            order_summary.total_base_qty += hidden_base_qty;
+            order_summary.total_quote_qty += market_state
+                .get_quote_from_base(hidden_base_qty, (order_id >> 64) as u64)
+                .unwrap();
+        }
+
+        match side {
+            Side::Bid => {
+                user_account
+                    .header
+                    .unlock_quote(order_summary.total_quote_qty)?;
+            }
+            Side::Ask => {
+                user_account
+                    .header
+                    .unlock_base(order_summary.total_base_qty)?;
+            }
+        };
+
+        user_account.remove_order(order_index)?;
+        number_of_cancelled_orders += 1;
+    }
+
+    Ok(number_of_cancelled_orders)
+}
+
+fn check_accounts(market_state: &DexState, accounts: &Accounts<AccountInfo>) -> ProgramResult {
+    check_account_key(
+        accounts.orderbook,
+        &market_state.orderbook,
+        DexError::InvalidOrderbookAccount,
+    )?;
+
+    Ok(())
+}
@@ -2,7 +2,7 @@
 use crate::{
     error::DexError,
     state::{CallBackInfo, DexState, UserAccount},
-    utils::{check_account_key, check_account_owner, check_signer},
+    utils::{check_account_key, check_account_owner, check_signer, get_order_callback_info},
 };
 use asset_agnostic_orderbook::{
     error::AoError,
@@ -34,7 +34,8 @@ pub struct Params {
     pub order_index: u64,
     /// Decide wether the `order_id` param is the order id from the user account or a client_order_id which was
     /// given by the user on creation.
-    /// The latter means the order_index param will be ignored.
+    /// The latter means the order_index param will be ignored, and that the oldest order is cancelled when several orders
+    /// share the client order id.
     pub is_client_id: bool,
     pub _padding: [u8; 7],
 }
@@ -42,6 +43,7 @@ pub struct Params {
 #[derive(InstructionsAccount)]
 pub struct Accounts<'a, T> {
     /// The DEX market
+    #[cons(writable)]
     pub market: &'a T,
 
     /// The orderbook
@@ -64,7 +66,7 @@ pub struct Accounts<'a, T> {
     #[cons(writable)]
     pub user: &'a T,
 
-    /// The user wallet
+    /// The user wallet, or the user account's delegate
     #[cons(signer)]
     pub user_owner: &'a T,
 }
@@ -99,8 +101,8 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         user_account_data: &'a mut [u8],
     ) -> Result<UserAccount<'a>, ProgramError> {
         let user_account = UserAccount::from_buffer(user_account_data)?;
-        if &user_account.header.owner != self.user_owner.key {
-            msg!("Invalid user account owner provided!");
+        if !user_account.header.is_order_authority(self.user_owner.key) {
+            msg!("Invalid user account owner or delegate provided!");
             return Err(ProgramError::InvalidArgument);
         }
         if &user_account.header.market != self.market.key {
@@ -127,7 +129,7 @@ pub(crate) fn process(
         _padding,
     } = params;
 
-    let market_state = DexState::get(accounts.market)?;
+    let mut market_state = DexState::get(accounts.market)?;
     let mut user_account_data = accounts.user.data.borrow_mut();
     let mut user_account = accounts.load_user_account(&mut user_account_data)?;
 
@@ -145,6 +147,37 @@ pub(crate) fn process(
         }
     }
 
+    execute(
+        program_id,
+        &accounts,
+        &mut market_state,
+        &mut user_account,
+        order_id,
+        order_index,
+    )
+}
+
+/// Cancels an order of a user account whose accounts have already been checked, releasing its locked tokens.
+///
+/// This is shared with the replace_order instruction, which places a new order right after.
+pub(crate) fn execute(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+    market_state: &mut DexState,
+    user_account: &mut UserAccount,
+    order_id: u128,
+    order_index: u64,
+) -> ProgramResult {
+    let side = get_side_from_order_id(order_id);
+    let slab_account = match side {
+        Side::Bid => accounts.bids,
+        Side::Ask => accounts.asks,
+    };
+    // The hidden quantity of an iceberg order is not on the book and has to be released separately
+    let hidden_base_qty = get_order_callback_info(slab_account, side, order_id)?
+        .map(|c| c.hidden_base_qty)
+        .unwrap_or(0);
+
     let invoke_params = asset_agnostic_orderbook::instruction::cancel_order::Params { order_id };
     let invoke_accounts = asset_agnostic_orderbook::instruction::cancel_order::Accounts {
         market: accounts.orderbook,
@@ -163,36 +196,29 @@ pub(crate) fn process(
         }
         Ok(s) => s,
     };
-    let side = get_side_from_order_id(order_id);
 
     market_state
         .unscale_order_summary(&mut order_summary)
         .unwrap();
+    market_state.remove_resting_base(side, order_summary.total_base_qty);
+
+    if hidden_base_qty != 0 {
+        order_summary.total_base_qty += hidden_base_qty;
+        order_summary.total_quote_qty += market_state
+            .get_quote_from_base(hidden_base_qty, (order_id >> 64) as u64)
+            .unwrap();
+    }
 
     match side {
         Side::Bid => {
-            user_account.header.quote_token_free = user_account
-                .header
-                .quote_token_free
-                .checked_add(order_summary.total_quote_qty)
-                .unwrap();
-            user_account.header.quote_token_locked = user_account
+            user_account
                 .header
-                .quote_token_locked
-                .checked_sub(order_summary.total_quote_qty)
-                .unwrap();
+                .unlock_quote(order_summary.total_quote_qty)?;
         }
         Side::Ask => {
-            user_account.header.base_token_free = user_account
-                .header
-                .base_token_free
-                .checked_add(order_summary.total_base_qty)
-                .unwrap();
-            user_account.header.base_token_locked = user_account
+            user_account
                 .header
-                .base_token_locked
-                .checked_sub(order_summary.total_base_qty)
-                .unwrap();
+                .unlock_base(order_summary.total_base_qty)?;
         }
     };
 
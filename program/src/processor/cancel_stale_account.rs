@@ -0,0 +1,128 @@
+//! Cancel all of a user's orders once the heartbeat of its dead-man switch has expired. This instruction is
+//! permissionless.
+//!
+//! The locked tokens of the cancelled orders are released to the user account's free balances, from which only its
+//! owner can settle them. The caller earns the market's crank reward for each cancelled order.
+use crate::{
+    error::DexError,
+    processor::{cancel_all::cancel_user_orders, consume_events::pay_crank_reward},
+    state::{DexState, UserAccount},
+    time::{SysvarClock, TimeProvider},
+    utils::check_account_owner,
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Clone, Copy, BorshDeserialize, BorshSerialize, BorshSize, Pod, Zeroable)]
+#[repr(C)]
+pub struct Params {}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The orderbook
+    #[cons(writable)]
+    pub orderbook: &'a T,
+
+    /// The AOB event queue
+    #[cons(writable)]
+    pub event_queue: &'a T,
+
+    /// The AOB bids shared memory
+    #[cons(writable)]
+    pub bids: &'a T,
+
+    /// The AOB asks shared memory
+    #[cons(writable)]
+    pub asks: &'a T,
+
+    /// The DEX user account whose heartbeat has expired
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The user account which receives the crank reward, if any
+    #[cons(writable)]
+    pub reward_target: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            event_queue: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            user: next_account_info(accounts_iter)?,
+            reward_target: next_account_info(accounts_iter)?,
+        };
+
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    market_state.check_orderbook(program_id, accounts.orderbook)?;
+
+    let number_of_cancelled_orders = {
+        let mut user_account_data = accounts.user.data.borrow_mut();
+        let mut user_account = UserAccount::from_buffer(&mut user_account_data)?;
+        if &user_account.header.market != accounts.market.key {
+            msg!("The provided user account doesn't match the current market");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if !user_account.header.is_heartbeat_expired(SysvarClock.now()?) {
+            msg!("The user account's heartbeat hasn't expired");
+            return Err(DexError::HeartbeatNotExpired.into());
+        }
+
+        cancel_user_orders(
+            program_id,
+            accounts.orderbook,
+            accounts.event_queue,
+            accounts.bids,
+            accounts.asks,
+            &mut market_state,
+            &mut user_account,
+        )?
+    };
+
+    msg!("Cancelled {} orders", number_of_cancelled_orders);
+
+    // The user account's data is released beforehand since it may also be the reward target
+    pay_crank_reward(
+        program_id,
+        accounts.market,
+        accounts.reward_target,
+        &mut market_state,
+        number_of_cancelled_orders,
+    )?;
+
+    market_state.update_best_prices(accounts.bids, accounts.asks, SysvarClock.now()?)?;
+
+    Ok(())
+}
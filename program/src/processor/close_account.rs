@@ -68,13 +68,16 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
 
     if user_account.header.number_of_orders != 0
         || user_account.header.quote_token_free != 0
+        || user_account.header.quote_token_locked != 0
         || user_account.header.base_token_free != 0
+        || user_account.header.base_token_locked != 0
     {
         msg!("The user account cannot be closed as it has pending orders or unsettled funds");
-        return Err(DexError::UserAccountStillActive.into());
+        return Err(DexError::AccountNotEmpty.into());
     }
 
-    user_account.header.tag = AccountTag::Closed as u64;
+    user_account_data.fill(0);
+    user_account_data[..8].copy_from_slice(&(AccountTag::Closed as u64).to_le_bytes());
 
     let mut lamports = accounts.user.lamports.borrow_mut();
     let mut target_lamports = accounts.target_lamports_account.lamports.borrow_mut();
@@ -2,7 +2,10 @@
 use crate::{
     error::DexError,
     state::{AccountTag, CallBackInfo, DexState},
-    utils::{check_account_key, check_account_owner, check_signer},
+    utils::{
+        check_account_key, check_account_owner, check_signer, check_token_program,
+        unpack_token_account,
+    },
 };
 use asset_agnostic_orderbook::error::AoError;
 use bonfida_utils::BorshSize;
@@ -16,11 +19,9 @@ use solana_program::{
     msg,
     program::invoke_signed,
     program_error::{PrintProgramError, ProgramError},
-    program_pack::Pack,
     pubkey::Pubkey,
 };
-use spl_token::instruction::close_account;
-use spl_token::state::Account;
+use spl_token_2022::instruction::close_account;
 
 #[derive(Clone, Copy, BorshDeserialize, BorshSerialize, BorshSize, Pod, Zeroable)]
 #[repr(C)]
@@ -93,11 +94,7 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         };
 
         // Check keys
-        check_account_key(
-            a.spl_token_program,
-            &spl_token::ID,
-            DexError::InvalidStateAccountOwner,
-        )?;
+        check_token_program(a.spl_token_program)?;
 
         // Check owners
         check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
@@ -119,8 +116,8 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
 
     check_accounts(program_id, &market_state, &accounts).unwrap();
 
-    let base_vault_data = Account::unpack_from_slice(&accounts.base_vault.data.borrow_mut())?;
-    let quote_vault_data = Account::unpack_from_slice(&accounts.quote_vault.data.borrow_mut())?;
+    let base_vault_data = unpack_token_account(accounts.base_vault)?;
+    let quote_vault_data = unpack_token_account(accounts.quote_vault)?;
 
     if base_vault_data.amount != 0 && quote_vault_data.amount != 0 {
         msg!("Market vaults need to be empty");
@@ -159,7 +156,7 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
 
     // Close token accounts
     let ix = close_account(
-        &spl_token::ID,
+        accounts.spl_token_program.key,
         accounts.base_vault.key,
         accounts.market.key,
         accounts.market_signer.key,
@@ -176,7 +173,7 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
         &[&[&accounts.market.key.to_bytes(), &[nonce]]],
     )?;
     let ix = close_account(
-        &spl_token::ID,
+        accounts.spl_token_program.key,
         accounts.quote_vault.key,
         accounts.market.key,
         accounts.market_signer.key,
@@ -208,13 +205,12 @@ fn check_accounts(
     market_state: &DexState,
     accounts: &Accounts<AccountInfo>,
 ) -> ProgramResult {
-    let market_signer = Pubkey::create_program_address(
-        &[
-            &accounts.market.key.to_bytes(),
-            &[market_state.signer_nonce as u8],
-        ],
-        program_id,
+    check_account_key(
+        accounts.spl_token_program,
+        &market_state.token_program,
+        DexError::InvalidSplTokenProgram,
     )?;
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
     check_account_key(
         accounts.market_signer,
         &market_signer,
@@ -4,14 +4,18 @@ use num_traits::FromPrimitive;
 
 use crate::{
     error::DexError,
-    state::{CallBackInfo, DexState, FeeTier, UserAccount},
-    utils::{check_account_key, check_account_owner, fp32_mul},
+    state::{
+        CallBackInfo, DexState, FeeTier, LiquidityMetrics, MarketStats, Order, TradeLog,
+        UserAccount,
+    },
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_key, check_account_owner, get_event_queue_head, get_event_queue_len},
 };
 use asset_agnostic_orderbook::{
     error::AoError,
     state::{
         event_queue::{EventQueue, EventRef, FillEvent, FillEventRef, OutEvent, OutEventRef},
-        AccountTag, Side,
+        AccountTag, SelfTradeBehavior, Side,
     },
 };
 use bonfida_utils::BorshSize;
@@ -23,6 +27,7 @@ use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
+    program::set_return_data,
     program_error::{PrintProgramError, ProgramError},
     pubkey::Pubkey,
 };
@@ -33,13 +38,49 @@ use solana_program::{
 The required arguments for a consume_events instruction.
 */
 pub struct Params {
-    /// The maximum number of events to consume
+    /// The maximum number of events to consume, which bounds the compute used by the instruction. The
+    /// `ConsumeEventsOutcome` return data tells how many events are left for another instruction to consume.
+    ///
+    /// Events are always consumed strictly from the head of the queue: the batch stops at the first event whose user
+    /// account wasn't provided, so that a crank can never skip events to process later ones first. The only exception
+    /// is consume_events_for_user, which the owner of a user account or its delegate signs to consume its own events
+    /// ahead of the others.
     pub max_iterations: u64,
     /// Decide if the transaction will fail when there are no events to consume.
     /// Useful for preflight verification.
     /// Value should be 0 or 1.
     /// Is u64 to allow for type casting.
     pub no_op_err: u64,
+    /// Whether or not the market's liquidity metrics account was given.
+    /// Value should be 0 or 1.
+    pub has_liquidity_metrics_account: u64,
+    /// Whether or not the market's stats account was given.
+    /// Value should be 0 or 1.
+    pub has_market_stats_account: u64,
+}
+
+/// The outcome of a consume_events instruction.
+///
+/// It is emitted as the instruction's return data so that cranks can keep sending instructions until the event queue
+/// is empty.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Default, PartialEq, Clone, Copy)]
+pub struct ConsumeEventsOutcome {
+    /// The number of events which were consumed
+    pub events_consumed: u64,
+    /// The number of events left in the event queue, including the events pushed by reposted iceberg slices
+    pub events_remaining: u64,
+    /// The index of the event queue's head once the consumed events were popped
+    pub head: u64,
+}
+
+/// The number of events at the head of the queue which can be consumed in order, given the user accounts credited by
+/// each queued event and the sorted user accounts provided to the instruction. Events past the first one whose user
+/// account is missing are never consumed, even if their own user account was provided.
+pub fn fifo_batch_len(event_user_accounts: &[Pubkey], user_accounts: &[Pubkey]) -> usize {
+    event_user_accounts
+        .iter()
+        .take_while(|k| user_accounts.binary_search(k).is_ok())
+        .count()
 }
 
 #[derive(InstructionsAccount)]
@@ -56,10 +97,30 @@ pub struct Accounts<'a, T> {
     #[cons(writable)]
     pub event_queue: &'a T,
 
-    /// The reward target
+    /// The AOB bids shared memory
+    #[cons(writable)]
+    pub bids: &'a T,
+
+    /// The AOB asks shared memory
+    #[cons(writable)]
+    pub asks: &'a T,
+
+    /// The market's trade log
+    #[cons(writable)]
+    pub trade_log: &'a T,
+
+    /// The user account which receives the crank reward, if any
     #[cons(writable)]
     pub reward_target: &'a T,
 
+    /// The market's optional liquidity metrics account, which records the makers of the consumed fills
+    #[cons(writable)]
+    pub liquidity_metrics: Option<&'a T>,
+
+    /// The market's optional stats account, which is refreshed once the events are consumed
+    #[cons(writable)]
+    pub market_stats: Option<&'a T>,
+
     /// The relevant user accounts
     #[cons(writable)]
     pub user_accounts: &'a [T],
@@ -69,17 +130,35 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
     pub fn parse(
         program_id: &Pubkey,
         accounts: &'a [AccountInfo<'b>],
+        has_liquidity_metrics_account: bool,
+        has_market_stats_account: bool,
     ) -> Result<Self, ProgramError> {
         let accounts_iter = &mut accounts.iter();
         let a = Self {
             market: next_account_info(accounts_iter)?,
             orderbook: next_account_info(accounts_iter)?,
             event_queue: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            trade_log: next_account_info(accounts_iter)?,
             reward_target: next_account_info(accounts_iter)?,
+            liquidity_metrics: if has_liquidity_metrics_account {
+                Some(next_account_info(accounts_iter)?)
+            } else {
+                None
+            },
+            market_stats: if has_market_stats_account {
+                Some(next_account_info(accounts_iter)?)
+            } else {
+                None
+            },
             user_accounts: accounts_iter.as_slice(),
         };
 
         check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        if let Some(market_stats) = a.market_stats {
+            check_account_owner(market_stats, program_id, DexError::InvalidStateAccountOwner)?;
+        }
 
         Ok(a)
     }
@@ -90,25 +169,65 @@ pub(crate) fn process(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
-    let accounts = Accounts::parse(program_id, accounts)?;
-
     let Params {
         max_iterations,
         no_op_err,
+        has_liquidity_metrics_account,
+        has_market_stats_account,
     } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
 
+    let accounts = Accounts::parse(
+        program_id,
+        accounts,
+        *has_liquidity_metrics_account == 1,
+        *has_market_stats_account == 1,
+    )?;
+
     let mut market_state = DexState::get(accounts.market)?;
 
     let mut event_queue_guard = accounts.event_queue.data.borrow_mut();
     let event_queue =
         EventQueue::<CallBackInfo>::from_buffer(&mut event_queue_guard, AccountTag::EventQueue)?;
 
-    check_accounts(&market_state, &accounts).unwrap();
+    check_accounts(program_id, &market_state, &accounts)?;
 
-    let mut total_iterations = 0;
+    let mut trade_log_data = accounts.trade_log.data.borrow_mut();
+    let mut trade_log = TradeLog::from_buffer(&mut trade_log_data)?;
 
-    for event in event_queue.iter().take(*max_iterations as usize) {
-        if consume_event(accounts.user_accounts, event, &mut market_state).is_err() {
+    let mut liquidity_metrics_data = accounts.liquidity_metrics.map(|a| a.data.borrow_mut());
+    let mut liquidity_metrics = liquidity_metrics_data
+        .as_mut()
+        .map(|d| LiquidityMetrics::from_buffer(&mut d[..]))
+        .transpose()?;
+
+    let current_timestamp = SysvarClock.now()?;
+    let mut total_iterations = 0;
+    let mut iceberg_slices = Vec::new();
+
+    let event_user_accounts = event_queue
+        .iter()
+        .take(*max_iterations as usize)
+        .map(|e| event_user_account(&e))
+        .collect::<Vec<_>>();
+    let user_account_keys = accounts
+        .user_accounts
+        .iter()
+        .map(|a| *a.key)
+        .collect::<Vec<_>>();
+    let batch_len = fifo_batch_len(&event_user_accounts, &user_account_keys);
+
+    for event in event_queue.iter().take(batch_len) {
+        if consume_event(
+            accounts.user_accounts,
+            event,
+            &mut market_state,
+            &mut trade_log,
+            liquidity_metrics.as_mut(),
+            &mut iceberg_slices,
+            current_timestamp,
+        )
+        .is_err()
+        {
             break;
         }
         total_iterations += 1;
@@ -119,13 +238,65 @@ pub(crate) fn process(
         if *no_op_err == 1 {
             return Err(DexError::NoOp.into());
         }
+        let outcome = ConsumeEventsOutcome {
+            events_consumed: 0,
+            events_remaining: event_queue.len() as u64,
+            head: event_queue.header.head,
+        };
+        set_return_data(&outcome.try_to_vec()?);
         return Ok(());
     }
 
     drop(event_queue_guard);
 
+    let head = pop_events(program_id, &accounts, total_iterations)?;
+
+    for slice in iceberg_slices {
+        repost_iceberg_slice(program_id, &accounts, &mut market_state, slice)?;
+    }
+
+    pay_crank_reward(
+        program_id,
+        accounts.market,
+        accounts.reward_target,
+        &mut market_state,
+        total_iterations,
+    )?;
+
+    market_state.update_best_prices(accounts.bids, accounts.asks, current_timestamp)?;
+    if let Some(market_stats) = accounts.market_stats {
+        MarketStats::mirror(
+            market_stats,
+            accounts.market.key,
+            &market_state,
+            current_timestamp,
+        )?;
+    }
+
+    let outcome = ConsumeEventsOutcome {
+        events_consumed: total_iterations,
+        events_remaining: get_event_queue_len(accounts.event_queue)?,
+        head,
+    };
+    set_return_data(&outcome.try_to_vec()?);
+
+    Ok(())
+}
+
+/// Removes the given number of events from the head of the event queue once they have been consumed, and returns the
+/// new head of the queue.
+///
+/// The head is checked to have moved by exactly the number of consumed events, which guarantees that events leave the
+/// queue in the order in which they were pushed.
+pub(crate) fn pop_events(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+    number_of_entries_to_consume: u64,
+) -> Result<u64, ProgramError> {
+    let (previous_head, capacity) = get_event_queue_head(accounts.event_queue)?;
+
     let invoke_params = asset_agnostic_orderbook::instruction::consume_events::Params {
-        number_of_entries_to_consume: total_iterations,
+        number_of_entries_to_consume,
     };
     let invoke_accounts = asset_agnostic_orderbook::instruction::consume_events::Accounts {
         market: accounts.orderbook,
@@ -141,22 +312,229 @@ pub(crate) fn process(
         return Err(DexError::AOBError.into());
     }
 
+    let (head, _) = get_event_queue_head(accounts.event_queue)?;
+    if head != (previous_head + number_of_entries_to_consume) % capacity {
+        msg!("The consumed events weren't popped from the head of the event queue");
+        return Err(DexError::EventQueueOutOfOrder.into());
+    }
+    Ok(head)
+}
+
+/// Crank rewards are taken out of the accumulated fees and credited to the reward target's free quote balance, from
+/// which they can be settled like any other funds. One reward is paid per consumed event or pruned order
+pub(crate) fn pay_crank_reward(
+    program_id: &Pubkey,
+    market: &AccountInfo,
+    reward_target: &AccountInfo,
+    market_state: &mut DexState,
+    rewarded_actions: u64,
+) -> ProgramResult {
+    let reward = std::cmp::min(
+        rewarded_actions.saturating_mul(market_state.crank_reward_per_event),
+        market_state.accumulated_fees,
+    );
+    if reward == 0 {
+        return Ok(());
+    }
+    check_account_owner(
+        reward_target,
+        program_id,
+        DexError::InvalidStateAccountOwner,
+    )?;
+    let mut reward_target_data = reward_target.data.borrow_mut();
+    let reward_target = UserAccount::from_buffer(&mut reward_target_data)?;
+    if &reward_target.header.market != market.key {
+        msg!("The reward target should be a user account of the current market");
+        return Err(ProgramError::InvalidArgument);
+    }
+    reward_target.header.quote_token_free = reward_target
+        .header
+        .quote_token_free
+        .checked_add(reward)
+        .ok_or(DexError::NumericalOverflow)?;
+    market_state.accumulated_fees -= reward;
+    msg!("Paid a crank reward of {}", reward);
     Ok(())
 }
 
-fn check_accounts(market_state: &DexState, accounts: &Accounts<AccountInfo>) -> ProgramResult {
+/// A filled iceberg order slice which has to be replenished from the order's hidden quantity
+pub(crate) struct IcebergSlice {
+    user_account_index: usize,
+    side: u8,
+    order_id: u128,
+    client_id: u128,
+    callback_info: CallBackInfo,
+}
+
+pub(crate) fn repost_iceberg_slice(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+    market_state: &mut DexState,
+    slice: IcebergSlice,
+) -> ProgramResult {
+    let IcebergSlice {
+        user_account_index,
+        side,
+        order_id,
+        client_id,
+        mut callback_info,
+    } = slice;
+    let limit_price = (order_id >> 64) as u64;
+    // Slices are reposted as post-only orders, which paused and cancel-only markets don't accept. The hidden quantity is
+    // released instead
+    if market_state.check_new_order_allowed(true).is_err() {
+        msg!("The iceberg slice is not reposted in the market's current status");
+        let mut user_account_data = accounts.user_accounts[user_account_index].data.borrow_mut();
+        let mut user_account = UserAccount::from_buffer(&mut user_account_data)?;
+        release_locked_base(
+            market_state,
+            &mut user_account,
+            side,
+            limit_price,
+            callback_info.hidden_base_qty,
+        );
+        return Ok(());
+    }
+    let slice_base_qty = std::cmp::min(
+        callback_info.display_base_qty,
+        callback_info.hidden_base_qty,
+    );
+    callback_info.hidden_base_qty -= slice_base_qty;
+    let invoke_params = asset_agnostic_orderbook::instruction::new_order::Params {
+        max_base_qty: market_state.scale_base_amount(slice_base_qty),
+        max_quote_qty: u64::MAX,
+        limit_price,
+        side: FromPrimitive::from_u8(side).unwrap(),
+        match_limit: 1,
+        callback_info,
+        // A slice which would cross the book isn't posted, which also upholds the post-only status
+        post_only: true,
+        post_allowed: true,
+        self_trade_behavior: SelfTradeBehavior::DecrementTake,
+    };
+    let invoke_accounts = asset_agnostic_orderbook::instruction::new_order::Accounts {
+        market: accounts.orderbook,
+        event_queue: accounts.event_queue,
+        bids: accounts.bids,
+        asks: accounts.asks,
+    };
+    let mut order_summary = match asset_agnostic_orderbook::instruction::new_order::process(
+        program_id,
+        invoke_accounts,
+        invoke_params,
+    ) {
+        Err(error) => {
+            error.print::<AoError>();
+            return Err(DexError::AOBError.into());
+        }
+        Ok(s) => s,
+    };
+    market_state
+        .unscale_order_summary(&mut order_summary)
+        .unwrap();
+    market_state.add_resting_base(
+        FromPrimitive::from_u8(side).unwrap(),
+        order_summary.total_base_qty_posted,
+    );
+
+    let mut user_account_data = accounts.user_accounts[user_account_index].data.borrow_mut();
+    let mut user_account = UserAccount::from_buffer(&mut user_account_data)?;
+
+    // Whatever could not be reposted is released back to the user
+    let base_qty_to_release = match order_summary.posted_order_id {
+        Some(new_order_id) => {
+            user_account.add_order(Order {
+                id: new_order_id,
+                client_id,
+            })?;
+            msg!("Reposted iceberg slice with order_id {:?}", new_order_id);
+            slice_base_qty - order_summary.total_base_qty_posted
+        }
+        None => {
+            msg!("The iceberg slice could not be reposted");
+            slice_base_qty + callback_info.hidden_base_qty
+        }
+    };
+    release_locked_base(
+        market_state,
+        &mut user_account,
+        side,
+        limit_price,
+        base_qty_to_release,
+    );
+
+    Ok(())
+}
+
+/// Moves the funds locked by the given base quantity of an order back to the user's free balance
+fn release_locked_base(
+    market_state: &DexState,
+    user_account: &mut UserAccount,
+    side: u8,
+    price: u64,
+    base_qty: u64,
+) {
+    if base_qty == 0 {
+        return;
+    }
+    match Side::from_u8(side).unwrap() {
+        Side::Ask => {
+            user_account.header.base_token_free = user_account
+                .header
+                .base_token_free
+                .checked_add(base_qty)
+                .unwrap();
+            user_account.header.base_token_locked = user_account
+                .header
+                .base_token_locked
+                .checked_sub(base_qty)
+                .unwrap();
+        }
+        Side::Bid => {
+            let qty_to_transfer = market_state.get_quote_from_base(base_qty, price);
+            user_account.header.quote_token_free = user_account
+                .header
+                .quote_token_free
+                .checked_add(qty_to_transfer.unwrap())
+                .unwrap();
+            user_account.header.quote_token_locked = user_account
+                .header
+                .quote_token_locked
+                .checked_sub(qty_to_transfer.unwrap())
+                .unwrap();
+        }
+    }
+}
+
+pub(crate) fn check_accounts(
+    program_id: &Pubkey,
+    market_state: &DexState,
+    accounts: &Accounts<AccountInfo>,
+) -> ProgramResult {
+    market_state.check_orderbook(program_id, accounts.orderbook)?;
     check_account_key(
-        accounts.orderbook,
-        &market_state.orderbook,
-        DexError::InvalidOrderbookAccount,
+        accounts.trade_log,
+        &market_state.trade_log,
+        DexError::InvalidTradeLogAccount,
     )?;
+    if let Some(a) = accounts.liquidity_metrics {
+        check_account_key(
+            a,
+            &market_state.liquidity_metrics,
+            DexError::InvalidLiquidityMetricsAccount,
+        )?;
+    }
     Ok(())
 }
 
-fn consume_event(
+pub(crate) fn consume_event(
     accounts: &[AccountInfo],
     event: EventRef<CallBackInfo>,
     market_state: &mut DexState,
+    trade_log: &mut TradeLog,
+    liquidity_metrics: Option<&mut LiquidityMetrics>,
+    iceberg_slices: &mut Vec<IcebergSlice>,
+    current_timestamp: i64,
 ) -> Result<(), DexError> {
     match event {
         EventRef::Fill(FillEventRef {
@@ -164,11 +542,13 @@ fn consume_event(
             maker_callback_info,
             taker_callback_info,
         }) => {
+            let maker_callback_info = CallBackInfo::read(maker_callback_info)?;
+            let taker_callback_info = CallBackInfo::read(taker_callback_info)?;
             let FillEvent {
                 tag: _,
                 taker_side,
                 mut quote_size,
-                maker_order_id: _,
+                maker_order_id,
                 mut base_size,
                 ..
             } = event;
@@ -181,33 +561,30 @@ fn consume_event(
             let maker_account_info = &accounts[accounts
                 .binary_search_by_key(&maker_callback_info.user_account, |k| *k.key)
                 .map_err(|_| DexError::MissingUserAccount)?];
+            // Both fee tiers were snapshotted at order placement, no discount token account is read here
             let (taker_fee_tier, is_referred) = FeeTier::from_u8(taker_callback_info.fee_tier);
             let mut maker_account_data = maker_account_info.data.borrow_mut();
             let mut maker_account = UserAccount::from_buffer(&mut maker_account_data).unwrap();
             let (maker_fee_tier, _) = FeeTier::from_u8(maker_callback_info.fee_tier);
-            let taker_fee = taker_fee_tier.taker_fee(quote_size);
-            let maker_rebate = maker_fee_tier.maker_rebate(quote_size);
+            // The taker was charged the fee of its whole matched quantity and its referrer was paid at that point,
+            // only the rounded down share of this fill is credited
+            let taker_fee = taker_fee_tier.taker_fee_credit(market_state, quote_size)?;
+            let net_taker_fee =
+                taker_fee_tier.net_taker_fee_credit(market_state, quote_size, is_referred)?;
+            let maker_rebate = maker_fee_tier.maker_rebate(market_state, quote_size)?;
             let royalties_fee =
                 market_state.royalties_bps.checked_mul(quote_size).unwrap() / 10_000;
-            let referral_fee = if is_referred {
-                taker_fee_tier.referral_fee(quote_size)
-            } else {
-                0
-            };
-            let total_fees = taker_fee
-                .checked_sub(maker_rebate)
-                .and_then(|n| n.checked_sub(referral_fee))
-                .unwrap();
 
-            market_state.accumulated_fees = market_state
-                .accumulated_fees
-                .checked_add(total_fees)
-                .unwrap();
+            // The rebates are paid out of the accumulated fees, which they can never overdraw
+            let rebate_boost =
+                maker_fee_tier.maker_rebate_boost(market_state, quote_size, current_timestamp)?;
+            let (maker_rebate, rebate_boost) =
+                market_state.pay_maker_rebates(net_taker_fee, maker_rebate, rebate_boost);
+            let maker_rebate = maker_rebate + rebate_boost;
 
             market_state.accumulated_royalties = market_state
                 .accumulated_royalties
-                .checked_add(royalties_fee)
-                .unwrap();
+                .saturating_add(royalties_fee);
 
             match Side::from_u8(*taker_side).unwrap() {
                 Side::Bid => {
@@ -216,7 +593,10 @@ fn consume_event(
                         .quote_token_free
                         .checked_add(quote_size + maker_rebate)
                         .unwrap();
-                    maker_account.header.accumulated_rebates += maker_rebate;
+                    maker_account.header.accumulated_rebates = maker_account
+                        .header
+                        .accumulated_rebates
+                        .saturating_add(maker_rebate);
                     maker_account.header.base_token_locked = maker_account
                         .header
                         .base_token_locked
@@ -234,12 +614,15 @@ fn consume_event(
                         .quote_token_locked
                         .checked_sub(quote_size)
                         .unwrap();
-                    maker_account
+                    maker_account.header.quote_token_free = maker_account
                         .header
                         .quote_token_free
                         .checked_add(maker_rebate)
                         .unwrap();
-                    maker_account.header.accumulated_rebates += maker_rebate;
+                    maker_account.header.accumulated_rebates = maker_account
+                        .header
+                        .accumulated_rebates
+                        .saturating_add(maker_rebate);
                 }
             };
 
@@ -247,30 +630,52 @@ fn consume_event(
             maker_account.header.accumulated_maker_quote_volume = maker_account
                 .header
                 .accumulated_maker_quote_volume
-                .checked_add(quote_size)
-                .unwrap();
+                .saturating_add(quote_size);
             maker_account.header.accumulated_maker_base_volume = maker_account
                 .header
                 .accumulated_maker_base_volume
-                .checked_add(base_size)
-                .unwrap();
+                .saturating_add(base_size);
+
+            maker_account
+                .header
+                .record_rolling_base_volume(base_size, current_timestamp);
+
+            if let Some(liquidity_metrics) = liquidity_metrics {
+                market_state.record_maker(liquidity_metrics, &maker_callback_info.user_account);
+            }
 
-            market_state.quote_volume = market_state.quote_volume.checked_add(quote_size).unwrap();
-            market_state.base_volume = market_state.base_volume.checked_add(base_size).unwrap();
+            market_state.quote_volume = market_state.quote_volume.saturating_add(quote_size);
+            market_state.base_volume = market_state.base_volume.saturating_add(base_size);
+            market_state.last_trade_price = (maker_order_id >> 64) as u64;
+            market_state.last_trade_timestamp = current_timestamp;
+
+            let fill = crate::state::FillEvent {
+                maker: maker_callback_info.user_account,
+                taker: taker_callback_info.user_account,
+                price: (maker_order_id >> 64) as u64,
+                base_qty: base_size,
+                quote_qty: quote_size,
+                taker_fee,
+                timestamp: current_timestamp,
+            };
+            fill.log();
+            trade_log.push(fill);
         }
         EventRef::Out(OutEventRef {
             event,
             callback_info,
         }) => {
+            let callback_info = CallBackInfo::read(callback_info)?;
             let OutEvent {
                 side,
                 order_id,
                 mut base_size,
                 ..
             } = event;
-            let user_account_info = &accounts[accounts
+            let user_account_index = accounts
                 .binary_search_by_key(&callback_info.user_account, |k| *k.key)
-                .map_err(|_| DexError::MissingUserAccount)?];
+                .map_err(|_| DexError::MissingUserAccount)?;
+            let user_account_info = &accounts[user_account_index];
             let mut user_account_data = user_account_info.data.borrow_mut();
             let mut user_account = UserAccount::from_buffer(&mut user_account_data).unwrap();
 
@@ -278,39 +683,41 @@ fn consume_event(
                 .checked_mul(market_state.base_currency_multiplier)
                 .unwrap();
 
+            let price = (order_id >> 64) as u64;
             if base_size != 0 {
-                match Side::from_u8(*side).unwrap() {
-                    Side::Ask => {
-                        user_account.header.base_token_free = user_account
-                            .header
-                            .base_token_free
-                            .checked_add(base_size)
-                            .unwrap();
-                        user_account.header.base_token_locked = user_account
-                            .header
-                            .base_token_locked
-                            .checked_sub(base_size)
-                            .unwrap();
-                    }
-                    Side::Bid => {
-                        let price = (order_id >> 64) as u64;
-                        let qty_to_transfer = fp32_mul(base_size, price);
-                        user_account.header.quote_token_free = user_account
-                            .header
-                            .quote_token_free
-                            .checked_add(qty_to_transfer.unwrap())
-                            .unwrap();
-                        user_account.header.quote_token_locked = user_account
-                            .header
-                            .quote_token_locked
-                            .checked_sub(qty_to_transfer.unwrap())
-                            .unwrap();
-                    }
-                }
+                market_state.remove_resting_base(Side::from_u8(*side).unwrap(), base_size);
+                // The order was cancelled, its hidden iceberg quantity is released along with it
+                release_locked_base(
+                    market_state,
+                    &mut user_account,
+                    *side,
+                    price,
+                    base_size + callback_info.hidden_base_qty,
+                );
             }
             let order_index = user_account.find_order_index(*order_id).unwrap();
+            if base_size == 0 && callback_info.hidden_base_qty != 0 {
+                iceberg_slices.push(IcebergSlice {
+                    user_account_index,
+                    side: *side,
+                    order_id: *order_id,
+                    client_id: user_account.read_order(order_index).unwrap().client_id,
+                    callback_info,
+                });
+            }
             user_account.remove_order(order_index).unwrap();
         }
     };
     Ok(())
 }
+
+/// The user account which is credited when the event is consumed
+pub(crate) fn event_user_account(event: &EventRef<CallBackInfo>) -> Pubkey {
+    match event {
+        EventRef::Fill(FillEventRef {
+            maker_callback_info,
+            ..
+        }) => maker_callback_info.user_account,
+        EventRef::Out(OutEventRef { callback_info, .. }) => callback_info.user_account,
+    }
+}
@@ -0,0 +1,276 @@
+//! Crank the processing of the DEX events which credit a single user account.
+//!
+//! Fill events are consumed when the user account is their maker, the taker side of a fill being settled when the
+//! order is placed, and out events are consumed when the user account owns the order. All other events are left in
+//! the queue, in their original order, for a regular consume_events instruction to process.
+//!
+//! The user's events are consumed ahead of the events queued before them, which means that the market's trade
+//! statistics and the trade log record them in that order. Since this bypasses the FIFO order of consume_events, only
+//! the owner of the user account or its delegate can crank its events this way.
+use crate::{
+    error::DexError,
+    processor::consume_events::{
+        self, consume_event, event_user_account, pay_crank_reward, pop_events, ConsumeEventsOutcome,
+    },
+    state::{CallBackInfo, DexState, LiquidityMetrics, TradeLog, UserAccount},
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_owner, check_signer, get_event_queue_len},
+};
+use asset_agnostic_orderbook::state::{event_queue::EventQueue, AccountTag};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::set_return_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a consume_events_for_user instruction.
+*/
+pub struct Params {
+    /// The maximum number of queued events to go through, starting from the head of the queue. Events which don't
+    /// belong to the user account count towards this limit as well.
+    pub max_iterations: u64,
+    /// Decide if the transaction will fail when there are no events to consume.
+    /// Value should be 0 or 1.
+    pub no_op_err: u64,
+    /// Whether or not the market's liquidity metrics account was given.
+    /// Value should be 0 or 1.
+    pub has_liquidity_metrics_account: u64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The orderbook
+    #[cons(writable)]
+    pub orderbook: &'a T,
+
+    /// The AOB event queue
+    #[cons(writable)]
+    pub event_queue: &'a T,
+
+    /// The AOB bids shared memory
+    #[cons(writable)]
+    pub bids: &'a T,
+
+    /// The AOB asks shared memory
+    #[cons(writable)]
+    pub asks: &'a T,
+
+    /// The market's trade log
+    #[cons(writable)]
+    pub trade_log: &'a T,
+
+    /// The user account which receives the crank reward, if any
+    #[cons(writable)]
+    pub reward_target: &'a T,
+
+    /// The market's optional liquidity metrics account, which records the makers of the consumed fills
+    #[cons(writable)]
+    pub liquidity_metrics: Option<&'a T>,
+
+    /// The user account whose events should be consumed
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The owner of the user account, or its delegate
+    #[cons(signer)]
+    pub user_owner: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+        has_liquidity_metrics_account: bool,
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            event_queue: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            trade_log: next_account_info(accounts_iter)?,
+            reward_target: next_account_info(accounts_iter)?,
+            liquidity_metrics: if has_liquidity_metrics_account {
+                Some(next_account_info(accounts_iter)?)
+            } else {
+                None
+            },
+            user: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+        };
+
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner or delegate should be a signer for this transaction!");
+            e
+        })?;
+
+        Ok(a)
+    }
+
+    /// The accounts of a consume_events instruction given the user account alone
+    fn consume_events_accounts(&self) -> consume_events::Accounts<'a, AccountInfo<'b>> {
+        consume_events::Accounts {
+            market: self.market,
+            orderbook: self.orderbook,
+            event_queue: self.event_queue,
+            bids: self.bids,
+            asks: self.asks,
+            trade_log: self.trade_log,
+            reward_target: self.reward_target,
+            liquidity_metrics: self.liquidity_metrics,
+            market_stats: None,
+            user_accounts: std::slice::from_ref(self.user),
+        }
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let Params {
+        max_iterations,
+        no_op_err,
+        has_liquidity_metrics_account,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let accounts = Accounts::parse(program_id, accounts, *has_liquidity_metrics_account == 1)?;
+    {
+        let mut user_account_data = accounts.user.data.borrow_mut();
+        let user_account = UserAccount::from_buffer(&mut user_account_data)?;
+        if !user_account
+            .header
+            .is_order_authority(accounts.user_owner.key)
+        {
+            msg!("Invalid user account owner or delegate provided!");
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+    let accounts = accounts.consume_events_accounts();
+    let user = &accounts.user_accounts[0];
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    let mut event_queue_guard = accounts.event_queue.data.borrow_mut();
+    let mut event_queue =
+        EventQueue::<CallBackInfo>::from_buffer(&mut event_queue_guard, AccountTag::EventQueue)?;
+
+    consume_events::check_accounts(program_id, &market_state, &accounts)?;
+
+    let mut trade_log_data = accounts.trade_log.data.borrow_mut();
+    let mut trade_log = TradeLog::from_buffer(&mut trade_log_data)?;
+
+    let mut liquidity_metrics_data = accounts.liquidity_metrics.map(|a| a.data.borrow_mut());
+    let mut liquidity_metrics = liquidity_metrics_data
+        .as_mut()
+        .map(|d| LiquidityMetrics::from_buffer(&mut d[..]))
+        .transpose()?;
+
+    let current_timestamp = SysvarClock.now()?;
+    let mut is_consumed = Vec::with_capacity(*max_iterations as usize);
+    let mut iceberg_slices = Vec::new();
+
+    for event in event_queue.iter().take(*max_iterations as usize) {
+        if &event_user_account(&event) != user.key {
+            is_consumed.push(false);
+            continue;
+        }
+        if consume_event(
+            accounts.user_accounts,
+            event,
+            &mut market_state,
+            &mut trade_log,
+            liquidity_metrics.as_mut(),
+            &mut iceberg_slices,
+            current_timestamp,
+        )
+        .is_err()
+        {
+            break;
+        }
+        is_consumed.push(true);
+    }
+
+    let total_iterations = is_consumed.iter().filter(|c| **c).count() as u64;
+    if total_iterations == 0 {
+        msg!("No event of the user account was consumed");
+        if *no_op_err == 1 {
+            return Err(DexError::NoOp.into());
+        }
+        let outcome = ConsumeEventsOutcome {
+            events_consumed: 0,
+            events_remaining: event_queue.len() as u64,
+            head: event_queue.header.head,
+        };
+        set_return_data(&outcome.try_to_vec()?);
+        return Ok(());
+    }
+
+    move_consumed_events_to_head(&mut event_queue, &is_consumed);
+    drop(event_queue_guard);
+
+    let head = pop_events(program_id, &accounts, total_iterations)?;
+
+    for slice in iceberg_slices {
+        consume_events::repost_iceberg_slice(program_id, &accounts, &mut market_state, slice)?;
+    }
+
+    pay_crank_reward(
+        program_id,
+        accounts.market,
+        accounts.reward_target,
+        &mut market_state,
+        total_iterations,
+    )?;
+
+    market_state.update_best_prices(accounts.bids, accounts.asks, current_timestamp)?;
+
+    let outcome = ConsumeEventsOutcome {
+        events_consumed: total_iterations,
+        events_remaining: get_event_queue_len(accounts.event_queue)?,
+        head,
+    };
+    set_return_data(&outcome.try_to_vec()?);
+
+    Ok(())
+}
+
+/// Shifts the skipped events towards the tail of the first `is_consumed.len()` queued events, keeping their order,
+/// so that the consumed events end up at the head of the queue from which they are popped
+fn move_consumed_events_to_head(event_queue: &mut EventQueue<CallBackInfo>, is_consumed: &[bool]) {
+    let capacity = event_queue.events.len();
+    let head = event_queue.header.head as usize;
+    let mut write_index = is_consumed.len();
+    for read_index in (0..is_consumed.len()).rev() {
+        if is_consumed[read_index] {
+            continue;
+        }
+        write_index -= 1;
+        if write_index != read_index {
+            let from = (head + read_index) % capacity;
+            let to = (head + write_index) % capacity;
+            event_queue.events[to] = event_queue.events[from];
+            event_queue.callback_infos[2 * to] = event_queue.callback_infos[2 * from];
+            event_queue.callback_infos[2 * to + 1] = event_queue.callback_infos[2 * from + 1];
+        }
+    }
+}
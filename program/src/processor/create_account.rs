@@ -0,0 +1,138 @@
+//! Initialize a user account which the fee payer has already allocated with a system program create_account
+//! instruction, sized for an explicit number of order slots.
+//!
+//! The account should be owned by the DEX program, rent-exempt, and exactly
+//! `USER_ACCOUNT_HEADER_LEN + max_orders * Order::LEN` bytes long, see `user_account_size`. Unlike initialize_account,
+//! the user account isn't derived from the market and owner, which is why it should sign the transaction.
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    error::DexError,
+    state::{
+        AccountTag, DexState, Order, UserAccount, UserAccountHeader, MAX_ORDERS_PER_ACCOUNT,
+        USER_ACCOUNT_HEADER_LEN,
+    },
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_owner, check_signer},
+};
+
+#[derive(Clone, Copy, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a create_account instruction.
+*/
+pub struct Params {
+    /// The maximum number of orders the user account may hold, which determines its size
+    pub max_orders: u32,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The user account's parent market
+    pub market: &'a T,
+
+    /// The user account to initialize, allocated beforehand and owned by the DEX program
+    #[cons(writable, signer)]
+    pub user: &'a T,
+
+    /// The owner of the user account
+    #[cons(signer)]
+    pub user_owner: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            user: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.user).map_err(|e| {
+            msg!("The user account should be a signer for this transaction!");
+            e
+        })?;
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+/// The size in bytes of a user account which can hold the given number of orders
+pub fn user_account_size(max_orders: u32) -> usize {
+    USER_ACCOUNT_HEADER_LEN + (max_orders as usize) * Order::LEN
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params { max_orders } =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    // Only checks that the market is a DEX market
+    DexState::get(accounts.market)?;
+
+    if *max_orders == 0 || *max_orders as usize > MAX_ORDERS_PER_ACCOUNT {
+        msg!(
+            "A user account should be able to hold between 1 and {} orders",
+            MAX_ORDERS_PER_ACCOUNT
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let space = user_account_size(*max_orders);
+    if accounts.user.data_len() != space {
+        msg!(
+            "A user account holding {} orders should be {} bytes long, not {}",
+            max_orders,
+            space,
+            accounts.user.data_len()
+        );
+        return Err(DexError::InvalidUserAccountSize.into());
+    }
+    if !Rent::get()?.is_exempt(accounts.user.lamports(), space) {
+        msg!("The user account should be funded to be rent-exempt");
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let u = UserAccount::from_buffer_unchecked(&mut user_account_data)?;
+    if u.header.tag != AccountTag::Uninitialized as u64 {
+        msg!("The user account is already initialized");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    *(u.header) = UserAccountHeader::new(
+        accounts.market.key,
+        accounts.user_owner.key,
+        &Pubkey::default(),
+        SysvarClock.now()?,
+    );
+
+    Ok(())
+}
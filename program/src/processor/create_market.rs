@@ -1,8 +1,16 @@
 //! Creates a new DEX market
 use crate::{
     error::DexError,
-    state::{AccountTag, CallBackInfo, DexState, MarketFeeType},
-    utils::{check_account_owner, check_metadata_account, verify_metadata},
+    processor::set_program_config::get_program_config_address,
+    state::{
+        find_market_signer, AccountTag, CallBackInfo, DexState, FeeRounding, MarketFeeType,
+        MarketStatus, ProgramConfig, TradeLog, DEX_STATE_VERSION,
+    },
+    time::{SysvarClock, TimeProvider},
+    utils::{
+        check_account_key, check_account_owner, check_metadata_account, check_signer,
+        check_token_program_owner, unpack_token_account, verify_metadata,
+    },
 };
 use asset_agnostic_orderbook::error::AoError;
 use bonfida_utils::checks::check_rent_exempt;
@@ -14,14 +22,18 @@ use bytemuck::{try_from_bytes, Pod, Zeroable};
 use mpl_token_metadata::state::{Metadata, TokenMetadataAccount};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    clock::Clock,
     entrypoint::ProgramResult,
     msg,
+    program::invoke,
     program_error::{PrintProgramError, ProgramError},
-    program_pack::Pack,
     pubkey::Pubkey,
-    sysvar::Sysvar,
+    system_instruction::transfer,
+    system_program,
 };
+use spl_token_2022::{extension::StateWithExtensions, state::Mint};
+
+/// The maximum number of decimals of the market's mints, above which prices can't be meaningfully scaled
+pub const MAX_MINT_DECIMALS: u8 = 18;
 
 #[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
 #[repr(C)]
@@ -29,13 +41,19 @@ use solana_program::{
 The required arguments for a create_market instruction.
 */
 pub struct Params {
-    /// The market's signer nonce (u64 for padding)
+    /// The market's signer nonce (u64 for padding), which should be the canonical nonce given by `find_market_signer`
     pub signer_nonce: u64,
     /// The minimum allowed order size in base token amount
     pub min_base_order_size: u64,
     pub tick_size: u64,
     pub base_currency_multiplier: u64,
     pub quote_currency_multiplier: u64,
+    /// The taker fee rate of the base fee tier as an FP32. Zero selects the default rate
+    pub base_taker_rate: u64,
+    /// The maker rebate rate as an FP32. It cannot exceed the lowest taker fee rate
+    pub base_maker_rebate: u64,
+    /// The base quantity increment in base token amount. Zero defaults to 1
+    pub base_lot_size: u64,
 }
 
 #[derive(InstructionsAccount)]
@@ -71,6 +89,31 @@ pub struct Accounts<'a, T> {
 
     /// The metaplex token metadata
     pub token_metadata: &'a T,
+
+    /// The trade log account which the market's fills will be recorded to
+    #[cons(writable)]
+    pub trade_log: &'a T,
+
+    /// The base mint, whose decimals are recorded in the market
+    pub base_mint: &'a T,
+
+    /// The quote mint, whose decimals are recorded in the market
+    pub quote_mint: &'a T,
+
+    /// The program config account, at the address given by `get_program_config_address`, which sets the market creation
+    /// fee. No fee is charged while it doesn't exist
+    pub program_config: &'a T,
+
+    /// The system program, which is required when the program charges a market creation fee
+    pub system_program: Option<&'a T>,
+
+    /// The account which pays the market creation fee, required along with the system program
+    #[cons(writable, signer)]
+    pub creation_fee_payer: Option<&'a T>,
+
+    /// The program's treasury, which collects the market creation fee
+    #[cons(writable)]
+    pub creation_fee_treasury: Option<&'a T>,
 }
 
 impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
@@ -90,22 +133,38 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
             asks: next_account_info(accounts_iter)?,
             bids: next_account_info(accounts_iter)?,
             token_metadata: next_account_info(accounts_iter)?,
+            trade_log: next_account_info(accounts_iter)?,
+            base_mint: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
+            program_config: next_account_info(accounts_iter)?,
+            system_program: next_account_info(accounts_iter).ok(),
+            creation_fee_payer: next_account_info(accounts_iter).ok(),
+            creation_fee_treasury: next_account_info(accounts_iter).ok(),
         };
+        a.check_account_owners(program_id)?;
+
+        Ok(a)
+    }
 
-        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
-        check_account_owner(a.orderbook, program_id, DexError::InvalidStateAccountOwner)?;
+    pub(crate) fn check_account_owners(&self, program_id: &Pubkey) -> ProgramResult {
+        check_account_owner(self.market, program_id, DexError::InvalidStateAccountOwner)?;
         check_account_owner(
-            a.base_vault,
-            &spl_token::ID,
+            self.orderbook,
+            program_id,
             DexError::InvalidStateAccountOwner,
         )?;
         check_account_owner(
-            a.quote_vault,
-            &spl_token::ID,
+            self.trade_log,
+            program_id,
             DexError::InvalidStateAccountOwner,
         )?;
-
-        Ok(a)
+        check_token_program_owner(self.base_vault, DexError::InvalidStateAccountOwner)?;
+        check_token_program_owner(self.quote_vault, DexError::InvalidStateAccountOwner)?;
+        if self.base_vault.owner != self.quote_vault.owner {
+            msg!("Both vaults should belong to the same token program");
+            return Err(DexError::InvalidStateAccountOwner.into());
+        }
+        Ok(())
     }
 }
 
@@ -116,7 +175,21 @@ pub(crate) fn process(
 ) -> ProgramResult {
     let accounts = Accounts::parse(program_id, accounts)?;
 
-    check_rent(&accounts)?;
+    let params =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    execute(program_id, &accounts, params)
+}
+
+/// Initializes the market and its orderbook once the account owners have been checked.
+///
+/// This is shared with the create_market_with_orderbook instruction, which allocates the orderbook account first.
+pub(crate) fn execute(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+    params: &Params,
+) -> ProgramResult {
+    check_rent(accounts)?;
 
     let Params {
         signer_nonce,
@@ -124,30 +197,56 @@ pub(crate) fn process(
         tick_size,
         base_currency_multiplier,
         quote_currency_multiplier,
-    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+        base_taker_rate,
+        base_maker_rebate,
+        base_lot_size,
+    } = params;
 
     if base_currency_multiplier == &0 || quote_currency_multiplier == &0 || tick_size == &0 {
         msg!("The currency multipliers and ticksize should be nonzero!");
         return Err(ProgramError::InvalidArgument);
     }
 
-    let market_signer = Pubkey::create_program_address(
-        &[&accounts.market.key.to_bytes(), &[*signer_nonce as u8]],
-        program_id,
-    )?;
+    let (market_signer, canonical_signer_nonce) =
+        find_market_signer(accounts.market.key, program_id);
+    if *signer_nonce != canonical_signer_nonce as u64 {
+        msg!(
+            "The signer nonce should be the canonical nonce {}",
+            canonical_signer_nonce
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
     let base_mint = check_vault_account_and_get_mint(accounts.base_vault, &market_signer)?;
     let quote_mint = check_vault_account_and_get_mint(accounts.quote_vault, &market_signer)?;
+    if base_mint == quote_mint {
+        msg!("The base and quote vaults should hold different tokens");
+        return Err(DexError::InvalidMarketMints.into());
+    }
+    let base_decimals = check_mint_account_and_get_decimals(
+        accounts.base_mint,
+        &base_mint,
+        accounts.base_vault.owner,
+    )?;
+    let quote_decimals = check_mint_account_and_get_decimals(
+        accounts.quote_mint,
+        &quote_mint,
+        accounts.quote_vault.owner,
+    )?;
 
     #[cfg(not(feature = "disable-mpl-checks"))]
     check_metadata_account(accounts.token_metadata, &base_mint)?;
 
-    let current_timestamp = Clock::get()?.unix_timestamp;
+    let current_timestamp = SysvarClock.now()?;
     if accounts.market.data.borrow()[0] != AccountTag::Uninitialized as u8 {
         // Checking the first byte is sufficient as there is a small number of AccountTags
         msg!("The market account contains initialized state!");
         return Err(ProgramError::InvalidArgument);
     }
 
+    collect_creation_fee(program_id, accounts)?;
+
+    initialize_trade_log(accounts.trade_log, accounts.market.key)?;
+
     let mut market_state = DexState::get_unchecked(accounts.market);
 
     let royalties_bps = if accounts.token_metadata.data_len() != 0 {
@@ -178,12 +277,58 @@ pub(crate) fn process(
         accumulated_fees: 0,
         min_base_order_size: *min_base_order_size,
         fee_type: MarketFeeType::Default as u8,
-        _padding: [0; 6],
+        status: MarketStatus::Active as u8,
+        is_quote_native: (quote_mint == spl_token::native_mint::ID
+            || quote_mint == spl_token_2022::native_mint::ID) as u8,
+        base_decimals,
+        quote_decimals,
+        version: DEX_STATE_VERSION,
+        fee_rounding: FeeRounding::Down as u8,
+        strict_fee_reserve: 0,
+        _padding: [0; 11],
+        legacy_orderbook: Pubkey::default(),
+        _reserved: [0; 224],
         royalties_bps: royalties_bps as u64,
         accumulated_royalties: 0,
         base_currency_multiplier: *base_currency_multiplier,
         quote_currency_multiplier: *quote_currency_multiplier,
+        base_taker_rate: *base_taker_rate,
+        base_maker_rebate: *base_maker_rebate,
+        referral_fee_share_bps: 0,
+        pending_admin: Pubkey::default(),
+        tick_size: *tick_size,
+        base_lot_size: std::cmp::max(*base_lot_size, 1),
+        trade_log: *accounts.trade_log.key,
+        token_program: *accounts.base_vault.owner,
+        oracle: Pubkey::default(),
+        max_deviation_bps: 0,
+        max_oracle_staleness: 0,
+        crank_reward_per_event: 0,
+        min_quote_order_size: 0,
+        best_bid_price: 0,
+        best_ask_price: u64::MAX,
+        twap_last_update: current_timestamp,
+        twap_price_cumulative: [0; 2],
+        last_trade_price: 0,
+        last_trade_timestamp: 0,
+        total_bid_base: 0,
+        total_ask_base: 0,
+        rebate_boost_rate: 0,
+        rebate_boost_end: 0,
+        accumulated_boosted_rebates: 0,
+        self_trade_fee_rate: 0,
+        liquidity_metrics: Pubkey::default(),
+        unique_makers: 0,
+        liquidity_metrics_epoch_start: 0,
+        fee_recipients: Pubkey::default(),
+        auto_halt_deviation_bps: 0,
+        total_base_in_vault: 0,
+        total_quote_in_vault: 0,
+        access_authority: Pubkey::default(),
+        max_price: 0,
+        max_orders_per_account: 0,
     };
+    market_state.check_fee_schedule()?;
 
     let invoke_params = asset_agnostic_orderbook::instruction::create_market::Params {
         min_base_order_size: *min_base_order_size / *base_currency_multiplier,
@@ -212,7 +357,7 @@ fn check_vault_account_and_get_mint(
     account: &AccountInfo,
     market_signer: &Pubkey,
 ) -> Result<Pubkey, ProgramError> {
-    let acc = spl_token::state::Account::unpack(&account.data.borrow())?;
+    let acc = unpack_token_account(account)?;
     if &acc.owner != market_signer {
         msg!("The vault account should be owned by the market signer");
         return Err(ProgramError::InvalidArgument);
@@ -224,6 +369,89 @@ fn check_vault_account_and_get_mint(
     Ok(acc.mint)
 }
 
+fn check_mint_account_and_get_decimals(
+    account: &AccountInfo,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<u8, ProgramError> {
+    check_account_key(account, mint, DexError::InvalidMintAccount)?;
+    check_account_owner(account, token_program, DexError::InvalidMintAccount)?;
+    let decimals = StateWithExtensions::<Mint>::unpack(&account.data.borrow())?
+        .base
+        .decimals;
+    if decimals > MAX_MINT_DECIMALS {
+        msg!(
+            "The mint has {} decimals, which is more than the maximum of {}",
+            decimals,
+            MAX_MINT_DECIMALS
+        );
+        return Err(DexError::InvalidMintAccount.into());
+    }
+    Ok(decimals)
+}
+
+/// Transfers the market creation fee from the fee payer to the program's treasury, when the program config sets one
+fn collect_creation_fee(program_id: &Pubkey, accounts: &Accounts<AccountInfo>) -> ProgramResult {
+    let (program_config_key, _) = get_program_config_address(program_id);
+    let config =
+        match ProgramConfig::load(program_id, &program_config_key, accounts.program_config)? {
+            Some(config) if config.market_creation_fee != 0 => config,
+            _ => return Ok(()),
+        };
+    let (system_program, fee_payer, treasury) = match (
+        accounts.system_program,
+        accounts.creation_fee_payer,
+        accounts.creation_fee_treasury,
+    ) {
+        (Some(s), Some(p), Some(t)) => (s, p, t),
+        _ => {
+            msg!(
+                "Creating a market costs {} lamports, the fee payer and treasury accounts should be given",
+                config.market_creation_fee
+            );
+            return Err(DexError::MarketCreationFeeNotPaid.into());
+        }
+    };
+    check_account_key(
+        system_program,
+        &system_program::ID,
+        DexError::InvalidSystemProgramAccount,
+    )?;
+    check_signer(fee_payer).map_err(|e| {
+        msg!("The market creation fee payer should be a signer for this transaction!");
+        e
+    })?;
+    check_account_key(
+        treasury,
+        &config.market_creation_fee_treasury,
+        DexError::MarketCreationFeeNotPaid,
+    )?;
+
+    invoke(
+        &transfer(fee_payer.key, treasury.key, config.market_creation_fee),
+        &[system_program.clone(), fee_payer.clone(), treasury.clone()],
+    )
+}
+
+/// Initializes the trade log of a market, which must be allocated beforehand and hold at least one record.
+///
+/// This is shared with the migrate_market instruction, since markets of the initial program version have no trade log.
+pub(crate) fn initialize_trade_log(trade_log: &AccountInfo, market: &Pubkey) -> ProgramResult {
+    let mut trade_log_data = trade_log.data.borrow_mut();
+    let mut trade_log = TradeLog::from_buffer_unchecked(&mut trade_log_data)?;
+    if trade_log.header.tag != AccountTag::Uninitialized as u64 {
+        msg!("The trade log account contains initialized state!");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if trade_log.capacity() == 0 {
+        msg!("The trade log account should be large enough to hold at least one record");
+        return Err(ProgramError::InvalidArgument);
+    }
+    trade_log.header.tag = AccountTag::TradeLog as u64;
+    trade_log.header.market = *market;
+    Ok(())
+}
+
 fn check_rent<'a>(accounts: &Accounts<'a, AccountInfo>) -> ProgramResult {
     check_rent_exempt(accounts.market)?;
     check_rent_exempt(accounts.orderbook)?;
@@ -232,5 +460,6 @@ fn check_rent<'a>(accounts: &Accounts<'a, AccountInfo>) -> ProgramResult {
     check_rent_exempt(accounts.event_queue)?;
     check_rent_exempt(accounts.asks)?;
     check_rent_exempt(accounts.bids)?;
+    check_rent_exempt(accounts.trade_log)?;
     Ok(())
 }
@@ -0,0 +1,130 @@
+//! Create the account mirroring a market's trading statistics. This instruction is permissionless.
+//!
+//! The account is derived from the market so that each market has a single stats account. Once created, it is
+//! refreshed by the new_order and consume_events instructions which are given it.
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::create_account,
+    system_program,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    error::DexError,
+    state::{DexState, MarketStats, MARKET_STATS_LEN},
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+
+/// The seed of the market stats account, along with the market's key
+pub const MARKET_STATS_SEED: &[u8] = b"market_stats";
+
+#[derive(Clone, Copy, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a create_market_stats instruction.
+*/
+pub struct Params {}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The system program
+    pub system_program: &'a T,
+
+    /// The DEX market
+    pub market: &'a T,
+
+    /// The market stats account to create, derived from the market
+    #[cons(writable)]
+    pub market_stats: &'a T,
+
+    /// The fee payer
+    #[cons(writable, signer)]
+    pub fee_payer: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            system_program: next_account_info(accounts_iter)?,
+            market: next_account_info(accounts_iter)?,
+            market_stats: next_account_info(accounts_iter)?,
+            fee_payer: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.fee_payer).map_err(|e| {
+            msg!("The fee payer should be a signer for this transaction!");
+            e
+        })?;
+        check_account_key(
+            a.system_program,
+            &system_program::ID,
+            DexError::InvalidSystemProgramAccount,
+        )?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+/// Returns the market stats address of a market, along with its nonce
+pub fn get_market_stats_address(program_id: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&market.to_bytes(), MARKET_STATS_SEED], program_id)
+}
+
+pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let market_state = DexState::get(accounts.market)?;
+
+    let (market_stats_key, market_stats_nonce) =
+        get_market_stats_address(program_id, accounts.market.key);
+    check_account_key(
+        accounts.market_stats,
+        &market_stats_key,
+        DexError::InvalidMarketStatsAccount,
+    )?;
+
+    let lamports = Rent::get()?.minimum_balance(MARKET_STATS_LEN);
+
+    let allocate_account = create_account(
+        accounts.fee_payer.key,
+        accounts.market_stats.key,
+        lamports,
+        MARKET_STATS_LEN as u64,
+        program_id,
+    );
+
+    invoke_signed(
+        &allocate_account,
+        &[
+            accounts.system_program.clone(),
+            accounts.fee_payer.clone(),
+            accounts.market_stats.clone(),
+        ],
+        &[&[
+            &accounts.market.key.to_bytes(),
+            MARKET_STATS_SEED,
+            &[market_stats_nonce],
+        ]],
+    )?;
+
+    let stats = MarketStats::new(accounts.market.key, &market_state, SysvarClock.now()?);
+    stats.serialize(&mut &mut accounts.market_stats.data.borrow_mut()[..])?;
+
+    Ok(())
+}
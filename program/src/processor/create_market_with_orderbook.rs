@@ -0,0 +1,232 @@
+//! Creates a new DEX market along with its orderbook account, which is allocated at a program derived address
+use crate::{
+    error::DexError,
+    processor::create_market,
+    state::CallBackInfo,
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+use asset_agnostic_orderbook::state::{
+    critbit::Slab, event_queue::EventQueue, market_state::MarketState,
+};
+use bonfida_utils::InstructionsAccount;
+use bytemuck::try_from_bytes;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::create_account,
+    system_program,
+    sysvar::Sysvar,
+};
+
+pub use create_market::Params;
+
+/// The seed which derives the orderbook address of a market, along with the market's address
+pub const ORDERBOOK_SEED: &[u8] = b"orderbook";
+
+/// The size of an orderbook account
+pub const ORDERBOOK_LEN: usize = 8 + MarketState::LEN;
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The system program
+    pub system_program: &'a T,
+
+    /// The fee payer, which funds the orderbook account
+    #[cons(writable, signer)]
+    pub fee_payer: &'a T,
+
+    /// The market account
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The orderbook account to allocate, at the address given by `get_orderbook_address`
+    #[cons(writable)]
+    pub orderbook: &'a T,
+
+    /// The base vault account
+    pub base_vault: &'a T,
+
+    /// The quote vault account
+    pub quote_vault: &'a T,
+
+    /// The market admin account
+    pub market_admin: &'a T,
+
+    #[cons(writable)]
+    /// The AOB event queue account
+    pub event_queue: &'a T,
+
+    /// The AOB asks account
+    #[cons(writable)]
+    pub asks: &'a T,
+
+    /// The AOB bids account
+    #[cons(writable)]
+    pub bids: &'a T,
+
+    /// The metaplex token metadata
+    pub token_metadata: &'a T,
+
+    /// The trade log account which the market's fills will be recorded to
+    #[cons(writable)]
+    pub trade_log: &'a T,
+
+    /// The base mint, whose decimals are recorded in the market
+    pub base_mint: &'a T,
+
+    /// The quote mint, whose decimals are recorded in the market
+    pub quote_mint: &'a T,
+
+    /// The program config account, which sets the market creation fee
+    pub program_config: &'a T,
+
+    /// The program's treasury, which collects the market creation fee from the fee payer when the program charges one
+    #[cons(writable)]
+    pub creation_fee_treasury: Option<&'a T>,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+
+        let a = Self {
+            system_program: next_account_info(accounts_iter)?,
+            fee_payer: next_account_info(accounts_iter)?,
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            base_vault: next_account_info(accounts_iter)?,
+            quote_vault: next_account_info(accounts_iter)?,
+            market_admin: next_account_info(accounts_iter)?,
+            event_queue: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            token_metadata: next_account_info(accounts_iter)?,
+            trade_log: next_account_info(accounts_iter)?,
+            base_mint: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
+            program_config: next_account_info(accounts_iter)?,
+            creation_fee_treasury: next_account_info(accounts_iter).ok(),
+        };
+
+        check_signer(a.fee_payer).map_err(|e| {
+            msg!("The fee payer should be a signer for this transaction!");
+            e
+        })?;
+        check_account_key(
+            a.system_program,
+            &system_program::ID,
+            DexError::InvalidSystemProgramAccount,
+        )?;
+        check_account_owner(
+            a.orderbook,
+            &system_program::ID,
+            DexError::InvalidStateAccountOwner,
+        )?;
+        check_account_owner(
+            a.event_queue,
+            program_id,
+            DexError::InvalidStateAccountOwner,
+        )?;
+        check_account_owner(a.asks, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.bids, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+
+    fn create_market_accounts(&self) -> create_market::Accounts<'a, AccountInfo<'b>> {
+        create_market::Accounts {
+            market: self.market,
+            orderbook: self.orderbook,
+            base_vault: self.base_vault,
+            quote_vault: self.quote_vault,
+            market_admin: self.market_admin,
+            event_queue: self.event_queue,
+            asks: self.asks,
+            bids: self.bids,
+            token_metadata: self.token_metadata,
+            trade_log: self.trade_log,
+            base_mint: self.base_mint,
+            quote_mint: self.quote_mint,
+            program_config: self.program_config,
+            system_program: Some(self.system_program),
+            creation_fee_payer: Some(self.fee_payer),
+            creation_fee_treasury: self.creation_fee_treasury,
+        }
+    }
+}
+
+/// Returns the orderbook address of a market, along with its nonce
+pub fn get_orderbook_address(program_id: &Pubkey, market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&market.to_bytes(), ORDERBOOK_SEED], program_id)
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let params =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let (orderbook_key, orderbook_nonce) = get_orderbook_address(program_id, accounts.market.key);
+    check_account_key(
+        accounts.orderbook,
+        &orderbook_key,
+        DexError::InvalidOrderbookAccount,
+    )?;
+
+    check_orderbook_account_sizes(&accounts)?;
+
+    let lamports = Rent::get()?.minimum_balance(ORDERBOOK_LEN);
+
+    let allocate_account = create_account(
+        accounts.fee_payer.key,
+        accounts.orderbook.key,
+        lamports,
+        ORDERBOOK_LEN as u64,
+        program_id,
+    );
+
+    invoke_signed(
+        &allocate_account,
+        &[
+            accounts.system_program.clone(),
+            accounts.fee_payer.clone(),
+            accounts.orderbook.clone(),
+        ],
+        &[&[
+            &accounts.market.key.to_bytes(),
+            ORDERBOOK_SEED,
+            &[orderbook_nonce],
+        ]],
+    )?;
+
+    let create_market_accounts = accounts.create_market_accounts();
+    create_market_accounts.check_account_owners(program_id)?;
+
+    create_market::execute(program_id, &create_market_accounts, params)
+}
+
+/// Makes sure that the event queue and the slabs can hold at least one entry before anything gets initialized
+fn check_orderbook_account_sizes(accounts: &Accounts<AccountInfo>) -> ProgramResult {
+    if accounts.event_queue.data_len() < EventQueue::<CallBackInfo>::compute_allocation_size(1) {
+        msg!("The event queue account is too small to hold a single event");
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    let min_slab_len = Slab::<CallBackInfo>::compute_allocation_size(1);
+    if accounts.asks.data_len() < min_slab_len || accounts.bids.data_len() < min_slab_len {
+        msg!("The asks and bids accounts are too small to hold a single order");
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    Ok(())
+}
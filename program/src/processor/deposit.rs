@@ -0,0 +1,252 @@
+//! Deposit base and quote tokens into the free balances of a user account, ahead of placing orders
+use crate::{
+    error::DexError,
+    state::{DexState, UserAccount},
+    utils::{
+        check_account_key, check_account_owner, check_signer, check_token_program,
+        get_mint_transfer_info, unpack_token_account,
+    },
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+#[derive(Clone, Copy, BorshDeserialize, BorshSerialize, BorshSize, Pod, Zeroable)]
+#[repr(C)]
+/**
+The required arguments for a deposit instruction.
+*/
+pub struct Params {
+    /// The quantity of base token to deposit
+    pub base_amount: u64,
+    /// The quantity of quote token to deposit
+    pub quote_amount: u64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The spl token program
+    pub spl_token_program: &'a T,
+
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The base token vault
+    #[cons(writable)]
+    pub base_vault: &'a T,
+
+    /// The quote token vault
+    #[cons(writable)]
+    pub quote_vault: &'a T,
+
+    /// The DEX user account
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The DEX user account owner wallet
+    #[cons(signer)]
+    pub user_owner: &'a T,
+
+    /// The source base token account
+    #[cons(writable)]
+    pub source_base_account: &'a T,
+
+    /// The source quote token account
+    #[cons(writable)]
+    pub source_quote_account: &'a T,
+
+    /// The base token mint
+    pub base_mint: &'a T,
+
+    /// The quote token mint
+    pub quote_mint: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            spl_token_program: next_account_info(accounts_iter)?,
+            market: next_account_info(accounts_iter)?,
+            base_vault: next_account_info(accounts_iter)?,
+            quote_vault: next_account_info(accounts_iter)?,
+            user: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+            source_base_account: next_account_info(accounts_iter)?,
+            source_quote_account: next_account_info(accounts_iter)?,
+            base_mint: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+        check_token_program(a.spl_token_program)?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+
+    pub fn load_user_account(
+        &self,
+        user_account_data: &'a mut [u8],
+    ) -> Result<UserAccount<'a>, ProgramError> {
+        let user_account = UserAccount::from_buffer(user_account_data)?;
+        if &user_account.header.owner != self.user_owner.key {
+            msg!("Invalid user account owner provided!");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if &user_account.header.market != self.market.key {
+            msg!("The provided user account doesn't match the current market");
+            return Err(ProgramError::InvalidArgument);
+        };
+        Ok(user_account)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params {
+        base_amount,
+        quote_amount,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let mut user_account = accounts.load_user_account(&mut user_account_data)?;
+
+    check_accounts(program_id, &market_state, &accounts)?;
+
+    // Fee-on-transfer mints withhold part of the transferred amount from the vault, so only the amount which
+    // actually reaches the vault is credited
+    let epoch = Clock::get()?.epoch;
+    let base_credited = transfer_to_vault(
+        &accounts,
+        accounts.source_base_account,
+        accounts.base_vault,
+        accounts.base_mint,
+        *base_amount,
+        epoch,
+    )?;
+    let quote_credited = transfer_to_vault(
+        &accounts,
+        accounts.source_quote_account,
+        accounts.quote_vault,
+        accounts.quote_mint,
+        *quote_amount,
+        epoch,
+    )?;
+    market_state.record_vault_inflow(base_credited, quote_credited)?;
+
+    user_account.header.base_token_free = user_account
+        .header
+        .base_token_free
+        .checked_add(base_credited)
+        .ok_or(DexError::NumericalOverflow)?;
+    user_account.header.quote_token_free = user_account
+        .header
+        .quote_token_free
+        .checked_add(quote_credited)
+        .ok_or(DexError::NumericalOverflow)?;
+
+    msg!(
+        "Deposited {} base and {} quote tokens net of transfer fees",
+        base_credited,
+        quote_credited
+    );
+
+    Ok(())
+}
+
+/// Transfers tokens from a source token account of the user wallet to a vault, returning the amount received by the
+/// vault
+fn transfer_to_vault<'a, 'b: 'a>(
+    accounts: &Accounts<'a, AccountInfo<'b>>,
+    source: &'a AccountInfo<'b>,
+    vault: &'a AccountInfo<'b>,
+    mint: &'a AccountInfo<'b>,
+    amount: u64,
+    epoch: u64,
+) -> Result<u64, ProgramError> {
+    if amount == 0 {
+        return Ok(0);
+    }
+    if &unpack_token_account(source)?.mint != mint.key {
+        msg!("The source token account doesn't match the vault's mint");
+        return Err(DexError::InvalidMintAccount.into());
+    }
+    let (decimals, transfer_fee) = get_mint_transfer_info(mint, amount, epoch)?;
+
+    let transfer_instruction = spl_token_2022::instruction::transfer_checked(
+        accounts.spl_token_program.key,
+        source.key,
+        mint.key,
+        vault.key,
+        accounts.user_owner.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+
+    invoke(
+        &transfer_instruction,
+        &[
+            accounts.spl_token_program.clone(),
+            source.clone(),
+            mint.clone(),
+            vault.clone(),
+            accounts.user_owner.clone(),
+        ],
+    )?;
+
+    Ok(amount - transfer_fee)
+}
+
+fn check_accounts(
+    program_id: &Pubkey,
+    market_state: &DexState,
+    accounts: &Accounts<AccountInfo>,
+) -> ProgramResult {
+    check_account_key(
+        accounts.spl_token_program,
+        &market_state.token_program,
+        DexError::InvalidSplTokenProgram,
+    )?;
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
+    market_state.check_vaults(&market_signer, accounts.base_vault, accounts.quote_vault)?;
+    check_account_key(
+        accounts.base_mint,
+        &market_state.base_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    check_account_key(
+        accounts.quote_mint,
+        &market_state.quote_mint,
+        DexError::InvalidMintAccount,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! Extract some or all of the accumulated fees to any quote token account. This is an admin instruction
+use crate::{
+    error::DexError,
+    state::DexState,
+    utils::{
+        check_account_key, check_account_owner, check_signer, check_token_program,
+        get_mint_transfer_info, unpack_token_account,
+    },
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a extract_fees instruction.
+*/
+pub struct Params {
+    /// The amount of quote tokens to extract, which cannot exceed the accumulated fees. Zero extracts all of them
+    pub amount: u64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The market admin account
+    #[cons(signer)]
+    pub market_admin: &'a T,
+
+    /// The DEX market signer
+    pub market_signer: &'a T,
+
+    /// The market quote token vault
+    #[cons(writable)]
+    pub quote_vault: &'a T,
+
+    /// The quote token mint
+    pub quote_mint: &'a T,
+
+    /// The destination quote token account
+    #[cons(writable)]
+    pub destination_token_account: &'a T,
+
+    /// The spl token program
+    pub spl_token_program: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            market_admin: next_account_info(accounts_iter)?,
+            market_signer: next_account_info(accounts_iter)?,
+            quote_vault: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
+            destination_token_account: next_account_info(accounts_iter)?,
+            spl_token_program: next_account_info(accounts_iter)?,
+        };
+
+        check_token_program(a.spl_token_program)?;
+
+        // Check owners
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+
+        // Check signers
+        check_signer(a.market_admin).map_err(|e| {
+            msg!("The market admin should be a signer for this transaction!");
+            e
+        })?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params { amount } =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+    check_accounts(program_id, &market_state, &accounts)?;
+
+    let amount = match *amount {
+        0 => market_state.accumulated_fees,
+        a if a > market_state.accumulated_fees => {
+            msg!(
+                "Cannot extract more than the {} accumulated fees",
+                market_state.accumulated_fees
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        a => a,
+    };
+
+    if amount == 0 {
+        msg!("There are no fees to be extracted from this market!");
+        return Err(DexError::NoOp.into());
+    }
+
+    let (quote_decimals, _) =
+        get_mint_transfer_info(accounts.quote_mint, amount, Clock::get()?.epoch)?;
+
+    let transfer_instruction = spl_token_2022::instruction::transfer_checked(
+        accounts.spl_token_program.key,
+        accounts.quote_vault.key,
+        accounts.quote_mint.key,
+        accounts.destination_token_account.key,
+        accounts.market_signer.key,
+        &[],
+        amount,
+        quote_decimals,
+    )?;
+
+    invoke_signed(
+        &transfer_instruction,
+        &[
+            accounts.spl_token_program.clone(),
+            accounts.quote_vault.clone(),
+            accounts.quote_mint.clone(),
+            accounts.destination_token_account.clone(),
+            accounts.market_signer.clone(),
+        ],
+        &[&[
+            &accounts.market.key.to_bytes(),
+            &[market_state.signer_nonce as u8],
+        ]],
+    )?;
+
+    // The counter is only decremented once the transfer went through
+    market_state.accumulated_fees -= amount;
+    market_state.record_vault_outflow(0, amount);
+
+    Ok(())
+}
+
+fn check_accounts(
+    program_id: &Pubkey,
+    market_state: &DexState,
+    accounts: &Accounts<AccountInfo>,
+) -> ProgramResult {
+    check_account_key(
+        accounts.market_admin,
+        &market_state.admin,
+        DexError::InvalidMarketAdminAccount,
+    )?;
+    check_account_key(
+        accounts.spl_token_program,
+        &market_state.token_program,
+        DexError::InvalidSplTokenProgram,
+    )?;
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
+    check_account_key(
+        accounts.market_signer,
+        &market_signer,
+        DexError::InvalidMarketSignerAccount,
+    )?;
+    check_account_key(
+        accounts.quote_vault,
+        &market_state.quote_vault,
+        DexError::InvalidQuoteVaultAccount,
+    )?;
+    check_account_key(
+        accounts.quote_mint,
+        &market_state.quote_mint,
+        DexError::InvalidMintAccount,
+    )?;
+
+    if unpack_token_account(accounts.destination_token_account)?.mint != market_state.quote_mint {
+        msg!("The destination token account should hold the market's quote token");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    Ok(())
+}
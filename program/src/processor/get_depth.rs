@@ -0,0 +1,190 @@
+//! Aggregate the resting orders of a market into price levels, without modifying any account.
+//!
+//! The outcome is emitted as the instruction's return data, as an `OrderbookDepth` holding exactly the requested
+//! number of levels on each side. Bids are sorted by descending price and asks by ascending price. When a side holds
+//! fewer price levels than requested, it is completed with empty levels whose price and quantity are both zero.
+//! Expired orders which haven't been pruned yet still rest on the book and are included.
+use crate::{
+    error::DexError,
+    state::{CallBackInfo, DexState},
+    utils::{check_account_key, check_account_owner},
+};
+use asset_agnostic_orderbook::state::{critbit::Slab, market_state::MarketState, AccountTag, Side};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::set_return_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// The maximum number of levels per side, which keeps the encoded depth within the return data size limit
+pub const MAX_DEPTH_LEVELS: u64 = 31;
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a get_depth instruction.
+*/
+pub struct Params {
+    /// The number of price levels to return on each side, which cannot exceed `MAX_DEPTH_LEVELS`
+    pub levels: u64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    pub market: &'a T,
+
+    /// The orderbook
+    pub orderbook: &'a T,
+
+    /// The AOB bids shared memory
+    pub bids: &'a T,
+
+    /// The AOB asks shared memory
+    pub asks: &'a T,
+}
+
+/// An aggregated price level of the book
+#[derive(BorshDeserialize, BorshSerialize, Debug, Default, PartialEq, Clone, Copy)]
+pub struct DepthLevel {
+    /// The price of the level (as a FP32). Zero for an empty level
+    pub price: u64,
+    /// The total base quantity resting at the price, in raw base token units. Zero for an empty level
+    pub base_qty: u64,
+}
+
+/// The outcome of a get_depth instruction, emitted as its return data
+#[derive(BorshDeserialize, BorshSerialize, Debug, Default, PartialEq, Clone)]
+pub struct OrderbookDepth {
+    /// The bid levels, best price first
+    pub bids: Vec<DepthLevel>,
+    /// The ask levels, best price first
+    pub asks: Vec<DepthLevel>,
+}
+
+impl DepthLevel {
+    /// Aggregates orders given as (price, base quantity) pairs in book order into exactly `levels` price levels,
+    /// completing them with empty levels when the orders span fewer prices.
+    pub fn aggregate(orders: impl Iterator<Item = (u64, u64)>, levels: usize) -> Vec<Self> {
+        let mut depth: Vec<Self> = Vec::with_capacity(levels);
+        for (price, base_qty) in orders {
+            if let Some(level) = depth.last_mut().filter(|level| level.price == price) {
+                level.base_qty = level.base_qty.saturating_add(base_qty);
+            } else if depth.len() == levels {
+                break;
+            } else {
+                depth.push(Self { price, base_qty });
+            }
+        }
+        depth.resize(levels, Self::default());
+        depth
+    }
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+        };
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.bids, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.asks, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params { levels } =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    if *levels > MAX_DEPTH_LEVELS {
+        msg!(
+            "The depth can hold at most {} levels per side",
+            MAX_DEPTH_LEVELS
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let market_state = DexState::get_ref(accounts.market)?;
+
+    check_accounts(&market_state, &accounts)?;
+
+    let depth = OrderbookDepth {
+        bids: get_side_depth(&market_state, accounts.bids, Side::Bid, *levels as usize)?,
+        asks: get_side_depth(&market_state, accounts.asks, Side::Ask, *levels as usize)?,
+    };
+    set_return_data(&depth.try_to_vec()?);
+
+    Ok(())
+}
+
+fn get_side_depth(
+    market_state: &DexState,
+    slab_account: &AccountInfo,
+    side: Side,
+    levels: usize,
+) -> Result<Vec<DepthLevel>, ProgramError> {
+    let mut slab_guard = slab_account.data.borrow_mut();
+    let (expected_tag, price_ascending) = match side {
+        Side::Bid => (AccountTag::Bids, false),
+        Side::Ask => (AccountTag::Asks, true),
+    };
+    let slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, expected_tag)?;
+
+    let mut depth = DepthLevel::aggregate(
+        slab.into_iter(price_ascending)
+            .map(|leaf| (leaf.price(), leaf.base_quantity)),
+        levels,
+    );
+    for level in depth.iter_mut() {
+        level.base_qty = market_state
+            .unscale_base_amount(level.base_qty)
+            .ok_or(DexError::NumericalOverflow)?;
+    }
+
+    Ok(depth)
+}
+
+fn check_accounts(market_state: &DexState, accounts: &Accounts<AccountInfo>) -> ProgramResult {
+    check_account_key(
+        accounts.orderbook,
+        &market_state.orderbook,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    let mut orderbook_guard = accounts.orderbook.data.borrow_mut();
+    let orderbook = MarketState::from_buffer(&mut orderbook_guard, AccountTag::Market)?;
+    check_account_key(
+        accounts.bids,
+        &orderbook.bids,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    check_account_key(
+        accounts.asks,
+        &orderbook.asks,
+        DexError::InvalidOrderbookAccount,
+    )?;
+
+    Ok(())
+}
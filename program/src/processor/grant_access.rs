@@ -0,0 +1,164 @@
+//! Grant a wallet access to a permissioned market by creating its access grant account. This instruction is signed by
+//! the market's access authority.
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::create_account,
+    system_program,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    error::DexError,
+    state::{AccessGrant, DexState, ACCESS_GRANT_LEN},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+
+/// The seed of the access grant accounts, along with the market's and the wallet's keys
+pub const ACCESS_GRANT_SEED: &[u8] = b"access_grant";
+
+#[derive(Clone, Copy, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a grant_access instruction.
+*/
+pub struct Params {
+    /// The wallet to grant access to
+    pub wallet: Pubkey,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The system program
+    pub system_program: &'a T,
+
+    /// The DEX market
+    pub market: &'a T,
+
+    /// The market's access authority
+    #[cons(signer)]
+    pub access_authority: &'a T,
+
+    /// The access grant account to create, derived from the market and the wallet
+    #[cons(writable)]
+    pub access_grant: &'a T,
+
+    /// The fee payer
+    #[cons(writable, signer)]
+    pub fee_payer: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            system_program: next_account_info(accounts_iter)?,
+            market: next_account_info(accounts_iter)?,
+            access_authority: next_account_info(accounts_iter)?,
+            access_grant: next_account_info(accounts_iter)?,
+            fee_payer: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.access_authority).map_err(|e| {
+            msg!("The access authority should be a signer for this transaction!");
+            e
+        })?;
+        check_signer(a.fee_payer).map_err(|e| {
+            msg!("The fee payer should be a signer for this transaction!");
+            e
+        })?;
+        check_account_key(
+            a.system_program,
+            &system_program::ID,
+            DexError::InvalidSystemProgramAccount,
+        )?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+/// Returns the access grant address of a wallet on a market, along with its nonce
+pub fn get_access_grant_address(
+    program_id: &Pubkey,
+    market: &Pubkey,
+    wallet: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&market.to_bytes(), &wallet.to_bytes(), ACCESS_GRANT_SEED],
+        program_id,
+    )
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params { wallet } =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let market_state = DexState::get(accounts.market)?;
+
+    if market_state.access_authority == Pubkey::default() {
+        msg!("The market isn't permissioned");
+        return Err(ProgramError::InvalidArgument);
+    }
+    check_account_key(
+        accounts.access_authority,
+        &market_state.access_authority,
+        DexError::Unauthorized,
+    )?;
+
+    let (access_grant_key, access_grant_nonce) =
+        get_access_grant_address(program_id, accounts.market.key, wallet);
+    check_account_key(
+        accounts.access_grant,
+        &access_grant_key,
+        DexError::Unauthorized,
+    )?;
+
+    let lamports = Rent::get()?.minimum_balance(ACCESS_GRANT_LEN);
+
+    let allocate_account = create_account(
+        accounts.fee_payer.key,
+        accounts.access_grant.key,
+        lamports,
+        ACCESS_GRANT_LEN as u64,
+        program_id,
+    );
+
+    invoke_signed(
+        &allocate_account,
+        &[
+            accounts.system_program.clone(),
+            accounts.fee_payer.clone(),
+            accounts.access_grant.clone(),
+        ],
+        &[&[
+            &accounts.market.key.to_bytes(),
+            &wallet.to_bytes(),
+            ACCESS_GRANT_SEED,
+            &[access_grant_nonce],
+        ]],
+    )?;
+
+    AccessGrant::new(accounts.market.key, wallet)
+        .serialize(&mut &mut accounts.access_grant.data.borrow_mut()[..])?;
+
+    Ok(())
+}
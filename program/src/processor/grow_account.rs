@@ -0,0 +1,134 @@
+//! Resize a user account to change the maximum number of orders it can hold
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::transfer,
+    system_program,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    error::DexError,
+    state::{Order, UserAccount, MAX_ORDERS_PER_ACCOUNT, USER_ACCOUNT_HEADER_LEN},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+
+#[derive(Clone, Copy, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a grow_account instruction.
+*/
+pub struct Params {
+    /// The new maximum number of orders the user account may hold, which cannot be lower than its current number of
+    /// orders
+    pub max_orders: u64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The system program
+    pub system_program: &'a T,
+
+    /// The user account to resize
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The owner of the user account
+    #[cons(signer)]
+    pub user_owner: &'a T,
+
+    /// The fee payer, which funds the additional rent
+    #[cons(writable, signer)]
+    pub fee_payer: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            system_program: next_account_info(accounts_iter)?,
+            user: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+            fee_payer: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+        check_account_key(
+            a.system_program,
+            &system_program::ID,
+            DexError::InvalidSystemProgramAccount,
+        )?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params { max_orders } =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    {
+        let mut user_account_data = accounts.user.data.borrow_mut();
+        let user_account = UserAccount::from_buffer(&mut user_account_data)?;
+        if &user_account.header.owner != accounts.user_owner.key {
+            msg!("Invalid user account owner provided!");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if *max_orders == 0 || *max_orders < user_account.header.number_of_orders as u64 {
+            msg!("The user account must be able to hold at least 1 order and all of its current orders");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if *max_orders > MAX_ORDERS_PER_ACCOUNT as u64 {
+            msg!(
+                "The maximum number of orders an account can hold is {}",
+                MAX_ORDERS_PER_ACCOUNT
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+
+    let space = max_orders
+        .checked_mul(Order::LEN as u64)
+        .and_then(|n| n.checked_add(USER_ACCOUNT_HEADER_LEN as u64))
+        .ok_or(DexError::NumericalOverflow)? as usize;
+
+    let lamports = Rent::get()?.minimum_balance(space);
+    let missing_lamports = lamports.saturating_sub(accounts.user.lamports());
+    if missing_lamports != 0 {
+        invoke(
+            &transfer(accounts.fee_payer.key, accounts.user.key, missing_lamports),
+            &[
+                accounts.system_program.clone(),
+                accounts.fee_payer.clone(),
+                accounts.user.clone(),
+            ],
+        )?;
+    }
+
+    // The header and the existing orders are left untouched, and any new order slots are zeroed
+    accounts.user.realloc(space, true)?;
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+//! Arm or refresh the dead-man switch of a user account.
+//!
+//! Once the heartbeat expires, anyone can cancel all of the account's orders with the cancel_stale_account
+//! instruction, which protects market makers against stale quotes when they stop checking in.
+use crate::{
+    error::DexError,
+    state::{UserAccount, MIN_HEARTBEAT_INTERVAL},
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_owner, check_signer},
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Clone, Copy, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a heartbeat instruction.
+*/
+pub struct Params {
+    /// The number of seconds from now after which the user account's orders can be cancelled by anyone, which should
+    /// be at least `MIN_HEARTBEAT_INTERVAL`. Zero disarms the dead-man switch.
+    pub heartbeat_interval: i64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX user account
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The user wallet, or the user account's delegate
+    #[cons(signer)]
+    pub user_owner: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            user: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params { heartbeat_interval } =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let user_account = UserAccount::from_buffer(&mut user_account_data)?;
+    if !user_account
+        .header
+        .is_order_authority(accounts.user_owner.key)
+    {
+        msg!("Invalid user account owner or delegate provided!");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if *heartbeat_interval == 0 {
+        user_account.header.heartbeat_expiry = 0;
+        return Ok(());
+    }
+
+    if *heartbeat_interval < MIN_HEARTBEAT_INTERVAL {
+        msg!(
+            "The heartbeat interval should be at least {} seconds",
+            MIN_HEARTBEAT_INTERVAL
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    user_account.header.heartbeat_expiry = SysvarClock
+        .now()?
+        .checked_add(*heartbeat_interval)
+        .ok_or(DexError::NumericalOverflow)?;
+
+    Ok(())
+}
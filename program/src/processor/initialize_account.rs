@@ -19,7 +19,10 @@ use solana_program::{
 
 use crate::{
     error::DexError,
-    state::{Order, UserAccount, UserAccountHeader, USER_ACCOUNT_HEADER_LEN},
+    state::{
+        Order, UserAccount, UserAccountHeader, MAX_ORDERS_PER_ACCOUNT, USER_ACCOUNT_HEADER_LEN,
+    },
+    time::{SysvarClock, TimeProvider},
     utils::{check_account_key, check_account_owner, check_signer},
 };
 
@@ -33,6 +36,11 @@ pub struct Params {
     pub market: Pubkey,
     /// The maximum number of orders the user account may hold
     pub max_orders: u64,
+    /// The wallet which onboarded the user and will receive the referral cut of its taker fees.
+    ///
+    /// The default pubkey means that the user account has no referrer, which can then be registered with the
+    /// register_referrer instruction shortly after the initialization.
+    pub referrer: Pubkey,
 }
 
 #[derive(InstructionsAccount)]
@@ -91,8 +99,11 @@ pub(crate) fn process(
 ) -> ProgramResult {
     let accounts = Accounts::parse(program_id, accounts)?;
 
-    let Params { market, max_orders } =
-        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let Params {
+        market,
+        max_orders,
+        referrer,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
 
     let market_key_bytes = market.to_bytes();
     let (user_account_key, user_account_nonce) = Pubkey::find_program_address(
@@ -105,11 +116,24 @@ pub(crate) fn process(
         return Err(ProgramError::InvalidArgument);
     }
 
+    if referrer == accounts.user_owner.key {
+        msg!("A user account cannot be referred by its own owner");
+        return Err(DexError::SelfReferral.into());
+    }
+
     if max_orders == &0 {
         msg!("The minimum number of orders an account should be able to hold is 1");
         return Err(ProgramError::InvalidArgument);
     }
 
+    if *max_orders > MAX_ORDERS_PER_ACCOUNT as u64 {
+        msg!(
+            "The maximum number of orders an account can hold is {}",
+            MAX_ORDERS_PER_ACCOUNT
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
     // (USER_ACCOUNT_HEADER_LEN as u64) + max_orders * (Order::LEN as u64);
     let space = max_orders
         .checked_mul(Order::LEN as u64)
@@ -143,7 +167,12 @@ pub(crate) fn process(
     let mut user_account_data = accounts.user.data.borrow_mut();
     let u = UserAccount::from_buffer_unchecked(&mut user_account_data)?;
 
-    *(u.header) = UserAccountHeader::new(market, accounts.user_owner.key);
+    *(u.header) = UserAccountHeader::new(
+        market,
+        accounts.user_owner.key,
+        referrer,
+        SysvarClock.now()?,
+    );
 
     Ok(())
 }
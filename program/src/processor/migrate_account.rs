@@ -0,0 +1,110 @@
+//! Upgrade a user account created by the initial program version to the current layout. This instruction is
+//! permissionless.
+//!
+//! The header is extended in place and the orders are moved after it, the additional rent being funded by the fee
+//! payer. Accounts which already follow the current layout are rejected.
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::transfer,
+    system_program,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    error::DexError,
+    state::{UserAccount, LEGACY_USER_ACCOUNT_HEADER_LEN, USER_ACCOUNT_HEADER_LEN},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a migrate_account instruction.
+*/
+pub struct Params {}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The system program
+    pub system_program: &'a T,
+
+    /// The user account to migrate
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The fee payer, which funds the additional rent
+    #[cons(writable, signer)]
+    pub fee_payer: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            system_program: next_account_info(accounts_iter)?,
+            user: next_account_info(accounts_iter)?,
+            fee_payer: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.fee_payer)?;
+        check_account_key(
+            a.system_program,
+            &system_program::ID,
+            DexError::InvalidSystemProgramAccount,
+        )?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let legacy_len = accounts.user.data_len();
+    if !UserAccount::is_legacy_layout(&accounts.user.data.borrow()) {
+        // Makes sure that the account is an up to date user account rather than an unrelated one
+        UserAccount::from_buffer(&mut accounts.user.data.borrow_mut())?;
+        msg!("The user account is already up to date");
+        return Err(DexError::NoOp.into());
+    }
+
+    let space = legacy_len + USER_ACCOUNT_HEADER_LEN - LEGACY_USER_ACCOUNT_HEADER_LEN;
+
+    let lamports = Rent::get()?.minimum_balance(space);
+    let missing_lamports = lamports.saturating_sub(accounts.user.lamports());
+    if missing_lamports != 0 {
+        invoke(
+            &transfer(accounts.fee_payer.key, accounts.user.key, missing_lamports),
+            &[
+                accounts.system_program.clone(),
+                accounts.fee_payer.clone(),
+                accounts.user.clone(),
+            ],
+        )?;
+    }
+
+    accounts.user.realloc(space, false)?;
+
+    UserAccount::migrate_legacy_buffer(&mut accounts.user.data.borrow_mut(), legacy_len)?;
+    msg!(
+        "Migrated the user account from {} to {} bytes",
+        legacy_len,
+        space
+    );
+
+    Ok(())
+}
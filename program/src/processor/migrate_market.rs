@@ -0,0 +1,562 @@
+//! Upgrade a market state created by the initial program version to the current layout. This is an admin instruction
+//!
+//! The market account is extended to the current length and its fields are relocated, the additional rent being
+//! funded by the fee payer. The amounts tracked for the vaults start from their current balances. Markets which already
+//! follow the current layout are rejected.
+//!
+//! The orderbook of the initial version stores 33 byte callback informations, which the current orderbook layout can't
+//! hold. A new orderbook is created with the same tick size and minimum order size, whose accounts must be allocated
+//! beforehand as for the create_market instruction. They must be rent exempt and at least as large as their legacy
+//! counterparts, so that they can hold all of the legacy entries. The events and resting orders of the legacy orderbook
+//! are then moved to it, their callback informations being read with `CallBackInfo::from_versioned_bytes`, so that no
+//! order has to be cancelled. Events are moved first, in their queue order, and orders keep their ids.
+//!
+//! Legacy markets have no trade log either, so the first instruction also initializes the given trade log account as
+//! create_market does.
+//!
+//! Each instruction moves at most `MAX_MIGRATED_ENTRIES` events and orders, and the instruction is repeated until the
+//! legacy orderbook is empty. No new orders are accepted in the meantime.
+use crate::{
+    error::DexError,
+    processor::create_market::initialize_trade_log,
+    state::{CallBackInfo, DexState, LegacyCallBackInfo, DEX_STATE_LEN},
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_key, check_account_owner, check_signer, unpack_token_account},
+};
+use asset_agnostic_orderbook::error::AoError;
+use asset_agnostic_orderbook::state::{
+    critbit::Slab,
+    event_queue::{EventQueue, EventRef, FillEventRef, OutEventRef},
+    market_state::MarketState,
+    AccountTag, Side,
+};
+use bonfida_utils::checks::check_rent_exempt;
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{bytes_of, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke,
+    program_error::{PrintProgramError, ProgramError},
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::transfer,
+    system_program,
+    sysvar::Sysvar,
+};
+
+/// The maximum number of legacy events and orders which a single migrate_market instruction moves
+pub const MAX_MIGRATED_ENTRIES: usize = 32;
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a migrate_market instruction.
+*/
+pub struct Params {}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The system program
+    pub system_program: &'a T,
+
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The base token vault
+    pub base_vault: &'a T,
+
+    /// The quote token vault
+    pub quote_vault: &'a T,
+
+    /// The fee payer, which funds the additional rent
+    #[cons(writable, signer)]
+    pub fee_payer: &'a T,
+
+    /// The market admin account
+    #[cons(signer)]
+    pub market_admin: &'a T,
+
+    /// The legacy orderbook
+    #[cons(writable)]
+    pub legacy_orderbook: &'a T,
+
+    /// The legacy AOB event queue
+    #[cons(writable)]
+    pub legacy_event_queue: &'a T,
+
+    /// The legacy AOB bids shared memory
+    #[cons(writable)]
+    pub legacy_bids: &'a T,
+
+    /// The legacy AOB asks shared memory
+    #[cons(writable)]
+    pub legacy_asks: &'a T,
+
+    /// The new orderbook account, which is created by the first migrate_market instruction of the market
+    #[cons(writable)]
+    pub orderbook: &'a T,
+
+    /// The new AOB event queue account
+    #[cons(writable)]
+    pub event_queue: &'a T,
+
+    /// The new AOB bids account
+    #[cons(writable)]
+    pub bids: &'a T,
+
+    /// The new AOB asks account
+    #[cons(writable)]
+    pub asks: &'a T,
+
+    /// The trade log account, which is initialized by the first migrate_market instruction of the market
+    #[cons(writable)]
+    pub trade_log: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            system_program: next_account_info(accounts_iter)?,
+            market: next_account_info(accounts_iter)?,
+            base_vault: next_account_info(accounts_iter)?,
+            quote_vault: next_account_info(accounts_iter)?,
+            fee_payer: next_account_info(accounts_iter)?,
+            market_admin: next_account_info(accounts_iter)?,
+            legacy_orderbook: next_account_info(accounts_iter)?,
+            legacy_event_queue: next_account_info(accounts_iter)?,
+            legacy_bids: next_account_info(accounts_iter)?,
+            legacy_asks: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            event_queue: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            trade_log: next_account_info(accounts_iter)?,
+        };
+
+        check_signer(a.fee_payer)?;
+        check_signer(a.market_admin).map_err(|e| {
+            msg!("The market admin should be a signer for this transaction!");
+            e
+        })?;
+        check_account_key(
+            a.system_program,
+            &system_program::ID,
+            DexError::InvalidSystemProgramAccount,
+        )?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(
+            a.legacy_orderbook,
+            program_id,
+            DexError::InvalidStateAccountOwner,
+        )?;
+        check_account_owner(a.orderbook, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.trade_log, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let legacy_len = accounts.market.data_len();
+    if DexState::is_legacy_len(legacy_len) {
+        migrate_state(program_id, &accounts, legacy_len)?;
+    }
+
+    let mut market_state = DexState::get(accounts.market)?;
+    check_account_key(
+        accounts.market_admin,
+        &market_state.admin,
+        DexError::InvalidMarketAdminAccount,
+    )?;
+    if market_state.legacy_orderbook == Pubkey::default() {
+        msg!("The market is already up to date");
+        return Err(DexError::NoOp.into());
+    }
+    check_account_key(
+        accounts.legacy_orderbook,
+        &market_state.legacy_orderbook,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    check_account_key(
+        accounts.orderbook,
+        &market_state.orderbook,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    check_account_key(
+        accounts.trade_log,
+        &market_state.trade_log,
+        DexError::InvalidTradeLogAccount,
+    )?;
+    check_orderbook_accounts(
+        accounts.legacy_orderbook,
+        accounts.legacy_event_queue,
+        accounts.legacy_bids,
+        accounts.legacy_asks,
+    )?;
+    check_orderbook_accounts(
+        accounts.orderbook,
+        accounts.event_queue,
+        accounts.bids,
+        accounts.asks,
+    )?;
+
+    let moved_events = move_legacy_events(program_id, &accounts)?;
+    let moved_orders = if moved_events < MAX_MIGRATED_ENTRIES {
+        move_legacy_orders(
+            &accounts,
+            &mut market_state,
+            MAX_MIGRATED_ENTRIES - moved_events,
+        )?
+    } else {
+        0
+    };
+    msg!(
+        "Moved {} events and {} orders from the legacy orderbook",
+        moved_events,
+        moved_orders
+    );
+
+    if is_legacy_orderbook_empty(&accounts)? {
+        market_state.legacy_orderbook = Pubkey::default();
+        market_state.update_best_prices(accounts.bids, accounts.asks, SysvarClock.now()?)?;
+        msg!("The legacy orderbook is empty, the market accepts new orders again");
+    }
+
+    Ok(())
+}
+
+/// Relocates a legacy market state to the current layout and creates the orderbook which the legacy orderbook's
+/// entries are moved to.
+fn migrate_state(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+    legacy_len: usize,
+) -> ProgramResult {
+    let lamports = Rent::get()?.minimum_balance(DEX_STATE_LEN);
+    let missing_lamports = lamports.saturating_sub(accounts.market.lamports());
+    if missing_lamports != 0 {
+        invoke(
+            &transfer(
+                accounts.fee_payer.key,
+                accounts.market.key,
+                missing_lamports,
+            ),
+            &[
+                accounts.system_program.clone(),
+                accounts.fee_payer.clone(),
+                accounts.market.clone(),
+            ],
+        )?;
+    }
+
+    accounts.market.realloc(DEX_STATE_LEN, true)?;
+
+    let mut market_data = accounts.market.data.borrow_mut();
+    let market_state =
+        DexState::migrate_legacy_buffer(&mut market_data, legacy_len, SysvarClock.now()?)?;
+
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
+    market_state.check_vaults(&market_signer, accounts.base_vault, accounts.quote_vault)?;
+    market_state.total_base_in_vault = unpack_token_account(accounts.base_vault)?.amount;
+    market_state.total_quote_in_vault = unpack_token_account(accounts.quote_vault)?.amount;
+
+    check_account_key(
+        accounts.legacy_orderbook,
+        &market_state.orderbook,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    let (min_base_order_size, tick_size) = {
+        let mut orderbook_guard = accounts.legacy_orderbook.data.borrow_mut();
+        let orderbook = MarketState::from_buffer(&mut orderbook_guard, AccountTag::Market)?;
+        (orderbook.min_base_order_size, orderbook.tick_size)
+    };
+
+    check_orderbook_accounts(
+        accounts.legacy_orderbook,
+        accounts.legacy_event_queue,
+        accounts.legacy_bids,
+        accounts.legacy_asks,
+    )?;
+    check_new_orderbook_accounts(accounts)?;
+
+    let invoke_params = asset_agnostic_orderbook::instruction::create_market::Params {
+        min_base_order_size,
+        tick_size,
+    };
+    let invoke_accounts = asset_agnostic_orderbook::instruction::create_market::Accounts {
+        market: accounts.orderbook,
+        event_queue: accounts.event_queue,
+        bids: accounts.bids,
+        asks: accounts.asks,
+    };
+    if let Err(error) = asset_agnostic_orderbook::instruction::create_market::process::<CallBackInfo>(
+        program_id,
+        invoke_accounts,
+        invoke_params,
+    ) {
+        error.print::<AoError>();
+        return Err(DexError::AOBError.into());
+    }
+
+    // The moved orders keep their ids, the new orderbook carries on with the sequence number which they were derived
+    // from so that the ids of the orders placed later can't collide with theirs
+    let legacy_seq_num = {
+        let mut legacy_event_queue_guard = accounts.legacy_event_queue.data.borrow_mut();
+        EventQueue::<LegacyCallBackInfo>::from_buffer(
+            &mut legacy_event_queue_guard,
+            AccountTag::EventQueue,
+        )?
+        .header
+        .seq_num
+    };
+    let mut event_queue_guard = accounts.event_queue.data.borrow_mut();
+    EventQueue::<CallBackInfo>::from_buffer(&mut event_queue_guard, AccountTag::EventQueue)?
+        .header
+        .seq_num = legacy_seq_num;
+
+    // Legacy markets have no trade log, which consume_events requires
+    check_rent_exempt(accounts.trade_log)?;
+    initialize_trade_log(accounts.trade_log, accounts.market.key)?;
+    market_state.trade_log = *accounts.trade_log.key;
+
+    market_state.legacy_orderbook = market_state.orderbook;
+    market_state.orderbook = *accounts.orderbook.key;
+
+    msg!(
+        "Migrated the market state from {} to {} bytes",
+        legacy_len,
+        DEX_STATE_LEN
+    );
+
+    Ok(())
+}
+
+/// Makes sure that the new orderbook accounts are rent exempt and can hold as many entries as the legacy ones before
+/// anything gets initialized
+fn check_new_orderbook_accounts(accounts: &Accounts<AccountInfo>) -> ProgramResult {
+    check_rent_exempt(accounts.orderbook)?;
+    check_rent_exempt(accounts.event_queue)?;
+    check_rent_exempt(accounts.bids)?;
+    check_rent_exempt(accounts.asks)?;
+
+    let legacy_event_capacity = {
+        let mut legacy_event_queue_guard = accounts.legacy_event_queue.data.borrow_mut();
+        EventQueue::<LegacyCallBackInfo>::from_buffer(
+            &mut legacy_event_queue_guard,
+            AccountTag::EventQueue,
+        )?
+        .events
+        .len()
+    };
+    if accounts.event_queue.data_len()
+        < EventQueue::<CallBackInfo>::compute_allocation_size(legacy_event_capacity)
+    {
+        msg!(
+            "The event queue account is too small to hold the {} events of the legacy one",
+            legacy_event_capacity
+        );
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    for (legacy_slab_account, slab_account, tag) in [
+        (accounts.legacy_bids, accounts.bids, AccountTag::Bids),
+        (accounts.legacy_asks, accounts.asks, AccountTag::Asks),
+    ] {
+        let legacy_order_capacity = {
+            let mut legacy_slab_guard = legacy_slab_account.data.borrow_mut();
+            Slab::<LegacyCallBackInfo>::from_buffer(&mut legacy_slab_guard, tag)?
+                .leaf_nodes
+                .len()
+        };
+        if slab_account.data_len()
+            < Slab::<CallBackInfo>::compute_allocation_size(legacy_order_capacity)
+        {
+            msg!(
+                "The asks and bids accounts are too small to hold the {} orders of the legacy ones",
+                legacy_order_capacity
+            );
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the event queue, bids and asks belong to the given orderbook
+fn check_orderbook_accounts(
+    orderbook: &AccountInfo,
+    event_queue: &AccountInfo,
+    bids: &AccountInfo,
+    asks: &AccountInfo,
+) -> ProgramResult {
+    let mut orderbook_guard = orderbook.data.borrow_mut();
+    let orderbook = MarketState::from_buffer(&mut orderbook_guard, AccountTag::Market)?;
+    check_account_key(
+        event_queue,
+        &orderbook.event_queue,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    check_account_key(bids, &orderbook.bids, DexError::InvalidOrderbookAccount)?;
+    check_account_key(asks, &orderbook.asks, DexError::InvalidOrderbookAccount)?;
+    Ok(())
+}
+
+/// Moves the events at the head of the legacy event queue to the new event queue and returns their number
+fn move_legacy_events(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+) -> Result<usize, ProgramError> {
+    let moved_events = {
+        let mut legacy_event_queue_guard = accounts.legacy_event_queue.data.borrow_mut();
+        let legacy_event_queue = EventQueue::<LegacyCallBackInfo>::from_buffer(
+            &mut legacy_event_queue_guard,
+            AccountTag::EventQueue,
+        )?;
+        let mut event_queue_guard = accounts.event_queue.data.borrow_mut();
+        let mut event_queue = EventQueue::<CallBackInfo>::from_buffer(
+            &mut event_queue_guard,
+            AccountTag::EventQueue,
+        )?;
+
+        let mut moved_events = 0;
+        for event in legacy_event_queue.iter().take(MAX_MIGRATED_ENTRIES) {
+            match event {
+                EventRef::Fill(FillEventRef {
+                    event,
+                    maker_callback_info,
+                    taker_callback_info,
+                }) => event_queue
+                    .push_back(
+                        *event,
+                        Some(CallBackInfo::from_versioned_bytes(bytes_of(
+                            maker_callback_info,
+                        ))?),
+                        Some(CallBackInfo::from_versioned_bytes(bytes_of(
+                            taker_callback_info,
+                        ))?),
+                    )
+                    .map_err(|_| DexError::EventQueueFull)?,
+                EventRef::Out(OutEventRef {
+                    event,
+                    callback_info,
+                }) => event_queue
+                    .push_back(
+                        *event,
+                        Some(CallBackInfo::from_versioned_bytes(bytes_of(callback_info))?),
+                        None,
+                    )
+                    .map_err(|_| DexError::EventQueueFull)?,
+            }
+            moved_events += 1;
+        }
+        moved_events
+    };
+    if moved_events == 0 {
+        return Ok(0);
+    }
+
+    let invoke_params = asset_agnostic_orderbook::instruction::consume_events::Params {
+        number_of_entries_to_consume: moved_events as u64,
+    };
+    let invoke_accounts = asset_agnostic_orderbook::instruction::consume_events::Accounts {
+        market: accounts.legacy_orderbook,
+        event_queue: accounts.legacy_event_queue,
+    };
+    if let Err(error) = asset_agnostic_orderbook::instruction::consume_events::process::<
+        LegacyCallBackInfo,
+    >(program_id, invoke_accounts, invoke_params)
+    {
+        error.print::<AoError>();
+        return Err(DexError::AOBError.into());
+    }
+    Ok(moved_events)
+}
+
+/// Moves up to the given number of resting orders from the legacy orderbook to the new one and returns their number.
+///
+/// The orders keep their ids, and thus their time priority. The new orderbook can't hold any other order yet, since
+/// the market rejects new orders until the legacy orderbook is empty.
+fn move_legacy_orders(
+    accounts: &Accounts<AccountInfo>,
+    market_state: &mut DexState,
+    max_orders: usize,
+) -> Result<usize, ProgramError> {
+    let mut moved_orders = 0;
+    for (legacy_slab_account, slab_account, tag, side) in [
+        (
+            accounts.legacy_bids,
+            accounts.bids,
+            AccountTag::Bids,
+            Side::Bid,
+        ),
+        (
+            accounts.legacy_asks,
+            accounts.asks,
+            AccountTag::Asks,
+            Side::Ask,
+        ),
+    ] {
+        let mut legacy_slab_guard = legacy_slab_account.data.borrow_mut();
+        let mut legacy_slab = Slab::<LegacyCallBackInfo>::from_buffer(&mut legacy_slab_guard, tag)?;
+        let mut slab_guard = slab_account.data.borrow_mut();
+        let mut slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, tag)?;
+        while moved_orders < max_orders {
+            let h = match legacy_slab.find_min() {
+                Some(h) => h as usize,
+                None => break,
+            };
+            let leaf = legacy_slab.leaf_nodes[h];
+            let callback_info =
+                CallBackInfo::from_versioned_bytes(bytes_of(&legacy_slab.callback_infos[h]))?;
+            legacy_slab
+                .remove_by_key(leaf.key)
+                .ok_or(DexError::OrderNotFound)?;
+            let (new_handle, _) = slab.insert_leaf(&leaf).map_err(|_| {
+                msg!("The new orderbook is full");
+                DexError::AOBError
+            })?;
+            slab.callback_infos[new_handle as usize] = callback_info;
+            market_state.add_resting_base(
+                side,
+                market_state
+                    .unscale_base_amount(leaf.base_quantity)
+                    .ok_or(DexError::NumericalOverflow)?,
+            );
+            moved_orders += 1;
+        }
+    }
+    Ok(moved_orders)
+}
+
+/// Whether the legacy orderbook holds neither events nor orders anymore
+fn is_legacy_orderbook_empty(accounts: &Accounts<AccountInfo>) -> Result<bool, ProgramError> {
+    let mut event_queue_guard = accounts.legacy_event_queue.data.borrow_mut();
+    let event_queue = EventQueue::<LegacyCallBackInfo>::from_buffer(
+        &mut event_queue_guard,
+        AccountTag::EventQueue,
+    )?;
+    if !event_queue.is_empty() {
+        return Ok(false);
+    }
+    for (slab_account, tag) in [
+        (accounts.legacy_bids, AccountTag::Bids),
+        (accounts.legacy_asks, AccountTag::Asks),
+    ] {
+        let mut slab_guard = slab_account.data.borrow_mut();
+        let slab = Slab::<LegacyCallBackInfo>::from_buffer(&mut slab_guard, tag)?;
+        if slab.find_min().is_some() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
@@ -1,12 +1,28 @@
-//! Execute a new order instruction. Supported types include Limit, IOC, FOK, or Post only.
+//! Execute a new order instruction. Supported types include Limit, IOC, FOK, Post only or Market.
 use crate::{
     error::DexError,
-    state::{CallBackInfo, DexState, FeeTier, Order, UserAccount},
+    state::{
+        CallBackInfo, DexState, FeeTier, LiquidityMetrics, MarketStats, Order, UserAccount,
+        CALLBACK_INFO_VERSION,
+    },
+    time::{SysvarClock, TimeProvider},
     utils::check_account_owner,
-    utils::{check_account_key, check_signer},
+    utils::{
+        check_account_key, check_signer, check_token_program, check_token_program_owner,
+        find_self_trade, fp32_mul, get_best_price, get_event_queue_free_slots,
+        get_mint_gross_transfer_info, get_mint_transfer_info, get_oracle_price,
+        get_order_callback_info, is_quote_budget_exhausted, simulate_match, simulate_self_match,
+        unpack_token_account,
+    },
 };
 use asset_agnostic_orderbook::error::AoError;
-use asset_agnostic_orderbook::state::Side;
+use asset_agnostic_orderbook::state::{
+    critbit::Slab,
+    event_queue::{EventQueue, EventTag, OutEvent},
+    get_side_from_order_id,
+    market_state::MarketState,
+    AccountTag, Side,
+};
 use bonfida_utils::BorshSize;
 use bonfida_utils::InstructionsAccount;
 use borsh::BorshDeserialize;
@@ -16,16 +32,21 @@ use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
-    program::{invoke, invoke_signed},
+    program::{invoke, invoke_signed, set_return_data},
     program_error::{PrintProgramError, ProgramError},
     pubkey::Pubkey,
-    system_program,
+    system_instruction, system_program,
+    sysvar::Sysvar,
 };
 
 use super::REFERRAL_MASK;
 
+/// The maximum number of expired maker orders which are removed from the book before matching a new order
+pub const MAX_EXPIRED_MAKER_CANCELS: u64 = 8;
+
 #[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
 #[repr(C)]
 /**
@@ -39,24 +60,133 @@ pub struct Params {
     pub client_order_id: [u64; 2],
     /// The order's limit price (as a FP32)
     pub limit_price: u64,
-    /// The max quantity of base token to match and post
+    /// The max quantity of base token to match and post, as a raw base token amount which should be a whole number of
+    /// the market's base lots. Ignored when `size_in_quote` is set
     pub max_base_qty: u64,
-    /// The max quantity of quote token to match and post
+    /// The max quantity of quote token to match and post, as a raw quote token amount
     pub max_quote_qty: u64,
     /// The maximum number of orders to be matched against.
     ///
     /// Setting this number too high can sometimes lead to excessive resource consumption which can cause a failure.
+    ///
+    /// Each match pushes an event to the event queue, so the limit is capped by the room left in the queue. When the
+    /// cap is reached while the order still crosses the book, the order is partially filled and its remainder is not
+    /// posted, since it would cross the book. An order which cannot match at all because the queue is full fails with
+    /// `EventQueueFull`.
+    ///
+    /// This bounds the compute used by the matching step. The `OrderFill` return data tells how much was matched, so
+    /// that clients can place another order for the rest.
     pub match_limit: u64,
+    /// The unix timestamp after which the order is considered cancelled. Zero means good-till-cancelled.
+    pub expiry_timestamp: i64,
+    /// For iceberg orders, the base quantity displayed on the book at any given time, as a raw base token amount which
+    /// should be a whole number of base lots. Ignored for other order types.
+    pub display_base_qty: u64,
+    /// The maximum taker fee the order may pay, in quote token amount, compared against the taker fee of the whole
+    /// matched quantity. Zero disables the check
+    pub max_taker_fee: u64,
+    /// For immediate-or-cancel orders, the minimum base quantity to match, as a raw base token amount which should be a
+    /// whole number of base lots. The order is aborted with `FillOrKillNotFilled` when less would be matched. Zero
+    /// disables the check, and the field is ignored for other order types.
+    pub min_base_to_fill: u64,
     /// The order's side (Bid or Ask)
     pub side: u8,
-    /// The order type (supported types include Limit, FOK, IOC and PostOnly)
+    /// The order type (supported types include Limit, FOK, IOC, PostOnly, Market and Iceberg)
     pub order_type: u8,
-    /// Configures what happens when this order is at least partially matched against an order belonging to the same user account
+    /// Configures what happens when this order is at least partially matched against an order belonging to the same user account.
+    /// Supported values are DecrementTake, CancelProvide, AbortTransaction and CancelBoth.
+    ///
+    /// The behavior is stored along with the order if it gets posted. When an incoming order from the same user account
+    /// later matches against it, the stored behavior of the first self-matched resting order prevails.
     pub self_trade_behavior: u8,
     /// Whether or not the optional discount token account was given
     pub has_discount_token_account: u8,
+    /// When set to 1, the order is rejected if it can't be entirely funded from the user account's free balances.
+    ///
+    /// This means that a reduce-only order never pulls new tokens from the user's wallet.
+    pub reduce_only: u8,
+    /// On markets quoted in native SOL, setting this to 1 wraps the lamports needed to fund a bid from the user wallet
+    /// into the user's quote token account, which should then be a native token account.
+    pub wrap_native_quote: u8,
+    /// Whether or not the market's oracle account was given
+    pub has_oracle_account: u8,
+    /// Whether or not the market's liquidity metrics account was given
+    pub has_liquidity_metrics_account: u8,
+    /// Whether or not the market's stats account was given
+    pub has_market_stats_account: u8,
+    /// When set to 1, the size of a bid is given by `max_quote_qty` alone. The base quantity is derived from it at the
+    /// limit price and rounded down to the market's base lots, so that the order never locks more quote than given.
+    /// Not supported for asks and market orders.
+    pub size_in_quote: u8,
+    /// Whether or not the user wallet's access grant was given, which is required on permissioned markets
+    pub has_access_grant_account: u8,
     /// To eliminate implicit padding
-    pub _padding: u32,
+    pub _padding: [u8; 5],
+}
+
+/// This enum describes all supported self-trade behaviors
+///
+/// All variants except `CancelBoth` are handled by the orderbook's matching engine directly.
+///
+/// Whatever the behavior, the taker fee is only charged on the quote quantity matched against the orders of other
+/// users, and the self-matched quantity never earns a maker rebate. See `SelfTradeBehavior::self_trade_fee` for the fee
+/// charged on the self-matched quantity itself.
+#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq, FromPrimitive, Clone, Copy)]
+pub enum SelfTradeBehavior {
+    /// The taker quantity is decremented by the self-matched quantity, without any token transfer. Only the market's
+    /// self-trade fee is charged, on the self-matched quote quantity
+    DecrementTake,
+    /// The resting maker order is cancelled and matching goes on against the other makers.
+    ///
+    /// The remainder of the incoming order is then posted at its limit price, if its order type allows it. The tokens
+    /// locked by the cancelled maker order are released once its out event is consumed, while the posted remainder
+    /// locks its own tokens right away. The cancelled order is free of charge, while the fills against the other
+    /// makers pay the regular taker fee.
+    CancelProvide,
+    /// The transaction is aborted with a `SelfTradeAborted` error, so that no fee is charged at all
+    AbortTransaction,
+    /// Both the resting maker order and the remainder of the incoming order are cancelled.
+    ///
+    /// The incoming order still matches against all the orders resting ahead of the self-matched order, but is never
+    /// posted on the book. As for `CancelProvide`, only the fills against the other makers pay the taker fee.
+    CancelBoth,
+}
+
+impl SelfTradeBehavior {
+    /// The fee charged on a self-matched quote quantity, rounded down.
+    ///
+    /// Only `DecrementTake` charges the market's self-trade fee, since it is the only behavior under which the
+    /// self-matched quantity counts as filled for the incoming order. The other behaviors either cancel the
+    /// self-matched order or abort the transaction, which is free.
+    pub fn self_trade_fee(
+        self,
+        market_state: &DexState,
+        self_matched_quote_qty: u64,
+    ) -> Result<u64, DexError> {
+        match self {
+            SelfTradeBehavior::DecrementTake => {
+                fp32_mul(self_matched_quote_qty, market_state.self_trade_fee_rate)
+                    .ok_or(DexError::NumericalOverflow)
+            }
+            SelfTradeBehavior::CancelProvide
+            | SelfTradeBehavior::AbortTransaction
+            | SelfTradeBehavior::CancelBoth => Ok(0),
+        }
+    }
+
+    fn to_orderbook_behavior(self) -> asset_agnostic_orderbook::state::SelfTradeBehavior {
+        match self {
+            SelfTradeBehavior::DecrementTake => {
+                asset_agnostic_orderbook::state::SelfTradeBehavior::DecrementTake
+            }
+            SelfTradeBehavior::CancelProvide | SelfTradeBehavior::CancelBoth => {
+                asset_agnostic_orderbook::state::SelfTradeBehavior::CancelProvide
+            }
+            SelfTradeBehavior::AbortTransaction => {
+                asset_agnostic_orderbook::state::SelfTradeBehavior::AbortTransaction
+            }
+        }
+    }
 }
 
 /// This enum describes all supported order types
@@ -64,12 +194,53 @@ pub struct Params {
 pub enum OrderType {
     #[allow(missing_docs)]
     Limit,
-    #[allow(missing_docs)]
+    /// Match as much as possible against resting orders and cancel the remainder instead of posting it.
+    ///
+    /// An order which doesn't match anything succeeds as a no-op.
     ImmediateOrCancel,
-    #[allow(missing_docs)]
+    /// Match the entire requested quantity against resting orders or abort the transaction.
+    ///
+    /// The match is simulated beforehand so that an order which cannot be filled fails before any state is written.
+    /// A bid is filled once its `max_base_qty` is matched or once its quote budget can't buy a single base unit at the
+    /// best remaining ask.
     FillOrKill,
     #[allow(missing_docs)]
     PostOnly,
+    /// Match against resting orders and cancel the remainder.
+    ///
+    /// A nonzero `limit_price` is the worst acceptable price, rounded to the tick size so that it never gets worse:
+    /// matching stops at the first price level beyond it. When it is zero the order walks the whole book.
+    ///
+    /// Bids are denominated in quote: the book is walked until the `max_quote_qty` budget (fees included) is
+    /// exhausted, the `max_base_qty` cap is reached, the limit price is crossed, or the book is empty.
+    /// At each price level, the base quantity bought is `fp32_div(remaining_quote, price)` rounded down, which
+    /// means that when the last level only partially fits the budget, the fraction of quote which cannot buy one
+    /// more (scaled) base unit is left unspent. Any unspent quote is never transferred from the user's wallet and
+    /// nothing is ever locked, including the budget left when matching stops at the limit price. Asks are denominated
+    /// in base and sell up to `max_base_qty` to the best bids.
+    Market,
+    /// Post only the `display_base_qty` slice of the order on the book, keeping the rest hidden.
+    ///
+    /// The full `max_base_qty` is locked when the order is placed. Each time the displayed slice is entirely filled,
+    /// the next slice is automatically reposted at the original limit price when the events are consumed. Iceberg
+    /// orders are post-only.
+    Iceberg,
+}
+
+/// The outcome of the matching step of a new_order instruction.
+///
+/// It is emitted as the instruction's return data so that calling programs can read it with `get_return_data`.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Default, PartialEq, Clone, Copy)]
+pub struct OrderFill {
+    /// The base quantity which was matched against resting orders
+    pub base_filled: u64,
+    /// The quote quantity which was matched against resting orders, fees excluded
+    pub quote_filled: u64,
+    /// The taker fees, royalties and self-trade fees paid in quote token
+    pub fee_paid: u64,
+    /// The average price of the matched quantity as an FP32, on the same scale as limit prices. Zero when nothing was
+    /// matched
+    pub average_price: u64,
 }
 
 #[derive(InstructionsAccount)]
@@ -108,6 +279,15 @@ pub struct Accounts<'a, T> {
     #[cons(writable)]
     pub quote_vault: &'a T,
 
+    /// The DEX market signer, which authorizes the referral fee transfers out of the quote vault
+    pub market_signer: &'a T,
+
+    /// The base token mint
+    pub base_mint: &'a T,
+
+    /// The quote token mint
+    pub quote_mint: &'a T,
+
     /// The DEX user account
     #[cons(writable)]
     pub user: &'a T,
@@ -116,14 +296,31 @@ pub struct Accounts<'a, T> {
     #[cons(writable)]
     pub user_token_account: &'a T,
 
-    /// The user wallet
+    /// The user wallet, or the user account's delegate
     #[cons(writable, signer)]
     pub user_owner: &'a T,
 
     /// The optional SRM or MSRM discount token account (must be owned by the user wallet)
     pub discount_token_account: Option<&'a T>,
 
-    /// The optional referrer's token account which will receive a 20% cut of the fees
+    /// The market's oracle price account, which is required when the market has an oracle
+    pub oracle: Option<&'a T>,
+
+    /// The market's optional liquidity metrics account, which records the user account as a maker when its order is
+    /// posted
+    #[cons(writable)]
+    pub liquidity_metrics: Option<&'a T>,
+
+    /// The market's optional stats account, which is refreshed once the order is placed
+    #[cons(writable)]
+    pub market_stats: Option<&'a T>,
+
+    /// The user wallet's access grant, which is required when the market has an access authority
+    pub access_grant: Option<&'a T>,
+
+    /// The optional referrer's token account which will receive its share of the fees.
+    ///
+    /// It is required when the user account has a referrer, and should then be owned by that referrer.
     #[cons(writable)]
     pub fee_referral_account: Option<&'a T>,
 }
@@ -133,6 +330,10 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         program_id: &Pubkey,
         accounts: &'a [AccountInfo<'b>],
         has_discount_token_account: bool,
+        has_oracle_account: bool,
+        has_liquidity_metrics_account: bool,
+        has_market_stats_account: bool,
+        has_access_grant_account: bool,
     ) -> Result<Self, ProgramError> {
         let accounts_iter = &mut accounts.iter();
         let a = Self {
@@ -145,6 +346,9 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
             asks: next_account_info(accounts_iter)?,
             base_vault: next_account_info(accounts_iter)?,
             quote_vault: next_account_info(accounts_iter)?,
+            market_signer: next_account_info(accounts_iter)?,
+            base_mint: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
             user: next_account_info(accounts_iter)?,
             user_token_account: next_account_info(accounts_iter)?,
             user_owner: next_account_info(accounts_iter)?,
@@ -153,6 +357,26 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
             } else {
                 None
             },
+            oracle: if has_oracle_account {
+                next_account_info(accounts_iter).ok()
+            } else {
+                None
+            },
+            liquidity_metrics: if has_liquidity_metrics_account {
+                next_account_info(accounts_iter).ok()
+            } else {
+                None
+            },
+            market_stats: if has_market_stats_account {
+                next_account_info(accounts_iter).ok()
+            } else {
+                None
+            },
+            access_grant: if has_access_grant_account {
+                next_account_info(accounts_iter).ok()
+            } else {
+                None
+            },
             fee_referral_account: next_account_info(accounts_iter).ok(),
         };
 
@@ -161,11 +385,7 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
             e
         })?;
 
-        check_account_key(
-            a.spl_token_program,
-            &spl_token::ID,
-            DexError::InvalidSplTokenProgram,
-        )?;
+        check_token_program(a.spl_token_program)?;
         check_account_key(
             a.system_program,
             &system_program::ID,
@@ -173,14 +393,13 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         )?;
 
         if let Some(discount_account) = a.discount_token_account {
-            check_account_owner(
-                discount_account,
-                &spl_token::ID,
-                DexError::InvalidSplTokenProgram,
-            )?
+            check_token_program_owner(discount_account, DexError::InvalidSplTokenProgram)?
         }
         check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
         check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        if let Some(market_stats) = a.market_stats {
+            check_account_owner(market_stats, program_id, DexError::InvalidStateAccountOwner)?;
+        }
 
         Ok(a)
     }
@@ -190,16 +409,8 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         user_account_data: &'a mut [u8],
     ) -> Result<UserAccount<'a>, ProgramError> {
         let user_account = UserAccount::from_buffer(user_account_data)?;
-        if &user_account.header.owner != self.user_owner.key {
-            msg!("Invalid user account owner provided!");
-            return Err(ProgramError::InvalidArgument);
-        }
-        if &user_account.header.market != self.market.key {
-            msg!("The provided user account doesn't match the current market");
-            return Err(ProgramError::InvalidArgument);
-        };
-        if &user_account.header.owner != self.user_owner.key {
-            msg!("Invalid user account owner provided!");
+        if !user_account.header.is_order_authority(self.user_owner.key) {
+            msg!("Invalid user account owner or delegate provided!");
             return Err(ProgramError::InvalidArgument);
         }
         if &user_account.header.market != self.market.key {
@@ -215,6 +426,75 @@ pub(crate) fn process(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
+    let params: &Params =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let accounts = Accounts::parse(
+        program_id,
+        accounts,
+        params.has_discount_token_account != 0,
+        params.has_oracle_account != 0,
+        params.has_liquidity_metrics_account != 0,
+        params.has_market_stats_account != 0,
+        params.has_access_grant_account != 0,
+    )?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let mut user_account = accounts.load_user_account(&mut user_account_data)?;
+
+    check_accounts(program_id, &market_state, &accounts)?;
+    check_referral_account(&accounts, &user_account.header.referrer)?;
+    market_state.check_access(
+        program_id,
+        accounts.market.key,
+        &user_account.header.owner,
+        accounts.access_grant,
+    )?;
+
+    let fill = execute(
+        program_id,
+        &accounts,
+        &mut market_state,
+        &mut user_account,
+        params,
+        &SysvarClock,
+    )?;
+    market_state.update_best_prices(accounts.bids, accounts.asks, SysvarClock.now()?)?;
+    mirror_market_stats(&accounts, &market_state)?;
+    set_return_data(&fill.try_to_vec()?);
+
+    Ok(())
+}
+
+/// Refreshes the market's stats account with the market's latest statistics, if it was given
+pub(crate) fn mirror_market_stats(
+    accounts: &Accounts<AccountInfo>,
+    market_state: &DexState,
+) -> ProgramResult {
+    if let Some(market_stats) = accounts.market_stats {
+        MarketStats::mirror(
+            market_stats,
+            accounts.market.key,
+            market_state,
+            SysvarClock.now()?,
+        )?;
+    }
+    Ok(())
+}
+
+/// Places a single order on behalf of a user account whose accounts have already been checked.
+///
+/// This is shared with the new_order_batch instruction, which loads the market and the user account only once for
+/// all of its orders. The order's expiry, the oracle's staleness and the rolling volumes are evaluated at the time
+/// given by the time provider.
+pub(crate) fn execute(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+    market_state: &mut DexState,
+    user_account: &mut UserAccount,
+    params: &Params,
+    clock: &impl TimeProvider,
+) -> Result<OrderFill, ProgramError> {
     let Params {
         side,
         limit_price,
@@ -223,17 +503,32 @@ pub(crate) fn process(
         order_type,
         self_trade_behavior,
         match_limit,
-        has_discount_token_account,
+        expiry_timestamp,
+        display_base_qty,
+        reduce_only,
+        wrap_native_quote,
         client_order_id,
+        max_taker_fee,
+        min_base_to_fill,
+        size_in_quote,
         ..
-    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    } = params;
     #[cfg(any(target_arch = "aarch64", feature = "aarch64-test"))]
     let client_order_id: &u128 = bytemuck::cast_ref(client_order_id);
-    let accounts = Accounts::parse(program_id, accounts, *has_discount_token_account != 0)?;
 
-    let market_state = DexState::get(accounts.market)?;
-    let mut user_account_data = accounts.user.data.borrow_mut();
-    let mut user_account = accounts.load_user_account(&mut user_account_data)?;
+    let max_base_qty = &if *size_in_quote != 0 {
+        if *side != Side::Bid as u8 || *order_type == OrderType::Market as u8 {
+            msg!("Only limit bids can be sized in quote");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let base_qty = market_state
+            .get_base_from_quote(max_quote_qty, *limit_price)
+            .ok_or(DexError::NumericalOverflow)?;
+        let base_lot_size = market_state.base_lot_size.max(1);
+        base_qty - base_qty % base_lot_size
+    } else {
+        *max_base_qty
+    };
 
     // Check the order size
     if max_base_qty < &market_state.min_base_order_size {
@@ -241,36 +536,248 @@ pub(crate) fn process(
         return Err(ProgramError::InvalidArgument);
     }
 
-    check_accounts(&market_state, &accounts).unwrap();
-    let (post_only, post_allowed) = match FromPrimitive::from_u8(*order_type).unwrap() {
+    let self_trade_behavior: SelfTradeBehavior =
+        FromPrimitive::from_u8(*self_trade_behavior).ok_or(ProgramError::InvalidArgument)?;
+    let (post_only, mut post_allowed) = match FromPrimitive::from_u8(*order_type).unwrap() {
         OrderType::Limit => (false, true),
-        OrderType::ImmediateOrCancel | OrderType::FillOrKill => (false, false),
-        OrderType::PostOnly => (true, true),
+        OrderType::ImmediateOrCancel | OrderType::FillOrKill | OrderType::Market => (false, false),
+        OrderType::PostOnly | OrderType::Iceberg => (true, true),
+    };
+    market_state.check_new_order_allowed(post_only)?;
+    let (displayed_base_qty, hidden_base_qty) = if *order_type == OrderType::Iceberg as u8 {
+        if display_base_qty == &0
+            || display_base_qty >= max_base_qty
+            || display_base_qty < &market_state.min_base_order_size
+        {
+            msg!("The iceberg display quantity should be above the minimum order size and below the order size");
+            return Err(ProgramError::InvalidArgument);
+        }
+        (*display_base_qty, max_base_qty - display_base_qty)
+    } else {
+        (*max_base_qty, 0)
+    };
+    let limit_price = if *order_type == OrderType::Market as u8 {
+        get_market_order_limit_price(
+            accounts.orderbook,
+            FromPrimitive::from_u8(*side).unwrap(),
+            *limit_price,
+        )?
+    } else {
+        // Market orders only ever match against resting orders which already comply
+        market_state.check_max_price(*limit_price)?;
+        market_state.check_order_granularity(*limit_price, *max_base_qty)?;
+        market_state.check_order_granularity(*limit_price, displayed_base_qty)?;
+        market_state.check_min_quote_order_size(*limit_price, *max_base_qty)?;
+        *limit_price
     };
+    let current_timestamp = clock.now()?;
+    if *expiry_timestamp != 0 && *expiry_timestamp <= current_timestamp {
+        msg!("The order's expiry timestamp has already passed");
+        return Err(DexError::OrderExpired.into());
+    }
+    if market_state.oracle != Pubkey::default()
+        && (*order_type != OrderType::Market as u8 || market_state.auto_halt_deviation_bps != 0)
+    {
+        check_oracle_price(
+            accounts,
+            market_state,
+            (*order_type != OrderType::Market as u8).then(|| limit_price),
+            current_timestamp,
+        )?;
+    }
+    cancel_expired_maker_orders(
+        program_id,
+        accounts.orderbook,
+        accounts.event_queue,
+        accounts.bids,
+        accounts.asks,
+        FromPrimitive::from_u8(*side).unwrap(),
+        limit_price,
+        current_timestamp,
+    )?;
+    if post_only {
+        check_post_only_crossing(
+            accounts,
+            FromPrimitive::from_u8(*side).unwrap(),
+            limit_price,
+        )?;
+    }
+    // The fee tier is snapshotted once, here, and is used for the taker fees of this instruction as well as for the
+    // rebate of the posted order, so that moving discount tokens afterwards has no effect on fees
     let fee_tier = accounts
         .discount_token_account
-        .map(|a| FeeTier::get(&market_state, a, accounts.user_owner.key))
-        .unwrap_or(Ok(FeeTier::Base))?;
+        .map(|a| FeeTier::get(market_state, a, &user_account.header.owner))
+        .unwrap_or(Ok(FeeTier::Base))?
+        .best(FeeTier::from_volume(
+            market_state,
+            user_account
+                .header
+                .current_rolling_base_volume(current_timestamp),
+        ));
     let callback_info = CallBackInfo {
         user_account: *accounts.user.key,
         fee_tier: fee_tier as u8
             | ((accounts.fee_referral_account.is_some() as u8) * REFERRAL_MASK),
+        self_trade_behavior: self_trade_behavior as u8,
+        version: CALLBACK_INFO_VERSION,
+        _padding: [0; 5],
+        expiry_timestamp: *expiry_timestamp,
+        display_base_qty: if hidden_base_qty != 0 {
+            displayed_base_qty
+        } else {
+            0
+        },
+        hidden_base_qty,
     };
-    if *side == Side::Bid as u8 && *order_type != OrderType::PostOnly as u8 {
+    if *side == Side::Bid as u8 && !post_only {
         // We make sure to leave enough quote quantity to pay for taker fees in the worst case
-        max_quote_qty = fee_tier.remove_taker_fee(max_quote_qty);
+        max_quote_qty = fee_tier.remove_taker_fee(market_state, max_quote_qty)?;
+    }
+    let mut max_base_qty_to_match = market_state.scale_base_amount(displayed_base_qty);
+    let self_trade = if post_only {
+        None
+    } else {
+        let taker_side = FromPrimitive::from_u8(*side).unwrap();
+        let maker_slab = match taker_side {
+            Side::Bid => accounts.asks,
+            Side::Ask => accounts.bids,
+        };
+        find_self_trade(
+            maker_slab,
+            taker_side,
+            limit_price,
+            *match_limit,
+            accounts.user.key,
+        )?
+    };
+    // The behavior stored with the first self-matched resting order takes precedence over the incoming order's
+    let matching_self_trade_behavior = self_trade
+        .map(|(_, _, c)| {
+            FromPrimitive::from_u8(c.self_trade_behavior)
+                .unwrap_or(SelfTradeBehavior::DecrementTake)
+        })
+        .unwrap_or(self_trade_behavior);
+    if matching_self_trade_behavior == SelfTradeBehavior::AbortTransaction {
+        if let Some((self_order_id, base_qty_ahead, _)) = self_trade {
+            if base_qty_ahead < max_base_qty_to_match {
+                msg!(
+                    "The order would self-trade against order {:?}",
+                    self_order_id
+                );
+                return Err(DexError::SelfTradeAborted.into());
+            }
+        }
+    }
+    if matching_self_trade_behavior == SelfTradeBehavior::CancelBoth {
+        if let Some((self_order_id, base_qty_ahead, _)) = self_trade {
+            cancel_self_trade_order(
+                program_id,
+                accounts,
+                market_state,
+                user_account,
+                self_order_id,
+            )?;
+            max_base_qty_to_match = max_base_qty_to_match.min(base_qty_ahead);
+            post_allowed = false;
+            if max_base_qty_to_match == 0 {
+                msg!("The self-matched order was cancelled along with the new order");
+                return Ok(OrderFill::default());
+            }
+        }
+    }
+    // Self-matches under DecrementTake never reach the event queue, so their fee is charged along with the taker fee
+    let self_trade_fee = match self_trade {
+        Some(_)
+            if matching_self_trade_behavior == SelfTradeBehavior::DecrementTake
+                && market_state.self_trade_fee_rate != 0 =>
+        {
+            let taker_side = FromPrimitive::from_u8(*side).unwrap();
+            let maker_slab = match taker_side {
+                Side::Bid => accounts.asks,
+                Side::Ask => accounts.bids,
+            };
+            let self_quote_qty = simulate_self_match(
+                maker_slab,
+                taker_side,
+                limit_price,
+                max_base_qty_to_match,
+                *match_limit,
+                accounts.user.key,
+            )?;
+            let self_quote_qty = market_state
+                .unscale_quote_amount(self_quote_qty)
+                .ok_or(DexError::NumericalOverflow)?;
+            matching_self_trade_behavior.self_trade_fee(market_state, self_quote_qty)?
+        }
+        _ => 0,
+    };
+    if *order_type == OrderType::FillOrKill as u8 {
+        let taker_side = FromPrimitive::from_u8(*side).unwrap();
+        let maker_slab = match taker_side {
+            Side::Bid => accounts.asks,
+            Side::Ask => accounts.bids,
+        };
+        let simulation = simulate_match(
+            maker_slab,
+            taker_side,
+            limit_price,
+            market_state.scale_base_amount(*max_base_qty),
+            market_state.scale_quote_amount(max_quote_qty),
+            *match_limit,
+        )?;
+        if !simulation.is_filled {
+            msg!("The fill-or-kill order cannot be entirely filled against the current book");
+            return Err(DexError::FillOrKillNotFilled.into());
+        }
+    }
+    let min_base_to_fill = if *order_type == OrderType::ImmediateOrCancel as u8 {
+        market_state.raw_to_base_lots(*min_base_to_fill)?;
+        *min_base_to_fill
+    } else {
+        0
+    };
+    if min_base_to_fill != 0 {
+        let taker_side = FromPrimitive::from_u8(*side).unwrap();
+        let maker_slab = match taker_side {
+            Side::Bid => accounts.asks,
+            Side::Ask => accounts.bids,
+        };
+        let simulation = simulate_match(
+            maker_slab,
+            taker_side,
+            limit_price,
+            max_base_qty_to_match,
+            market_state.scale_quote_amount(max_quote_qty),
+            *match_limit,
+        )?;
+        if simulation.base_qty < market_state.scale_base_amount(min_base_to_fill) {
+            msg!("The immediate-or-cancel order cannot reach its minimum fill against the current book");
+            return Err(DexError::FillOrKillNotFilled.into());
+        }
     }
 
+    // Each match pushes a fill event, so a lagging crank only limits how much of the order can be matched
+    let match_limit = if post_only {
+        *match_limit
+    } else {
+        let free_event_slots = get_event_queue_free_slots(accounts.event_queue)?;
+        if free_event_slots == 0 {
+            msg!("The event queue is full, no order can be matched until it is cranked");
+            return Err(DexError::EventQueueFull.into());
+        }
+        (*match_limit).min(free_event_slots)
+    };
+
     let invoke_params = asset_agnostic_orderbook::instruction::new_order::Params {
-        max_base_qty: market_state.scale_base_amount(*max_base_qty),
+        max_base_qty: max_base_qty_to_match,
         max_quote_qty: market_state.scale_quote_amount(max_quote_qty),
-        limit_price: *limit_price,
+        limit_price,
         side: FromPrimitive::from_u8(*side).unwrap(),
-        match_limit: *match_limit,
+        match_limit,
         callback_info,
         post_only,
         post_allowed,
-        self_trade_behavior: FromPrimitive::from_u8(*self_trade_behavior).unwrap(),
+        self_trade_behavior: matching_self_trade_behavior.to_orderbook_behavior(),
     };
     let invoke_accounts = asset_agnostic_orderbook::instruction::new_order::Accounts {
         market: accounts.orderbook,
@@ -286,6 +793,9 @@ pub(crate) fn process(
     ) {
         Err(error) => {
             error.print::<AoError>();
+            if error == ProgramError::Custom(AoError::EventQueueFull as u32) {
+                return Err(DexError::EventQueueFull.into());
+            }
             return Err(DexError::AOBError.into());
         }
         Ok(s) => s,
@@ -295,37 +805,85 @@ pub(crate) fn process(
         .unscale_order_summary(&mut order_summary)
         .unwrap();
 
+    // The hidden quantity of an iceberg order is added to the summary below, but it isn't on the book
+    let (taker_side, maker_side) = match FromPrimitive::from_u8(*side).unwrap() {
+        Side::Bid => (Side::Bid, Side::Ask),
+        Side::Ask => (Side::Ask, Side::Bid),
+    };
+    market_state.remove_resting_base(
+        maker_side,
+        order_summary.total_base_qty - order_summary.total_base_qty_posted,
+    );
+    market_state.add_resting_base(taker_side, order_summary.total_base_qty_posted);
+
+    // The simulation doesn't account for self-trading or the event queue capacity either
+    if order_summary.total_base_qty < min_base_to_fill {
+        msg!(
+            "The immediate-or-cancel order matched {:?} base tokens, below its minimum fill",
+            order_summary.total_base_qty
+        );
+        return Err(DexError::FillOrKillNotFilled.into());
+    }
+
+    if *order_type == OrderType::ImmediateOrCancel as u8 && order_summary.total_base_qty == 0 {
+        msg!("The immediate-or-cancel order did not match any resting order");
+        return Ok(OrderFill::default());
+    }
+
+    if hidden_base_qty != 0 && order_summary.posted_order_id.is_some() {
+        // The hidden quantity of an iceberg order is locked along with its first slice
+        let displayed_quote_qty = market_state
+            .get_quote_from_base(order_summary.total_base_qty_posted, limit_price)
+            .ok_or(DexError::NumericalOverflow)?;
+        order_summary.total_base_qty += hidden_base_qty;
+        order_summary.total_base_qty_posted += hidden_base_qty;
+        let total_posted_quote_qty = market_state
+            .get_quote_from_base(order_summary.total_base_qty_posted, limit_price)
+            .ok_or(DexError::NumericalOverflow)?;
+        order_summary.total_quote_qty = order_summary
+            .total_quote_qty
+            .checked_add(total_posted_quote_qty - displayed_quote_qty)
+            .ok_or(DexError::NumericalOverflow)?;
+        if *side == Side::Bid as u8 && order_summary.total_quote_qty > max_quote_qty {
+            msg!("The max quote quantity is insufficient to lock the whole iceberg order");
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+
     let posted_quote_qty = market_state
-        .get_quote_from_base(order_summary.total_base_qty_posted, *limit_price)
-        .unwrap();
+        .get_quote_from_base(order_summary.total_base_qty_posted, limit_price)
+        .ok_or(DexError::NumericalOverflow)?;
 
-    let (qty_to_transfer, transfer_destination, referral_fee) =
+    let (qty_to_transfer, transfer_destination, referral_fee, matched_quote_qty, fee_paid) =
         match FromPrimitive::from_u8(*side).unwrap() {
             Side::Bid => {
                 // We update the order summary to properly handle the FOK order type
                 let matched_quote_qty = order_summary.total_quote_qty - posted_quote_qty;
-                let taker_fee = fee_tier.taker_fee(matched_quote_qty);
+                let taker_fee = fee_tier.taker_fee(market_state, matched_quote_qty)?;
+                check_max_taker_fee(taker_fee, *max_taker_fee)?;
                 let royalties_fees = matched_quote_qty
                     .checked_mul(market_state.royalties_bps)
                     .unwrap()
                     / 10_000;
-                order_summary.total_quote_qty += taker_fee + royalties_fees;
-                let referral_fee = fee_tier.referral_fee(matched_quote_qty);
-                let q = order_summary
-                    .total_quote_qty
-                    .saturating_sub(user_account.header.quote_token_free);
-                user_account.header.quote_token_free = user_account
+                order_summary.total_quote_qty += taker_fee + royalties_fees + self_trade_fee;
+                let referral_fee = fee_tier.referral_fee(market_state, matched_quote_qty)?;
+                // The orderbook fills at the makers' prices, so the matched quote already carries the price improvement
+                let q = user_account
                     .header
-                    .quote_token_free
-                    .saturating_sub(order_summary.total_quote_qty);
-                user_account.header.quote_token_locked += posted_quote_qty;
+                    .debit_bid_quote(order_summary.total_quote_qty, posted_quote_qty);
                 user_account.header.base_token_free = order_summary
                     .total_base_qty
                     .checked_sub(order_summary.total_base_qty_posted)
                     .and_then(|n| n.checked_add(user_account.header.base_token_free))
                     .unwrap();
 
-                (q, accounts.quote_vault, referral_fee)
+                (
+                    q,
+                    accounts.quote_vault,
+                    referral_fee,
+                    matched_quote_qty,
+                    taker_fee + royalties_fees + self_trade_fee,
+                )
             }
             Side::Ask => {
                 let q = order_summary
@@ -337,30 +895,50 @@ pub(crate) fn process(
                     .saturating_sub(order_summary.total_base_qty);
                 user_account.header.base_token_locked += order_summary.total_base_qty_posted;
                 let taken_quote_qty = order_summary.total_quote_qty - posted_quote_qty;
-                let taker_fee = fee_tier.taker_fee(taken_quote_qty);
+                let taker_fee = fee_tier.taker_fee(market_state, taken_quote_qty)?;
+                check_max_taker_fee(taker_fee, *max_taker_fee)?;
                 let royalties_fees = taken_quote_qty
                     .checked_mul(market_state.royalties_bps)
                     .unwrap()
                     / 10_000;
-                let referral_fee = fee_tier.referral_fee(taken_quote_qty);
+                let referral_fee = fee_tier.referral_fee(market_state, taken_quote_qty)?;
+                // The self-trade fee can exceed the proceeds, in which case the rest comes out of the free balance
                 user_account.header.quote_token_free = taken_quote_qty
-                    .checked_sub(taker_fee + royalties_fees)
-                    .and_then(|n| n.checked_add(user_account.header.quote_token_free))
-                    .unwrap();
-                (q, accounts.base_vault, referral_fee)
+                    .checked_add(user_account.header.quote_token_free)
+                    .and_then(|n| n.checked_sub(taker_fee + royalties_fees + self_trade_fee))
+                    .ok_or(DexError::InsufficientFunds)?;
+                (
+                    q,
+                    accounts.base_vault,
+                    referral_fee,
+                    taken_quote_qty,
+                    taker_fee + royalties_fees + self_trade_fee,
+                )
             }
         };
 
-    let abort = match FromPrimitive::from_u8(*order_type).unwrap() {
-        OrderType::ImmediateOrCancel => order_summary.total_base_qty == 0,
-        OrderType::FillOrKill => {
-            if *side == Side::Bid as u8 {
-                order_summary.total_quote_qty < max_quote_qty
-            } else {
-                &order_summary.total_base_qty < max_base_qty
-            }
+    if *order_type == OrderType::FillOrKill as u8 {
+        // The simulation doesn't account for self-trading, so the actual match is checked as well, with the same
+        // criterion: the base quantity is matched or, for bids, the quote budget is exhausted against the best ask left
+        let base_filled = market_state.scale_base_amount(order_summary.total_base_qty)
+            >= market_state.scale_base_amount(*max_base_qty);
+        let is_filled = if *side == Side::Bid as u8 && !base_filled {
+            let quote_left =
+                market_state.scale_quote_amount(max_quote_qty.saturating_sub(matched_quote_qty));
+            get_best_price(accounts.asks, Side::Ask)?
+                .filter(|price| *price <= limit_price)
+                .map_or(false, |price| is_quote_budget_exhausted(quote_left, price))
+        } else {
+            base_filled
+        };
+        if !is_filled {
+            msg!("The fill-or-kill order could not be entirely filled");
+            return Err(DexError::FillOrKillNotFilled.into());
         }
-        OrderType::PostOnly => order_summary.posted_order_id.is_none(),
+    }
+
+    let abort = match FromPrimitive::from_u8(*order_type).unwrap() {
+        OrderType::PostOnly | OrderType::Iceberg => order_summary.posted_order_id.is_none(),
         _ => false,
     };
 
@@ -372,13 +950,43 @@ pub(crate) fn process(
         return Err(DexError::TransactionAborted.into());
     }
 
-    let token_transfer_instruction = spl_token::instruction::transfer(
+    if *reduce_only != 0 && qty_to_transfer != 0 {
+        msg!(
+            "The reduce-only order requires {:?} more tokens than the user account's free balance",
+            qty_to_transfer
+        );
+        return Err(DexError::ReduceOnlyViolation.into());
+    }
+
+    // Fee-on-transfer mints withhold part of the transferred amount from the vault, so the transfer is grossed up
+    // for the vault to receive the whole quantity required by the order. Any excess is credited to the free balance.
+    let epoch = Clock::get()?.epoch;
+    let transfer_mint = if *side == Side::Bid as u8 {
+        accounts.quote_mint
+    } else {
+        accounts.base_mint
+    };
+    if &unpack_token_account(accounts.user_token_account)?.mint != transfer_mint.key {
+        msg!("The user token account doesn't match the vault's mint");
+        return Err(DexError::InvalidMintAccount.into());
+    }
+    let (decimals, gross_qty_to_transfer, transfer_fee) =
+        get_mint_gross_transfer_info(transfer_mint, qty_to_transfer, epoch)?;
+    let credited_qty = gross_qty_to_transfer - transfer_fee;
+
+    if *wrap_native_quote != 0 && *side == Side::Bid as u8 && gross_qty_to_transfer != 0 {
+        wrap_native_quote_tokens(accounts, market_state, gross_qty_to_transfer)?;
+    }
+
+    let token_transfer_instruction = spl_token_2022::instruction::transfer_checked(
         accounts.spl_token_program.key,
         accounts.user_token_account.key,
+        transfer_mint.key,
         transfer_destination.key,
         accounts.user_owner.key,
         &[],
-        qty_to_transfer,
+        gross_qty_to_transfer,
+        decimals,
     )?;
 
     invoke(
@@ -386,19 +994,38 @@ pub(crate) fn process(
         &[
             accounts.spl_token_program.clone(),
             accounts.user_token_account.clone(),
+            transfer_mint.clone(),
             transfer_destination.clone(),
             accounts.user_owner.clone(),
         ],
     )?;
+    if *side == Side::Bid as u8 {
+        market_state.record_vault_inflow(0, credited_qty)?;
+        user_account.header.quote_token_free = user_account
+            .header
+            .quote_token_free
+            .checked_add(credited_qty - qty_to_transfer)
+            .ok_or(DexError::NumericalOverflow)?;
+    } else {
+        market_state.record_vault_inflow(credited_qty, 0)?;
+        user_account.header.base_token_free = user_account
+            .header
+            .base_token_free
+            .checked_add(credited_qty - qty_to_transfer)
+            .ok_or(DexError::NumericalOverflow)?;
+    }
 
     if let Some(a) = accounts.fee_referral_account {
-        let referral_fee_transfer_instruction = spl_token::instruction::transfer(
+        let (quote_decimals, _) = get_mint_transfer_info(accounts.quote_mint, referral_fee, epoch)?;
+        let referral_fee_transfer_instruction = spl_token_2022::instruction::transfer_checked(
             accounts.spl_token_program.key,
             accounts.quote_vault.key,
+            accounts.quote_mint.key,
             a.key,
-            accounts.user_owner.key,
+            accounts.market_signer.key,
             &[],
             referral_fee,
+            quote_decimals,
         )?;
 
         invoke_signed(
@@ -406,50 +1033,435 @@ pub(crate) fn process(
             &[
                 accounts.spl_token_program.clone(),
                 accounts.quote_vault.clone(),
+                accounts.quote_mint.clone(),
                 a.clone(),
-                accounts.user_owner.clone(),
+                accounts.market_signer.clone(),
             ],
             &[&[
                 &accounts.market.key.to_bytes(),
                 &[market_state.signer_nonce as u8],
             ]],
         )?;
+        market_state.record_vault_outflow(0, referral_fee);
     }
 
+    market_state.accumulated_fees = market_state.accumulated_fees.saturating_add(self_trade_fee);
+
     if let Some(order_id) = order_summary.posted_order_id {
+        market_state.check_open_orders_limit(user_account.header.number_of_orders)?;
         user_account.add_order(Order {
             id: order_id,
             client_id: *client_order_id,
         })?;
         msg!("Added new order with order_id {:?}", order_id);
+        if let Some(a) = accounts.liquidity_metrics {
+            let mut liquidity_metrics_data = a.data.borrow_mut();
+            let mut liquidity_metrics = LiquidityMetrics::from_buffer(&mut liquidity_metrics_data)?;
+            market_state.record_maker(&mut liquidity_metrics, accounts.user.key);
+        }
     }
 
-    user_account.header.accumulated_taker_base_volume += order_summary
+    let taken_base_qty = order_summary
         .total_base_qty
         .saturating_sub(order_summary.total_base_qty_posted);
-    user_account.header.accumulated_taker_quote_volume += order_summary
-        .total_quote_qty
-        .saturating_sub(posted_quote_qty);
+    user_account.header.accumulated_taker_base_volume = user_account
+        .header
+        .accumulated_taker_base_volume
+        .saturating_add(taken_base_qty);
+    user_account
+        .header
+        .record_rolling_base_volume(taken_base_qty, current_timestamp);
+    user_account.header.accumulated_taker_quote_volume = user_account
+        .header
+        .accumulated_taker_quote_volume
+        .saturating_add(
+            order_summary
+                .total_quote_qty
+                .saturating_sub(posted_quote_qty),
+        );
+    // Taker fees are charged here, the maker's side of the fill events only ever earns rebates
+    user_account.header.accumulated_fees_paid = user_account
+        .header
+        .accumulated_fees_paid
+        .saturating_add(fee_paid);
 
-    Ok(())
+    Ok(OrderFill {
+        base_filled: taken_base_qty,
+        quote_filled: matched_quote_qty,
+        fee_paid,
+        average_price: market_state
+            .get_average_price(taken_base_qty, matched_quote_qty)
+            .unwrap_or(0),
+    })
 }
 
-fn check_accounts(market_state: &DexState, accounts: &Accounts<AccountInfo>) -> ProgramResult {
+pub(crate) fn check_accounts(
+    program_id: &Pubkey,
+    market_state: &DexState,
+    accounts: &Accounts<AccountInfo>,
+) -> ProgramResult {
     check_account_key(
-        accounts.orderbook,
-        &market_state.orderbook,
-        DexError::InvalidOrderbookAccount,
+        accounts.spl_token_program,
+        &market_state.token_program,
+        DexError::InvalidSplTokenProgram,
+    )?;
+    market_state.check_orderbook(program_id, accounts.orderbook)?;
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
+    check_account_key(
+        accounts.market_signer,
+        &market_signer,
+        DexError::InvalidMarketSignerAccount,
     )?;
+    market_state.check_vaults(&market_signer, accounts.base_vault, accounts.quote_vault)?;
     check_account_key(
-        accounts.base_vault,
-        &market_state.base_vault,
-        DexError::InvalidBaseVaultAccount,
+        accounts.base_mint,
+        &market_state.base_mint,
+        DexError::InvalidMintAccount,
     )?;
     check_account_key(
-        accounts.quote_vault,
-        &market_state.quote_vault,
-        DexError::InvalidQuoteVaultAccount,
+        accounts.quote_mint,
+        &market_state.quote_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    if let Some(a) = accounts.liquidity_metrics {
+        check_account_key(
+            a,
+            &market_state.liquidity_metrics,
+            DexError::InvalidLiquidityMetricsAccount,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// User accounts which were onboarded by a referrer always share the referral cut of their taker fees with it.
+///
+/// Referring oneself is never allowed.
+pub(crate) fn check_referral_account(
+    accounts: &Accounts<AccountInfo>,
+    referrer: &Pubkey,
+) -> ProgramResult {
+    let referral_account = match accounts.fee_referral_account {
+        Some(a) => a,
+        None if referrer == &Pubkey::default() => return Ok(()),
+        None => {
+            msg!("The user account's referrer token account should be provided");
+            return Err(DexError::InvalidReferralAccount.into());
+        }
+    };
+    let referral_account_owner = unpack_token_account(referral_account)?.owner;
+    if &referral_account_owner == accounts.user_owner.key {
+        msg!("The fee referral account cannot be owned by the user");
+        return Err(DexError::SelfReferral.into());
+    }
+    if referrer != &Pubkey::default() && &referral_account_owner != referrer {
+        msg!("The fee referral account should be owned by the user account's referrer");
+        return Err(DexError::InvalidReferralAccount.into());
+    }
+    Ok(())
+}
+
+/// Removes the expired orders which the new order would otherwise be matched against.
+///
+/// An out event is pushed to the event queue for each of these orders so that the maker's locked tokens are refunded
+/// to its user account on the next crank, exactly as for an order which was cancelled by the matching engine. At most
+/// `MAX_EXPIRED_MAKER_CANCELS` orders are removed, independently of the taker's match limit.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn cancel_expired_maker_orders(
+    program_id: &Pubkey,
+    orderbook: &AccountInfo,
+    event_queue: &AccountInfo,
+    bids: &AccountInfo,
+    asks: &AccountInfo,
+    taker_side: Side,
+    limit_price: u64,
+    current_timestamp: i64,
+) -> ProgramResult {
+    let (maker_slab_account, maker_slab_tag, maker_side) = match taker_side {
+        Side::Bid => (asks, AccountTag::Asks, Side::Ask),
+        Side::Ask => (bids, AccountTag::Bids, Side::Bid),
+    };
+    for _ in 0..MAX_EXPIRED_MAKER_CANCELS {
+        let (order_id, base_size, callback_info) = {
+            let mut slab_guard = maker_slab_account.data.borrow_mut();
+            let slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, maker_slab_tag)?;
+            let best_handle = match taker_side {
+                Side::Bid => slab.find_min(),
+                Side::Ask => slab.find_max(),
+            };
+            let h = match best_handle {
+                Some(h) => h as usize,
+                None => break,
+            };
+            let leaf = slab.leaf_nodes[h];
+            let callback_info = CallBackInfo::read(&slab.callback_infos[h])?;
+            let crosses = match taker_side {
+                Side::Bid => leaf.price() <= limit_price,
+                Side::Ask => leaf.price() >= limit_price,
+            };
+            if !crosses || !callback_info.is_expired(current_timestamp) {
+                break;
+            }
+            (leaf.key, leaf.base_quantity, callback_info)
+        };
+
+        cancel_expired_order(
+            program_id,
+            orderbook,
+            event_queue,
+            bids,
+            asks,
+            maker_side,
+            order_id,
+            base_size,
+            &callback_info,
+        )?;
+    }
+    Ok(())
+}
+
+/// Removes an expired order from the book and pushes an out event for it.
+///
+/// The order's locked tokens are released to its owner's user account once the event is consumed.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn cancel_expired_order(
+    program_id: &Pubkey,
+    orderbook: &AccountInfo,
+    event_queue: &AccountInfo,
+    bids: &AccountInfo,
+    asks: &AccountInfo,
+    side: Side,
+    order_id: u128,
+    base_size: u64,
+    callback_info: &CallBackInfo,
+) -> ProgramResult {
+    let invoke_params = asset_agnostic_orderbook::instruction::cancel_order::Params { order_id };
+    let invoke_accounts = asset_agnostic_orderbook::instruction::cancel_order::Accounts {
+        market: orderbook,
+        event_queue,
+        bids,
+        asks,
+    };
+    if let Err(error) = asset_agnostic_orderbook::instruction::cancel_order::process::<CallBackInfo>(
+        program_id,
+        invoke_accounts,
+        invoke_params,
+    ) {
+        error.print::<AoError>();
+        return Err(DexError::AOBError.into());
+    }
+
+    let mut event_queue_guard = event_queue.data.borrow_mut();
+    let mut event_queue =
+        EventQueue::<CallBackInfo>::from_buffer(&mut event_queue_guard, AccountTag::EventQueue)?;
+    let out_event = OutEvent {
+        tag: EventTag::Out as u8,
+        side: side as u8,
+        order_id,
+        base_size,
+        ..OutEvent::zeroed()
+    };
+    event_queue
+        .push_back(out_event, Some(callback_info), None)
+        .map_err(|_| DexError::EventQueueFull)?;
+    msg!(
+        "Cancelled expired order {:?} of user account {}",
+        order_id,
+        callback_info.user_account
+    );
+    Ok(())
+}
+
+/// Cancels a resting order of the user which an incoming `CancelBoth` order would self-match against.
+///
+/// The order is removed from the user account right away and its locked tokens are released.
+fn cancel_self_trade_order(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+    market_state: &mut DexState,
+    user_account: &mut UserAccount,
+    order_id: u128,
+) -> ProgramResult {
+    let side = get_side_from_order_id(order_id);
+    let slab_account = match side {
+        Side::Bid => accounts.bids,
+        Side::Ask => accounts.asks,
+    };
+    let hidden_base_qty = get_order_callback_info(slab_account, side, order_id)?
+        .map(|c| c.hidden_base_qty)
+        .unwrap_or(0);
+
+    let invoke_params = asset_agnostic_orderbook::instruction::cancel_order::Params { order_id };
+    let invoke_accounts = asset_agnostic_orderbook::instruction::cancel_order::Accounts {
+        market: accounts.orderbook,
+        event_queue: accounts.event_queue,
+        bids: accounts.bids,
+        asks: accounts.asks,
+    };
+    let mut order_summary = match asset_agnostic_orderbook::instruction::cancel_order::process::<
+        CallBackInfo,
+    >(program_id, invoke_accounts, invoke_params)
+    {
+        Err(error) => {
+            error.print::<AoError>();
+            return Err(DexError::AOBError.into());
+        }
+        Ok(s) => s,
+    };
+    market_state
+        .unscale_order_summary(&mut order_summary)
+        .unwrap();
+    market_state.remove_resting_base(side, order_summary.total_base_qty);
+
+    match side {
+        Side::Bid => {
+            let qty_to_release = order_summary.total_quote_qty
+                + market_state
+                    .get_quote_from_base(hidden_base_qty, (order_id >> 64) as u64)
+                    .unwrap();
+            user_account.header.quote_token_free = user_account
+                .header
+                .quote_token_free
+                .checked_add(qty_to_release)
+                .unwrap();
+            user_account.header.quote_token_locked = user_account
+                .header
+                .quote_token_locked
+                .checked_sub(qty_to_release)
+                .unwrap();
+        }
+        Side::Ask => {
+            let qty_to_release = order_summary.total_base_qty + hidden_base_qty;
+            user_account.header.base_token_free = user_account
+                .header
+                .base_token_free
+                .checked_add(qty_to_release)
+                .unwrap();
+            user_account.header.base_token_locked = user_account
+                .header
+                .base_token_locked
+                .checked_sub(qty_to_release)
+                .unwrap();
+        }
+    };
+
+    let order_index = user_account.find_order_index(order_id)?;
+    user_account.remove_order(order_index)?;
+    msg!("Cancelled self-matched order {:?}", order_id);
+    Ok(())
+}
+
+/// Rejects all orders while the book diverges too much from the price of the market's oracle, as well as limit prices
+/// which deviate too much from it. Nothing is checked when the oracle price is stale
+fn check_oracle_price(
+    accounts: &Accounts<AccountInfo>,
+    market_state: &DexState,
+    limit_price: Option<u64>,
+    current_timestamp: i64,
+) -> ProgramResult {
+    let oracle = accounts.oracle.ok_or_else(|| {
+        msg!("The market's oracle account should be provided");
+        DexError::InvalidOracleAccount
+    })?;
+    check_account_key(oracle, &market_state.oracle, DexError::InvalidOracleAccount)?;
+    if let Some(oracle_price) = get_oracle_price(market_state, oracle, current_timestamp)? {
+        market_state.check_oracle_divergence(oracle_price)?;
+        if let Some(limit_price) = limit_price {
+            market_state.check_price_band(limit_price, oracle_price)?;
+        }
+    }
+    Ok(())
+}
+
+/// Aborts the order when its taker fee exceeds the limit set by the user, if any
+fn check_max_taker_fee(taker_fee: u64, max_taker_fee: u64) -> ProgramResult {
+    if max_taker_fee != 0 && taker_fee > max_taker_fee {
+        msg!(
+            "The taker fee of {} exceeds the maximum of {}",
+            taker_fee,
+            max_taker_fee
+        );
+        return Err(DexError::FeeExceedsLimit.into());
+    }
+    Ok(())
+}
+
+/// Wraps lamports from the user wallet into its native quote token account
+fn wrap_native_quote_tokens(
+    accounts: &Accounts<AccountInfo>,
+    market_state: &DexState,
+    lamports: u64,
+) -> ProgramResult {
+    if market_state.is_quote_native == 0 {
+        msg!("Only the lamports of markets quoted in native SOL can be wrapped");
+        return Err(ProgramError::InvalidArgument);
+    }
+    invoke(
+        &system_instruction::transfer(
+            accounts.user_owner.key,
+            accounts.user_token_account.key,
+            lamports,
+        ),
+        &[
+            accounts.system_program.clone(),
+            accounts.user_owner.clone(),
+            accounts.user_token_account.clone(),
+        ],
     )?;
+    invoke(
+        &spl_token_2022::instruction::sync_native(
+            accounts.spl_token_program.key,
+            accounts.user_token_account.key,
+        )?,
+        &[
+            accounts.spl_token_program.clone(),
+            accounts.user_token_account.clone(),
+        ],
+    )?;
+    msg!("Wrapped {} lamports", lamports);
+    Ok(())
+}
+
+/// Market orders are sent to the orderbook with their worst acceptable price, tick-aligned so that it never gets
+/// worse, or with the most aggressive tick-aligned limit price when they don't have one
+fn get_market_order_limit_price(
+    orderbook: &AccountInfo,
+    side: Side,
+    worst_price: u64,
+) -> Result<u64, ProgramError> {
+    let mut orderbook_guard = orderbook.data.borrow_mut();
+    let orderbook = MarketState::from_buffer(&mut orderbook_guard, AccountTag::Market)?;
+    let tick_size = orderbook.tick_size;
+    Ok(match (side, worst_price) {
+        (Side::Bid, 0) => u64::MAX - (u64::MAX % tick_size),
+        (Side::Bid, p) => p - (p % tick_size),
+        (Side::Ask, p) if p % tick_size == 0 => p,
+        (Side::Ask, p) => p
+            .checked_add(tick_size - p % tick_size)
+            .ok_or(DexError::NumericalOverflow)?,
+    })
+}
 
+/// Post-only orders which would cross the spread are rejected before reaching the orderbook.
+///
+/// An order sitting exactly at the touch (on the best price level of its own side) does not cross and is accepted.
+fn check_post_only_crossing(
+    accounts: &Accounts<AccountInfo>,
+    side: Side,
+    limit_price: u64,
+) -> ProgramResult {
+    let would_cross = match side {
+        Side::Bid => {
+            get_best_price(accounts.asks, Side::Ask)?.map(|best_ask| limit_price >= best_ask)
+        }
+        Side::Ask => {
+            get_best_price(accounts.bids, Side::Bid)?.map(|best_bid| limit_price <= best_bid)
+        }
+    }
+    .unwrap_or(false);
+    if would_cross {
+        msg!("The post-only order would cross the spread");
+        return Err(DexError::PostOnlyWouldCross.into());
+    }
     Ok(())
 }
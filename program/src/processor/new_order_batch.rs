@@ -0,0 +1,365 @@
+//! Execute several new orders atomically on behalf of a single user account.
+use crate::{
+    error::DexError,
+    processor::new_order,
+    state::{DexState, UserAccount},
+    time::{SysvarClock, TimeProvider},
+    utils::{
+        check_account_key, check_account_owner, check_signer, check_token_program,
+        check_token_program_owner,
+    },
+};
+use asset_agnostic_orderbook::state::Side;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use num_traits::FromPrimitive;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+
+/// The maximum number of orders in a single batch.
+///
+/// Each order costs as much compute as a standalone new_order instruction, and the transaction size limit leaves
+/// room for about this many order parameters once all the accounts are included.
+pub const MAX_BATCH_SIZE: usize = 8;
+
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+/**
+The required arguments for a new_order_batch instruction.
+*/
+pub struct Params {
+    /// The orders to place, in order of execution. There can be at most `MAX_BATCH_SIZE` of them.
+    ///
+    /// The `has_discount_token_account`, `has_oracle_account`, `has_liquidity_metrics_account` and
+    /// `has_access_grant_account` fields of each order are ignored in favor of the batch-wide ones, and the market stats
+    /// account isn't supported.
+    pub orders: Vec<new_order::Params>,
+    /// Whether or not the optional discount token account was given
+    pub has_discount_token_account: u8,
+    /// Whether or not the market's oracle account was given
+    pub has_oracle_account: u8,
+    /// Whether or not the market's liquidity metrics account was given
+    pub has_liquidity_metrics_account: u8,
+    /// Whether or not the user wallet's access grant was given, which is required on permissioned markets
+    pub has_access_grant_account: u8,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The SPL token program
+    pub spl_token_program: &'a T,
+
+    /// The system program
+    pub system_program: &'a T,
+
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The orderbook
+    #[cons(writable)]
+    pub orderbook: &'a T,
+
+    /// The AOB event queue
+    #[cons(writable)]
+    pub event_queue: &'a T,
+
+    /// The AOB bids shared memory
+    #[cons(writable)]
+    pub bids: &'a T,
+
+    /// The AOB asks shared memory
+    #[cons(writable)]
+    pub asks: &'a T,
+
+    /// The base token vault
+    #[cons(writable)]
+    pub base_vault: &'a T,
+
+    /// The quote token vault
+    #[cons(writable)]
+    pub quote_vault: &'a T,
+
+    /// The DEX market signer, which authorizes the referral fee transfers out of the quote vault
+    pub market_signer: &'a T,
+
+    /// The base token mint
+    pub base_mint: &'a T,
+
+    /// The quote token mint
+    pub quote_mint: &'a T,
+
+    /// The DEX user account
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The user base token account, which funds the asks
+    #[cons(writable)]
+    pub user_base_token_account: &'a T,
+
+    /// The user quote token account, which funds the bids
+    #[cons(writable)]
+    pub user_quote_token_account: &'a T,
+
+    /// The user wallet, or the user account's delegate
+    #[cons(writable, signer)]
+    pub user_owner: &'a T,
+
+    /// The optional SRM or MSRM discount token account (must be owned by the user wallet)
+    pub discount_token_account: Option<&'a T>,
+
+    /// The market's oracle price account, which is required when the market has an oracle
+    pub oracle: Option<&'a T>,
+
+    /// The market's optional liquidity metrics account, which records the user account as a maker when its orders are
+    /// posted
+    #[cons(writable)]
+    pub liquidity_metrics: Option<&'a T>,
+
+    /// The user wallet's access grant, which is required when the market has an access authority
+    pub access_grant: Option<&'a T>,
+
+    /// The optional referrer's token account which will receive its share of the fees.
+    ///
+    /// It is required when the user account has a referrer, and should then be owned by that referrer.
+    #[cons(writable)]
+    pub fee_referral_account: Option<&'a T>,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+        has_discount_token_account: bool,
+        has_oracle_account: bool,
+        has_liquidity_metrics_account: bool,
+        has_access_grant_account: bool,
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            spl_token_program: next_account_info(accounts_iter)?,
+            system_program: next_account_info(accounts_iter)?,
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            event_queue: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            base_vault: next_account_info(accounts_iter)?,
+            quote_vault: next_account_info(accounts_iter)?,
+            market_signer: next_account_info(accounts_iter)?,
+            base_mint: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
+            user: next_account_info(accounts_iter)?,
+            user_base_token_account: next_account_info(accounts_iter)?,
+            user_quote_token_account: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+            discount_token_account: if has_discount_token_account {
+                next_account_info(accounts_iter).ok()
+            } else {
+                None
+            },
+            oracle: if has_oracle_account {
+                next_account_info(accounts_iter).ok()
+            } else {
+                None
+            },
+            liquidity_metrics: if has_liquidity_metrics_account {
+                next_account_info(accounts_iter).ok()
+            } else {
+                None
+            },
+            access_grant: if has_access_grant_account {
+                next_account_info(accounts_iter).ok()
+            } else {
+                None
+            },
+            fee_referral_account: next_account_info(accounts_iter).ok(),
+        };
+
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+
+        check_token_program(a.spl_token_program)?;
+        check_account_key(
+            a.system_program,
+            &system_program::ID,
+            DexError::InvalidSystemProgramAccount,
+        )?;
+
+        if let Some(discount_account) = a.discount_token_account {
+            check_token_program_owner(discount_account, DexError::InvalidSplTokenProgram)?
+        }
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+
+    pub fn load_user_account(
+        &self,
+        user_account_data: &'a mut [u8],
+    ) -> Result<UserAccount<'a>, ProgramError> {
+        let user_account = UserAccount::from_buffer(user_account_data)?;
+        if !user_account.header.is_order_authority(self.user_owner.key) {
+            msg!("Invalid user account owner or delegate provided!");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if &user_account.header.market != self.market.key {
+            msg!("The provided user account doesn't match the current market");
+            return Err(ProgramError::InvalidArgument);
+        };
+        Ok(user_account)
+    }
+
+    /// The accounts of a single new_order instruction funded from the given token account
+    fn order_accounts(
+        &self,
+        user_token_account: &'a AccountInfo<'b>,
+    ) -> new_order::Accounts<'a, AccountInfo<'b>> {
+        new_order::Accounts {
+            spl_token_program: self.spl_token_program,
+            system_program: self.system_program,
+            market: self.market,
+            orderbook: self.orderbook,
+            event_queue: self.event_queue,
+            bids: self.bids,
+            asks: self.asks,
+            base_vault: self.base_vault,
+            quote_vault: self.quote_vault,
+            market_signer: self.market_signer,
+            base_mint: self.base_mint,
+            quote_mint: self.quote_mint,
+            user: self.user,
+            user_token_account,
+            user_owner: self.user_owner,
+            discount_token_account: self.discount_token_account,
+            oracle: self.oracle,
+            liquidity_metrics: self.liquidity_metrics,
+            market_stats: None,
+            access_grant: self.access_grant,
+            fee_referral_account: self.fee_referral_account,
+        }
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let Params {
+        orders,
+        has_discount_token_account,
+        has_oracle_account,
+        has_liquidity_metrics_account,
+        has_access_grant_account,
+    } = Params::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    let accounts = Accounts::parse(
+        program_id,
+        accounts,
+        has_discount_token_account != 0,
+        has_oracle_account != 0,
+        has_liquidity_metrics_account != 0,
+        has_access_grant_account != 0,
+    )?;
+
+    if orders.is_empty() || orders.len() > MAX_BATCH_SIZE {
+        msg!(
+            "A batch should contain between 1 and {} orders",
+            MAX_BATCH_SIZE
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut market_state = DexState::get(accounts.market)?;
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let mut user_account = accounts.load_user_account(&mut user_account_data)?;
+
+    check_accounts(program_id, &market_state, &accounts)?;
+
+    let bid_accounts = accounts.order_accounts(accounts.user_quote_token_account);
+    let ask_accounts = accounts.order_accounts(accounts.user_base_token_account);
+    new_order::check_referral_account(&bid_accounts, &user_account.header.referrer)?;
+    market_state.check_access(
+        program_id,
+        accounts.market.key,
+        &user_account.header.owner,
+        accounts.access_grant,
+    )?;
+
+    // Any failing order makes the whole instruction fail, which rolls back the orders placed before it
+    for (i, order) in orders.iter().enumerate() {
+        let order_accounts = match FromPrimitive::from_u8(order.side) {
+            Some(Side::Bid) => &bid_accounts,
+            Some(Side::Ask) => &ask_accounts,
+            None => {
+                msg!("Order {} of the batch has an invalid side", i);
+                return Err(ProgramError::InvalidArgument);
+            }
+        };
+        new_order::execute(
+            program_id,
+            order_accounts,
+            &mut market_state,
+            &mut user_account,
+            order,
+            &SysvarClock,
+        )
+        .map_err(|e| {
+            msg!("Order {} of the batch failed", i);
+            e
+        })?;
+    }
+
+    market_state.update_best_prices(accounts.bids, accounts.asks, SysvarClock.now()?)?;
+
+    Ok(())
+}
+
+fn check_accounts(
+    program_id: &Pubkey,
+    market_state: &DexState,
+    accounts: &Accounts<AccountInfo>,
+) -> ProgramResult {
+    check_account_key(
+        accounts.spl_token_program,
+        &market_state.token_program,
+        DexError::InvalidSplTokenProgram,
+    )?;
+    market_state.check_orderbook(program_id, accounts.orderbook)?;
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
+    check_account_key(
+        accounts.market_signer,
+        &market_signer,
+        DexError::InvalidMarketSignerAccount,
+    )?;
+    market_state.check_vaults(&market_signer, accounts.base_vault, accounts.quote_vault)?;
+    check_account_key(
+        accounts.base_mint,
+        &market_state.base_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    check_account_key(
+        accounts.quote_mint,
+        &market_state.quote_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    if let Some(a) = accounts.liquidity_metrics {
+        check_account_key(
+            a,
+            &market_state.liquidity_metrics,
+            DexError::InvalidLiquidityMetricsAccount,
+        )?;
+    }
+
+    Ok(())
+}
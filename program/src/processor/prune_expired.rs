@@ -0,0 +1,181 @@
+//! Cancel the expired orders resting on the book. This instruction is permissionless.
+//!
+//! The orders to prune are found offchain and given by id, so that the instruction only looks up these orders instead
+//! of walking the book. Orders which are no longer on the book or haven't expired yet are skipped.
+//!
+//! Each pruned order pushes an out event, through which its locked tokens are released to its owner's user account
+//! once the events are consumed. The caller earns the market's crank reward for each pruned order.
+use crate::{
+    error::DexError,
+    processor::{consume_events::pay_crank_reward, new_order::cancel_expired_order},
+    state::{CallBackInfo, DexState},
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_owner, get_event_queue_free_slots},
+};
+use asset_agnostic_orderbook::state::{critbit::Slab, get_side_from_order_id, AccountTag, Side};
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// The maximum number of orders which a single prune_expired instruction can be given
+pub const MAX_PRUNED_ORDERS: usize = 16;
+
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+/**
+The required arguments for a prune_expired instruction.
+*/
+pub struct Params {
+    /// The ids of the expired orders to cancel. There can be at most `MAX_PRUNED_ORDERS` of them, and the orders past
+    /// the room left in the event queue are ignored.
+    pub order_ids: Vec<u128>,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The orderbook
+    #[cons(writable)]
+    pub orderbook: &'a T,
+
+    /// The AOB event queue
+    #[cons(writable)]
+    pub event_queue: &'a T,
+
+    /// The AOB bids shared memory
+    #[cons(writable)]
+    pub bids: &'a T,
+
+    /// The AOB asks shared memory
+    #[cons(writable)]
+    pub asks: &'a T,
+
+    /// The user account which receives the crank reward, if any
+    #[cons(writable)]
+    pub reward_target: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            event_queue: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            reward_target: next_account_info(accounts_iter)?,
+        };
+
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params { order_ids } = Params::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    if order_ids.len() > MAX_PRUNED_ORDERS {
+        msg!("At most {} orders can be pruned at once", MAX_PRUNED_ORDERS);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    market_state.check_orderbook(program_id, accounts.orderbook)?;
+
+    let current_timestamp = SysvarClock.now()?;
+    let free_event_slots = get_event_queue_free_slots(accounts.event_queue)?;
+
+    let mut pruned_orders = 0;
+    for order_id in order_ids {
+        if pruned_orders == free_event_slots {
+            break;
+        }
+        let side = get_side_from_order_id(order_id);
+        let slab_account = match side {
+            Side::Bid => accounts.bids,
+            Side::Ask => accounts.asks,
+        };
+        let (base_size, callback_info) =
+            match find_expired_order(slab_account, side, order_id, current_timestamp)? {
+                Some(o) => o,
+                None => continue,
+            };
+        cancel_expired_order(
+            program_id,
+            accounts.orderbook,
+            accounts.event_queue,
+            accounts.bids,
+            accounts.asks,
+            side,
+            order_id,
+            base_size,
+            &callback_info,
+        )?;
+        pruned_orders += 1;
+    }
+
+    if pruned_orders == 0 {
+        msg!("No expired order was found");
+        return Ok(());
+    }
+
+    pay_crank_reward(
+        program_id,
+        accounts.market,
+        accounts.reward_target,
+        &mut market_state,
+        pruned_orders,
+    )?;
+
+    market_state.update_best_prices(accounts.bids, accounts.asks, current_timestamp)?;
+
+    Ok(())
+}
+
+/// Returns the base size (scaled) and callback information of an order, if it rests on the book and has expired
+fn find_expired_order(
+    slab_account: &AccountInfo,
+    side: Side,
+    order_id: u128,
+    current_timestamp: i64,
+) -> Result<Option<(u64, CallBackInfo)>, ProgramError> {
+    let mut slab_guard = slab_account.data.borrow_mut();
+    let expected_tag = match side {
+        Side::Bid => AccountTag::Bids,
+        Side::Ask => AccountTag::Asks,
+    };
+    let slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, expected_tag)?;
+
+    let h = match slab.find_by_key(order_id) {
+        Some(h) => h as usize,
+        None => return Ok(None),
+    };
+    let callback_info = CallBackInfo::read(&slab.callback_infos[h])?;
+    if !callback_info.is_expired(current_timestamp) {
+        return Ok(None);
+    }
+
+    Ok(Some((slab.leaf_nodes[h].base_quantity, callback_info)))
+}
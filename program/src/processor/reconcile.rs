@@ -0,0 +1,117 @@
+//! Compare the balances of a market's vaults with the token amounts which the program tracks for them, without
+//! modifying any account.
+//!
+//! The outcome is emitted as the instruction's return data, as a `VaultReconciliation`. A non-zero discrepancy means
+//! that tokens reached or left the vaults outside of the program's accounting, or that the accounting itself drifted.
+use crate::{
+    error::DexError,
+    state::DexState,
+    utils::{check_account_owner, unpack_token_account},
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::set_return_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Clone, Copy, BorshDeserialize, BorshSerialize, BorshSize, Pod, Zeroable)]
+#[repr(C)]
+pub struct Params {}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    pub market: &'a T,
+
+    /// The base token vault
+    pub base_vault: &'a T,
+
+    /// The quote token vault
+    pub quote_vault: &'a T,
+}
+
+/// The outcome of a reconcile instruction, emitted as its return data
+#[derive(BorshDeserialize, BorshSerialize, Debug, Default, PartialEq, Clone, Copy)]
+pub struct VaultReconciliation {
+    /// The balance of the base vault
+    pub base_vault_balance: u64,
+    /// The balance of the quote vault
+    pub quote_vault_balance: u64,
+    /// The base vault balance minus the amount tracked by the market. A negative value means that the market owes more
+    /// base tokens than it holds
+    pub base_discrepancy: i128,
+    /// The quote vault balance minus the amount tracked by the market. A negative value means that the market owes more
+    /// quote tokens than it holds
+    pub quote_discrepancy: i128,
+}
+
+impl VaultReconciliation {
+    /// Compares the given vault balances with the amounts tracked by the market state
+    pub fn new(market_state: &DexState, base_vault_balance: u64, quote_vault_balance: u64) -> Self {
+        Self {
+            base_vault_balance,
+            quote_vault_balance,
+            base_discrepancy: base_vault_balance as i128 - market_state.total_base_in_vault as i128,
+            quote_discrepancy: quote_vault_balance as i128
+                - market_state.total_quote_in_vault as i128,
+        }
+    }
+
+    /// Whether the vault balances match the tracked amounts
+    pub fn is_balanced(&self) -> bool {
+        self.base_discrepancy == 0 && self.quote_discrepancy == 0
+    }
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            base_vault: next_account_info(accounts_iter)?,
+            quote_vault: next_account_info(accounts_iter)?,
+        };
+
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let market_state = DexState::get_ref(accounts.market)?;
+
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
+    market_state.check_vaults(&market_signer, accounts.base_vault, accounts.quote_vault)?;
+
+    let reconciliation = VaultReconciliation::new(
+        &market_state,
+        unpack_token_account(accounts.base_vault)?.amount,
+        unpack_token_account(accounts.quote_vault)?.amount,
+    );
+
+    if !reconciliation.is_balanced() {
+        msg!(
+            "The vaults diverge from the tracked amounts by {} base and {} quote tokens",
+            reconciliation.base_discrepancy,
+            reconciliation.quote_discrepancy
+        );
+    }
+
+    set_return_data(&reconciliation.try_to_vec()?);
+
+    Ok(())
+}
@@ -0,0 +1,213 @@
+//! Reduce the size of an existing order without losing its place in the queue.
+use crate::{
+    error::DexError,
+    state::{CallBackInfo, DexState, UserAccount},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+use asset_agnostic_orderbook::state::{
+    critbit::Slab, get_side_from_order_id, market_state::MarketState, AccountTag, Side,
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Clone, Copy, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a reduce_order instruction.
+*/
+pub struct Params {
+    /// The order_id of the order to reduce
+    pub order_id: u128,
+    /// The new base quantity of the order, in base token amount. It should be smaller than the current quantity and
+    /// at least the market's minimum order size
+    pub new_base_qty: u64,
+    /// To eliminate implicit padding
+    pub _padding: u64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The orderbook
+    pub orderbook: &'a T,
+
+    /// The AOB bids shared memory
+    #[cons(writable)]
+    pub bids: &'a T,
+
+    /// The AOB asks shared memory
+    #[cons(writable)]
+    pub asks: &'a T,
+
+    /// The DEX user account
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The user wallet, or the user account's delegate
+    #[cons(signer)]
+    pub user_owner: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            user: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.bids, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.asks, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+
+    pub fn load_user_account(
+        &self,
+        user_account_data: &'a mut [u8],
+    ) -> Result<UserAccount<'a>, ProgramError> {
+        let user_account = UserAccount::from_buffer(user_account_data)?;
+        if !user_account.header.is_order_authority(self.user_owner.key) {
+            msg!("Invalid user account owner or delegate provided!");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if &user_account.header.market != self.market.key {
+            msg!("The provided user account doesn't match the current market");
+            return Err(ProgramError::InvalidArgument);
+        };
+        Ok(user_account)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params {
+        order_id,
+        new_base_qty,
+        _padding,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let mut user_account = accounts.load_user_account(&mut user_account_data)?;
+
+    check_accounts(&market_state, &accounts)?;
+
+    // Makes sure that the order belongs to the user account
+    user_account.find_order_index(*order_id)?;
+
+    let price = (order_id >> 64) as u64;
+    if *new_base_qty < market_state.min_base_order_size {
+        msg!("The new base order size is too small.");
+        return Err(ProgramError::InvalidArgument);
+    }
+    market_state.check_order_granularity(price, *new_base_qty)?;
+
+    let side = get_side_from_order_id(*order_id);
+    let (slab_account, expected_tag) = match side {
+        Side::Bid => (accounts.bids, AccountTag::Bids),
+        Side::Ask => (accounts.asks, AccountTag::Asks),
+    };
+    let mut slab_guard = slab_account.data.borrow_mut();
+    let mut slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, expected_tag)?;
+    let handle = slab.find_by_key(*order_id).ok_or(DexError::OrderNotFound)? as usize;
+
+    if CallBackInfo::read(&slab.callback_infos[handle])?.hidden_base_qty != 0 {
+        msg!("Iceberg orders cannot be reduced");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let new_scaled_base_qty = market_state.scale_base_amount(*new_base_qty);
+    let current_base_qty = market_state
+        .unscale_base_amount(slab.leaf_nodes[handle].base_quantity)
+        .ok_or(DexError::NumericalOverflow)?;
+    if new_base_qty % market_state.base_currency_multiplier != 0 {
+        msg!(
+            "The new base quantity should be a multiple of {}",
+            market_state.base_currency_multiplier
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    if new_scaled_base_qty == 0 || *new_base_qty >= current_base_qty {
+        msg!(
+            "The new base quantity should be smaller than the current quantity of {}",
+            current_base_qty
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    slab.leaf_nodes[handle].base_quantity = new_scaled_base_qty;
+    market_state.remove_resting_base(side, current_base_qty - new_base_qty);
+
+    // The order keeps its key in the slab, and thus its priority
+    match side {
+        Side::Bid => {
+            let released_quote_qty = market_state
+                .get_quote_from_base(current_base_qty, price)
+                .and_then(|n| {
+                    n.checked_sub(market_state.get_quote_from_base(*new_base_qty, price)?)
+                })
+                .ok_or(DexError::NumericalOverflow)?;
+            user_account.header.unlock_quote(released_quote_qty)?;
+        }
+        Side::Ask => {
+            let released_base_qty = current_base_qty - new_base_qty;
+            user_account.header.unlock_base(released_base_qty)?;
+        }
+    };
+
+    Ok(())
+}
+
+fn check_accounts(market_state: &DexState, accounts: &Accounts<AccountInfo>) -> ProgramResult {
+    check_account_key(
+        accounts.orderbook,
+        &market_state.orderbook,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    // The slabs are modified directly rather than through the orderbook program, which usually checks them
+    let mut orderbook_guard = accounts.orderbook.data.borrow_mut();
+    let orderbook = MarketState::from_buffer(&mut orderbook_guard, AccountTag::Market)?;
+    check_account_key(
+        accounts.bids,
+        &orderbook.bids,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    check_account_key(
+        accounts.asks,
+        &orderbook.asks,
+        DexError::InvalidOrderbookAccount,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+//! Register the referrer of a user account which has none, within a window after the account's initialization.
+//!
+//! Referrers can't be attached once the user has had time to generate volume, and the registration is final. Each
+//! registration is emitted as structured program log data tagged with `REFERRER_LOG_TAG`.
+use crate::{
+    error::DexError,
+    state::{UserAccount, REFERRER_LOG_TAG, REFERRER_REGISTRATION_WINDOW},
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_owner, check_signer},
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    log::sol_log_data,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Clone, Copy, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a register_referrer instruction.
+*/
+pub struct Params {
+    /// The wallet which onboarded the user and will receive the referral cut of its taker fees
+    pub referrer: Pubkey,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX user account
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The owner of the user account
+    #[cons(signer)]
+    pub user_owner: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            user: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params { referrer } =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let user_account = UserAccount::from_buffer(&mut user_account_data)?;
+    if &user_account.header.owner != accounts.user_owner.key {
+        msg!("Invalid user account owner provided!");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if user_account.header.referrer != Pubkey::default() {
+        msg!("The user account's referrer is already set");
+        return Err(DexError::ReferrerAlreadySet.into());
+    }
+    if referrer == &Pubkey::default() {
+        msg!("The referrer cannot be the default pubkey");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if referrer == accounts.user_owner.key {
+        msg!("A user account cannot be referred by its own owner");
+        return Err(DexError::SelfReferral.into());
+    }
+
+    let current_timestamp = SysvarClock.now()?;
+    if current_timestamp.saturating_sub(user_account.header.creation_timestamp)
+        > REFERRER_REGISTRATION_WINDOW
+    {
+        msg!(
+            "A referrer can only be registered in the {} seconds following the user account's initialization",
+            REFERRER_REGISTRATION_WINDOW
+        );
+        return Err(DexError::ReferrerRegistrationClosed.into());
+    }
+
+    user_account.header.referrer = *referrer;
+    sol_log_data(&[
+        REFERRER_LOG_TAG,
+        &accounts.user.key.to_bytes(),
+        &referrer.to_bytes(),
+        &current_timestamp.to_le_bytes(),
+    ]);
+
+    Ok(())
+}
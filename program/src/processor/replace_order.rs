@@ -0,0 +1,101 @@
+//! Atomically cancel an existing order and place a new one.
+//!
+//! Both steps happen within the same instruction, so the original order is left untouched whenever the new order
+//! fails. The tokens released by the cancelled order are credited to the free balances, which fund the new order
+//! first: only the difference is transferred from the user's token account.
+use crate::{
+    processor::{cancel_order, new_order},
+    state::DexState,
+    time::{SysvarClock, TimeProvider},
+};
+use bonfida_utils::BorshSize;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program::set_return_data,
+    program_error::ProgramError, pubkey::Pubkey,
+};
+
+pub use new_order::Accounts;
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a replace_order instruction.
+*/
+pub struct Params {
+    /// The order_id of the order to cancel
+    pub order_id: u128,
+    /// The new order to place, whose discount, oracle and liquidity metrics account flags also describe the instruction's accounts
+    pub new_order: new_order::Params,
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let params: &Params =
+        try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let accounts = Accounts::parse(
+        program_id,
+        accounts,
+        params.new_order.has_discount_token_account != 0,
+        params.new_order.has_oracle_account != 0,
+        params.new_order.has_liquidity_metrics_account != 0,
+        params.new_order.has_market_stats_account != 0,
+        params.new_order.has_access_grant_account != 0,
+    )?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let mut user_account = accounts.load_user_account(&mut user_account_data)?;
+
+    new_order::check_accounts(program_id, &market_state, &accounts)?;
+    new_order::check_referral_account(&accounts, &user_account.header.referrer)?;
+    market_state.check_access(
+        program_id,
+        accounts.market.key,
+        &user_account.header.owner,
+        accounts.access_grant,
+    )?;
+
+    let order_index = user_account.find_order_index(params.order_id)?;
+    cancel_order::execute(
+        program_id,
+        &cancel_accounts(&accounts),
+        &mut market_state,
+        &mut user_account,
+        params.order_id,
+        order_index as u64,
+    )?;
+
+    let fill = new_order::execute(
+        program_id,
+        &accounts,
+        &mut market_state,
+        &mut user_account,
+        &params.new_order,
+        &SysvarClock,
+    )?;
+    market_state.update_best_prices(accounts.bids, accounts.asks, SysvarClock.now()?)?;
+    new_order::mirror_market_stats(&accounts, &market_state)?;
+    set_return_data(&fill.try_to_vec()?);
+
+    Ok(())
+}
+
+fn cancel_accounts<'a, 'b: 'a>(
+    accounts: &Accounts<'a, AccountInfo<'b>>,
+) -> cancel_order::Accounts<'a, AccountInfo<'b>> {
+    cancel_order::Accounts {
+        market: accounts.market,
+        orderbook: accounts.orderbook,
+        event_queue: accounts.event_queue,
+        bids: accounts.bids,
+        asks: accounts.asks,
+        user: accounts.user,
+        user_owner: accounts.user_owner,
+    }
+}
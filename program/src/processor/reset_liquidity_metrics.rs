@@ -0,0 +1,125 @@
+//! Start a new liquidity metrics epoch, clearing the market's maker bitmap and unique maker count. This is an admin
+//! instruction.
+//!
+//! The first call with a fresh liquidity metrics account attaches it to the market, after which the market's makers
+//! are recorded by the new_order and consume_events instructions which are given the account.
+use crate::{
+    error::DexError,
+    state::{AccountTag, DexState, LiquidityMetrics},
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a reset_liquidity_metrics instruction.
+*/
+pub struct Params {}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The market's liquidity metrics account, or a zeroed account owned by the DEX program to attach to the market
+    #[cons(writable)]
+    pub liquidity_metrics: &'a T,
+
+    /// The market admin account
+    #[cons(signer)]
+    pub market_admin: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            liquidity_metrics: next_account_info(accounts_iter)?,
+            market_admin: next_account_info(accounts_iter)?,
+        };
+
+        // Check owners
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(
+            a.liquidity_metrics,
+            program_id,
+            DexError::InvalidStateAccountOwner,
+        )?;
+
+        // Check signers
+        check_signer(a.market_admin).map_err(|e| {
+            msg!("The market admin should be a signer for this transaction!");
+            e
+        })?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    check_account_key(
+        accounts.market_admin,
+        &market_state.admin,
+        DexError::InvalidMarketAdminAccount,
+    )?;
+
+    let mut liquidity_metrics_data = accounts.liquidity_metrics.data.borrow_mut();
+    let mut liquidity_metrics =
+        LiquidityMetrics::from_buffer_unchecked(&mut liquidity_metrics_data)?;
+
+    if market_state.liquidity_metrics == Pubkey::default() {
+        if liquidity_metrics.header.tag != AccountTag::Uninitialized as u64 {
+            msg!("The liquidity metrics account should be uninitialized");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if liquidity_metrics.bit_count() == 0 {
+            msg!("The liquidity metrics account is too small to hold any maker");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        liquidity_metrics.header.tag = AccountTag::LiquidityMetrics as u64;
+        liquidity_metrics.header.market = *accounts.market.key;
+        market_state.liquidity_metrics = *accounts.liquidity_metrics.key;
+        msg!(
+            "Tracking the market's makers with a bitmap of {} bits",
+            liquidity_metrics.bit_count()
+        );
+    } else {
+        check_account_key(
+            accounts.liquidity_metrics,
+            &market_state.liquidity_metrics,
+            DexError::InvalidLiquidityMetricsAccount,
+        )?;
+        msg!(
+            "Resetting the unique maker count of {}",
+            market_state.unique_makers
+        );
+    }
+
+    liquidity_metrics.clear();
+    market_state.unique_makers = 0;
+    market_state.liquidity_metrics_epoch_start = SysvarClock.now()?;
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! Revoke a wallet's access to a permissioned market by closing its access grant account. This instruction is signed
+//! by the market's access authority.
+//!
+//! The wallet's resting orders are left untouched and it can still cancel them and settle its funds.
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    error::DexError,
+    state::{AccessGrant, AccountTag, DexState},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+
+#[derive(Clone, Copy, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a revoke_access instruction.
+*/
+pub struct Params {}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    pub market: &'a T,
+
+    /// The market's access authority
+    #[cons(signer)]
+    pub access_authority: &'a T,
+
+    /// The access grant account to close
+    #[cons(writable)]
+    pub access_grant: &'a T,
+
+    /// The account which receives the access grant's lamports
+    #[cons(writable)]
+    pub target_lamports_account: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            access_authority: next_account_info(accounts_iter)?,
+            access_grant: next_account_info(accounts_iter)?,
+            target_lamports_account: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.access_authority).map_err(|e| {
+            msg!("The access authority should be a signer for this transaction!");
+            e
+        })?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.access_grant, program_id, DexError::Unauthorized)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let market_state = DexState::get(accounts.market)?;
+
+    check_account_key(
+        accounts.access_authority,
+        &market_state.access_authority,
+        DexError::Unauthorized,
+    )?;
+
+    let mut access_grant_data = accounts.access_grant.data.borrow_mut();
+    let grant = AccessGrant::deserialize(&mut &access_grant_data[..])?;
+    if grant.tag != AccountTag::AccessGrant as u64 || &grant.market != accounts.market.key {
+        msg!("The access grant doesn't belong to this market");
+        return Err(DexError::Unauthorized.into());
+    }
+
+    access_grant_data.fill(0);
+    access_grant_data[..8].copy_from_slice(&(AccountTag::Closed as u64).to_le_bytes());
+
+    let mut lamports = accounts.access_grant.lamports.borrow_mut();
+    let mut target_lamports = accounts.target_lamports_account.lamports.borrow_mut();
+
+    **target_lamports += **lamports;
+    **lamports = 0;
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! Set the market's base taker fee and maker rebate rates in basis points, along with the rounding of its taker fees.
+//! This is an admin instruction
+use crate::{
+    error::DexError,
+    state::{DexState, FeeRounding},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use num_traits::FromPrimitive;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a set_fee_bps instruction.
+*/
+pub struct Params {
+    /// The taker fee rate of the base fee tier in basis points. Zero selects the default rate
+    pub taker_fee_bps: u16,
+    /// The maker rebate rate in basis points. It cannot exceed the taker fee rate of any fee tier
+    pub maker_rebate_bps: u16,
+    /// The rounding of the taker fees, as a `FeeRounding`
+    pub fee_rounding: u8,
+    /// Set to 1 to withhold the whole maker rebate of fills whose rebates the accumulated fees can't cover, instead of
+    /// reducing the rebates
+    pub strict_fee_reserve: u8,
+    /// To eliminate implicit padding
+    pub _padding: [u8; 2],
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The market admin account
+    #[cons(signer)]
+    pub market_admin: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            market_admin: next_account_info(accounts_iter)?,
+        };
+
+        // Check owners
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+
+        // Check signers
+        check_signer(a.market_admin).map_err(|e| {
+            msg!("The market admin should be a signer for this transaction!");
+            e
+        })?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params {
+        taker_fee_bps,
+        maker_rebate_bps,
+        fee_rounding,
+        strict_fee_reserve,
+        _padding,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    check_account_key(
+        accounts.market_admin,
+        &market_state.admin,
+        DexError::InvalidMarketAdminAccount,
+    )?;
+
+    if *taker_fee_bps > 10_000 {
+        msg!("The taker fee rate cannot exceed 100%");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if *taker_fee_bps != 0 && maker_rebate_bps > taker_fee_bps {
+        msg!("The maker rebate rate cannot exceed the taker fee rate");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if FeeRounding::from_u8(*fee_rounding).is_none() {
+        msg!("Invalid fee rounding mode");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    market_state.base_taker_rate = bps_to_fp32(*taker_fee_bps);
+    market_state.base_maker_rebate = bps_to_fp32(*maker_rebate_bps);
+    market_state.fee_rounding = *fee_rounding;
+    market_state.strict_fee_reserve = (*strict_fee_reserve != 0) as u8;
+    // The rebate should also be covered by the discounted taker rates of the other fee tiers
+    market_state.check_fee_schedule()?;
+
+    Ok(())
+}
+
+fn bps_to_fp32(bps: u16) -> u64 {
+    ((bps as u64) << 32) / 10_000
+}
@@ -0,0 +1,156 @@
+//! Set the token accounts between which the market's accumulated fees are split when swept. This is an admin
+//! instruction.
+//!
+//! The first call with a fresh fee recipients account attaches it to the market, after which the sweep_fees
+//! instruction distributes the fees according to the recipients' weights.
+use crate::{
+    error::DexError,
+    state::{AccountTag, DexState, FeeRecipient, FeeRecipients},
+    utils::{check_account_key, check_account_owner, check_signer, unpack_token_account},
+};
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Clone, BorshDeserialize, BorshSerialize)]
+/**
+The required arguments for a set_fee_recipients instruction.
+*/
+pub struct Params {
+    /// The recipient quote token accounts along with their share of the fees in basis points, which should sum to
+    /// 10000. There can be at most `MAX_FEE_RECIPIENTS` of them.
+    pub fee_recipients: Vec<(Pubkey, u16)>,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The market's fee recipients account, or a zeroed account owned by the DEX program to attach to the market
+    #[cons(writable)]
+    pub fee_recipients: &'a T,
+
+    /// The market admin account
+    #[cons(signer)]
+    pub market_admin: &'a T,
+
+    /// The recipient token accounts, in the order of the instruction's parameters
+    pub recipient_token_accounts: &'a [T],
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            fee_recipients: next_account_info(accounts_iter)?,
+            market_admin: next_account_info(accounts_iter)?,
+            recipient_token_accounts: accounts_iter.as_slice(),
+        };
+
+        // Check owners
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(
+            a.fee_recipients,
+            program_id,
+            DexError::InvalidStateAccountOwner,
+        )?;
+
+        // Check signers
+        check_signer(a.market_admin).map_err(|e| {
+            msg!("The market admin should be a signer for this transaction!");
+            e
+        })?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+    let Params { fee_recipients } = Params::try_from_slice(instruction_data)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    check_account_key(
+        accounts.market_admin,
+        &market_state.admin,
+        DexError::InvalidMarketAdminAccount,
+    )?;
+
+    if accounts.recipient_token_accounts.len() != fee_recipients.len() {
+        msg!("Each fee recipient's token account should be provided");
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let mut recipients = Vec::with_capacity(fee_recipients.len());
+    for ((token_account, weight_bps), token_account_info) in fee_recipients
+        .iter()
+        .zip(accounts.recipient_token_accounts.iter())
+    {
+        check_account_key(
+            token_account_info,
+            token_account,
+            DexError::InvalidFeeRecipients,
+        )?;
+        if token_account == &market_state.quote_vault {
+            msg!("The market's quote vault cannot receive fees");
+            return Err(DexError::InvalidAccountAliasing.into());
+        }
+        if token_account_info.owner != &market_state.token_program
+            || unpack_token_account(token_account_info)?.mint != market_state.quote_mint
+        {
+            msg!("The fee recipients should be token accounts of the market's quote mint");
+            return Err(DexError::InvalidFeeRecipients.into());
+        }
+        recipients.push(FeeRecipient {
+            token_account: *token_account,
+            weight_bps: *weight_bps,
+            _padding: [0; 6],
+        });
+    }
+
+    let mut fee_recipients_data = accounts.fee_recipients.data.borrow_mut();
+    let mut fee_recipients_account =
+        FeeRecipients::from_buffer_unchecked(&mut fee_recipients_data)?;
+
+    if market_state.fee_recipients == Pubkey::default() {
+        if fee_recipients_account.header.tag != AccountTag::Uninitialized as u64 {
+            msg!("The fee recipients account should be uninitialized");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        fee_recipients_account.header.tag = AccountTag::FeeRecipients as u64;
+        fee_recipients_account.header.market = *accounts.market.key;
+        market_state.fee_recipients = *accounts.fee_recipients.key;
+    } else {
+        check_account_key(
+            accounts.fee_recipients,
+            &market_state.fee_recipients,
+            DexError::InvalidFeeRecipientsAccount,
+        )?;
+    }
+
+    fee_recipients_account.set_recipients(&recipients)?;
+    msg!(
+        "The accumulated fees are now split between {} recipients",
+        recipients.len()
+    );
+
+    Ok(())
+}
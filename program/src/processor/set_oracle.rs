@@ -0,0 +1,147 @@
+//! Set or clear the oracle which the market's limit prices are checked against. This is an admin instruction
+use crate::{
+    error::DexError,
+    state::DexState,
+    time::{SysvarClock, TimeProvider},
+    utils::{check_account_key, check_account_owner, check_signer, get_oracle_price},
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use spl_token_2022::{extension::StateWithExtensions, state::Mint};
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a set_oracle instruction.
+*/
+pub struct Params {
+    /// The maximum deviation of limit prices from the oracle price, in basis points. Zero clears the market's oracle
+    pub max_deviation_bps: u64,
+    /// The age in seconds after which oracle prices are considered stale and limit prices aren't checked anymore
+    pub max_oracle_staleness: u64,
+    /// The deviation in basis points between the oracle price and the mid price of the book above which new orders are
+    /// rejected. Zero disables the halt
+    pub auto_halt_deviation_bps: u64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The market admin account
+    #[cons(signer)]
+    pub market_admin: &'a T,
+
+    /// The Pyth price account of the market's pair
+    pub oracle: &'a T,
+
+    /// The base token mint
+    pub base_mint: &'a T,
+
+    /// The quote token mint
+    pub quote_mint: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            market_admin: next_account_info(accounts_iter)?,
+            oracle: next_account_info(accounts_iter)?,
+            base_mint: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
+        };
+
+        // Check owners
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+
+        // Check signers
+        check_signer(a.market_admin).map_err(|e| {
+            msg!("The market admin should be a signer for this transaction!");
+            e
+        })?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params {
+        max_deviation_bps,
+        max_oracle_staleness,
+        auto_halt_deviation_bps,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    check_account_key(
+        accounts.market_admin,
+        &market_state.admin,
+        DexError::InvalidMarketAdminAccount,
+    )?;
+
+    if *max_deviation_bps == 0 {
+        msg!("Clearing the market's oracle");
+        market_state.oracle = Pubkey::default();
+        market_state.max_deviation_bps = 0;
+        market_state.max_oracle_staleness = 0;
+        market_state.auto_halt_deviation_bps = 0;
+        return Ok(());
+    }
+
+    check_account_key(
+        accounts.base_mint,
+        &market_state.base_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    check_account_key(
+        accounts.quote_mint,
+        &market_state.quote_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    market_state.base_decimals =
+        StateWithExtensions::<Mint>::unpack(&accounts.base_mint.data.borrow())?
+            .base
+            .decimals;
+    market_state.quote_decimals =
+        StateWithExtensions::<Mint>::unpack(&accounts.quote_mint.data.borrow())?
+            .base
+            .decimals;
+
+    market_state.oracle = *accounts.oracle.key;
+    market_state.max_deviation_bps = *max_deviation_bps;
+    market_state.max_oracle_staleness = *max_oracle_staleness;
+    market_state.auto_halt_deviation_bps = *auto_halt_deviation_bps;
+
+    // The oracle account is loaded to make sure that it is a valid price account
+    let oracle_price = get_oracle_price(&market_state, accounts.oracle, SysvarClock.now()?)?;
+    msg!(
+        "Setting the market's oracle to {} with a current price of {:?}",
+        accounts.oracle.key,
+        oracle_price
+    );
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! Create or update the program config, which holds the market creation fee and the treasury collecting it.
+//!
+//! The config account is derived from the program id, so that the program has a single config. It is created by the
+//! program's upgrade authority, and then updated by the config's authority.
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::create_account,
+    system_program,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    error::DexError,
+    state::{AccountTag, ProgramConfig, PROGRAM_CONFIG_LEN},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+
+/// The seed of the program config account
+pub const PROGRAM_CONFIG_SEED: &[u8] = b"program_config";
+
+#[derive(Clone, Copy, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a set_program_config instruction.
+*/
+pub struct Params {
+    /// The fee in lamports charged for creating a market. Zero disables the fee
+    pub market_creation_fee: u64,
+    /// The account which collects the market creation fees
+    pub market_creation_fee_treasury: Pubkey,
+    /// The account which may update the program config from now on. The default key keeps the current authority
+    pub new_authority: Pubkey,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The system program
+    pub system_program: &'a T,
+
+    /// The program config account, at the address given by `get_program_config_address`
+    #[cons(writable)]
+    pub program_config: &'a T,
+
+    /// The config's authority, or the program's upgrade authority when the config is created
+    #[cons(signer)]
+    pub authority: &'a T,
+
+    /// The fee payer, which funds the program config account
+    #[cons(writable, signer)]
+    pub fee_payer: &'a T,
+
+    /// The program data account of the DEX program, which is required to create the config
+    pub program_data: Option<&'a T>,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        _program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            system_program: next_account_info(accounts_iter)?,
+            program_config: next_account_info(accounts_iter)?,
+            authority: next_account_info(accounts_iter)?,
+            fee_payer: next_account_info(accounts_iter)?,
+            program_data: next_account_info(accounts_iter).ok(),
+        };
+        check_signer(a.authority).map_err(|e| {
+            msg!("The program config authority should be a signer for this transaction!");
+            e
+        })?;
+        check_signer(a.fee_payer).map_err(|e| {
+            msg!("The fee payer should be a signer for this transaction!");
+            e
+        })?;
+        check_account_key(
+            a.system_program,
+            &system_program::ID,
+            DexError::InvalidSystemProgramAccount,
+        )?;
+
+        Ok(a)
+    }
+}
+
+/// Returns the program config address, along with its nonce
+pub fn get_program_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PROGRAM_CONFIG_SEED], program_id)
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params {
+        market_creation_fee,
+        market_creation_fee_treasury,
+        new_authority,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let (program_config_key, program_config_nonce) = get_program_config_address(program_id);
+    let authority =
+        match ProgramConfig::load(program_id, &program_config_key, accounts.program_config)? {
+            Some(config) => {
+                check_account_key(
+                    accounts.authority,
+                    &config.authority,
+                    DexError::Unauthorized,
+                )?;
+                config.authority
+            }
+            None => {
+                check_upgrade_authority(program_id, accounts.program_data, accounts.authority.key)?;
+                create_program_config_account(program_id, &accounts, program_config_nonce)?;
+                *accounts.authority.key
+            }
+        };
+
+    msg!(
+        "Setting a market creation fee of {} lamports, collected by {}",
+        market_creation_fee,
+        market_creation_fee_treasury
+    );
+
+    let config = ProgramConfig {
+        tag: AccountTag::ProgramConfig as u64,
+        authority: if *new_authority == Pubkey::default() {
+            authority
+        } else {
+            *new_authority
+        },
+        market_creation_fee: *market_creation_fee,
+        market_creation_fee_treasury: *market_creation_fee_treasury,
+    };
+    config.serialize(&mut &mut accounts.program_config.data.borrow_mut()[..])?;
+
+    Ok(())
+}
+
+/// Makes sure that the signer is the upgrade authority recorded in the program data account of the DEX program
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data: Option<&AccountInfo>,
+    authority: &Pubkey,
+) -> ProgramResult {
+    let program_data = program_data.ok_or_else(|| {
+        msg!("The program data account should be provided to create the program config");
+        ProgramError::NotEnoughAccountKeys
+    })?;
+    let (program_data_key, _) =
+        Pubkey::find_program_address(&[&program_id.to_bytes()], &bpf_loader_upgradeable::ID);
+    check_account_key(program_data, &program_data_key, DexError::Unauthorized)?;
+    check_account_owner(
+        program_data,
+        &bpf_loader_upgradeable::ID,
+        DexError::Unauthorized,
+    )?;
+    match limited_deserialize(&program_data.data.borrow()) {
+        Ok(UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address: Some(upgrade_authority),
+            ..
+        }) if &upgrade_authority == authority => Ok(()),
+        _ => {
+            msg!("The program config should be created by the program's upgrade authority");
+            Err(DexError::Unauthorized.into())
+        }
+    }
+}
+
+fn create_program_config_account(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+    program_config_nonce: u8,
+) -> ProgramResult {
+    let lamports = Rent::get()?.minimum_balance(PROGRAM_CONFIG_LEN);
+
+    let allocate_account = create_account(
+        accounts.fee_payer.key,
+        accounts.program_config.key,
+        lamports,
+        PROGRAM_CONFIG_LEN as u64,
+        program_id,
+    );
+
+    invoke_signed(
+        &allocate_account,
+        &[
+            accounts.system_program.clone(),
+            accounts.fee_payer.clone(),
+            accounts.program_config.clone(),
+        ],
+        &[&[PROGRAM_CONFIG_SEED, &[program_config_nonce]]],
+    )
+}
@@ -2,25 +2,45 @@
 use crate::{
     error::DexError,
     state::{DexState, UserAccount},
-    utils::{check_account_key, check_account_owner, check_signer},
+    utils::{
+        check_account_key, check_account_owner, check_signer, check_token_program,
+        get_mint_transfer_info, unpack_token_account,
+    },
 };
 use bonfida_utils::BorshSize;
 use bonfida_utils::InstructionsAccount;
 use borsh::BorshDeserialize;
 use borsh::BorshSerialize;
-use bytemuck::{Pod, Zeroable};
+use bytemuck::{try_from_bytes, Pod, Zeroable};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
-    program::invoke_signed,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     pubkey::Pubkey,
+    sysvar::Sysvar,
 };
 
 #[derive(Clone, Copy, BorshDeserialize, BorshSerialize, BorshSize, Pod, Zeroable)]
 #[repr(C)]
-pub struct Params {}
+/**
+The required arguments for a settle instruction.
+*/
+pub struct Params {
+    /// On markets quoted in native SOL, setting this to 1 closes the destination quote token account once the quote
+    /// tokens are settled, which unwraps them along with the account's rent into the user wallet.
+    ///
+    /// The destination quote token account should then be a temporary native token account owned by the user wallet.
+    pub unwrap_native_quote: u64,
+    /// Setting this to 1 lets the destination token accounts be owned by any wallet, such as the vaults of a
+    /// custodian. Since only the owner of the user account can sign a settlement, its funds can't be redirected by
+    /// anyone else. The destination token accounts must still hold the market's mints.
+    ///
+    /// By default, the destination token accounts must be owned by the user account owner.
+    pub settle_destination: u64,
+}
 
 #[derive(InstructionsAccount)]
 pub struct Accounts<'a, T> {
@@ -28,6 +48,7 @@ pub struct Accounts<'a, T> {
     pub spl_token_program: &'a T,
 
     /// The DEX market
+    #[cons(writable)]
     pub market: &'a T,
 
     /// The base token vault
@@ -46,7 +67,7 @@ pub struct Accounts<'a, T> {
     pub user: &'a T,
 
     /// The DEX user account owner wallet
-    #[cons(signer)]
+    #[cons(writable, signer)]
     pub user_owner: &'a T,
 
     /// The destination base token account
@@ -56,6 +77,12 @@ pub struct Accounts<'a, T> {
     /// The destination quote token account
     #[cons(writable)]
     pub destination_quote_account: &'a T,
+
+    /// The base token mint
+    pub base_mint: &'a T,
+
+    /// The quote token mint
+    pub quote_mint: &'a T,
 }
 
 impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
@@ -74,22 +101,25 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
             user_owner: next_account_info(accounts_iter)?,
             destination_base_account: next_account_info(accounts_iter)?,
             destination_quote_account: next_account_info(accounts_iter)?,
+            base_mint: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
         };
         check_signer(a.user_owner).map_err(|e| {
             msg!("The user account owner should be a signer for this transaction!");
             e
         })?;
-        check_account_key(
-            a.spl_token_program,
-            &spl_token::ID,
-            DexError::InvalidSplTokenProgram,
-        )?;
-        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
-        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+        a.check_account_owners(program_id)?;
 
         Ok(a)
     }
 
+    pub(crate) fn check_account_owners(&self, program_id: &Pubkey) -> ProgramResult {
+        check_token_program(self.spl_token_program)?;
+        check_account_owner(self.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(self.user, program_id, DexError::InvalidStateAccountOwner)?;
+        Ok(())
+    }
+
     pub fn load_user_account(
         &self,
         user_account_data: &'a mut [u8],
@@ -107,23 +137,64 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
     }
 }
 
-pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
     let accounts = Accounts::parse(program_id, accounts)?;
 
-    let market_state = DexState::get(accounts.market)?;
+    let Params {
+        unwrap_native_quote,
+        settle_destination,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    execute(
+        program_id,
+        &accounts,
+        *unwrap_native_quote != 0,
+        *settle_destination != 0,
+    )
+}
+
+/// Transfers the free balances of the user account to the destination token accounts, which can only be owned by
+/// another wallet than the user account owner when `settle_destination` is set
+pub(crate) fn execute(
+    program_id: &Pubkey,
+    accounts: &Accounts<AccountInfo>,
+    unwrap_native_quote: bool,
+    settle_destination: bool,
+) -> ProgramResult {
+    let mut market_state = DexState::get(accounts.market)?;
 
     let mut user_account_data = accounts.user.data.borrow_mut();
     let mut user_account = accounts.load_user_account(&mut user_account_data)?;
 
-    check_accounts(program_id, &market_state, &accounts).unwrap();
+    check_accounts(program_id, &market_state, accounts, settle_destination)?;
 
-    let transfer_quote_instruction = spl_token::instruction::transfer(
-        &spl_token::ID,
+    // Fee-on-transfer mints withhold part of the transferred amount from the destination account. The whole free
+    // balance still leaves the vault, which is what the user account is debited with.
+    let epoch = Clock::get()?.epoch;
+    let (quote_decimals, quote_transfer_fee) = get_mint_transfer_info(
+        accounts.quote_mint,
+        user_account.header.quote_token_free,
+        epoch,
+    )?;
+    let (base_decimals, base_transfer_fee) = get_mint_transfer_info(
+        accounts.base_mint,
+        user_account.header.base_token_free,
+        epoch,
+    )?;
+
+    let transfer_quote_instruction = spl_token_2022::instruction::transfer_checked(
+        accounts.spl_token_program.key,
         &market_state.quote_vault,
+        accounts.quote_mint.key,
         accounts.destination_quote_account.key,
         accounts.market_signer.key,
         &[],
         user_account.header.quote_token_free,
+        quote_decimals,
     )?;
 
     invoke_signed(
@@ -131,6 +202,7 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
         &[
             accounts.spl_token_program.clone(),
             accounts.quote_vault.clone(),
+            accounts.quote_mint.clone(),
             accounts.destination_quote_account.clone(),
             accounts.market_signer.clone(),
         ],
@@ -140,13 +212,15 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
         ]],
     )?;
 
-    let transfer_base_instruction = spl_token::instruction::transfer(
-        &spl_token::ID,
+    let transfer_base_instruction = spl_token_2022::instruction::transfer_checked(
+        accounts.spl_token_program.key,
         &market_state.base_vault,
+        accounts.base_mint.key,
         accounts.destination_base_account.key,
         accounts.market_signer.key,
         &[],
         user_account.header.base_token_free,
+        base_decimals,
     )?;
 
     invoke_signed(
@@ -154,6 +228,7 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
         &[
             accounts.spl_token_program.clone(),
             accounts.base_vault.clone(),
+            accounts.base_mint.clone(),
             accounts.destination_base_account.clone(),
             accounts.market_signer.clone(),
         ],
@@ -163,6 +238,43 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
         ]],
     )?;
 
+    if unwrap_native_quote {
+        if market_state.is_quote_native == 0 {
+            msg!("Only the quote tokens of markets quoted in native SOL can be unwrapped");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if settle_destination {
+            msg!("Unwrapping requires a destination quote token account owned by the user wallet");
+            return Err(ProgramError::InvalidArgument);
+        }
+        // Closing the native token account returns its rent along with the unwrapped lamports
+        let close_instruction = spl_token_2022::instruction::close_account(
+            accounts.spl_token_program.key,
+            accounts.destination_quote_account.key,
+            accounts.user_owner.key,
+            accounts.user_owner.key,
+            &[],
+        )?;
+        invoke(
+            &close_instruction,
+            &[
+                accounts.spl_token_program.clone(),
+                accounts.destination_quote_account.clone(),
+                accounts.user_owner.clone(),
+            ],
+        )?;
+    }
+
+    msg!(
+        "Settled {} base and {} quote tokens net of transfer fees",
+        user_account.header.base_token_free - base_transfer_fee,
+        user_account.header.quote_token_free - quote_transfer_fee
+    );
+
+    market_state.record_vault_outflow(
+        user_account.header.base_token_free,
+        user_account.header.quote_token_free,
+    );
     user_account.header.quote_token_free = 0;
     user_account.header.base_token_free = 0;
 
@@ -173,29 +285,78 @@ fn check_accounts(
     program_id: &Pubkey,
     market_state: &DexState,
     accounts: &Accounts<AccountInfo>,
+    settle_destination: bool,
 ) -> ProgramResult {
-    let market_signer = Pubkey::create_program_address(
-        &[
-            &accounts.market.key.to_bytes(),
-            &[market_state.signer_nonce as u8],
-        ],
-        program_id,
+    check_account_key(
+        accounts.spl_token_program,
+        &market_state.token_program,
+        DexError::InvalidSplTokenProgram,
     )?;
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
     check_account_key(
         accounts.market_signer,
         &market_signer,
         DexError::InvalidMarketSignerAccount,
     )?;
+    market_state.check_vaults(&market_signer, accounts.base_vault, accounts.quote_vault)?;
     check_account_key(
-        accounts.base_vault,
-        &market_state.base_vault,
-        DexError::InvalidBaseVaultAccount,
+        accounts.base_mint,
+        &market_state.base_mint,
+        DexError::InvalidMintAccount,
     )?;
     check_account_key(
-        accounts.quote_vault,
-        &market_state.quote_vault,
-        DexError::InvalidQuoteVaultAccount,
+        accounts.quote_mint,
+        &market_state.quote_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    let destination_owner = if settle_destination {
+        None
+    } else {
+        Some(accounts.user_owner.key)
+    };
+    check_destination_accounts(
+        market_state,
+        destination_owner,
+        accounts.destination_base_account,
+        accounts.destination_quote_account,
     )?;
 
     Ok(())
 }
+
+/// Makes sure that the destination token accounts are distinct from each other and from the market vaults, that they
+/// hold the market's mints and, unless `user_owner` is `None`, that they belong to the user account owner
+pub(crate) fn check_destination_accounts(
+    market_state: &DexState,
+    user_owner: Option<&Pubkey>,
+    destination_base_account: &AccountInfo,
+    destination_quote_account: &AccountInfo,
+) -> ProgramResult {
+    if destination_base_account.key == destination_quote_account.key {
+        msg!("The base and quote destination accounts should be distinct");
+        return Err(DexError::InvalidAccountAliasing.into());
+    }
+    for (destination, mint) in [
+        (destination_base_account, &market_state.base_mint),
+        (destination_quote_account, &market_state.quote_mint),
+    ] {
+        if destination.key == &market_state.base_vault
+            || destination.key == &market_state.quote_vault
+        {
+            msg!("A market vault cannot be used as a destination account");
+            return Err(DexError::InvalidAccountAliasing.into());
+        }
+        let destination_token_account = unpack_token_account(destination)?;
+        if &destination_token_account.mint != mint {
+            msg!("The destination accounts should hold the market's mints");
+            return Err(DexError::InvalidMintAccount.into());
+        }
+        if let Some(user_owner) = user_owner {
+            if &destination_token_account.owner != user_owner {
+                msg!("The destination accounts should be owned by the user account owner");
+                return Err(DexError::InvalidAccountAliasing.into());
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! Extract available base and quote token assets from the user accounts of a wallet on several markets at once
+use crate::{
+    processor::settle,
+    utils::{check_signer, check_token_program},
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// The maximum number of markets which can be settled in a single instruction
+pub const MAX_MARKETS: usize = 4;
+
+/// The number of accounts which describe the settlement of a single market
+pub const ACCOUNTS_PER_MARKET: usize = 9;
+
+#[derive(Clone, Copy, BorshDeserialize, BorshSerialize, BorshSize, Pod, Zeroable)]
+#[repr(C)]
+pub struct Params {}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The spl token program
+    pub spl_token_program: &'a T,
+
+    /// The DEX user accounts owner wallet
+    #[cons(signer)]
+    pub user_owner: &'a T,
+
+    /// The accounts of each market, ordered as in a settle instruction without the token program and user wallet
+    #[cons(writable)]
+    pub market_accounts: &'a [T],
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        _program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            spl_token_program: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+            market_accounts: accounts_iter.as_slice(),
+        };
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+        check_token_program(a.spl_token_program)?;
+        let number_of_markets = a.market_accounts.len() / ACCOUNTS_PER_MARKET;
+        if a.market_accounts.len() % ACCOUNTS_PER_MARKET != 0
+            || number_of_markets == 0
+            || number_of_markets > MAX_MARKETS
+        {
+            msg!(
+                "Between 1 and {} markets should be provided, with {} accounts each",
+                MAX_MARKETS,
+                ACCOUNTS_PER_MARKET
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(a)
+    }
+
+    /// The settle accounts of each market
+    pub(crate) fn settle_accounts(
+        &self,
+        program_id: &Pubkey,
+    ) -> Result<Vec<settle::Accounts<'a, AccountInfo<'b>>>, ProgramError> {
+        self.market_accounts
+            .chunks_exact(ACCOUNTS_PER_MARKET)
+            .map(|c| {
+                let a = settle::Accounts {
+                    spl_token_program: self.spl_token_program,
+                    market: &c[0],
+                    base_vault: &c[1],
+                    quote_vault: &c[2],
+                    market_signer: &c[3],
+                    user: &c[4],
+                    user_owner: self.user_owner,
+                    destination_base_account: &c[5],
+                    destination_quote_account: &c[6],
+                    base_mint: &c[7],
+                    quote_mint: &c[8],
+                };
+                a.check_account_owners(program_id)?;
+                Ok(a)
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let settle_accounts = accounts.settle_accounts(program_id)?;
+
+    for a in settle_accounts.iter() {
+        // Each user account is checked against its market and the signing owner wallet
+        settle::execute(program_id, a, false, false)?;
+    }
+
+    Ok(())
+}
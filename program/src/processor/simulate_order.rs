@@ -0,0 +1,178 @@
+//! Compute how much an order would fill against the current book, without modifying any account.
+//!
+//! The outcome is emitted as the instruction's return data, as an `OrderFill`. Self-trading and expired maker orders
+//! are not taken into account.
+use crate::{
+    error::DexError,
+    processor::new_order::OrderFill,
+    state::{DexState, FeeTier},
+    utils::{check_account_key, check_account_owner, simulate_match},
+};
+use asset_agnostic_orderbook::state::{market_state::MarketState, AccountTag, Side};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use num_traits::FromPrimitive;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::set_return_data,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for a simulate_order instruction.
+*/
+pub struct Params {
+    /// The order's limit price (as a FP32)
+    pub limit_price: u64,
+    /// The max quantity of base token to match
+    pub max_base_qty: u64,
+    /// The max quantity of quote token to match, fees included
+    pub max_quote_qty: u64,
+    /// The maximum number of orders to be matched against
+    pub match_limit: u64,
+    /// The order's side (Bid or Ask)
+    pub side: u8,
+    /// The fee tier used to compute the taker fees
+    pub fee_tier: u8,
+    /// To eliminate implicit padding
+    pub _padding: [u8; 6],
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    pub market: &'a T,
+
+    /// The orderbook
+    pub orderbook: &'a T,
+
+    /// The AOB bids shared memory
+    pub bids: &'a T,
+
+    /// The AOB asks shared memory
+    pub asks: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+        };
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.bids, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.asks, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params {
+        limit_price,
+        max_base_qty,
+        max_quote_qty,
+        match_limit,
+        side,
+        fee_tier,
+        _padding,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let market_state = DexState::get_ref(accounts.market)?;
+
+    check_accounts(&market_state, &accounts)?;
+
+    let taker_side: Side = FromPrimitive::from_u8(*side).ok_or(ProgramError::InvalidArgument)?;
+    if *fee_tier > FeeTier::Stable as u8 {
+        msg!("Invalid fee tier");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let (fee_tier, _) = FeeTier::from_u8(*fee_tier);
+
+    let maker_slab = match taker_side {
+        Side::Bid => accounts.asks,
+        Side::Ask => accounts.bids,
+    };
+    let max_quote_qty = match taker_side {
+        // The quote budget of a bid also pays for its taker fees, as for a new order
+        Side::Bid => fee_tier.remove_taker_fee(&market_state, *max_quote_qty)?,
+        Side::Ask => *max_quote_qty,
+    };
+    let simulation = simulate_match(
+        maker_slab,
+        taker_side,
+        *limit_price,
+        market_state.scale_base_amount(*max_base_qty),
+        market_state.scale_quote_amount(max_quote_qty),
+        *match_limit,
+    )?;
+
+    let base_filled = market_state
+        .unscale_base_amount(simulation.base_qty)
+        .ok_or(DexError::NumericalOverflow)?;
+    let quote_filled = market_state
+        .unscale_quote_amount(simulation.quote_qty)
+        .ok_or(DexError::NumericalOverflow)?;
+    let royalties_fees = quote_filled
+        .checked_mul(market_state.royalties_bps)
+        .ok_or(DexError::NumericalOverflow)?
+        / 10_000;
+    let fee_paid = fee_tier
+        .taker_fee(&market_state, quote_filled)?
+        .checked_add(royalties_fees)
+        .ok_or(DexError::NumericalOverflow)?;
+
+    let fill = OrderFill {
+        base_filled,
+        quote_filled,
+        fee_paid,
+        average_price: market_state
+            .get_average_price(base_filled, quote_filled)
+            .unwrap_or(0),
+    };
+    set_return_data(&fill.try_to_vec()?);
+
+    Ok(())
+}
+
+fn check_accounts(market_state: &DexState, accounts: &Accounts<AccountInfo>) -> ProgramResult {
+    check_account_key(
+        accounts.orderbook,
+        &market_state.orderbook,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    let mut orderbook_guard = accounts.orderbook.data.borrow_mut();
+    let orderbook = MarketState::from_buffer(&mut orderbook_guard, AccountTag::Market)?;
+    check_account_key(
+        accounts.bids,
+        &orderbook.bids,
+        DexError::InvalidOrderbookAccount,
+    )?;
+    check_account_key(
+        accounts.asks,
+        &orderbook.asks,
+        DexError::InvalidOrderbookAccount,
+    )?;
+
+    Ok(())
+}
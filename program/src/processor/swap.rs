@@ -1,7 +1,20 @@
+//! Swap an exact input amount against the orderbook, with a minimum output amount as slippage protection.
+//!
+//! The order is matched immediately and never posted. The output is transferred straight to the user's token account
+//! and emitted as return data, so that swap routers don't need a user account nor any consume_events or settle step.
+//! Expired maker orders at the top of the book are cancelled before matching, as for new orders.
+//!
+//! With fee-on-transfer mints, the transfer fee of the input is set aside from the exact input quantity before
+//! matching, and the minimum output quantity applies to the amount received net of the output's transfer fee.
 use crate::{
     error::DexError,
-    state::{CallBackInfo, DexState, FeeTier},
-    utils::{check_account_key, check_account_owner, check_signer},
+    processor::new_order::cancel_expired_maker_orders,
+    state::{CallBackInfo, DexState, FeeTier, CALLBACK_INFO_VERSION},
+    time::{SysvarClock, TimeProvider},
+    utils::{
+        check_account_key, check_account_owner, check_signer, check_token_program,
+        check_token_program_owner, get_mint_gross_transfer_info, get_mint_transfer_info,
+    },
 };
 use asset_agnostic_orderbook::state::{SelfTradeBehavior, Side};
 use asset_agnostic_orderbook::{error::AoError, state::AccountTag};
@@ -13,13 +26,16 @@ use bytemuck::{try_from_bytes, Pod, Zeroable};
 use num_traits::FromPrimitive;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
     program::invoke,
     program::invoke_signed,
+    program::set_return_data,
     program_error::{PrintProgramError, ProgramError},
     pubkey::Pubkey,
     system_program,
+    sysvar::Sysvar,
 };
 
 use super::REFERRAL_MASK;
@@ -27,12 +43,12 @@ use super::REFERRAL_MASK;
 #[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
 #[repr(C)]
 /**
-The required arguments for a new_order instruction.
+The required arguments for a swap instruction.
 */
 pub struct Params {
-    /// For bids, the min output quantity. For asks, the exact input quantity.
+    /// For bids, the minimum output quantity. For asks, the exact input quantity.
     pub base_qty: u64,
-    /// For bids, the exact input quantity. For asks, the min output quantity.
+    /// For bids, the exact input quantity, fees included. For asks, the minimum output quantity, fees excluded.
     pub quote_qty: u64,
     /// The maximum number of orders to be matched against.
     ///
@@ -42,8 +58,23 @@ pub struct Params {
     pub side: u8,
     /// Whether or not the optional discount token account was given
     pub has_discount_token_account: u8,
+    /// Whether or not the user wallet's access grant was given, which is required on permissioned markets
+    pub has_access_grant_account: u8,
     /// To eliminate implicit padding
-    pub _padding: [u8; 6],
+    pub _padding: [u8; 5],
+}
+
+/// The outcome of a swap instruction.
+///
+/// It is emitted as the instruction's return data so that calling programs can read it with `get_return_data`.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Default, PartialEq, Clone, Copy)]
+pub struct SwapOutcome {
+    /// The quantity which was transferred from the user's input token account, transfer fees included
+    pub input_qty: u64,
+    /// The quantity which was received by the user's output token account, net of transfer fees
+    pub output_qty: u64,
+    /// The taker fees and royalties paid in quote token
+    pub fee_paid: u64,
 }
 
 #[derive(InstructionsAccount)]
@@ -85,6 +116,12 @@ pub struct Accounts<'a, T> {
     /// The DEX market signer
     pub market_signer: &'a T,
 
+    /// The base token mint
+    pub base_mint: &'a T,
+
+    /// The quote token mint
+    pub quote_mint: &'a T,
+
     /// The user base token account
     #[cons(writable)]
     pub user_base_account: &'a T,
@@ -100,7 +137,10 @@ pub struct Accounts<'a, T> {
     /// The optional SRM or MSRM discount token account (must be owned by the user wallet)
     pub discount_token_account: Option<&'a T>,
 
-    /// The optional referrer's token account which will receive a 20% cut of the fees
+    /// The user wallet's access grant, which is required when the market has an access authority
+    pub access_grant: Option<&'a T>,
+
+    /// The optional referrer's token account which will receive its share of the fees
     #[cons(writable)]
     pub fee_referral_account: Option<&'a T>,
 }
@@ -110,6 +150,7 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
         program_id: &Pubkey,
         accounts: &'a [AccountInfo<'b>],
         has_discount_token_account: bool,
+        has_access_grant_account: bool,
     ) -> Result<Self, ProgramError> {
         let accounts_iter = &mut accounts.iter();
         let a = Self {
@@ -123,6 +164,8 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
             base_vault: next_account_info(accounts_iter)?,
             quote_vault: next_account_info(accounts_iter)?,
             market_signer: next_account_info(accounts_iter)?,
+            base_mint: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
             user_base_account: next_account_info(accounts_iter)?,
             user_quote_account: next_account_info(accounts_iter)?,
             user_owner: next_account_info(accounts_iter)?,
@@ -131,28 +174,25 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
             } else {
                 None
             },
+            access_grant: if has_access_grant_account {
+                next_account_info(accounts_iter).ok()
+            } else {
+                None
+            },
             fee_referral_account: next_account_info(accounts_iter).ok(),
         };
         check_signer(a.user_owner).map_err(|e| {
             msg!("The user account owner should be a signer for this transaction!");
             e
         })?;
-        check_account_key(
-            a.spl_token_program,
-            &spl_token::ID,
-            DexError::InvalidSplTokenProgram,
-        )?;
+        check_token_program(a.spl_token_program)?;
         check_account_key(
             a.system_program,
             &system_program::ID,
             DexError::InvalidSystemProgramAccount,
         )?;
         if let Some(discount_account) = a.discount_token_account {
-            check_account_owner(
-                discount_account,
-                &spl_token::ID,
-                DexError::InvalidSplTokenProgram,
-            )?
+            check_token_program_owner(discount_account, DexError::InvalidSplTokenProgram)?
         }
         check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
 
@@ -171,11 +211,17 @@ pub(crate) fn process(
         mut quote_qty,
         match_limit,
         has_discount_token_account,
+        has_access_grant_account,
         _padding: _,
     } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
-    let accounts = Accounts::parse(program_id, accounts, *has_discount_token_account != 0)?;
+    let accounts = Accounts::parse(
+        program_id,
+        accounts,
+        *has_discount_token_account != 0,
+        *has_access_grant_account != 0,
+    )?;
 
-    let market_state = DexState::get(accounts.market)?;
+    let mut market_state = DexState::get(accounts.market)?;
 
     // Check the order size
     if base_qty < &market_state.min_base_order_size {
@@ -183,7 +229,14 @@ pub(crate) fn process(
         return Err(ProgramError::InvalidArgument);
     }
 
-    check_accounts(program_id, &market_state, &accounts).unwrap();
+    check_accounts(program_id, &market_state, &accounts)?;
+    market_state.check_access(
+        program_id,
+        accounts.market.key,
+        accounts.user_owner.key,
+        accounts.access_grant,
+    )?;
+    market_state.check_new_order_allowed(false)?;
     let fee_tier = accounts
         .discount_token_account
         .map(|a| FeeTier::get(&market_state, a, accounts.user_owner.key))
@@ -192,10 +245,22 @@ pub(crate) fn process(
         user_account: Pubkey::default(),
         fee_tier: fee_tier as u8
             | ((accounts.fee_referral_account.is_some() as u8) * REFERRAL_MASK),
+        self_trade_behavior: 0,
+        version: CALLBACK_INFO_VERSION,
+        _padding: [0; 5],
+        expiry_timestamp: 0,
+        display_base_qty: 0,
+        hidden_base_qty: 0,
     };
+    let epoch = Clock::get()?.epoch;
+    let mut input_base_qty = *base_qty;
     if *side == Side::Bid as u8 {
+        // The input's transfer fee is withheld from the vault, so it is set aside from the exact input quantity
+        quote_qty -= get_mint_transfer_info(accounts.quote_mint, quote_qty, epoch)?.1;
         // We make sure to leave enough quote quantity to pay for taker fees in the worst case
-        quote_qty = fee_tier.remove_taker_fee(quote_qty);
+        quote_qty = fee_tier.remove_taker_fee(&market_state, quote_qty)?;
+    } else {
+        input_base_qty -= get_mint_transfer_info(accounts.base_mint, input_base_qty, epoch)?.1;
     }
 
     let mut orderbook_guard = accounts.orderbook.data.borrow_mut();
@@ -213,9 +278,21 @@ pub(crate) fn process(
                 market_state.scale_quote_amount(quote_qty),
                 u64::MAX - (u64::MAX % tick_size),
             ),
-            Side::Ask => (market_state.scale_base_amount(*base_qty), u64::MAX, 0),
+            Side::Ask => (market_state.scale_base_amount(input_base_qty), u64::MAX, 0),
         };
 
+    // Expired makers are removed first so that the swap is never filled against them
+    cancel_expired_maker_orders(
+        program_id,
+        accounts.orderbook,
+        accounts.event_queue,
+        accounts.bids,
+        accounts.asks,
+        FromPrimitive::from_u8(*side).unwrap(),
+        limit_price,
+        SysvarClock.now()?,
+    )?;
+
     let invoke_params = asset_agnostic_orderbook::instruction::new_order::Params {
         max_base_qty: max_base_qty_scaled,
         max_quote_qty: max_quote_qty_scaled,
@@ -250,44 +327,49 @@ pub(crate) fn process(
     market_state
         .unscale_order_summary(&mut order_summary)
         .unwrap();
+    // Swaps are never posted, everything they take leaves the other side of the book
+    let maker_side = match FromPrimitive::from_u8(*side).unwrap() {
+        Side::Bid => Side::Ask,
+        Side::Ask => Side::Bid,
+    };
+    market_state.remove_resting_base(maker_side, order_summary.total_base_qty);
 
-    let referral_fee = fee_tier.referral_fee(order_summary.total_quote_qty);
+    let referral_fee = fee_tier.referral_fee(&market_state, order_summary.total_quote_qty)?;
     let royalties_fees = order_summary
         .total_quote_qty
         .checked_mul(market_state.royalties_bps)
         .unwrap()
         / 10_000;
-    let (is_valid, base_transfer_qty, quote_transfer_qty) =
-        match FromPrimitive::from_u8(*side).unwrap() {
-            Side::Bid => {
-                // We update the order summary to properly handle the FOK order type
-
-                order_summary.total_quote_qty +=
-                    fee_tier.taker_fee(order_summary.total_quote_qty) + royalties_fees;
-
-                let is_valid = &order_summary.total_base_qty >= base_qty;
-
-                (
-                    is_valid,
-                    order_summary.total_base_qty,
-                    order_summary.total_quote_qty,
-                )
-            }
-            Side::Ask => {
-                let taker_fee = fee_tier.taker_fee(order_summary.total_quote_qty);
-
-                let is_valid = order_summary.total_quote_qty >= quote_qty;
-
-                (
-                    is_valid,
-                    order_summary.total_base_qty,
-                    order_summary
-                        .total_quote_qty
-                        .checked_sub(taker_fee + royalties_fees)
-                        .unwrap(),
-                )
-            }
-        };
+    let fee_paid =
+        fee_tier.taker_fee(&market_state, order_summary.total_quote_qty)? + royalties_fees;
+    let (is_valid, base_transfer_qty, quote_transfer_qty) = match FromPrimitive::from_u8(*side)
+        .unwrap()
+    {
+        Side::Bid => {
+            // We update the order summary to properly handle the FOK order type
+
+            order_summary.total_quote_qty += fee_paid;
+
+            let base_out_fee =
+                get_mint_transfer_info(accounts.base_mint, order_summary.total_base_qty, epoch)?.1;
+            let is_valid = &(order_summary.total_base_qty - base_out_fee) >= base_qty;
+
+            (
+                is_valid,
+                order_summary.total_base_qty,
+                order_summary.total_quote_qty,
+            )
+        }
+        Side::Ask => {
+            let quote_out_qty = order_summary.total_quote_qty.checked_sub(fee_paid).unwrap();
+
+            let quote_out_fee =
+                get_mint_transfer_info(accounts.quote_mint, quote_out_qty, epoch)?.1;
+            let is_valid = quote_out_qty - quote_out_fee >= quote_qty;
+
+            (is_valid, order_summary.total_base_qty, quote_out_qty)
+        }
+    };
 
     if !is_valid {
         msg!("Insufficient output amount");
@@ -298,26 +380,33 @@ pub(crate) fn process(
         base_transfer_qty,
         accounts.user_base_account,
         accounts.base_vault,
+        accounts.base_mint,
     );
     let quote_transfer_params = (
         quote_transfer_qty,
         accounts.user_quote_account,
         accounts.quote_vault,
+        accounts.quote_mint,
     );
 
-    let (transfer_in_qty, transfer_in_from, transfer_in_to) =
+    let (transfer_in_qty, transfer_in_from, transfer_in_to, transfer_in_mint) =
         match FromPrimitive::from_u8(*side).unwrap() {
             Side::Bid => quote_transfer_params,
             Side::Ask => base_transfer_params,
         };
 
-    let transfer_in_instruction = spl_token::instruction::transfer(
+    // The input transfer is grossed up for the vault to receive the whole matched quantity
+    let (transfer_in_decimals, gross_transfer_in_qty, transfer_in_fee) =
+        get_mint_gross_transfer_info(transfer_in_mint, transfer_in_qty, epoch)?;
+    let transfer_in_instruction = spl_token_2022::instruction::transfer_checked(
         accounts.spl_token_program.key,
         transfer_in_from.key,
+        transfer_in_mint.key,
         transfer_in_to.key,
         accounts.user_owner.key,
         &[],
-        transfer_in_qty,
+        gross_transfer_in_qty,
+        transfer_in_decimals,
     )?;
 
     invoke(
@@ -325,24 +414,30 @@ pub(crate) fn process(
         &[
             accounts.spl_token_program.clone(),
             transfer_in_from.clone(),
+            transfer_in_mint.clone(),
             transfer_in_to.clone(),
             accounts.user_owner.clone(),
         ],
     )?;
+    let credited_in_qty = gross_transfer_in_qty - transfer_in_fee;
 
-    let (transfer_out_qty, transfer_out_to, transfer_out_from) =
+    let (transfer_out_qty, transfer_out_to, transfer_out_from, transfer_out_mint) =
         match FromPrimitive::from_u8(*side).unwrap() {
             Side::Bid => base_transfer_params,
             Side::Ask => quote_transfer_params,
         };
 
-    let transfer_out_instruction = spl_token::instruction::transfer(
+    let (transfer_out_decimals, transfer_out_fee) =
+        get_mint_transfer_info(transfer_out_mint, transfer_out_qty, epoch)?;
+    let transfer_out_instruction = spl_token_2022::instruction::transfer_checked(
         accounts.spl_token_program.key,
         transfer_out_from.key,
+        transfer_out_mint.key,
         transfer_out_to.key,
         accounts.market_signer.key,
         &[],
         transfer_out_qty,
+        transfer_out_decimals,
     )?;
 
     invoke_signed(
@@ -350,6 +445,7 @@ pub(crate) fn process(
         &[
             accounts.spl_token_program.clone(),
             transfer_out_from.clone(),
+            transfer_out_mint.clone(),
             transfer_out_to.clone(),
             accounts.market_signer.clone(),
         ],
@@ -359,14 +455,28 @@ pub(crate) fn process(
         ]],
     )?;
 
+    match FromPrimitive::from_u8(*side).unwrap() {
+        Side::Bid => {
+            market_state.record_vault_inflow(0, credited_in_qty)?;
+            market_state.record_vault_outflow(base_transfer_qty, 0);
+        }
+        Side::Ask => {
+            market_state.record_vault_inflow(credited_in_qty, 0)?;
+            market_state.record_vault_outflow(0, quote_transfer_qty);
+        }
+    }
+
     if let Some(fee_token_account) = accounts.fee_referral_account {
-        let referral_fee_transfer_instruction = spl_token::instruction::transfer(
+        let (quote_decimals, _) = get_mint_transfer_info(accounts.quote_mint, referral_fee, epoch)?;
+        let referral_fee_transfer_instruction = spl_token_2022::instruction::transfer_checked(
             accounts.spl_token_program.key,
             accounts.quote_vault.key,
+            accounts.quote_mint.key,
             fee_token_account.key,
-            accounts.user_owner.key,
+            accounts.market_signer.key,
             &[],
             referral_fee,
+            quote_decimals,
         )?;
 
         invoke_signed(
@@ -374,16 +484,25 @@ pub(crate) fn process(
             &[
                 accounts.spl_token_program.clone(),
                 accounts.quote_vault.clone(),
+                accounts.quote_mint.clone(),
                 fee_token_account.clone(),
-                accounts.user_owner.clone(),
+                accounts.market_signer.clone(),
             ],
             &[&[
                 &accounts.market.key.to_bytes(),
                 &[market_state.signer_nonce as u8],
             ]],
         )?;
+        market_state.record_vault_outflow(0, referral_fee);
     }
 
+    let outcome = SwapOutcome {
+        input_qty: gross_transfer_in_qty,
+        output_qty: transfer_out_qty - transfer_out_fee,
+        fee_paid,
+    };
+    set_return_data(&outcome.try_to_vec()?);
+
     Ok(())
 }
 
@@ -392,13 +511,12 @@ fn check_accounts(
     market_state: &DexState,
     accounts: &Accounts<AccountInfo>,
 ) -> ProgramResult {
-    let market_signer = Pubkey::create_program_address(
-        &[
-            &accounts.market.key.to_bytes(),
-            &[market_state.signer_nonce as u8],
-        ],
-        program_id,
+    check_account_key(
+        accounts.spl_token_program,
+        &market_state.token_program,
+        DexError::InvalidSplTokenProgram,
     )?;
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
     check_account_key(
         accounts.market_signer,
         &market_signer,
@@ -419,6 +537,16 @@ fn check_accounts(
         &market_state.quote_vault,
         DexError::InvalidQuoteVaultAccount,
     )?;
+    check_account_key(
+        accounts.base_mint,
+        &market_state.base_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    check_account_key(
+        accounts.quote_mint,
+        &market_state.quote_mint,
+        DexError::InvalidMintAccount,
+    )?;
 
     Ok(())
 }
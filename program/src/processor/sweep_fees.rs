@@ -1,29 +1,39 @@
 //! Extract accumulated fees from the market. This is an admin instruction
+//!
+//! Markets with a fee recipients account split the accumulated fees between the listed token accounts according to
+//! their weights. The fees of other markets are swept to a single destination owned by the sweep authority.
 use crate::{
     error::DexError,
     processor::SWEEP_AUTHORITY,
-    state::DexState,
-    utils::{check_account_key, check_account_owner, check_metadata_account},
+    state::{DexState, FeeRecipients},
+    utils::{
+        check_account_key, check_account_owner, check_metadata_account, check_token_program,
+        get_mint_transfer_info, unpack_token_account,
+    },
 };
-use bonfida_utils::checks::check_token_account_owner;
 use bonfida_utils::BorshSize;
 use bonfida_utils::InstructionsAccount;
 use borsh::BorshDeserialize;
 use borsh::BorshSerialize;
-use bytemuck::{Pod, Zeroable};
+use bytemuck::{try_from_bytes, Pod, Zeroable};
 use mpl_token_metadata::state::{Metadata, TokenMetadataAccount};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
     program::invoke_signed,
     program_error::ProgramError,
     pubkey::Pubkey,
+    sysvar::Sysvar,
 };
 
 #[derive(Clone, Copy, BorshDeserialize, BorshSerialize, BorshSize, Pod, Zeroable)]
 #[repr(C)]
-pub struct Params {}
+pub struct Params {
+    /// Whether or not the market's fee recipients account was given, which is required when the market has one
+    pub has_fee_recipients_account: u8,
+}
 
 #[derive(InstructionsAccount)]
 pub struct Accounts<'a, T> {
@@ -38,7 +48,10 @@ pub struct Accounts<'a, T> {
     #[cons(writable)]
     pub quote_vault: &'a T,
 
-    /// The destination token account
+    /// The quote token mint
+    pub quote_mint: &'a T,
+
+    /// The destination token account, which receives the fees when the market has no fee recipients account
     #[cons(writable)]
     pub destination_token_account: &'a T,
 
@@ -48,6 +61,13 @@ pub struct Accounts<'a, T> {
     /// The metadata account
     pub token_metadata: &'a T,
 
+    /// The market's fee recipients account
+    pub fee_recipients: Option<&'a T>,
+
+    /// The token accounts of the fee recipients, in the order of the fee recipients account
+    #[cons(writable)]
+    pub fee_recipient_token_accounts: &'a [T],
+
     /// The creator token account
     #[cons(writable)]
     pub creators_token_accounts: &'a [T],
@@ -57,24 +77,56 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
     pub fn parse(
         program_id: &Pubkey,
         accounts: &'a [AccountInfo<'b>],
+        has_fee_recipients_account: bool,
     ) -> Result<Self, ProgramError> {
         let accounts_iter = &mut accounts.iter();
 
+        let market = next_account_info(accounts_iter)?;
+        let market_signer = next_account_info(accounts_iter)?;
+        let quote_vault = next_account_info(accounts_iter)?;
+        let quote_mint = next_account_info(accounts_iter)?;
+        let destination_token_account = next_account_info(accounts_iter)?;
+        let spl_token_program = next_account_info(accounts_iter)?;
+        let token_metadata = next_account_info(accounts_iter)?;
+        let fee_recipients = if has_fee_recipients_account {
+            Some(next_account_info(accounts_iter)?)
+        } else {
+            None
+        };
+
+        // The number of recipient token accounts is read from the fee recipients account
+        let number_of_recipients = match fee_recipients {
+            Some(f) => {
+                check_account_owner(f, program_id, DexError::InvalidStateAccountOwner)?;
+                let mut fee_recipients_data = f.data.borrow_mut();
+                FeeRecipients::from_buffer(&mut fee_recipients_data)?
+                    .header
+                    .number_of_recipients as usize
+            }
+            None => 0,
+        };
+        let remaining_accounts = accounts_iter.as_slice();
+        if remaining_accounts.len() < number_of_recipients {
+            msg!("Each fee recipient's token account should be provided");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        let (fee_recipient_token_accounts, creators_token_accounts) =
+            remaining_accounts.split_at(number_of_recipients);
+
         let a = Self {
-            market: next_account_info(accounts_iter)?,
-            market_signer: next_account_info(accounts_iter)?,
-            quote_vault: next_account_info(accounts_iter)?,
-            destination_token_account: next_account_info(accounts_iter)?,
-            spl_token_program: next_account_info(accounts_iter)?,
-            token_metadata: next_account_info(accounts_iter)?,
-            creators_token_accounts: accounts_iter.as_slice(),
+            market,
+            market_signer,
+            quote_vault,
+            quote_mint,
+            destination_token_account,
+            spl_token_program,
+            token_metadata,
+            fee_recipients,
+            fee_recipient_token_accounts,
+            creators_token_accounts,
         };
 
-        check_account_key(
-            a.spl_token_program,
-            &spl_token::ID,
-            DexError::InvalidSplTokenProgram,
-        )?;
+        check_token_program(a.spl_token_program)?;
 
         check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
 
@@ -82,12 +134,20 @@ impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
     }
 }
 
-pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    let accounts = Accounts::parse(program_id, accounts)?;
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let Params {
+        has_fee_recipients_account,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let accounts = Accounts::parse(program_id, accounts, *has_fee_recipients_account != 0)?;
 
     let mut market_state = DexState::get(accounts.market)?;
     check_accounts(program_id, &market_state, &accounts)?;
     check_metadata_account(accounts.token_metadata, &market_state.base_mint)?;
+    let epoch = Clock::get()?.epoch;
 
     let mut no_op = true;
 
@@ -112,19 +172,24 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
 
                 check_token_account_owner(token_destination, &creator.address)?;
 
-                let transfer_instruction = spl_token::instruction::transfer(
-                    &spl_token::ID,
+                let (quote_decimals, _) =
+                    get_mint_transfer_info(accounts.quote_mint, amount, epoch)?;
+                let transfer_instruction = spl_token_2022::instruction::transfer_checked(
+                    accounts.spl_token_program.key,
                     accounts.quote_vault.key,
+                    accounts.quote_mint.key,
                     token_destination.key,
                     accounts.market_signer.key,
                     &[],
                     amount,
+                    quote_decimals,
                 )?;
                 invoke_signed(
                     &transfer_instruction,
                     &[
                         accounts.spl_token_program.clone(),
                         accounts.quote_vault.clone(),
+                        accounts.quote_mint.clone(),
                         token_destination.clone(),
                         accounts.market_signer.clone(),
                     ],
@@ -144,34 +209,58 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
                 .accumulated_royalties
                 .checked_sub(royalties_sum)
                 .ok_or(DexError::NumericalOverflow)?;
+            market_state.record_vault_outflow(0, royalties_sum);
         }
     }
 
     if market_state.accumulated_fees != 0 {
         no_op = false;
-        let transfer_instruction = spl_token::instruction::transfer(
-            &spl_token::ID,
-            accounts.quote_vault.key,
-            accounts.destination_token_account.key,
-            accounts.market_signer.key,
-            &[],
-            market_state.accumulated_fees,
-        )?;
-
-        invoke_signed(
-            &transfer_instruction,
-            &[
-                accounts.spl_token_program.clone(),
-                accounts.quote_vault.clone(),
-                accounts.destination_token_account.clone(),
-                accounts.market_signer.clone(),
-            ],
-            &[&[
-                &accounts.market.key.to_bytes(),
-                &[market_state.signer_nonce as u8],
-            ]],
-        )?;
+        if market_state.fee_recipients == Pubkey::default() {
+            if unpack_token_account(accounts.destination_token_account)?.owner != SWEEP_AUTHORITY {
+                msg!("The destination token account should be owned by the sweep authority");
+                return Err(ProgramError::InvalidArgument);
+            }
+            transfer_fees(
+                &accounts,
+                &market_state,
+                accounts.destination_token_account,
+                market_state.accumulated_fees,
+                epoch,
+            )?;
+            let swept_fees = market_state.accumulated_fees;
+            market_state.record_vault_outflow(0, swept_fees);
+        } else {
+            let fee_recipients_account = accounts.fee_recipients.ok_or_else(|| {
+                msg!("The market's fee recipients account should be provided");
+                DexError::InvalidFeeRecipientsAccount
+            })?;
+            check_account_key(
+                fee_recipients_account,
+                &market_state.fee_recipients,
+                DexError::InvalidFeeRecipientsAccount,
+            )?;
+            let mut fee_recipients_data = fee_recipients_account.data.borrow_mut();
+            let fee_recipients = FeeRecipients::from_buffer(&mut fee_recipients_data)?;
+            let shares = fee_recipients.split(market_state.accumulated_fees);
+            for ((recipient, share), token_account) in fee_recipients
+                .recipients()
+                .iter()
+                .zip(shares)
+                .zip(accounts.fee_recipient_token_accounts)
+            {
+                check_account_key(
+                    token_account,
+                    &recipient.token_account,
+                    DexError::InvalidFeeRecipients,
+                )?;
+                if share != 0 {
+                    transfer_fees(&accounts, &market_state, token_account, share, epoch)?;
+                    market_state.record_vault_outflow(0, share);
+                }
+            }
+        }
 
+        // The counter is only reset once the transfers went through
         market_state.accumulated_fees = 0;
     }
 
@@ -183,18 +272,52 @@ pub(crate) fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramR
     Ok(())
 }
 
-fn check_accounts(
-    program_id: &Pubkey,
-    market_state: &DexState,
+fn transfer_fees(
     accounts: &Accounts<AccountInfo>,
+    market_state: &DexState,
+    destination: &AccountInfo,
+    amount: u64,
+    epoch: u64,
 ) -> ProgramResult {
-    let market_signer = Pubkey::create_program_address(
+    let (quote_decimals, _) = get_mint_transfer_info(accounts.quote_mint, amount, epoch)?;
+    let transfer_instruction = spl_token_2022::instruction::transfer_checked(
+        accounts.spl_token_program.key,
+        accounts.quote_vault.key,
+        accounts.quote_mint.key,
+        destination.key,
+        accounts.market_signer.key,
+        &[],
+        amount,
+        quote_decimals,
+    )?;
+
+    invoke_signed(
+        &transfer_instruction,
         &[
+            accounts.spl_token_program.clone(),
+            accounts.quote_vault.clone(),
+            accounts.quote_mint.clone(),
+            destination.clone(),
+            accounts.market_signer.clone(),
+        ],
+        &[&[
             &accounts.market.key.to_bytes(),
             &[market_state.signer_nonce as u8],
-        ],
-        program_id,
+        ]],
+    )
+}
+
+fn check_accounts(
+    program_id: &Pubkey,
+    market_state: &DexState,
+    accounts: &Accounts<AccountInfo>,
+) -> ProgramResult {
+    check_account_key(
+        accounts.spl_token_program,
+        &market_state.token_program,
+        DexError::InvalidSplTokenProgram,
     )?;
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
     check_account_key(
         accounts.market_signer,
         &market_signer,
@@ -205,8 +328,11 @@ fn check_accounts(
         &market_state.quote_vault,
         DexError::InvalidQuoteVaultAccount,
     )?;
-
-    check_token_account_owner(accounts.destination_token_account, &SWEEP_AUTHORITY)?;
+    check_account_key(
+        accounts.quote_mint,
+        &market_state.quote_mint,
+        DexError::InvalidMintAccount,
+    )?;
 
     Ok(())
 }
@@ -0,0 +1,101 @@
+//! Update the market's fee schedule. This is an admin instruction
+use crate::{
+    error::DexError,
+    state::DexState,
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for an update_fee_schedule instruction.
+*/
+pub struct Params {
+    /// The taker fee rate of the base fee tier as an FP32. Zero selects the default rate
+    pub base_taker_rate: u64,
+    /// The maker rebate rate as an FP32. It cannot exceed the lowest taker fee rate
+    pub base_maker_rebate: u64,
+    /// The share of the net taker fees which is paid out to referrers, in basis points. Zero selects the default share
+    pub referral_fee_share_bps: u64,
+    /// The fee rate as an FP32 charged on the quote quantity which takers self-match under `DecrementTake`. Zero
+    /// waives the fee
+    pub self_trade_fee_rate: u64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The market admin account
+    #[cons(signer)]
+    pub market_admin: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            market_admin: next_account_info(accounts_iter)?,
+        };
+
+        // Check owners
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+
+        // Check signers
+        check_signer(a.market_admin).map_err(|e| {
+            msg!("The market admin should be a signer for this transaction!");
+            e
+        })?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params {
+        base_taker_rate,
+        base_maker_rebate,
+        referral_fee_share_bps,
+        self_trade_fee_rate,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    check_account_key(
+        accounts.market_admin,
+        &market_state.admin,
+        DexError::InvalidMarketAdminAccount,
+    )?;
+
+    market_state.base_taker_rate = *base_taker_rate;
+    market_state.base_maker_rebate = *base_maker_rebate;
+    market_state.referral_fee_share_bps = *referral_fee_share_bps;
+    market_state.self_trade_fee_rate = *self_trade_fee_rate;
+    market_state.check_fee_schedule()?;
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! Update the market's parameters. This is an admin instruction
+use crate::{
+    error::DexError,
+    state::{DexState, MAX_PRICE},
+    utils::{check_account_key, check_account_owner, check_signer},
+};
+use asset_agnostic_orderbook::state::{market_state::MarketState, AccountTag};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[derive(Copy, Clone, Zeroable, Pod, BorshDeserialize, BorshSerialize, BorshSize)]
+#[repr(C)]
+/**
+The required arguments for an update_market_params instruction.
+*/
+pub struct Params {
+    /// The new minimum allowed order size in base token amount.
+    ///
+    /// Orders already resting below this size remain valid and can still be cancelled.
+    pub min_base_order_size: u64,
+    /// The new reward in quote tokens paid to the cranker for each consumed event. Zero disables crank rewards.
+    pub crank_reward_per_event: u64,
+    /// The new minimum allowed quote notional of a limit order. Zero disables the check.
+    pub min_quote_order_size: u64,
+    /// The new maximum limit price (as a FP32) of new orders, which cannot exceed `MAX_PRICE`. Zero means that
+    /// `MAX_PRICE` applies.
+    ///
+    /// Orders already resting above this price remain valid and can still be cancelled.
+    pub max_price: u64,
+    /// The new maximum number of resting orders per user account. Zero means unlimited.
+    ///
+    /// Accounts already holding more orders keep them, but can't post new ones until they are below the limit.
+    pub max_orders_per_account: u32,
+    /// To eliminate implicit padding
+    pub _padding: [u8; 4],
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The orderbook
+    #[cons(writable)]
+    pub orderbook: &'a T,
+
+    /// The market admin account
+    #[cons(signer)]
+    pub market_admin: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            market: next_account_info(accounts_iter)?,
+            orderbook: next_account_info(accounts_iter)?,
+            market_admin: next_account_info(accounts_iter)?,
+        };
+
+        // Check owners
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.orderbook, program_id, DexError::InvalidStateAccountOwner)?;
+
+        // Check signers
+        check_signer(a.market_admin).map_err(|e| {
+            msg!("The market admin should be a signer for this transaction!");
+            e
+        })?;
+
+        Ok(a)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params {
+        min_base_order_size,
+        crank_reward_per_event,
+        min_quote_order_size,
+        max_price,
+        max_orders_per_account,
+        _padding,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    check_account_key(
+        accounts.market_admin,
+        &market_state.admin,
+        DexError::InvalidMarketAdminAccount,
+    )?;
+    check_account_key(
+        accounts.orderbook,
+        &market_state.orderbook,
+        DexError::InvalidOrderbookAccount,
+    )?;
+
+    if min_base_order_size == &0 {
+        msg!("The minimum base order size should be nonzero!");
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if *max_price > MAX_PRICE {
+        msg!("The maximum price cannot exceed {}", MAX_PRICE);
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    msg!(
+        "Updating the minimum base order size from {} to {}",
+        market_state.min_base_order_size,
+        min_base_order_size
+    );
+    market_state.min_base_order_size = *min_base_order_size;
+
+    if crank_reward_per_event != &market_state.crank_reward_per_event {
+        msg!(
+            "Updating the crank reward per event from {} to {}",
+            market_state.crank_reward_per_event,
+            crank_reward_per_event
+        );
+        market_state.crank_reward_per_event = *crank_reward_per_event;
+    }
+
+    if min_quote_order_size != &market_state.min_quote_order_size {
+        msg!(
+            "Updating the minimum quote order size from {} to {}",
+            market_state.min_quote_order_size,
+            min_quote_order_size
+        );
+        market_state.min_quote_order_size = *min_quote_order_size;
+    }
+
+    if max_price != &market_state.max_price {
+        msg!(
+            "Updating the maximum price from {} to {}",
+            market_state.max_price,
+            max_price
+        );
+        market_state.max_price = *max_price;
+    }
+
+    if max_orders_per_account != &market_state.max_orders_per_account {
+        msg!(
+            "Updating the maximum number of orders per account from {} to {}",
+            market_state.max_orders_per_account,
+            max_orders_per_account
+        );
+        market_state.max_orders_per_account = *max_orders_per_account;
+    }
+
+    // The orderbook holds its own copy of the minimum order size, in scaled units
+    let mut orderbook_guard = accounts.orderbook.data.borrow_mut();
+    let orderbook = MarketState::from_buffer(&mut orderbook_guard, AccountTag::Market)?;
+    orderbook.min_base_order_size = market_state.scale_base_amount(*min_base_order_size);
+
+    Ok(())
+}
@@ -0,0 +1,282 @@
+//! Withdraw part of the free base and quote token balances of a user account
+use crate::{
+    error::DexError,
+    processor::settle::check_destination_accounts,
+    state::{DexState, UserAccount},
+    utils::{
+        check_account_key, check_account_owner, check_signer, check_token_program,
+        get_mint_transfer_info,
+    },
+};
+use bonfida_utils::BorshSize;
+use bonfida_utils::InstructionsAccount;
+use borsh::BorshDeserialize;
+use borsh::BorshSerialize;
+use bytemuck::{try_from_bytes, Pod, Zeroable};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+#[derive(Clone, Copy, BorshDeserialize, BorshSerialize, BorshSize, Pod, Zeroable)]
+#[repr(C)]
+/**
+The required arguments for a withdraw instruction.
+*/
+pub struct Params {
+    /// The quantity of base token to withdraw, which should not exceed the free base balance
+    pub base_amount: u64,
+    /// The quantity of quote token to withdraw, which should not exceed the free quote balance
+    pub quote_amount: u64,
+}
+
+#[derive(InstructionsAccount)]
+pub struct Accounts<'a, T> {
+    /// The spl token program
+    pub spl_token_program: &'a T,
+
+    /// The DEX market
+    #[cons(writable)]
+    pub market: &'a T,
+
+    /// The base token vault
+    #[cons(writable)]
+    pub base_vault: &'a T,
+
+    /// The quote token vault
+    #[cons(writable)]
+    pub quote_vault: &'a T,
+
+    /// The DEX market signer account
+    pub market_signer: &'a T,
+
+    /// The DEX user account
+    #[cons(writable)]
+    pub user: &'a T,
+
+    /// The DEX user account owner wallet
+    #[cons(signer)]
+    pub user_owner: &'a T,
+
+    /// The destination base token account
+    #[cons(writable)]
+    pub destination_base_account: &'a T,
+
+    /// The destination quote token account
+    #[cons(writable)]
+    pub destination_quote_account: &'a T,
+
+    /// The base token mint
+    pub base_mint: &'a T,
+
+    /// The quote token mint
+    pub quote_mint: &'a T,
+}
+
+impl<'a, 'b: 'a> Accounts<'a, AccountInfo<'b>> {
+    pub fn parse(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> Result<Self, ProgramError> {
+        let accounts_iter = &mut accounts.iter();
+        let a = Self {
+            spl_token_program: next_account_info(accounts_iter)?,
+            market: next_account_info(accounts_iter)?,
+            base_vault: next_account_info(accounts_iter)?,
+            quote_vault: next_account_info(accounts_iter)?,
+            market_signer: next_account_info(accounts_iter)?,
+            user: next_account_info(accounts_iter)?,
+            user_owner: next_account_info(accounts_iter)?,
+            destination_base_account: next_account_info(accounts_iter)?,
+            destination_quote_account: next_account_info(accounts_iter)?,
+            base_mint: next_account_info(accounts_iter)?,
+            quote_mint: next_account_info(accounts_iter)?,
+        };
+        check_signer(a.user_owner).map_err(|e| {
+            msg!("The user account owner should be a signer for this transaction!");
+            e
+        })?;
+        check_token_program(a.spl_token_program)?;
+        check_account_owner(a.market, program_id, DexError::InvalidStateAccountOwner)?;
+        check_account_owner(a.user, program_id, DexError::InvalidStateAccountOwner)?;
+
+        Ok(a)
+    }
+
+    pub fn load_user_account(
+        &self,
+        user_account_data: &'a mut [u8],
+    ) -> Result<UserAccount<'a>, ProgramError> {
+        let user_account = UserAccount::from_buffer(user_account_data)?;
+        if &user_account.header.owner != self.user_owner.key {
+            msg!("Invalid user account owner provided!");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if &user_account.header.market != self.market.key {
+            msg!("The provided user account doesn't match the current market");
+            return Err(ProgramError::InvalidArgument);
+        };
+        Ok(user_account)
+    }
+}
+
+pub(crate) fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    let accounts = Accounts::parse(program_id, accounts)?;
+
+    let Params {
+        base_amount,
+        quote_amount,
+    } = try_from_bytes(instruction_data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let mut market_state = DexState::get(accounts.market)?;
+
+    let mut user_account_data = accounts.user.data.borrow_mut();
+    let mut user_account = accounts.load_user_account(&mut user_account_data)?;
+
+    check_accounts(program_id, &market_state, &accounts)?;
+
+    // Balances are debited before any transfer, so that an over-withdrawal leaves no side effect
+    user_account.header.base_token_free = user_account
+        .header
+        .base_token_free
+        .checked_sub(*base_amount)
+        .ok_or_else(|| {
+            msg!(
+                "The free base balance of {} is insufficient",
+                user_account.header.base_token_free
+            );
+            DexError::InsufficientFunds
+        })?;
+    user_account.header.quote_token_free = user_account
+        .header
+        .quote_token_free
+        .checked_sub(*quote_amount)
+        .ok_or_else(|| {
+            msg!(
+                "The free quote balance of {} is insufficient",
+                user_account.header.quote_token_free
+            );
+            DexError::InsufficientFunds
+        })?;
+
+    // As for settle, the destination accounts of fee-on-transfer mints receive the amounts net of transfer fees
+    let epoch = Clock::get()?.epoch;
+    let base_transfer_fee = transfer_from_vault(
+        &accounts,
+        &market_state,
+        accounts.base_vault,
+        accounts.base_mint,
+        accounts.destination_base_account,
+        *base_amount,
+        epoch,
+    )?;
+    let quote_transfer_fee = transfer_from_vault(
+        &accounts,
+        &market_state,
+        accounts.quote_vault,
+        accounts.quote_mint,
+        accounts.destination_quote_account,
+        *quote_amount,
+        epoch,
+    )?;
+    market_state.record_vault_outflow(*base_amount, *quote_amount);
+
+    msg!(
+        "Withdrew {} base and {} quote tokens net of transfer fees",
+        base_amount - base_transfer_fee,
+        quote_amount - quote_transfer_fee
+    );
+
+    Ok(())
+}
+
+/// Transfers tokens from a vault to a destination token account, returning the fee withheld by the mint
+fn transfer_from_vault<'a, 'b: 'a>(
+    accounts: &Accounts<'a, AccountInfo<'b>>,
+    market_state: &DexState,
+    vault: &'a AccountInfo<'b>,
+    mint: &'a AccountInfo<'b>,
+    destination: &'a AccountInfo<'b>,
+    amount: u64,
+    epoch: u64,
+) -> Result<u64, ProgramError> {
+    if amount == 0 {
+        return Ok(0);
+    }
+    let (decimals, transfer_fee) = get_mint_transfer_info(mint, amount, epoch)?;
+
+    let transfer_instruction = spl_token_2022::instruction::transfer_checked(
+        accounts.spl_token_program.key,
+        vault.key,
+        mint.key,
+        destination.key,
+        accounts.market_signer.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+
+    invoke_signed(
+        &transfer_instruction,
+        &[
+            accounts.spl_token_program.clone(),
+            vault.clone(),
+            mint.clone(),
+            destination.clone(),
+            accounts.market_signer.clone(),
+        ],
+        &[&[
+            &accounts.market.key.to_bytes(),
+            &[market_state.signer_nonce as u8],
+        ]],
+    )?;
+
+    Ok(transfer_fee)
+}
+
+fn check_accounts(
+    program_id: &Pubkey,
+    market_state: &DexState,
+    accounts: &Accounts<AccountInfo>,
+) -> ProgramResult {
+    check_account_key(
+        accounts.spl_token_program,
+        &market_state.token_program,
+        DexError::InvalidSplTokenProgram,
+    )?;
+    let market_signer = market_state.market_signer(accounts.market.key, program_id)?;
+    check_account_key(
+        accounts.market_signer,
+        &market_signer,
+        DexError::InvalidMarketSignerAccount,
+    )?;
+    market_state.check_vaults(&market_signer, accounts.base_vault, accounts.quote_vault)?;
+    check_account_key(
+        accounts.base_mint,
+        &market_state.base_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    check_account_key(
+        accounts.quote_mint,
+        &market_state.quote_mint,
+        DexError::InvalidMintAccount,
+    )?;
+    check_destination_accounts(
+        market_state,
+        Some(accounts.user_owner.key),
+        accounts.destination_base_account,
+        accounts.destination_quote_account,
+    )?;
+
+    Ok(())
+}
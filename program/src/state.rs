@@ -1,16 +1,29 @@
-use asset_agnostic_orderbook::state::{orderbook::CallbackInfo, OrderSummary};
+use asset_agnostic_orderbook::state::{
+    get_side_from_order_id, orderbook::CallbackInfo, OrderSummary,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
-use bytemuck::{try_cast_slice_mut, try_from_bytes_mut, Pod, Zeroable};
+use bytemuck::{
+    bytes_of, cast_slice_mut, try_cast_slice_mut, try_from_bytes, try_from_bytes_mut, Pod, Zeroable,
+};
 use num_derive::{FromPrimitive, ToPrimitive};
+use num_traits::FromPrimitive;
 use solana_program::{
-    account_info::AccountInfo, msg, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    account_info::AccountInfo, entrypoint::ProgramResult, log::sol_log_data, msg,
+    program_error::ProgramError, pubkey::Pubkey,
+};
+use std::{
+    cell::{Ref, RefMut},
+    convert::TryInto,
+    mem::size_of,
 };
-use std::{cell::RefMut, convert::TryInto, mem::size_of};
 
 use crate::{
     error::DexError,
     processor::{MSRM_MINT, REFERRAL_MASK, SRM_MINT},
-    utils::{fp32_div, fp32_mul, FP_32_ONE},
+    utils::{
+        check_account_key, check_account_owner, fp32_div, fp32_mul, fp32_mul_half_up,
+        get_best_price, unpack_token_account, FP_32_ONE,
+    },
 };
 
 #[derive(Clone, Debug, PartialEq, Copy)]
@@ -21,6 +34,12 @@ pub enum AccountTag {
     DexState,
     UserAccount,
     Closed,
+    TradeLog,
+    LiquidityMetrics,
+    FeeRecipients,
+    MarketStats,
+    AccessGrant,
+    ProgramConfig,
 }
 
 #[derive(Clone, Copy, PartialEq, FromPrimitive, ToPrimitive)]
@@ -37,7 +56,7 @@ pub enum Side {
 pub enum SelfTradeBehavior {
     /// Decrement take means that both the maker and taker sides of the matched orders are decremented.
     ///
-    /// This is equivalent to a normal order match, except for the fact that no fees are applies.
+    /// This is equivalent to a normal order match, except for the fact that only the market's self-trade fee applies.
     DecrementTake,
     /// Cancels the maker side of the order.
     CancelProvide,
@@ -45,7 +64,45 @@ pub enum SelfTradeBehavior {
     AbortTransaction,
 }
 
-/// The primary market state object
+/// This enum describes the trading statuses a market can be set to by its admin
+///
+/// Cancelling orders and settling funds are always allowed so that users can exit the market.
+#[derive(Clone, Copy, PartialEq, Debug, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum MarketStatus {
+    /// Regular trading
+    Active,
+    /// No new orders or swaps can be placed and filled iceberg slices are not reposted, but events are still consumed
+    Paused,
+    /// Only post-only orders can be placed, and filled iceberg slices are only reposted when they don't cross the book
+    PostOnly,
+    /// No new orders or swaps can be placed and filled iceberg slices are not reposted, but events are still consumed
+    CancelOnly,
+}
+
+/// The rounding of the taker fees charged on a market, as stored in its `fee_rounding` field.
+///
+/// Maker rebates and referral fees are always rounded down, and the quote amount which a bid can match once its taker
+/// fee is removed is rounded down as well. Since the taker rate covers both the maker rebate and the referral rate, the
+/// fees paid out of a trade can never exceed the taker fee collected on it, whichever rounding is selected.
+///
+/// The rounding only applies to the fee charged to the taker for its whole matched quantity. The share of it which is
+/// credited to the accumulated fees for each fill, net of the referral fee, is always rounded down, see
+/// `FeeTier::net_taker_fee_credit`.
+#[derive(Clone, Copy, PartialEq, Debug, FromPrimitive, ToPrimitive)]
+#[repr(u8)]
+pub enum FeeRounding {
+    /// Taker fees are truncated to the token's smallest unit, in favor of the takers
+    Down,
+    /// Taker fees are rounded to the nearest unit, with a fractional part of exactly one half rounded up
+    HalfUp,
+}
+
+/// The primary market state object.
+///
+/// Markets created by the initial program version hold the shorter `LegacyDexState` layout, and must be upgraded with
+/// the migrate_market instruction before they can be loaded. New fields are carved out of the trailing `_reserved`
+/// space, which is zero on existing markets, so that the length of the state doesn't change again.
 #[derive(Copy, Clone, Pod, Zeroable)]
 #[repr(C)]
 pub struct DexState {
@@ -65,11 +122,13 @@ pub struct DexState {
     pub admin: Pubkey,
     /// The market's creation timestamp on the Solana runtime clock.
     pub creation_timestamp: i64,
-    /// The market's total historical volume in base token
+    /// The market's total historical volume in base token. This field is just a metric which saturates at `u64::MAX`.
     pub base_volume: u64,
-    /// The market's total historical volume in quote token
+    /// The market's total historical volume in quote token. This field is just a metric which saturates at `u64::MAX`.
     pub quote_volume: u64,
-    /// The market's fees which are available for extraction by the market admin
+    /// The market's fees which are available for extraction by the market admin.
+    ///
+    /// This counter saturates instead of overflowing and is only reset once the fees were transferred out.
     pub accumulated_fees: u64,
     /// The market's minimum allowed order size in base token amount
     pub min_base_order_size: u64,
@@ -81,28 +140,331 @@ pub struct DexState {
     pub base_currency_multiplier: u64,
     /// The quote currency multiplier
     pub quote_currency_multiplier: u64,
+    /// The taker fee rate of the base fee tier as an FP32, which discounted tiers are scaled from. Zero stands for the
+    /// default rate of the market's fee type.
+    pub base_taker_rate: u64,
+    /// The maker rebate rate as an FP32. Zero means that makers don't earn any rebate.
+    pub base_maker_rebate: u64,
+    /// The share of the net taker fees which is paid out to referrers, in basis points. Zero stands for the default
+    /// share of 20%.
+    pub referral_fee_share_bps: u64,
+    /// The admin nominated by the current market admin, which only takes over once it accepts the role. The default
+    /// pubkey means that no handoff is pending.
+    pub pending_admin: Pubkey,
+    /// The minimum limit price increment as an FP32. Zero means that prices are not checked by the DEX
+    pub tick_size: u64,
+    /// The base quantity increment in raw base token amount. All the base quantities of the instructions are raw
+    /// amounts which should be a whole number of lots, see `base_lots_to_raw` and `raw_to_base_lots`. Zero means that
+    /// quantities are not checked by the DEX
+    pub base_lot_size: u64,
+    /// The account which the market's fills are recorded to when the events are consumed
+    pub trade_log: Pubkey,
+    /// The Pyth price account which limit prices are checked against. The default pubkey means that the market has no
+    /// oracle.
+    pub oracle: Pubkey,
+    /// The maximum deviation of limit prices from the oracle price, in basis points
+    pub max_deviation_bps: u64,
+    /// The age in seconds after which oracle prices are considered stale, in which case limit prices aren't checked
+    pub max_oracle_staleness: u64,
+    /// The token program which both vaults belong to, either the SPL token program or the SPL Token-2022 program.
+    ///
+    /// Token-2022 mints may use the transfer fee and mint close authority extensions, and mints with any other extension
+    /// are rejected with `DexError::UnsupportedMintExtension`. Transfers into the vaults are grossed up so that the
+    /// vaults receive the full amount, and users receive the amount net of the fee on transfers out of the vaults.
+    pub token_program: Pubkey,
+    /// The reward in quote tokens paid out of the accumulated fees to the cranker for each consumed event
+    pub crank_reward_per_event: u64,
+    /// The minimum allowed quote notional of a limit order, in quote token amount. Zero disables the check
+    pub min_quote_order_size: u64,
+    /// The price of the best bid as an FP32, as of the last new_order or consume_events instruction. Zero means that
+    /// there were no bids
+    pub best_bid_price: u64,
+    /// The price of the best ask as an FP32, as of the last new_order or consume_events instruction. `u64::MAX` means
+    /// that there were no asks
+    pub best_ask_price: u64,
+    /// The unix timestamp of the last update of the TWAP accumulator
+    pub twap_last_update: i64,
+    /// The sum of the mid price as an FP32 multiplied by the number of seconds it was held for, accumulated each time the
+    /// best prices are updated. A TWAP is obtained by sampling this value twice and dividing the difference by the
+    /// elapsed time.
+    ///
+    /// The u128 is stored as its low and high u64 words, since u128 isn't aligned the same way on-chain and off-chain.
+    /// Use `twap_price_cumulative()` to read it.
+    pub twap_price_cumulative: [u64; 2],
+    /// The price of the last trade as an FP32. Zero means that there were no trades yet
+    pub last_trade_price: u64,
+    /// The unix timestamp of the last trade. Zero means that there were no trades yet
+    pub last_trade_timestamp: i64,
+    /// The total base quantity resting on the bids side of the book, in base token amount. Hidden iceberg quantities
+    /// are excluded, and orders which leave the book through an out event are only subtracted once the event is
+    /// consumed
+    pub total_bid_base: u64,
+    /// The total base quantity resting on the asks side of the book, in base token amount. Hidden iceberg quantities
+    /// are excluded, and orders which leave the book through an out event are only subtracted once the event is
+    /// consumed
+    pub total_ask_base: u64,
+    /// The extra maker rebate rate as an FP32, which is paid out of the accumulated fees on top of the regular rebate
+    /// while the rebate boost is running
+    pub rebate_boost_rate: u64,
+    /// The unix timestamp at which the rebate boost ends, without requiring any admin action. Zero means that no boost
+    /// was ever set
+    pub rebate_boost_end: i64,
+    /// The total boosted rebates paid out of the accumulated fees, in quote token amount. This field is just a metric
+    /// which saturates at `u64::MAX`.
+    pub accumulated_boosted_rebates: u64,
+    /// The fee rate as an FP32 charged to the taker on the quote quantity it self-matches under `DecrementTake`, which
+    /// is credited to the accumulated fees. Zero means that self-matches are free
+    pub self_trade_fee_rate: u64,
+    /// The bitmap account which records the makers of the current metrics epoch. The default pubkey means that the
+    /// market doesn't track its makers
+    pub liquidity_metrics: Pubkey,
+    /// The approximate number of distinct user accounts which posted an order or had one filled since the start of the
+    /// metrics epoch. See `LiquidityMetrics` for the accuracy of this count
+    pub unique_makers: u64,
+    /// The unix timestamp at which the current metrics epoch started, which is when the liquidity metrics account was
+    /// last reset
+    pub liquidity_metrics_epoch_start: i64,
+    /// The account listing the token accounts between which the accumulated fees are split when swept. The default
+    /// pubkey means that all the fees are swept to a single destination
+    pub fee_recipients: Pubkey,
+    /// The deviation in basis points between the oracle price and the mid price of the book above which new orders are
+    /// rejected until prices converge again. Stale oracle prices never halt the market. Zero disables the halt
+    pub auto_halt_deviation_bps: u64,
+    /// The amount of base tokens which the program has moved into the base vault minus the amount it has moved out,
+    /// which should always match the vault's balance
+    pub total_base_in_vault: u64,
+    /// The amount of quote tokens which the program has moved into the quote vault minus the amount it has moved out,
+    /// which should always match the vault's balance
+    pub total_quote_in_vault: u64,
+    /// The authority which grants wallets access to the market. The default pubkey means that anyone can trade the
+    /// market, otherwise placing orders and swapping require an access grant from this authority
+    pub access_authority: Pubkey,
+    /// The maximum limit price (as a FP32) of new orders on the market, which can only lower `MAX_PRICE`. Zero means
+    /// that `MAX_PRICE` applies
+    pub max_price: u64,
+    /// The maximum number of resting orders a single user account may hold on the market, regardless of its number of
+    /// order slots. Zero means unlimited
+    pub max_orders_per_account: u32,
     /// The signer nonce is necessary for the market to perform as a signing entity
     pub signer_nonce: u8,
     /// Fee type (e.g. default or stable)
     pub fee_type: u8,
-    /// Padding
-    pub _padding: [u8; 6],
+    /// The market's trading status, as a `MarketStatus`
+    pub status: u8,
+    /// Set to 1 when the quote mint is the native SOL mint, which enables the wrapping and unwrapping of lamports
+    pub is_quote_native: u8,
+    /// The number of decimals of the base mint, read from the mint at market creation. Along with the quote decimals,
+    /// it converts FP32 prices to human-readable ones
+    pub base_decimals: u8,
+    /// The number of decimals of the quote mint, read from the mint at market creation
+    pub quote_decimals: u8,
+    /// The version of the state's layout, which is set when the market is created or migrated
+    pub version: u8,
+    /// The rounding of the market's taker fees, as a `FeeRounding`
+    pub fee_rounding: u8,
+    /// Set to 1 to pay no maker rebate at all on fills whose rebates the accumulated fees can't cover, instead of
+    /// reducing the rebates to the available amount. The fills are consumed either way
+    pub strict_fee_reserve: u8,
+    /// To eliminate implicit padding
+    pub _padding: [u8; 11],
+    /// The orderbook of the initial program version whose orders and events the migrate_market instruction is still
+    /// moving to the market's orderbook. No new orders are accepted until they are all moved. The default pubkey means
+    /// that there is nothing left to move
+    pub legacy_orderbook: Pubkey,
+    /// Space reserved for the fields of future versions, which must treat zero as their default value
+    pub _reserved: [u8; 224],
 }
 
 /// Size in bytes of the dex state object
 pub const DEX_STATE_LEN: usize = size_of::<DexState>();
 
+/// The market state of the initial program version, which the migrate_market instruction relocates to the current
+/// layout
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+#[allow(missing_docs)]
+pub struct LegacyDexState {
+    pub tag: u64,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub orderbook: Pubkey,
+    pub admin: Pubkey,
+    pub creation_timestamp: i64,
+    pub base_volume: u64,
+    pub quote_volume: u64,
+    pub accumulated_fees: u64,
+    pub min_base_order_size: u64,
+    pub royalties_bps: u64,
+    pub accumulated_royalties: u64,
+    pub base_currency_multiplier: u64,
+    pub quote_currency_multiplier: u64,
+    pub signer_nonce: u8,
+    pub fee_type: u8,
+    pub _padding: [u8; 6],
+}
+
+/// Size in bytes of the market state of the initial program version
+pub const LEGACY_DEX_STATE_LEN: usize = size_of::<LegacyDexState>();
+
+/// The version of the dex state layout of the deployed program. States with a newer version are rejected, and states
+/// with the legacy layout, which predates the version field, can be upgraded with the migrate_market instruction
+pub const DEX_STATE_VERSION: u8 = 1;
+
+/// The maximum limit price (as a FP32) of new orders on any market, which is 2^24 quote lots per base lot.
+///
+/// Bounding the price keeps the quote value of any quantity below 2^40 base lots within a `u64`.
+pub const MAX_PRICE: u64 = 1 << 56;
+
+/// Derives the market signer, which is the program address owning the vaults of a market, from the market's signer
+/// nonce. Fails when the nonce yields an address which lies on the curve
+pub fn market_signer(
+    market: &Pubkey,
+    signer_nonce: u8,
+    program_id: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    Ok(Pubkey::create_program_address(
+        &[&market.to_bytes(), &[signer_nonce]],
+        program_id,
+    )?)
+}
+
+/// Finds the market signer of a market along with its canonical signer nonce, which is the only nonce accepted when
+/// creating the market
+pub fn find_market_signer(market: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[&market.to_bytes()], program_id)
+}
+
+/// The taker fee rate of the base fee tier on default markets which don't configure their own (4 bps), as an FP32
+pub const DEFAULT_TAKER_RATE: u64 = (40 << 32) / 100_000;
+/// The share of the net taker fees which is paid out to referrers on markets which don't configure their own (20%)
+pub const DEFAULT_REFERRAL_FEE_SHARE_BPS: u64 = 2_000;
+/// The taker fee rate of the base fee tier on stable markets which don't configure their own (1 bps), as an FP32
+pub const DEFAULT_STABLE_TAKER_RATE: u64 = (10 << 32) / 100_000;
+
 impl DexState {
+    /// Borrows the market state in place for writing. The state is never copied out of the account data.
     pub(crate) fn get<'a, 'b: 'a>(
         account_info: &'a AccountInfo<'b>,
     ) -> Result<RefMut<'a, Self>, ProgramError> {
+        Self::check_len(account_info)?;
         let a = Self::get_unchecked(account_info);
         if a.tag != AccountTag::DexState as u64 {
             return Err(ProgramError::InvalidAccountData);
         };
+        a.check_version()?;
+        Ok(a)
+    }
+
+    /// Borrows the market state in place for reading only, which lets instructions which never update the market share
+    /// the account data.
+    pub(crate) fn get_ref<'a, 'b: 'a>(
+        account_info: &'a AccountInfo<'b>,
+    ) -> Result<Ref<'a, Self>, ProgramError> {
+        Self::check_len(account_info)?;
+        let a = Ref::map(account_info.data.borrow(), |s| {
+            try_from_bytes::<Self>(&s[0..DEX_STATE_LEN]).unwrap()
+        });
+        if a.tag != AccountTag::DexState as u64 {
+            return Err(ProgramError::InvalidAccountData);
+        };
+        a.check_version()?;
         Ok(a)
     }
 
+    /// Whether an account of the given length holds a market state of the initial program version
+    pub fn is_legacy_len(data_len: usize) -> bool {
+        (LEGACY_DEX_STATE_LEN..DEX_STATE_LEN).contains(&data_len)
+    }
+
+    /// Rejects accounts too short to hold the current layout, pointing legacy markets to their migration
+    fn check_len(account_info: &AccountInfo) -> Result<(), ProgramError> {
+        if Self::is_legacy_len(account_info.data_len()) {
+            msg!("The market state follows the legacy layout and must be upgraded with the migrate_market instruction");
+            return Err(DexError::UnsupportedStateVersion.into());
+        }
+        if account_info.data_len() < DEX_STATE_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(())
+    }
+
+    /// Rejects states written by a newer program, whose layout this program may misread
+    fn check_version(&self) -> Result<(), DexError> {
+        if self.version > DEX_STATE_VERSION {
+            msg!(
+                "The market state has version {} while the program supports up to version {}",
+                self.version,
+                DEX_STATE_VERSION
+            );
+            return Err(DexError::UnsupportedStateVersion);
+        }
+        Ok(())
+    }
+
+    /// Relocates a legacy state to the current layout.
+    ///
+    /// The fields which didn't exist in the legacy layout start from the values of a newly created market, where zero
+    /// fee rates and referral share fall back to the defaults which legacy markets applied. Legacy markets only
+    /// supported the spl token program. The resting base quantities are accumulated as migrate_market moves the legacy
+    /// orders, and the cached best prices are set once all of them were moved.
+    pub fn from_legacy(legacy: &LegacyDexState, current_timestamp: i64) -> Self {
+        Self {
+            tag: legacy.tag,
+            base_mint: legacy.base_mint,
+            quote_mint: legacy.quote_mint,
+            base_vault: legacy.base_vault,
+            quote_vault: legacy.quote_vault,
+            orderbook: legacy.orderbook,
+            admin: legacy.admin,
+            creation_timestamp: legacy.creation_timestamp,
+            base_volume: legacy.base_volume,
+            quote_volume: legacy.quote_volume,
+            accumulated_fees: legacy.accumulated_fees,
+            min_base_order_size: legacy.min_base_order_size,
+            royalties_bps: legacy.royalties_bps,
+            accumulated_royalties: legacy.accumulated_royalties,
+            base_currency_multiplier: legacy.base_currency_multiplier,
+            quote_currency_multiplier: legacy.quote_currency_multiplier,
+            signer_nonce: legacy.signer_nonce,
+            fee_type: legacy.fee_type,
+            status: MarketStatus::Active as u8,
+            is_quote_native: (legacy.quote_mint == spl_token::native_mint::ID) as u8,
+            version: DEX_STATE_VERSION,
+            fee_rounding: FeeRounding::Down as u8,
+            base_lot_size: 1,
+            token_program: spl_token::ID,
+            best_ask_price: u64::MAX,
+            twap_last_update: current_timestamp,
+            ..Self::zeroed()
+        }
+    }
+
+    /// Rewrites a legacy market state in the current layout.
+    ///
+    /// The buffer holds the `legacy_len` bytes of the legacy account, followed by the bytes by which the account was
+    /// extended to the current length. The vault totals are left to the caller, which reads them from the vaults.
+    pub fn migrate_legacy_buffer(
+        buf: &mut [u8],
+        legacy_len: usize,
+        current_timestamp: i64,
+    ) -> Result<&mut Self, ProgramError> {
+        if !Self::is_legacy_len(legacy_len) || buf.len() < DEX_STATE_LEN {
+            msg!("The market state doesn't follow the legacy layout");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let legacy: LegacyDexState = *try_from_bytes(&buf[..LEGACY_DEX_STATE_LEN])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if legacy.tag != AccountTag::DexState as u64 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let market_state: &mut Self = try_from_bytes_mut(&mut buf[..DEX_STATE_LEN])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        *market_state = Self::from_legacy(&legacy, current_timestamp);
+        Ok(market_state)
+    }
+
     pub(crate) fn get_unchecked<'a, 'b: 'a>(account_info: &'a AccountInfo<'b>) -> RefMut<'a, Self> {
         let a = RefMut::map(account_info.data.borrow_mut(), |s| {
             try_from_bytes_mut::<Self>(&mut s[0..DEX_STATE_LEN]).unwrap()
@@ -126,6 +488,30 @@ impl DexState {
         scaled_base_amount.checked_mul(self.base_currency_multiplier)
     }
 
+    /// Converts a number of base lots to a raw base token amount. A market without a lot size has lots of a single
+    /// base token unit
+    pub fn base_lots_to_raw(&self, base_lots: u64) -> Result<u64, DexError> {
+        base_lots
+            .checked_mul(self.base_lot_size.max(1))
+            .ok_or_else(|| {
+                msg!("The base quantity of {} lots overflows", base_lots);
+                DexError::NumericalOverflow
+            })
+    }
+
+    /// Converts a raw base token amount to a number of base lots, failing when the amount isn't a whole number of lots
+    pub fn raw_to_base_lots(&self, raw_base_amount: u64) -> Result<u64, DexError> {
+        let base_lot_size = self.base_lot_size.max(1);
+        if raw_base_amount % base_lot_size != 0 {
+            msg!(
+                "The base quantity should be a multiple of {}",
+                base_lot_size
+            );
+            return Err(DexError::InvalidLotSize);
+        }
+        Ok(raw_base_amount / base_lot_size)
+    }
+
     pub(crate) fn unscale_order_summary(&self, order_summary: &mut OrderSummary) -> Option<()> {
         order_summary.total_base_qty = self.unscale_base_amount(order_summary.total_base_qty)?;
         order_summary.total_base_qty_posted =
@@ -134,6 +520,394 @@ impl DexState {
         Some(())
     }
 
+    /// The rounding of the market's taker fees. Unknown values fall back to rounding down
+    pub fn fee_rounding(&self) -> FeeRounding {
+        FromPrimitive::from_u8(self.fee_rounding).unwrap_or(FeeRounding::Down)
+    }
+
+    /// Credits the net taker fee of a fill to the accumulated fees and pays the maker's regular and boosted rebates out
+    /// of them, returning the amounts actually paid.
+    ///
+    /// The rebates can never draw the accumulated fees below zero. When they exceed what is available, both are reduced
+    /// proportionally and rounded down, unless the market's fee reserve is strict, in which case no rebate is paid on
+    /// the fill at all. The fill itself always goes through so that the event queue is never blocked, and the shortfall
+    /// is logged.
+    pub fn pay_maker_rebates(
+        &mut self,
+        net_taker_fee: u64,
+        maker_rebate: u64,
+        rebate_boost: u64,
+    ) -> (u64, u64) {
+        let available = self.accumulated_fees.saturating_add(net_taker_fee);
+        let requested = maker_rebate as u128 + rebate_boost as u128;
+        let (paid_rebate, paid_boost) = if requested <= available as u128 {
+            (maker_rebate, rebate_boost)
+        } else if self.strict_fee_reserve != 0 {
+            msg!(
+                "The maker rebates of {} exceed the {} available fees and are not paid",
+                requested,
+                available
+            );
+            (0, 0)
+        } else {
+            // Both shares are below the available amount, so they fit back into a u64
+            (
+                (maker_rebate as u128 * available as u128 / requested) as u64,
+                (rebate_boost as u128 * available as u128 / requested) as u64,
+            )
+        };
+        self.accumulated_fees = available - paid_rebate - paid_boost;
+        self.accumulated_boosted_rebates =
+            self.accumulated_boosted_rebates.saturating_add(paid_boost);
+        (paid_rebate, paid_boost)
+    }
+
+    /// The maximum limit price (as a FP32) of new orders on the market
+    pub fn effective_max_price(&self) -> u64 {
+        if self.max_price == 0 {
+            MAX_PRICE
+        } else {
+            self.max_price.min(MAX_PRICE)
+        }
+    }
+
+    /// Rejects the limit prices above the market's maximum price
+    pub fn check_max_price(&self, limit_price: u64) -> Result<(), DexError> {
+        if limit_price > self.effective_max_price() {
+            msg!(
+                "The limit price {} exceeds the maximum price {}",
+                limit_price,
+                self.effective_max_price()
+            );
+            return Err(DexError::PriceTooLarge);
+        }
+        Ok(())
+    }
+
+    /// Checks that a user account holding the given number of resting orders may post one more on the market
+    pub fn check_open_orders_limit(&self, number_of_orders: u32) -> Result<(), DexError> {
+        if self.max_orders_per_account != 0 && number_of_orders >= self.max_orders_per_account {
+            msg!(
+                "The market allows at most {} resting orders per account",
+                self.max_orders_per_account
+            );
+            return Err(DexError::TooManyOrders);
+        }
+        Ok(())
+    }
+
+    /// Checks that the market's status allows placing a new order or swap
+    pub(crate) fn check_new_order_allowed(&self, post_only: bool) -> Result<(), DexError> {
+        if self.legacy_orderbook != Pubkey::default() {
+            msg!("The orders of the legacy orderbook are still being migrated");
+            return Err(DexError::MarketPaused);
+        }
+        match FromPrimitive::from_u8(self.status) {
+            Some(MarketStatus::Active) => Ok(()),
+            Some(MarketStatus::PostOnly) if post_only => Ok(()),
+            _ => {
+                msg!("The market doesn't accept this order in its current status");
+                Err(DexError::MarketPaused)
+            }
+        }
+    }
+
+    /// Converts a price in quote token per base token, as `price * 10^exponent`, to the market's FP32 price scale
+    pub(crate) fn convert_oracle_price(&self, price: u64, exponent: i32) -> Option<u64> {
+        let exponent = exponent + self.quote_decimals as i32 - self.base_decimals as i32;
+        let scaled_price = (price as u128)
+            .checked_mul(self.base_currency_multiplier as u128)?
+            .checked_mul(FP_32_ONE as u128)?;
+        let scaled_price = if exponent >= 0 {
+            scaled_price.checked_mul(10u128.checked_pow(exponent as u32)?)?
+        } else {
+            scaled_price.checked_div(10u128.checked_pow(exponent.unsigned_abs())?)?
+        };
+        scaled_price
+            .checked_div(self.quote_currency_multiplier as u128)?
+            .try_into()
+            .ok()
+    }
+
+    /// Checks that a limit price lies within the market's band around the given oracle price
+    pub(crate) fn check_price_band(
+        &self,
+        limit_price: u64,
+        oracle_price: u64,
+    ) -> Result<(), DexError> {
+        let deviation = (limit_price as u128).max(oracle_price as u128)
+            - (limit_price as u128).min(oracle_price as u128);
+        if deviation * 10_000 > (self.max_deviation_bps as u128) * (oracle_price as u128) {
+            msg!(
+                "The limit price {} deviates by more than {} bps from the oracle price {}",
+                limit_price,
+                self.max_deviation_bps,
+                oracle_price
+            );
+            return Err(DexError::PriceOutsideBand);
+        }
+        Ok(())
+    }
+
+    /// Checks that the mid price of the book doesn't deviate from the given oracle price by more than the market's
+    /// auto-halt threshold. A book missing either side has no mid price and never halts the market
+    pub(crate) fn check_oracle_divergence(&self, oracle_price: u64) -> Result<(), DexError> {
+        if self.auto_halt_deviation_bps == 0
+            || self.best_bid_price == 0
+            || self.best_ask_price == u64::MAX
+        {
+            return Ok(());
+        }
+        let mid_price = (self.best_bid_price as u128 + self.best_ask_price as u128) / 2;
+        let deviation = mid_price.max(oracle_price as u128) - mid_price.min(oracle_price as u128);
+        if deviation * 10_000 > (self.auto_halt_deviation_bps as u128) * (oracle_price as u128) {
+            msg!(
+                "The mid price {} deviates by more than {} bps from the oracle price {}, new orders are halted",
+                mid_price,
+                self.auto_halt_deviation_bps,
+                oracle_price
+            );
+            return Err(DexError::OracleDivergenceHalt);
+        }
+        Ok(())
+    }
+
+    /// Checks that an order's limit price and base quantity fall on the market's tick and lot grid
+    pub(crate) fn check_order_granularity(
+        &self,
+        limit_price: u64,
+        base_qty: u64,
+    ) -> Result<(), DexError> {
+        if self.tick_size != 0 && limit_price % self.tick_size != 0 {
+            msg!("The limit price should be a multiple of {}", self.tick_size);
+            return Err(DexError::InvalidTickSize);
+        }
+        self.raw_to_base_lots(base_qty)?;
+        Ok(())
+    }
+
+    /// Rejects orders whose quote notional at the given limit price is below the market's minimum
+    pub(crate) fn check_min_quote_order_size(
+        &self,
+        limit_price: u64,
+        base_qty: u64,
+    ) -> Result<(), DexError> {
+        if self.min_quote_order_size == 0 {
+            return Ok(());
+        }
+        // A notional which overflows is always large enough
+        let quote_qty = self
+            .get_quote_from_base(base_qty, limit_price)
+            .unwrap_or(u64::MAX);
+        if quote_qty < self.min_quote_order_size {
+            msg!(
+                "The quote order size should be at least {}",
+                self.min_quote_order_size
+            );
+            return Err(DexError::OrderTooSmall);
+        }
+        Ok(())
+    }
+
+    /// The taker fee rate of the base fee tier, as an FP32
+    pub(crate) fn taker_rate(&self) -> u64 {
+        match self.base_taker_rate {
+            0 if self.fee_type == MarketFeeType::Stable as u8 => DEFAULT_STABLE_TAKER_RATE,
+            0 => DEFAULT_TAKER_RATE,
+            r => r,
+        }
+    }
+
+    /// The share of the net taker fees which is paid out to referrers, in basis points
+    pub(crate) fn referral_fee_share_bps(&self) -> u64 {
+        match self.referral_fee_share_bps {
+            0 => DEFAULT_REFERRAL_FEE_SHARE_BPS,
+            s => s,
+        }
+    }
+
+    /// The market signer is the program address which owns the market's vaults
+    pub(crate) fn market_signer(
+        &self,
+        market: &Pubkey,
+        program_id: &Pubkey,
+    ) -> Result<Pubkey, ProgramError> {
+        market_signer(market, self.signer_nonce, program_id)
+    }
+
+    /// Checks that the given vaults are the market's, and that they are still owned by the market signer
+    pub(crate) fn check_vaults(
+        &self,
+        market_signer: &Pubkey,
+        base_vault: &AccountInfo,
+        quote_vault: &AccountInfo,
+    ) -> ProgramResult {
+        check_account_key(
+            base_vault,
+            &self.base_vault,
+            DexError::InvalidBaseVaultAccount,
+        )?;
+        check_account_key(
+            quote_vault,
+            &self.quote_vault,
+            DexError::InvalidQuoteVaultAccount,
+        )?;
+        if &unpack_token_account(base_vault)?.owner != market_signer {
+            msg!("The base vault should be owned by the market signer");
+            return Err(DexError::InvalidBaseVaultAccount.into());
+        }
+        if &unpack_token_account(quote_vault)?.owner != market_signer {
+            msg!("The quote vault should be owned by the market signer");
+            return Err(DexError::InvalidQuoteVaultAccount.into());
+        }
+        Ok(())
+    }
+
+    /// Checks that the given orderbook is the market's. The orderbook is run by this program, which has to own it
+    pub(crate) fn check_orderbook(
+        &self,
+        program_id: &Pubkey,
+        orderbook: &AccountInfo,
+    ) -> ProgramResult {
+        check_account_key(
+            orderbook,
+            &self.orderbook,
+            DexError::InvalidOrderbookAccount,
+        )?;
+        check_account_owner(orderbook, program_id, DexError::InvalidStateAccountOwner)
+    }
+
+    /// Records a maker in the liquidity metrics bitmap, counting it as a new unique maker when its bit wasn't set yet
+    pub(crate) fn record_maker(
+        &mut self,
+        liquidity_metrics: &mut LiquidityMetrics,
+        user_account: &Pubkey,
+    ) {
+        if liquidity_metrics.insert(user_account) {
+            self.unique_makers = self.unique_makers.saturating_add(1);
+        }
+    }
+
+    /// Caches the top of the book so that it can be read from the market account alone.
+    ///
+    /// The mid price held until now is accumulated into the TWAP beforehand.
+    pub(crate) fn update_best_prices(
+        &mut self,
+        bids: &AccountInfo,
+        asks: &AccountInfo,
+        current_timestamp: i64,
+    ) -> ProgramResult {
+        self.accumulate_twap(current_timestamp);
+        self.best_bid_price =
+            get_best_price(bids, asset_agnostic_orderbook::state::Side::Bid)?.unwrap_or(0);
+        self.best_ask_price =
+            get_best_price(asks, asset_agnostic_orderbook::state::Side::Ask)?.unwrap_or(u64::MAX);
+        Ok(())
+    }
+
+    /// The cached best bid price, or `None` if there were no bids
+    pub fn best_bid(&self) -> Option<u64> {
+        Some(self.best_bid_price).filter(|p| *p != 0)
+    }
+
+    /// The cached best ask price, or `None` if there were no asks
+    pub fn best_ask(&self) -> Option<u64> {
+        Some(self.best_ask_price).filter(|p| *p != u64::MAX)
+    }
+
+    /// The cached difference between the best ask and bid prices as an FP32, or `None` if either side was empty
+    pub fn spread(&self) -> Option<u64> {
+        self.best_ask()?.checked_sub(self.best_bid()?)
+    }
+
+    /// The cached mid price as an FP32, or `None` if either side was empty
+    pub fn mid_price(&self) -> Option<u64> {
+        let bid = self.best_bid()?;
+        let ask = self.best_ask()?;
+        Some(((bid as u128 + ask as u128) / 2) as u64)
+    }
+
+    /// The sum of the mid price as an FP32 multiplied by the number of seconds it was held for
+    pub fn twap_price_cumulative(&self) -> u128 {
+        let [lo, hi] = self.twap_price_cumulative;
+        ((hi as u128) << 64) | lo as u128
+    }
+
+    /// Accumulates the mid price held since the last update into the TWAP accumulator. Time during which either side
+    /// of the book was empty is skipped
+    pub fn accumulate_twap(&mut self, current_timestamp: i64) {
+        let elapsed = current_timestamp
+            .saturating_sub(self.twap_last_update)
+            .max(0) as u128;
+        if let Some(mid_price) = self.mid_price() {
+            let cumulative = self
+                .twap_price_cumulative()
+                .saturating_add((mid_price as u128).saturating_mul(elapsed));
+            self.twap_price_cumulative = [cumulative as u64, (cumulative >> 64) as u64];
+        }
+        self.twap_last_update = current_timestamp;
+    }
+
+    fn total_resting_base_mut(&mut self, side: asset_agnostic_orderbook::state::Side) -> &mut u64 {
+        match side {
+            asset_agnostic_orderbook::state::Side::Bid => &mut self.total_bid_base,
+            asset_agnostic_orderbook::state::Side::Ask => &mut self.total_ask_base,
+        }
+    }
+
+    /// Records a base quantity which was posted on the given side of the book
+    pub fn add_resting_base(&mut self, side: asset_agnostic_orderbook::state::Side, base_qty: u64) {
+        let total = self.total_resting_base_mut(side);
+        *total = total.saturating_add(base_qty);
+    }
+
+    /// Records a base quantity which left the given side of the book, because it was matched or cancelled
+    pub fn remove_resting_base(
+        &mut self,
+        side: asset_agnostic_orderbook::state::Side,
+        base_qty: u64,
+    ) {
+        let total = self.total_resting_base_mut(side);
+        *total = total.saturating_sub(base_qty);
+    }
+
+    /// Records tokens which were transferred into the vaults, net of any transfer fee withheld from the vaults
+    pub fn record_vault_inflow(&mut self, base_qty: u64, quote_qty: u64) -> Result<(), DexError> {
+        self.total_base_in_vault = self
+            .total_base_in_vault
+            .checked_add(base_qty)
+            .ok_or(DexError::NumericalOverflow)?;
+        self.total_quote_in_vault = self
+            .total_quote_in_vault
+            .checked_add(quote_qty)
+            .ok_or(DexError::NumericalOverflow)?;
+        Ok(())
+    }
+
+    /// Records tokens which were transferred out of the vaults. The totals saturate rather than fail so that an
+    /// accounting discrepancy never prevents users from withdrawing, it is reported by the reconcile instruction instead
+    pub fn record_vault_outflow(&mut self, base_qty: u64, quote_qty: u64) {
+        self.total_base_in_vault = self.total_base_in_vault.saturating_sub(base_qty);
+        self.total_quote_in_vault = self.total_quote_in_vault.saturating_sub(quote_qty);
+    }
+
+    /// Makes sure that the maker rebate can always be paid out of the taker fee, whatever the taker's fee tier
+    pub(crate) fn check_fee_schedule(&self) -> Result<(), ProgramError> {
+        if self.base_maker_rebate > FeeTier::MSrm.taker_rate(self) {
+            msg!("The maker rebate rate cannot exceed the lowest taker fee rate");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if self.referral_fee_share_bps > 10_000 {
+            msg!("The referral fee share cannot exceed 100%");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if self.self_trade_fee_rate > FP_32_ONE {
+            msg!("The self-trade fee rate cannot exceed 100%");
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
     pub(crate) fn get_quote_from_base(
         &self,
         raw_base_amount: u64,
@@ -144,6 +918,40 @@ impl DexState {
             .and_then(|n| n.checked_div(self.base_currency_multiplier as u128))
             .and_then(|n| n.try_into().ok())
     }
+
+    /// The raw base amount worth a raw quote amount at a scaled FP32 price, which is the inverse of
+    /// `get_quote_from_base`. It is rounded down so that its quote value never exceeds the quote amount
+    pub(crate) fn get_base_from_quote(
+        &self,
+        raw_quote_amount: u64,
+        scaled_price_fp32: u64,
+    ) -> Option<u64> {
+        ((raw_quote_amount as u128) << 32)
+            .checked_mul(self.base_currency_multiplier as u128)?
+            .checked_div(
+                (scaled_price_fp32 as u128).checked_mul(self.quote_currency_multiplier as u128)?,
+            )?
+            .try_into()
+            .ok()
+    }
+
+    /// The average price of a trade as a scaled FP32, which is the inverse of `get_quote_from_base`. `None` when no
+    /// base quantity was traded
+    pub(crate) fn get_average_price(
+        &self,
+        raw_base_amount: u64,
+        raw_quote_amount: u64,
+    ) -> Option<u64> {
+        ((raw_quote_amount as u128) << 32)
+            .checked_mul(self.base_currency_multiplier as u128)
+            .and_then(|n| {
+                n.checked_div(
+                    (raw_base_amount as u128)
+                        .checked_mul(self.quote_currency_multiplier as u128)?,
+                )
+            })
+            .and_then(|n| n.try_into().ok())
+    }
 }
 
 /// This header describes a user account's state
@@ -168,6 +976,9 @@ pub struct UserAccountHeader {
     ///
     /// The actual rebates will always be transfer to the user account's main balance. This field is just a metric.
     pub accumulated_rebates: u64,
+    /// The all time quantity of quote token fees paid by this user account as a taker, royalties and self-trade fees
+    /// included. This field is just a metric.
+    pub accumulated_fees_paid: u64,
     /// The accumulated maker quote volume of the user. This field is just a metric.
     pub accumulated_maker_quote_volume: u64,
     /// The accumulated maker quote volume of the user. This field is just a metric.
@@ -176,12 +987,52 @@ pub struct UserAccountHeader {
     pub accumulated_taker_quote_volume: u64,
     /// The accumulated taker quote volume of the user. This field is just a metric.
     pub accumulated_taker_base_volume: u64,
+    /// The wallet which onboarded the user and receives the referral cut of its taker fees. The default pubkey means
+    /// that the user account has no referrer.
+    pub referrer: Pubkey,
+    /// The base volume matched by the user, either as maker or taker, since the start of the current volume window.
+    ///
+    /// This volume determines the user's volume-based fee tier.
+    pub rolling_base_volume: u64,
+    /// The unix timestamp at which the current volume window started
+    pub volume_window_start: i64,
+    /// The wallet which may post and cancel orders on behalf of the owner. The default pubkey means that the user
+    /// account has no delegate.
+    pub delegate: Pubkey,
+    /// The unix timestamp at which the user account was initialized, which opens its referrer registration window
+    pub creation_timestamp: i64,
+    /// The unix timestamp after which anyone may cancel all of the user's orders, as set by the latest heartbeat.
+    ///
+    /// Zero means that the dead-man switch is disabled.
+    pub heartbeat_expiry: i64,
     /// We are forced to add padding here to keep the subsequent field as a u32 which maintains Borsh compatibility while respecting alignment constraints
     _padding: u32,
     /// The user account's number of active orders.
     pub number_of_orders: u32,
 }
 
+/// The header of the user accounts created by the initial program version, which the migrate_account instruction
+/// rewrites in the current layout
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+#[allow(missing_docs)]
+pub struct LegacyUserAccountHeader {
+    pub tag: u64,
+    pub market: Pubkey,
+    pub owner: Pubkey,
+    pub base_token_free: u64,
+    pub base_token_locked: u64,
+    pub quote_token_free: u64,
+    pub quote_token_locked: u64,
+    pub accumulated_rebates: u64,
+    pub accumulated_maker_quote_volume: u64,
+    pub accumulated_maker_base_volume: u64,
+    pub accumulated_taker_quote_volume: u64,
+    pub accumulated_taker_base_volume: u64,
+    _padding: u32,
+    pub number_of_orders: u32,
+}
+
 /// Represents and order in the user account. The client id offers an alias which can be used off-chain to map custom ids to an actual order id.
 #[derive(Copy, Clone, Pod, Zeroable)]
 #[repr(C)]
@@ -197,17 +1048,46 @@ impl Order {
     pub const LEN: usize = std::mem::size_of::<Self>();
 }
 
-#[allow(missing_docs)]
+/// A view over a user account's data. The header and the order slots are borrowed directly from the account buffer so
+/// that order operations never go through the account's `RefCell` or bump its reference count.
 pub struct UserAccount<'a> {
+    /// The header holding the user's balances and metrics
     pub header: &'a mut UserAccountHeader,
     orders: &'a mut [Order],
 }
 
 /// Size in bytes of the user account header object
-pub const USER_ACCOUNT_HEADER_LEN: usize = 152;
+pub const USER_ACCOUNT_HEADER_LEN: usize = 256;
+
+/// Size in bytes of the user account header of the initial program version
+pub const LEGACY_USER_ACCOUNT_HEADER_LEN: usize = 152;
+
+/// The maximum number of orders a user account can hold, whatever the length of its data
+pub const MAX_ORDERS_PER_ACCOUNT: usize = 10_000;
+
+/// The duration in seconds after which the rolling volume of a user account is reset
+pub const VOLUME_WINDOW_DURATION: i64 = 30 * 24 * 3600;
+
+/// The duration in seconds after the initialization of a user account during which its referrer can be registered
+pub const REFERRER_REGISTRATION_WINDOW: i64 = 24 * 3600;
+
+/// The minimum duration in seconds of a heartbeat, after which a user account's orders can be cancelled by anyone.
+///
+/// Shorter intervals would let crankers cancel the orders of a market maker whose heartbeats are merely delayed by
+/// network congestion.
+pub const MIN_HEARTBEAT_INTERVAL: i64 = 60;
+
+/// The tag of the structured referrer registration logs, which is the first field of their data. The following fields
+/// are the user account, the referrer and the registration timestamp as little-endian bytes
+pub const REFERRER_LOG_TAG: &[u8] = b"dex_referrer";
 
 impl UserAccountHeader {
-    pub(crate) fn new(market: &Pubkey, owner: &Pubkey) -> Self {
+    pub(crate) fn new(
+        market: &Pubkey,
+        owner: &Pubkey,
+        referrer: &Pubkey,
+        creation_timestamp: i64,
+    ) -> Self {
         Self {
             tag: AccountTag::UserAccount as u64,
             market: *market,
@@ -218,58 +1098,253 @@ impl UserAccountHeader {
             quote_token_locked: 0,
             number_of_orders: 0,
             accumulated_rebates: 0,
+            accumulated_fees_paid: 0,
             _padding: 0,
             accumulated_maker_quote_volume: 0,
             accumulated_maker_base_volume: 0,
             accumulated_taker_quote_volume: 0,
             accumulated_taker_base_volume: 0,
+            referrer: *referrer,
+            rolling_base_volume: 0,
+            volume_window_start: 0,
+            delegate: Pubkey::default(),
+            creation_timestamp,
+            heartbeat_expiry: 0,
         }
     }
-}
 
-impl<'a> UserAccount<'a> {
-    #[allow(missing_docs)]
-    pub fn from_buffer(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
-        let user_acc = UserAccount::from_buffer_unchecked(buf).unwrap();
-        if user_acc.header.tag != AccountTag::UserAccount as u64 {
-            return Err(ProgramError::InvalidAccountData);
-        };
-        Ok(user_acc)
+    /// Converts a legacy header to the current layout.
+    ///
+    /// The fields which didn't exist in the legacy layout start from their initial values: the account has no
+    /// referrer nor delegate, no recorded fees or rolling volume, and its dead-man switch is disarmed. Its creation
+    /// timestamp is unknown and set to zero, which keeps its referrer registration window closed.
+    pub fn from_legacy(legacy: &LegacyUserAccountHeader) -> Self {
+        Self {
+            tag: legacy.tag,
+            base_token_free: legacy.base_token_free,
+            base_token_locked: legacy.base_token_locked,
+            quote_token_free: legacy.quote_token_free,
+            quote_token_locked: legacy.quote_token_locked,
+            number_of_orders: legacy.number_of_orders,
+            accumulated_rebates: legacy.accumulated_rebates,
+            accumulated_maker_quote_volume: legacy.accumulated_maker_quote_volume,
+            accumulated_maker_base_volume: legacy.accumulated_maker_base_volume,
+            accumulated_taker_quote_volume: legacy.accumulated_taker_quote_volume,
+            accumulated_taker_base_volume: legacy.accumulated_taker_base_volume,
+            ..Self::new(&legacy.market, &legacy.owner, &Pubkey::default(), 0)
+        }
     }
 
-    #[allow(missing_docs)]
-    pub fn from_buffer_unchecked(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
-        let (hd, tl) = buf.split_at_mut(USER_ACCOUNT_HEADER_LEN);
-        let header: &mut UserAccountHeader = try_from_bytes_mut(hd).unwrap();
-        let orders = try_cast_slice_mut(tl).unwrap();
+    /// Whether the given wallet may post and cancel orders for the user account, which is true of the owner and of
+    /// the current delegate
+    pub(crate) fn is_order_authority(&self, wallet: &Pubkey) -> bool {
+        &self.owner == wallet || (self.delegate != Pubkey::default() && &self.delegate == wallet)
+    }
 
-        Ok(Self { header, orders })
+    /// Debits the quote cost of a bid from the free balance and returns the shortfall which must be deposited.
+    ///
+    /// The cost is the matched quote at the makers' prices, fees included, plus the quote locked at the limit price
+    /// for the posted remainder, which moves to the locked balance. Any price improvement over the limit price is
+    /// never debited and remains free.
+    pub fn debit_bid_quote(&mut self, total_quote_qty: u64, posted_quote_qty: u64) -> u64 {
+        let deposit_qty = total_quote_qty.saturating_sub(self.quote_token_free);
+        self.quote_token_free = self.quote_token_free.saturating_sub(total_quote_qty);
+        self.quote_token_locked += posted_quote_qty;
+        deposit_qty
     }
-}
 
-impl<'a> UserAccount<'a> {
-    #[allow(missing_docs)]
-    pub fn read_order(&self, order_index: usize) -> Result<Order, DexError> {
-        if order_index >= self.header.number_of_orders as usize {
-            return Err(DexError::InvalidOrderIndex);
-        }
-        Ok(self.orders[order_index])
+    /// Moves the base tokens released by a cancelled or reduced ask from the locked to the free balance.
+    ///
+    /// The release can't exceed the locked balance unless the account's accounting has drifted, which is reported as
+    /// an error rather than wrapping the balance.
+    pub fn unlock_base(&mut self, base_qty: u64) -> Result<(), DexError> {
+        self.base_token_locked = self
+            .base_token_locked
+            .checked_sub(base_qty)
+            .ok_or_else(|| {
+                msg!(
+                    "Releasing {} base tokens from a locked balance of {}",
+                    base_qty,
+                    self.base_token_locked
+                );
+                DexError::LockedBalanceUnderflow
+            })?;
+        self.base_token_free = self
+            .base_token_free
+            .checked_add(base_qty)
+            .ok_or(DexError::NumericalOverflow)?;
+        Ok(())
     }
 
-    #[allow(missing_docs)]
-    pub fn remove_order(&mut self, order_index: usize) -> Result<(), DexError> {
-        if order_index >= self.header.number_of_orders as usize {
+    /// Moves the quote tokens released by a cancelled or reduced bid from the locked to the free balance.
+    ///
+    /// The release can't exceed the locked balance unless the account's accounting has drifted, which is reported as
+    /// an error rather than wrapping the balance.
+    pub fn unlock_quote(&mut self, quote_qty: u64) -> Result<(), DexError> {
+        self.quote_token_locked =
+            self.quote_token_locked
+                .checked_sub(quote_qty)
+                .ok_or_else(|| {
+                    msg!(
+                        "Releasing {} quote tokens from a locked balance of {}",
+                        quote_qty,
+                        self.quote_token_locked
+                    );
+                    DexError::LockedBalanceUnderflow
+                })?;
+        self.quote_token_free = self
+            .quote_token_free
+            .checked_add(quote_qty)
+            .ok_or(DexError::NumericalOverflow)?;
+        Ok(())
+    }
+
+    /// Whether the dead-man switch of the user account is armed and its heartbeat has expired at the given timestamp
+    pub fn is_heartbeat_expired(&self, current_timestamp: i64) -> bool {
+        self.heartbeat_expiry != 0 && current_timestamp > self.heartbeat_expiry
+    }
+
+    /// The base volume matched by the user in the volume window which is current at the given timestamp
+    pub fn current_rolling_base_volume(&self, current_timestamp: i64) -> u64 {
+        if current_timestamp.saturating_sub(self.volume_window_start) >= VOLUME_WINDOW_DURATION {
+            0
+        } else {
+            self.rolling_base_volume
+        }
+    }
+
+    /// Adds matched base volume to the rolling volume, starting a new window when the current one has elapsed
+    pub(crate) fn record_rolling_base_volume(&mut self, base_qty: u64, current_timestamp: i64) {
+        if current_timestamp.saturating_sub(self.volume_window_start) >= VOLUME_WINDOW_DURATION {
+            self.rolling_base_volume = 0;
+            self.volume_window_start = current_timestamp;
+        }
+        self.rolling_base_volume = self.rolling_base_volume.saturating_add(base_qty);
+    }
+}
+
+impl<'a> UserAccount<'a> {
+    /// Loads a user account, rejecting the accounts which still follow the legacy layout since their header would be
+    /// misread
+    pub fn from_buffer(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if Self::is_legacy_layout(buf) {
+            msg!("The user account follows the legacy layout and must be upgraded with the migrate_account instruction");
+            return Err(DexError::UnsupportedStateVersion.into());
+        }
+        let user_acc = UserAccount::from_buffer_unchecked(buf)?;
+        if user_acc.header.tag != AccountTag::UserAccount as u64 {
+            return Err(ProgramError::InvalidAccountData);
+        };
+        Ok(user_acc)
+    }
+
+    #[allow(missing_docs)]
+    pub fn from_buffer_unchecked(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if buf.len() < USER_ACCOUNT_HEADER_LEN {
+            return Err(DexError::CorruptUserAccount.into());
+        }
+        let (hd, tl) = buf.split_at_mut(USER_ACCOUNT_HEADER_LEN);
+        let header: &mut UserAccountHeader =
+            try_from_bytes_mut(hd).map_err(|_| DexError::CorruptUserAccount)?;
+        // A trailing partial order slot is never used
+        let orders_len = tl.len() - tl.len() % Order::LEN;
+        let orders =
+            try_cast_slice_mut(&mut tl[..orders_len]).map_err(|_| DexError::CorruptUserAccount)?;
+
+        Ok(Self { header, orders })
+    }
+}
+
+impl<'a> UserAccount<'a> {
+    /// Whether the data of a user account follows the layout of the initial program version.
+    ///
+    /// The headers of both layouts are followed by whole order slots, and the header lengths differ by a
+    /// non-multiple of the slot length, so that the length of the data tells the layouts apart.
+    pub fn is_legacy_layout(buf: &[u8]) -> bool {
+        buf.len() >= LEGACY_USER_ACCOUNT_HEADER_LEN
+            && (buf.len() - LEGACY_USER_ACCOUNT_HEADER_LEN) % Order::LEN == 0
+            && buf[..8] == (AccountTag::UserAccount as u64).to_le_bytes()
+    }
+
+    /// Rewrites a legacy user account in the current layout, moving its orders after the new header.
+    ///
+    /// The buffer holds the `legacy_len` bytes of the legacy account, followed by the bytes by which the header grows.
+    pub fn migrate_legacy_buffer(buf: &mut [u8], legacy_len: usize) -> Result<(), ProgramError> {
+        if legacy_len > buf.len() || !Self::is_legacy_layout(&buf[..legacy_len]) {
+            msg!("The user account doesn't follow the legacy layout");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if buf.len() - legacy_len != USER_ACCOUNT_HEADER_LEN - LEGACY_USER_ACCOUNT_HEADER_LEN {
+            msg!("The user account must be resized to the length of the current layout");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let legacy_header: LegacyUserAccountHeader =
+            *try_from_bytes(&buf[..LEGACY_USER_ACCOUNT_HEADER_LEN])
+                .map_err(|_| DexError::CorruptUserAccount)?;
+        buf.copy_within(
+            LEGACY_USER_ACCOUNT_HEADER_LEN..legacy_len,
+            USER_ACCOUNT_HEADER_LEN,
+        );
+
+        let user_account = Self::from_buffer_unchecked(buf)?;
+        *user_account.header = UserAccountHeader::from_legacy(&legacy_header);
+
+        Ok(())
+    }
+}
+
+impl<'a> UserAccount<'a> {
+    /// The orders which are currently active, failing if the account data cannot hold as many orders as the header
+    /// describes
+    fn active_orders(&self) -> Result<&[Order], DexError> {
+        self.orders
+            .get(..self.header.number_of_orders as usize)
+            .ok_or(DexError::CorruptUserAccount)
+    }
+
+    #[allow(missing_docs)]
+    pub fn read_order(&self, order_index: usize) -> Result<Order, DexError> {
+        self.active_orders()?
+            .get(order_index)
+            .copied()
+            .ok_or(DexError::InvalidOrderIndex)
+    }
+
+    /// Removes the order at the given index by moving the last active order into its slot, returning the id of the
+    /// removed order
+    pub fn remove_order(&mut self, order_index: usize) -> Result<u128, DexError> {
+        if order_index >= self.active_orders()?.len() {
             return Err(DexError::InvalidOrderIndex);
         }
+        let removed_order_id = self.orders[order_index].id;
         if self.header.number_of_orders - order_index as u32 != 1 {
             self.orders[order_index] = self.orders[self.header.number_of_orders as usize - 1];
         }
         self.header.number_of_orders -= 1;
-        Ok(())
+        Ok(removed_order_id)
     }
 
-    #[allow(missing_docs)]
+    /// The number of orders which the account data can hold, given its length past the header and the maximum number
+    /// of orders per account
+    pub fn capacity(&self) -> usize {
+        std::cmp::min(self.orders.len(), MAX_ORDERS_PER_ACCOUNT)
+    }
+
+    /// The number of orders which can still be added before the account data needs to be reallocated
+    pub fn remaining_slots(&self) -> usize {
+        self.capacity()
+            .saturating_sub(self.header.number_of_orders as usize)
+    }
+
+    /// Stores a new open order, failing when the account already holds an order with the same id. Orders are looked up
+    /// by id, so a duplicate would never be found again once the first copy is removed.
     pub fn add_order(&mut self, order: Order) -> Result<(), DexError> {
+        if self.header.number_of_orders as usize >= MAX_ORDERS_PER_ACCOUNT {
+            return Err(DexError::UserAccountFull);
+        }
+        if self.active_orders()?.iter().any(|o| o.id == order.id) {
+            return Err(DexError::DuplicateOrder);
+        }
         let slot = self
             .orders
             .get_mut(self.header.number_of_orders as usize)
@@ -279,10 +1354,18 @@ impl<'a> UserAccount<'a> {
         Ok(())
     }
 
+    /// Iterates over the ids of the account's open orders
+    pub fn iter_orders(&self) -> impl Iterator<Item = u128> + '_ {
+        self.orders
+            .iter()
+            .take(self.header.number_of_orders as usize)
+            .map(|o| o.id)
+    }
+
     #[allow(missing_docs)]
     pub fn find_order_index(&self, order_id: u128) -> Result<usize, DexError> {
         let res = self
-            .orders
+            .active_orders()?
             .iter()
             .enumerate()
             .find(|(_, b)| b.id == order_id)
@@ -291,44 +1374,249 @@ impl<'a> UserAccount<'a> {
         Ok(res)
     }
 
-    #[allow(missing_docs)]
+    /// Finds the order with the given client id, picking the oldest one when several orders share it
     pub fn find_order_id_and_index_by_client_id(
         &self,
         client_order_id: u128,
     ) -> Result<(u64, u128), DexError> {
         let res = self
-            .orders
+            .active_orders()?
             .iter()
             .enumerate()
-            .find(|(_, b)| b.client_id == client_order_id)
+            .filter(|(_, b)| b.client_id == client_order_id)
+            .min_by_key(|(_, b)| get_order_sequence_number(b.id))
             .map(|(idx, b)| (idx as u64, b.id))
             .ok_or(DexError::OrderNotFound)?;
         Ok(res)
     }
 }
 
-#[doc(hidden)]
-#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy)]
+/// An owned summary of a user account's open orders and balances, meant to be decoded off-chain from the raw account
+/// data.
+///
+/// ```no_run
+/// # use solana_client::rpc_client::RpcClient;
+/// # use solana_program::pubkey::Pubkey;
+/// use dex_v4::state::OpenOrdersView;
+///
+/// # fn print_open_orders(
+/// #     rpc_client: &RpcClient,
+/// #     user_account_key: &Pubkey,
+/// # ) -> Result<(), Box<dyn std::error::Error>> {
+/// let data = rpc_client.get_account_data(user_account_key)?;
+/// let view = OpenOrdersView::from_account_data(&data)?;
+/// for order_id in &view.orders {
+///     println!("Open order {}", order_id);
+/// }
+/// println!("Free base: {}, free quote: {}", view.base_token_free, view.quote_token_free);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, PartialEq)]
+pub struct OpenOrdersView {
+    /// The ids of the open orders
+    pub orders: Vec<u128>,
+    /// The amount of base token available for settlement
+    pub base_token_free: u64,
+    /// The amount of base token currently locked in the orderbook
+    pub base_token_locked: u64,
+    /// The amount of quote token available for settlement
+    pub quote_token_free: u64,
+    /// The amount of quote token currently locked in the orderbook
+    pub quote_token_locked: u64,
+}
+
+impl OpenOrdersView {
+    /// Decodes the view from the data of a user account, which does not need to be aligned
+    pub fn from_account_data(data: &[u8]) -> Result<Self, ProgramError> {
+        // The account data is copied to an aligned buffer so that it can be cast in place
+        let mut words = vec![0u64; (data.len() + 7) / 8];
+        let buf = &mut cast_slice_mut::<_, u8>(&mut words)[..data.len()];
+        buf.copy_from_slice(data);
+        let user_account = UserAccount::from_buffer(buf)?;
+        // Fails if the header describes more orders than the account data holds
+        user_account.active_orders()?;
+        Ok(Self {
+            orders: user_account.iter_orders().collect(),
+            base_token_free: user_account.header.base_token_free,
+            base_token_locked: user_account.header.base_token_locked,
+            quote_token_free: user_account.header.quote_token_free,
+            quote_token_locked: user_account.header.quote_token_locked,
+        })
+    }
+}
+
+/// The orderbook sequence number of an order, which increases with the order's creation time
+fn get_order_sequence_number(order_id: u128) -> u64 {
+    // The orderbook inverts the sequence number of bids so that older bids come first at a given price
+    match get_side_from_order_id(order_id) {
+        asset_agnostic_orderbook::state::Side::Bid => !(order_id as u64),
+        asset_agnostic_orderbook::state::Side::Ask => order_id as u64,
+    }
+}
+
+/// A record of a single fill, written to the market's trade log when the events are consumed
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, Zeroable, Pod, PartialEq)]
+#[repr(C)]
+pub struct FillEvent {
+    /// The user account of the maker
+    pub maker: Pubkey,
+    /// The user account of the taker
+    pub taker: Pubkey,
+    /// The fill price (as a FP32), which is the limit price of the maker order
+    pub price: u64,
+    /// The base quantity which was matched
+    pub base_qty: u64,
+    /// The quote quantity which was matched, fees excluded
+    pub quote_qty: u64,
+    /// The fees paid by the taker in quote token, referral share included
+    pub taker_fee: u64,
+    /// The unix timestamp at which the fill was recorded
+    pub timestamp: i64,
+}
+
+/// The tag of the structured fill logs, which is the first field of their data
+pub const FILL_LOG_TAG: &[u8] = b"dex_fill";
+
+/// The version of the structured fill logs, which is their second field. The third field is the borsh-encoded
+/// `FillEvent`, and fields may only be appended in later versions
+pub const FILL_LOG_VERSION: u8 = 1;
+
+impl FillEvent {
+    /// The length in bytes of the record's binary representation
+    pub const LEN: usize = std::mem::size_of::<Self>();
+
+    /// Emits the fill as structured program log data, so that indexers which only ingest logs can parse it
+    pub(crate) fn log(&self) {
+        sol_log_data(&[
+            FILL_LOG_TAG,
+            &[FILL_LOG_VERSION],
+            &self.try_to_vec().unwrap(),
+        ]);
+    }
+}
+
+/// This header describes the state of a trade log, which is a ring buffer of `FillEvent` records.
+///
+/// Once the buffer is full, every new record overwrites the oldest one. Consumers can detect that they missed records
+/// by comparing the sequence number with the last one they processed.
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+pub struct TradeLogHeader {
+    /// This u64 is used to verify and version the trade log
+    pub tag: u64,
+    /// The trade log's associated DEX market
+    pub market: Pubkey,
+    /// The index of the oldest record in the buffer
+    pub head: u64,
+    /// The number of records currently held in the buffer
+    pub count: u64,
+    /// The total number of records ever written, which is the sequence number of the next record
+    pub sequence_number: u64,
+}
+
+/// Size in bytes of the trade log header object
+pub const TRADE_LOG_HEADER_LEN: usize = size_of::<TradeLogHeader>();
+
+#[allow(missing_docs)]
+pub struct TradeLog<'a> {
+    pub header: &'a mut TradeLogHeader,
+    records: &'a mut [FillEvent],
+}
+
+impl<'a> TradeLog<'a> {
+    #[allow(missing_docs)]
+    pub fn from_buffer(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
+        let trade_log = TradeLog::from_buffer_unchecked(buf)?;
+        if trade_log.header.tag != AccountTag::TradeLog as u64 {
+            return Err(ProgramError::InvalidAccountData);
+        };
+        Ok(trade_log)
+    }
+
+    #[allow(missing_docs)]
+    pub fn from_buffer_unchecked(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if buf.len() < TRADE_LOG_HEADER_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (hd, tl) = buf.split_at_mut(TRADE_LOG_HEADER_LEN);
+        let header: &mut TradeLogHeader =
+            try_from_bytes_mut(hd).map_err(|_| ProgramError::InvalidAccountData)?;
+        // A trailing partial record slot is never used
+        let records_len = tl.len() - tl.len() % FillEvent::LEN;
+        let records = try_cast_slice_mut(&mut tl[..records_len])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        Ok(Self { header, records })
+    }
+
+    /// The maximum number of records held in the buffer
+    pub fn capacity(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Reads the record at the given position, counted from the oldest record in the buffer
+    pub fn read(&self, index: usize) -> Option<FillEvent> {
+        if index >= self.header.count as usize {
+            return None;
+        }
+        self.records
+            .get((self.header.head as usize + index) % self.capacity())
+            .copied()
+    }
+
+    /// Appends a record to the buffer, overwriting the oldest one when the buffer is full
+    pub(crate) fn push(&mut self, record: FillEvent) {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return;
+        }
+        let tail = (self.header.head as usize + self.header.count as usize) % capacity;
+        self.records[tail] = record;
+        if self.header.count as usize == capacity {
+            self.header.head = ((self.header.head as usize + 1) % capacity) as u64;
+        } else {
+            self.header.count += 1;
+        }
+        self.header.sequence_number = self.header.sequence_number.wrapping_add(1);
+    }
+}
+
+/// The fee schedule of a market, as stored in its `fee_type` field
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MarketFeeType {
+    /// Taker fees are discounted according to the taker's SRM or MSRM holdings, or its rolling volume
     Default,
+    /// All takers pay the market's base taker rate
     Stable,
 }
 
-#[doc(hidden)]
-#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy)]
+/// The fee tier of a taker, which discounts the market's base taker rate.
+///
+/// Integrations can use it to precompute the exact fees charged by the program, which rounds every fee down except
+/// for the taker fees of markets whose `FeeRounding` is `HalfUp`.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FeeTier {
+    /// No discount
     Base,
+    /// Holding at least 100 SRM, or a rolling volume of 1 000 minimum orders
     Srm2,
+    /// Holding at least 1 000 SRM, or a rolling volume of 10 000 minimum orders
     Srm3,
+    /// Holding at least 10 000 SRM, or a rolling volume of 100 000 minimum orders
     Srm4,
+    /// Holding at least 100 000 SRM, or a rolling volume of 1 000 000 minimum orders
     Srm5,
+    /// Holding at least 1 000 000 SRM, or a rolling volume of 10 000 000 minimum orders
     Srm6,
+    /// Holding at least one MSRM
     MSrm,
+    /// The only tier of markets with the stable fee type
     Stable,
 }
 
-#[doc(hidden)]
 impl FeeTier {
+    /// Maps the SRM and MSRM balances of a discount token account to a fee tier
     pub fn from_srm_and_msrm_balances(
         dex_state: &DexState,
         srm_held: u64,
@@ -351,6 +1639,35 @@ impl FeeTier {
         }
     }
 
+    /// Maps a user's rolling base volume to a fee tier. Thresholds are expressed in multiples of the market's
+    /// minimum order size.
+    pub fn from_volume(dex_state: &DexState, rolling_base_volume: u64) -> FeeTier {
+        if dex_state.fee_type == MarketFeeType::Stable as u8 {
+            return FeeTier::Stable;
+        }
+
+        let lots = rolling_base_volume / dex_state.min_base_order_size.max(1);
+
+        match () {
+            () if lots >= 10_000_000 => FeeTier::Srm6,
+            () if lots >= 1_000_000 => FeeTier::Srm5,
+            () if lots >= 100_000 => FeeTier::Srm4,
+            () if lots >= 10_000 => FeeTier::Srm3,
+            () if lots >= 1_000 => FeeTier::Srm2,
+            () => FeeTier::Base,
+        }
+    }
+
+    /// Returns the tier with the lowest taker fees
+    pub fn best(self, other: FeeTier) -> FeeTier {
+        if other.taker_rate_multiplier() < self.taker_rate_multiplier() {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Decodes the fee tier tag of a `CallBackInfo`, along with whether the user was referred
     pub fn from_u8(tag: u8) -> (Self, bool) {
         let is_referred = (tag & REFERRAL_MASK) != 0;
         let fee_tier = match tag & (!REFERRAL_MASK) {
@@ -360,17 +1677,21 @@ impl FeeTier {
             3 => FeeTier::Srm4,
             4 => FeeTier::Srm5,
             5 => FeeTier::Srm6,
+            6 => FeeTier::MSrm,
+            7 => FeeTier::Stable,
             _ => unreachable!(),
         };
         (fee_tier, is_referred)
     }
 
+    /// Reads the fee tier granted by a discount token account holding SRM or MSRM, which should share its owner with
+    /// the user account
     pub fn get(
         dex_state: &DexState,
         account: &AccountInfo,
         expected_owner: &Pubkey,
     ) -> Result<Self, ProgramError> {
-        let parsed_token_account = spl_token::state::Account::unpack(&account.data.borrow())?;
+        let parsed_token_account = unpack_token_account(account)?;
         if &parsed_token_account.owner != expected_owner {
             msg!("The discount token account must share its owner with the user account.");
             return Err(ProgramError::InvalidArgument);
@@ -388,46 +1709,118 @@ impl FeeTier {
         ))
     }
 
-    pub fn taker_rate(self) -> u64 {
+    /// The discount multiplier applied to the market's base taker rate, as an FP32
+    pub fn taker_rate_multiplier(self) -> u64 {
         match self {
-            FeeTier::Base => (40 << 32) / 100_000,
-            FeeTier::Srm2 => (39 << 32) / 100_000,
-            FeeTier::Srm3 => (38 << 32) / 100_000,
-            FeeTier::Srm4 => (36 << 32) / 100_000,
-            FeeTier::Srm5 => (34 << 32) / 100_000,
-            FeeTier::Srm6 => (32 << 32) / 100_000,
-            FeeTier::MSrm => (30 << 32) / 100_000,
-            FeeTier::Stable => (10 << 32) / 100_000,
+            FeeTier::Base => FP_32_ONE,
+            FeeTier::Srm2 => (39 << 32) / 40,
+            FeeTier::Srm3 => (38 << 32) / 40,
+            FeeTier::Srm4 => (36 << 32) / 40,
+            FeeTier::Srm5 => (34 << 32) / 40,
+            FeeTier::Srm6 => (32 << 32) / 40,
+            FeeTier::MSrm => (30 << 32) / 40,
+            FeeTier::Stable => FP_32_ONE,
+        }
+    }
+
+    /// The tier's taker rate on the given market, as an FP32
+    pub fn taker_rate(self, dex_state: &DexState) -> u64 {
+        fp32_mul(dex_state.taker_rate(), self.taker_rate_multiplier()).unwrap()
+    }
+
+    /// The maker rebate rate on the given market, as an FP32. It doesn't depend on the tier
+    pub fn maker_rate(self, dex_state: &DexState) -> u64 {
+        dex_state.base_maker_rebate
+    }
+
+    /// The regular maker rebate paid on a matched quote amount, rounded down
+    pub fn maker_rebate(self, dex_state: &DexState, quote_qty: u64) -> Result<u64, DexError> {
+        let rate = self.maker_rate(dex_state);
+        fp32_mul(quote_qty, rate).ok_or(DexError::NumericalOverflow)
+    }
+
+    /// The boosted rebate paid on top of the regular maker rebate, which is capped by the accumulated fees it is
+    /// funded from. It is zero once the boost has ended.
+    pub fn maker_rebate_boost(
+        self,
+        dex_state: &DexState,
+        quote_qty: u64,
+        current_timestamp: i64,
+    ) -> Result<u64, DexError> {
+        if current_timestamp >= dex_state.rebate_boost_end {
+            return Ok(0);
         }
+        let boost =
+            fp32_mul(quote_qty, dex_state.rebate_boost_rate).ok_or(DexError::NumericalOverflow)?;
+        Ok(boost.min(dex_state.accumulated_fees))
     }
 
-    pub fn maker_rate(self) -> u64 {
-        0
+    /// The largest matched quote amount whose taker fee fits, along with it, in the given quote amount.
+    ///
+    /// The result is rounded down, so that the matched amount plus its taker fee never exceeds the given amount with
+    /// either `FeeRounding`: when `q * (1 + rate) <= quote_qty`, rounding `q * rate` half up adds less than one unit.
+    pub fn remove_taker_fee(self, dex_state: &DexState, quote_qty: u64) -> Result<u64, DexError> {
+        let rate = self.taker_rate(dex_state);
+        FP_32_ONE
+            .checked_add(rate)
+            .and_then(|r| fp32_div(quote_qty, r))
+            .ok_or(DexError::NumericalOverflow)
     }
 
-    pub fn maker_rebate(self, _quote_qty: u64) -> u64 {
-        0
+    /// The taker fee charged on a matched quote amount, rounded according to the market's `FeeRounding`
+    pub fn taker_fee(self, dex_state: &DexState, quote_qty: u64) -> Result<u64, DexError> {
+        let rate = self.taker_rate(dex_state);
+        match dex_state.fee_rounding() {
+            FeeRounding::Down => fp32_mul(quote_qty, rate),
+            FeeRounding::HalfUp => fp32_mul_half_up(quote_qty, rate),
+        }
+        .ok_or(DexError::NumericalOverflow)
     }
 
-    pub fn remove_taker_fee(self, quote_qty: u64) -> u64 {
-        let rate = self.taker_rate();
-        fp32_div(quote_qty, FP_32_ONE + rate).unwrap()
+    /// The taker fee credited to the accumulated fees for a single fill of a matched quote amount, rounded down.
+    ///
+    /// Takers are charged the fee of their whole matched quantity at once, which is split into several fills when
+    /// consuming the events. Rounding each fill half up could then credit more than the fee actually charged, whereas
+    /// the rounded down credits of the fills never add up to more than it.
+    pub fn taker_fee_credit(self, dex_state: &DexState, quote_qty: u64) -> Result<u64, DexError> {
+        let rate = self.taker_rate(dex_state);
+        fp32_mul(quote_qty, rate).ok_or(DexError::NumericalOverflow)
     }
 
-    pub fn taker_fee(self, quote_qty: u64) -> u64 {
-        let rate = self.taker_rate();
-        fp32_mul(quote_qty, rate).unwrap()
+    /// The taker fee credited to the accumulated fees for a single fill of a matched quote amount once the referral fee
+    /// is taken out of it, rounded down.
+    ///
+    /// The referral fee is paid once for the taker's whole matched quantity, so the credit of each fill is computed at
+    /// the rate net of the referral rate. Subtracting the rounded referral fee of each fill from its rounded taker fee
+    /// instead could credit more than the fee which the market keeps.
+    pub fn net_taker_fee_credit(
+        self,
+        dex_state: &DexState,
+        quote_qty: u64,
+        is_referred: bool,
+    ) -> Result<u64, DexError> {
+        let mut rate = self.taker_rate(dex_state);
+        if is_referred {
+            // The referral rate is a share of the taker rate
+            rate -= self.referral_rate(dex_state);
+        }
+        fp32_mul(quote_qty, rate).ok_or(DexError::NumericalOverflow)
     }
 
-    pub fn referral_rate(self) -> u64 {
-        let taker_rate = self.taker_rate();
-        let min_maker_rebate = Self::Base.maker_rate();
-        taker_rate.saturating_sub(min_maker_rebate) / 5
+    /// The share of the taker rate which goes to the referrer, as an FP32
+    pub fn referral_rate(self, dex_state: &DexState) -> u64 {
+        let taker_rate = self.taker_rate(dex_state);
+        let min_maker_rebate = Self::Base.maker_rate(dex_state);
+        // The share is at most 100%, so the result always fits back into a u64
+        ((taker_rate.saturating_sub(min_maker_rebate) as u128)
+            * (dex_state.referral_fee_share_bps() as u128)
+            / 10_000) as u64
     }
 
-    pub fn referral_fee(self, quote_qty: u64) -> u64 {
-        let rate = self.referral_rate();
-        fp32_mul(quote_qty, rate).unwrap()
+    /// The referral fee paid out of the taker fee charged on a matched quote amount, rounded down
+    pub fn referral_fee(self, dex_state: &DexState, quote_qty: u64) -> Result<u64, DexError> {
+        let rate = self.referral_rate(dex_state);
+        fp32_mul(quote_qty, rate).ok_or(DexError::NumericalOverflow)
     }
 }
 #[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, Zeroable, Pod, PartialEq)]
@@ -436,8 +1829,81 @@ impl FeeTier {
 pub struct CallBackInfo {
     #[allow(missing_docs)]
     pub user_account: Pubkey,
-    #[allow(missing_docs)]
+    /// The fee tier of the order's user, snapshotted when the order is placed, with the referral flag in the upper
+    /// bit. Fees are never recomputed from the discount token account when the order is matched.
+    ///
+    /// In a self-trade, each side still uses its own snapshot: the taker fee follows the taker order's tier and the
+    /// maker rebate follows the maker order's tier.
     pub fee_tier: u8,
+    /// The self-trade behavior of the order, applied when an incoming order from the same user account matches against it.
+    ///
+    /// Orders posted before this field was introduced hold zero, which stands for DecrementTake.
+    pub self_trade_behavior: u8,
+    /// The layout version of the callback information, which is `CALLBACK_INFO_VERSION` for new orders.
+    ///
+    /// It isn't the leading byte since the orderbook reads the user account as the callback id at the start of the
+    /// record. Records written before this field was introduced hold zero, as it used to be padding.
+    pub version: u8,
+    /// To eliminate implicit padding
+    pub _padding: [u8; 5],
+    /// The unix timestamp after which the order is considered cancelled. Zero means the order never expires.
+    pub expiry_timestamp: i64,
+    /// For iceberg orders, the base quantity which is shown on the book for each slice. Zero for other orders.
+    pub display_base_qty: u64,
+    /// For iceberg orders, the locked base quantity which still has to be reposted once the current slice is filled.
+    pub hidden_base_qty: u64,
+}
+
+/// The current layout version of the callback information
+pub const CALLBACK_INFO_VERSION: u8 = 1;
+
+impl CallBackInfo {
+    /// The length in bytes of the current layout
+    pub const LEN: usize = size_of::<Self>();
+
+    /// Parses callback information from any of its layouts, defaulting the fields which are missing.
+    ///
+    /// Missing fields default to zero, meaning that the order uses `DecrementTake`, never expires and isn't an
+    /// iceberg order. Records of a version newer than `CALLBACK_INFO_VERSION` are rejected.
+    pub fn from_versioned_bytes(data: &[u8]) -> Result<Self, DexError> {
+        match data.len() {
+            LegacyCallBackInfo::LEN => {
+                let mut callback_info = Self::zeroed();
+                callback_info.user_account = Pubkey::new(&data[..32]);
+                callback_info.fee_tier = data[32];
+                Ok(callback_info)
+            }
+            Self::LEN => {
+                // The borsh encoding of the record matches its memory layout, which lifts any alignment requirement
+                let callback_info = Self::try_from_slice(data).map_err(|_| {
+                    msg!("Failed to parse the callback information");
+                    DexError::UnsupportedCallbackInfoVersion
+                })?;
+                if callback_info.version > CALLBACK_INFO_VERSION {
+                    msg!(
+                        "Unsupported callback information version {}",
+                        callback_info.version
+                    );
+                    return Err(DexError::UnsupportedCallbackInfoVersion);
+                }
+                Ok(callback_info)
+            }
+            _ => {
+                msg!("Invalid callback information length {}", data.len());
+                Err(DexError::UnsupportedCallbackInfoVersion)
+            }
+        }
+    }
+
+    /// Reads a record stored in the orderbook through `from_versioned_bytes`, which checks its version
+    pub fn read(record: &Self) -> Result<Self, DexError> {
+        Self::from_versioned_bytes(bytes_of(record))
+    }
+
+    #[allow(missing_docs)]
+    pub fn is_expired(&self, current_timestamp: i64) -> bool {
+        self.expiry_timestamp != 0 && self.expiry_timestamp <= current_timestamp
+    }
 }
 
 impl CallbackInfo for CallBackInfo {
@@ -447,3 +1913,400 @@ impl CallbackInfo for CallBackInfo {
         &self.user_account
     }
 }
+
+/// The 33 byte callback information of the initial program version.
+///
+/// Orderbooks created by the initial version hold these records. The migrate_market instruction reads them with
+/// `CallBackInfo::from_versioned_bytes` as it moves their orders and events to an orderbook of the current layout.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Copy, Zeroable, Pod, PartialEq)]
+#[repr(C)]
+#[allow(missing_docs)]
+pub struct LegacyCallBackInfo {
+    pub user_account: Pubkey,
+    pub fee_tier: u8,
+}
+
+impl LegacyCallBackInfo {
+    /// The length in bytes of the initial layout
+    pub const LEN: usize = size_of::<Self>();
+}
+
+impl CallbackInfo for LegacyCallBackInfo {
+    type CallbackId = Pubkey;
+
+    fn as_callback_id(&self) -> &Self::CallbackId {
+        &self.user_account
+    }
+}
+
+/// This header describes the state of a liquidity metrics account, which is a bitmap of the makers of the current
+/// metrics epoch.
+///
+/// Each user account is mapped to a single bit through the low bits of its pubkey. The unique maker count of the
+/// market is only incremented when a maker's bit wasn't set yet, which makes it an underestimate: makers which share a
+/// bit with a maker recorded earlier in the epoch are never counted. With `m` bits and `n` distinct makers, about
+/// `n * (n - 1) / (2 * m)` makers go missing as long as `n` is well below `m`, so the bitmap should be sized for many
+/// more makers than expected. Given the number `z` of bits left unset, `-m * ln(z / m)` is a better estimate of `n`
+/// which can be computed offchain.
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+pub struct LiquidityMetricsHeader {
+    /// This u64 is used to verify and version the liquidity metrics account
+    pub tag: u64,
+    /// The liquidity metrics account's associated DEX market
+    pub market: Pubkey,
+}
+
+/// Size in bytes of the liquidity metrics header object
+pub const LIQUIDITY_METRICS_HEADER_LEN: usize = size_of::<LiquidityMetricsHeader>();
+
+#[allow(missing_docs)]
+pub struct LiquidityMetrics<'a> {
+    pub header: &'a mut LiquidityMetricsHeader,
+    bitmap: &'a mut [u8],
+}
+
+impl<'a> LiquidityMetrics<'a> {
+    #[allow(missing_docs)]
+    pub fn from_buffer(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
+        let liquidity_metrics = LiquidityMetrics::from_buffer_unchecked(buf)?;
+        if liquidity_metrics.header.tag != AccountTag::LiquidityMetrics as u64 {
+            return Err(ProgramError::InvalidAccountData);
+        };
+        Ok(liquidity_metrics)
+    }
+
+    #[allow(missing_docs)]
+    pub fn from_buffer_unchecked(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if buf.len() < LIQUIDITY_METRICS_HEADER_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (hd, bitmap) = buf.split_at_mut(LIQUIDITY_METRICS_HEADER_LEN);
+        let header: &mut LiquidityMetricsHeader =
+            try_from_bytes_mut(hd).map_err(|_| ProgramError::InvalidAccountData)?;
+
+        Ok(Self { header, bitmap })
+    }
+
+    /// The number of bits of the bitmap
+    pub fn bit_count(&self) -> usize {
+        self.bitmap.len() * 8
+    }
+
+    /// The number of bits which are currently set
+    pub fn set_bit_count(&self) -> usize {
+        self.bitmap.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// Whether the bit of a user account is set, which is also the case for any user account sharing its bit
+    pub fn contains(&self, user_account: &Pubkey) -> bool {
+        match self.bit_index(user_account) {
+            Some(i) => self.bitmap[i / 8] & (1 << (i % 8)) != 0,
+            None => false,
+        }
+    }
+
+    /// Sets the bit of a user account, returning whether it wasn't set yet
+    pub fn insert(&mut self, user_account: &Pubkey) -> bool {
+        match self.bit_index(user_account) {
+            Some(i) => {
+                let is_new = self.bitmap[i / 8] & (1 << (i % 8)) == 0;
+                self.bitmap[i / 8] |= 1 << (i % 8);
+                is_new
+            }
+            None => false,
+        }
+    }
+
+    /// Unsets all the bits, which starts a new metrics epoch
+    pub fn clear(&mut self) {
+        self.bitmap.iter_mut().for_each(|b| *b = 0);
+    }
+
+    fn bit_index(&self, user_account: &Pubkey) -> Option<usize> {
+        if self.bitmap.is_empty() {
+            return None;
+        }
+        let low_bits = u64::from_le_bytes(user_account.to_bytes()[..8].try_into().unwrap());
+        Some((low_bits % self.bit_count() as u64) as usize)
+    }
+}
+
+/// The maximum number of token accounts between which the accumulated fees can be split
+pub const MAX_FEE_RECIPIENTS: usize = 8;
+
+/// This header describes the state of a fee recipients account, which lists the token accounts between which the
+/// accumulated fees of a market are split when swept
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+pub struct FeeRecipientsHeader {
+    /// This u64 is used to verify and version the fee recipients account
+    pub tag: u64,
+    /// The fee recipients account's associated DEX market
+    pub market: Pubkey,
+    /// The number of recipients in use
+    pub number_of_recipients: u64,
+}
+
+/// Size in bytes of the fee recipients header object
+pub const FEE_RECIPIENTS_HEADER_LEN: usize = size_of::<FeeRecipientsHeader>();
+
+/// A token account receiving a share of the swept fees
+#[derive(Copy, Clone, Pod, Zeroable, BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+#[repr(C)]
+pub struct FeeRecipient {
+    /// The recipient quote token account
+    pub token_account: Pubkey,
+    /// The share of the fees received by the token account, in basis points
+    pub weight_bps: u16,
+    /// To eliminate implicit padding
+    pub _padding: [u8; 6],
+}
+
+impl FeeRecipient {
+    /// Size in bytes of a serialized fee recipient
+    pub const LEN: usize = size_of::<Self>();
+}
+
+/// Size in bytes of a fee recipients account
+pub const FEE_RECIPIENTS_LEN: usize =
+    FEE_RECIPIENTS_HEADER_LEN + MAX_FEE_RECIPIENTS * FeeRecipient::LEN;
+
+#[allow(missing_docs)]
+pub struct FeeRecipients<'a> {
+    pub header: &'a mut FeeRecipientsHeader,
+    recipients: &'a mut [FeeRecipient],
+}
+
+impl<'a> FeeRecipients<'a> {
+    #[allow(missing_docs)]
+    pub fn from_buffer(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
+        let fee_recipients = FeeRecipients::from_buffer_unchecked(buf)?;
+        if fee_recipients.header.tag != AccountTag::FeeRecipients as u64 {
+            return Err(ProgramError::InvalidAccountData);
+        };
+        Ok(fee_recipients)
+    }
+
+    #[allow(missing_docs)]
+    pub fn from_buffer_unchecked(buf: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if buf.len() < FEE_RECIPIENTS_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (hd, rem) = buf[..FEE_RECIPIENTS_LEN].split_at_mut(FEE_RECIPIENTS_HEADER_LEN);
+        let header: &mut FeeRecipientsHeader =
+            try_from_bytes_mut(hd).map_err(|_| ProgramError::InvalidAccountData)?;
+        let recipients: &mut [FeeRecipient] =
+            try_cast_slice_mut(rem).map_err(|_| ProgramError::InvalidAccountData)?;
+
+        Ok(Self { header, recipients })
+    }
+
+    /// The recipients in use
+    pub fn recipients(&self) -> &[FeeRecipient] {
+        &self.recipients[..self.header.number_of_recipients as usize]
+    }
+
+    /// Replaces the recipients, whose weights should sum to 100%
+    pub fn set_recipients(&mut self, recipients: &[FeeRecipient]) -> Result<(), DexError> {
+        if recipients.is_empty() || recipients.len() > MAX_FEE_RECIPIENTS {
+            msg!(
+                "There should be between 1 and {} fee recipients",
+                MAX_FEE_RECIPIENTS
+            );
+            return Err(DexError::InvalidFeeRecipients);
+        }
+        let weight_sum = recipients.iter().map(|r| r.weight_bps as u64).sum::<u64>();
+        if weight_sum != 10_000 {
+            msg!(
+                "The fee recipient weights sum to {} bps instead of 10000",
+                weight_sum
+            );
+            return Err(DexError::InvalidFeeRecipients);
+        }
+        self.recipients[..recipients.len()].copy_from_slice(recipients);
+        self.header.number_of_recipients = recipients.len() as u64;
+        Ok(())
+    }
+
+    /// Splits an amount between the recipients according to their weights. The rounding remainder goes to the last
+    /// recipient so that the shares always sum to the amount
+    pub fn split(&self, amount: u64) -> Vec<u64> {
+        let recipients = self.recipients();
+        let mut shares = recipients
+            .iter()
+            .map(|r| ((amount as u128) * (r.weight_bps as u128) / 10_000) as u64)
+            .collect::<Vec<_>>();
+        let distributed = shares.iter().sum::<u64>();
+        if let Some(last) = shares.last_mut() {
+            *last += amount - distributed;
+        }
+        shares
+    }
+}
+
+/// A mirror of the market's trading statistics, which analytics consumers can decode with borsh without reading the
+/// market state.
+///
+/// The mirror is only refreshed by the new_order and consume_events instructions which are given the account, so it
+/// may lag behind the market. Its `last_update_timestamp` tells how fresh it is.
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct MarketStats {
+    /// This u64 is used to verify and version the market stats
+    pub tag: u64,
+    /// The market stats' associated DEX market
+    pub market: Pubkey,
+    /// The market's total historical volume in base token
+    pub base_volume: u64,
+    /// The market's total historical volume in quote token
+    pub quote_volume: u64,
+    /// The price of the last trade as an FP32. Zero means that there were no trades yet
+    pub last_trade_price: u64,
+    /// The unix timestamp of the last trade. Zero means that there were no trades yet
+    pub last_trade_timestamp: i64,
+    /// The price of the best bid as an FP32. Zero means that there were no bids
+    pub best_bid_price: u64,
+    /// The price of the best ask as an FP32. `u64::MAX` means that there were no asks
+    pub best_ask_price: u64,
+    /// The total base quantity resting on the bids side of the book, in base token amount
+    pub total_bid_base: u64,
+    /// The total base quantity resting on the asks side of the book, in base token amount
+    pub total_ask_base: u64,
+    /// The unix timestamp of the last refresh of the mirror
+    pub last_update_timestamp: i64,
+}
+
+/// Size in bytes of the borsh-encoded market stats
+pub const MARKET_STATS_LEN: usize = 112;
+
+impl MarketStats {
+    pub(crate) fn new(market: &Pubkey, market_state: &DexState, current_timestamp: i64) -> Self {
+        Self {
+            tag: AccountTag::MarketStats as u64,
+            market: *market,
+            base_volume: market_state.base_volume,
+            quote_volume: market_state.quote_volume,
+            last_trade_price: market_state.last_trade_price,
+            last_trade_timestamp: market_state.last_trade_timestamp,
+            best_bid_price: market_state.best_bid_price,
+            best_ask_price: market_state.best_ask_price,
+            total_bid_base: market_state.total_bid_base,
+            total_ask_base: market_state.total_ask_base,
+            last_update_timestamp: current_timestamp,
+        }
+    }
+
+    /// Refreshes a market stats account with the current statistics of its market
+    pub(crate) fn mirror(
+        account: &AccountInfo,
+        market: &Pubkey,
+        market_state: &DexState,
+        current_timestamp: i64,
+    ) -> ProgramResult {
+        let mut data = account.data.borrow_mut();
+        let stats = Self::deserialize(&mut &data[..])?;
+        if stats.tag != AccountTag::MarketStats as u64 || &stats.market != market {
+            msg!("The market stats account doesn't belong to this market");
+            return Err(DexError::InvalidMarketStatsAccount.into());
+        }
+        Self::new(market, market_state, current_timestamp).serialize(&mut &mut data[..])?;
+        Ok(())
+    }
+}
+
+/// The allowlist entry which lets a wallet trade a market whose access is restricted by an access authority. It is
+/// created by the grant_access instruction at an address derived from the market and the wallet.
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct AccessGrant {
+    /// This u64 is used to verify and version the access grant
+    pub tag: u64,
+    /// The market which the wallet may trade
+    pub market: Pubkey,
+    /// The wallet which was granted access
+    pub wallet: Pubkey,
+}
+
+/// Size in bytes of the borsh-encoded access grant
+pub const ACCESS_GRANT_LEN: usize = 72;
+
+impl AccessGrant {
+    pub(crate) fn new(market: &Pubkey, wallet: &Pubkey) -> Self {
+        Self {
+            tag: AccountTag::AccessGrant as u64,
+            market: *market,
+            wallet: *wallet,
+        }
+    }
+}
+
+impl DexState {
+    /// Makes sure that the wallet may trade the market, which is always the case when the market has no access
+    /// authority. Otherwise the wallet's access grant should be provided.
+    pub fn check_access(
+        &self,
+        program_id: &Pubkey,
+        market: &Pubkey,
+        wallet: &Pubkey,
+        access_grant: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        if self.access_authority == Pubkey::default() {
+            return Ok(());
+        }
+        let access_grant = access_grant.ok_or_else(|| {
+            msg!("The market is permissioned, the wallet's access grant should be provided");
+            DexError::Unauthorized
+        })?;
+        check_account_owner(access_grant, program_id, DexError::Unauthorized)?;
+        let grant = AccessGrant::deserialize(&mut &access_grant.data.borrow()[..])
+            .map_err(|_| DexError::Unauthorized)?;
+        if grant.tag != AccountTag::AccessGrant as u64
+            || &grant.market != market
+            || &grant.wallet != wallet
+        {
+            msg!("The access grant doesn't belong to this wallet and market");
+            return Err(DexError::Unauthorized.into());
+        }
+        Ok(())
+    }
+}
+
+/// The program-wide settings, held in a single account derived from the program id. It is created and updated by the
+/// set_program_config instruction.
+#[derive(Clone, Copy, Debug, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct ProgramConfig {
+    /// This u64 is used to verify and version the program config
+    pub tag: u64,
+    /// The account which may update the program config
+    pub authority: Pubkey,
+    /// The fee in lamports charged for creating a market, which deters the spamming of permissionless listing
+    /// platforms. Zero disables the fee
+    pub market_creation_fee: u64,
+    /// The account which collects the market creation fees
+    pub market_creation_fee_treasury: Pubkey,
+}
+
+/// Size in bytes of the borsh-encoded program config
+pub const PROGRAM_CONFIG_LEN: usize = 80;
+
+impl ProgramConfig {
+    /// Loads the program config, which must be the account derived from the program id. No config is returned when the
+    /// account wasn't created yet.
+    pub(crate) fn load(
+        program_id: &Pubkey,
+        expected_key: &Pubkey,
+        account: &AccountInfo,
+    ) -> Result<Option<Self>, ProgramError> {
+        check_account_key(account, expected_key, DexError::InvalidProgramConfigAccount)?;
+        if account.data_is_empty() {
+            return Ok(None);
+        }
+        check_account_owner(account, program_id, DexError::InvalidProgramConfigAccount)?;
+        let config = Self::deserialize(&mut &account.data.borrow()[..])
+            .map_err(|_| DexError::InvalidProgramConfigAccount)?;
+        if config.tag != AccountTag::ProgramConfig as u64 {
+            msg!("The program config account contains another state!");
+            return Err(DexError::InvalidProgramConfigAccount.into());
+        }
+        Ok(Some(config))
+    }
+}
@@ -0,0 +1,33 @@
+//! The source of the current time for the program's time-dependent logic.
+//!
+//! Order expiry, the TWAP accumulator, heartbeats, rolling volumes and rebate boosts are all computed from a unix
+//! timestamp which the processors read from a `TimeProvider`. On chain, this is the Clock sysvar, while tests can inject
+//! a fixed timestamp without running a validator.
+use solana_program::{clock::Clock, program_error::ProgramError, sysvar::Sysvar};
+
+/// A source of the current unix timestamp
+pub trait TimeProvider {
+    /// The current unix timestamp, in seconds
+    fn now(&self) -> Result<i64, ProgramError>;
+}
+
+/// Reads the current time from the Clock sysvar. This is the time provider of every instruction.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SysvarClock;
+
+impl TimeProvider for SysvarClock {
+    #[inline(always)]
+    fn now(&self) -> Result<i64, ProgramError> {
+        Ok(Clock::get()?.unix_timestamp)
+    }
+}
+
+/// Always returns the same timestamp, which makes time-dependent logic deterministic in tests
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedTime(pub i64);
+
+impl TimeProvider for FixedTime {
+    fn now(&self) -> Result<i64, ProgramError> {
+        Ok(self.0)
+    }
+}
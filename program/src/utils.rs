@@ -1,12 +1,21 @@
-use crate::error::DexError;
+use crate::{
+    error::DexError,
+    state::{CallBackInfo, DexState},
+};
+use asset_agnostic_orderbook::state::{critbit::Slab, event_queue::EventQueue, AccountTag, Side};
 use mpl_token_metadata::{
     pda::find_metadata_account,
     state::{Creator, Metadata, TokenMetadataAccount},
 };
+use pyth_sdk_solana::load_price_feed_from_account_info;
 use solana_program::{
     account_info::AccountInfo, entrypoint::ProgramResult, msg, program_error::ProgramError,
     pubkey::Pubkey,
 };
+use spl_token_2022::{
+    extension::{transfer_fee::TransferFeeConfig, ExtensionType, StateWithExtensions},
+    state::Mint,
+};
 
 // Safety verification functions
 pub fn check_account_key(
@@ -38,6 +47,109 @@ pub fn check_signer(account: &AccountInfo) -> ProgramResult {
     Ok(())
 }
 
+/// Checks that the given account is either the SPL token program or the SPL Token-2022 program
+pub fn check_token_program(account: &AccountInfo) -> Result<(), DexError> {
+    if account.key != &spl_token::ID && account.key != &spl_token_2022::ID {
+        return Err(DexError::InvalidSplTokenProgram);
+    }
+    Ok(())
+}
+
+/// Checks that the given account is owned by either the SPL token program or the SPL Token-2022 program
+pub fn check_token_program_owner(account: &AccountInfo, error: DexError) -> Result<(), DexError> {
+    if account.owner != &spl_token::ID && account.owner != &spl_token_2022::ID {
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Returns the decimals of a mint of either token program, along with the fee which the mint withholds on a transfer of
+/// the given amount during the given epoch.
+///
+/// Only the Token-2022 transfer fee and mint close authority extensions are supported, mints with any other extension
+/// are rejected.
+pub(crate) fn get_mint_transfer_info(
+    mint: &AccountInfo,
+    amount: u64,
+    epoch: u64,
+) -> Result<(u8, u64), ProgramError> {
+    let data = mint.data.borrow();
+    let state = StateWithExtensions::<Mint>::unpack(&data)?;
+    let extension_types = state
+        .get_extension_types()
+        .map_err(|_| DexError::UnsupportedMintExtension)?;
+    for extension_type in extension_types {
+        match extension_type {
+            ExtensionType::TransferFeeConfig | ExtensionType::MintCloseAuthority => (),
+            _ => {
+                msg!("The {:?} mint extension is not supported", extension_type);
+                return Err(DexError::UnsupportedMintExtension.into());
+            }
+        }
+    }
+    let fee = match state.get_extension::<TransferFeeConfig>() {
+        Ok(transfer_fee_config) => transfer_fee_config
+            .calculate_epoch_fee(epoch, amount)
+            .ok_or(DexError::NumericalOverflow)?,
+        Err(_) => 0,
+    };
+    Ok((state.base.decimals, fee))
+}
+
+/// Returns the decimals of a mint along with the amount to transfer for the destination to receive at least the given
+/// net amount during the given epoch, and the fee which the mint withholds on that transfer.
+pub(crate) fn get_mint_gross_transfer_info(
+    mint: &AccountInfo,
+    net_amount: u64,
+    epoch: u64,
+) -> Result<(u8, u64, u64), ProgramError> {
+    let (decimals, mut transfer_fee) = get_mint_transfer_info(mint, net_amount, epoch)?;
+    let mut gross_amount = net_amount;
+    // The fee only grows with the amount and is capped, so this converges within a few iterations
+    while gross_amount - transfer_fee < net_amount {
+        gross_amount = net_amount
+            .checked_add(transfer_fee)
+            .ok_or(DexError::NumericalOverflow)?;
+        transfer_fee = get_mint_transfer_info(mint, gross_amount, epoch)?.1;
+    }
+    Ok((decimals, gross_amount, transfer_fee))
+}
+
+/// Returns the current price of a Pyth price account converted to the market's FP32 price scale.
+///
+/// `None` is returned when the oracle isn't currently trading or when its price is older than the market's maximum
+/// staleness.
+pub(crate) fn get_oracle_price(
+    dex_state: &DexState,
+    oracle: &AccountInfo,
+    current_timestamp: i64,
+) -> Result<Option<u64>, ProgramError> {
+    let price_feed = load_price_feed_from_account_info(oracle).map_err(|_| {
+        msg!("Failed to load the oracle price account");
+        DexError::InvalidOracleAccount
+    })?;
+    if current_timestamp.saturating_sub(price_feed.publish_time)
+        > dex_state.max_oracle_staleness as i64
+    {
+        msg!("The oracle price is stale, limit prices are not checked");
+        return Ok(None);
+    }
+    let price = match price_feed.get_current_price() {
+        Some(p) if p.price > 0 => p,
+        _ => return Ok(None),
+    };
+    Ok(dex_state.convert_oracle_price(price.price as u64, price.expo))
+}
+
+/// Unpacks a token account of either token program. Token-2022 extensions are ignored.
+pub(crate) fn unpack_token_account(
+    account: &AccountInfo,
+) -> Result<spl_token_2022::state::Account, ProgramError> {
+    let data = account.data.borrow();
+    let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&data)?;
+    Ok(state.base)
+}
+
 pub(crate) const FP_32_ONE: u64 = 1 << 32;
 
 /// a is fp0, b is fp32 and result is a/b fp0
@@ -54,6 +166,14 @@ pub(crate) fn fp32_mul(a: u64, b_fp32: u64) -> Option<u64> {
         .and_then(|e| safe_downcast(e >> 32))
 }
 
+/// a is fp0, b is fp32 and result is a*b fp0, rounded to the nearest integer with halves rounded up
+pub(crate) fn fp32_mul_half_up(a: u64, b_fp32: u64) -> Option<u64> {
+    (a as u128)
+        .checked_mul(b_fp32 as u128)
+        .and_then(|e| e.checked_add(1 << 31))
+        .and_then(|e| safe_downcast(e >> 32))
+}
+
 fn safe_downcast(n: u128) -> Option<u64> {
     static BOUND: u128 = u64::MAX as u128;
     if n > BOUND {
@@ -63,6 +183,238 @@ fn safe_downcast(n: u128) -> Option<u64> {
     }
 }
 
+/// Returns the limit price (as a scaled FP32) of the best order resting on the given side of the book.
+///
+/// For bids this is the highest price, for asks the lowest. `None` is returned when the side is empty.
+pub(crate) fn get_best_price(
+    slab_account: &AccountInfo,
+    side: Side,
+) -> Result<Option<u64>, ProgramError> {
+    let mut slab_guard = slab_account.data.borrow_mut();
+    let expected_tag = match side {
+        Side::Bid => AccountTag::Bids,
+        Side::Ask => AccountTag::Asks,
+    };
+    let slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, expected_tag)?;
+    let best_handle = match side {
+        Side::Bid => slab.find_max(),
+        Side::Ask => slab.find_min(),
+    };
+    Ok(best_handle.map(|h| slab.leaf_nodes[h as usize].price()))
+}
+
+/// Returns the number of events which can still be pushed to the event queue before it is full
+pub(crate) fn get_event_queue_free_slots(
+    event_queue_account: &AccountInfo,
+) -> Result<u64, ProgramError> {
+    let mut event_queue_guard = event_queue_account.data.borrow_mut();
+    let event_queue =
+        EventQueue::<CallBackInfo>::from_buffer(&mut event_queue_guard, AccountTag::EventQueue)?;
+    Ok((event_queue.events.len() as u64).saturating_sub(event_queue.len() as u64))
+}
+
+/// Returns the number of events waiting in the event queue
+pub(crate) fn get_event_queue_len(event_queue_account: &AccountInfo) -> Result<u64, ProgramError> {
+    let mut event_queue_guard = event_queue_account.data.borrow_mut();
+    let event_queue =
+        EventQueue::<CallBackInfo>::from_buffer(&mut event_queue_guard, AccountTag::EventQueue)?;
+    Ok(event_queue.len() as u64)
+}
+
+/// Returns the index of the event queue's head along with the queue's capacity
+pub(crate) fn get_event_queue_head(
+    event_queue_account: &AccountInfo,
+) -> Result<(u64, u64), ProgramError> {
+    let mut event_queue_guard = event_queue_account.data.borrow_mut();
+    let event_queue =
+        EventQueue::<CallBackInfo>::from_buffer(&mut event_queue_guard, AccountTag::EventQueue)?;
+    Ok((event_queue.header.head, event_queue.events.len() as u64))
+}
+
+/// Returns the callback information attached to a resting order, or `None` if the order isn't on the book.
+pub(crate) fn get_order_callback_info(
+    slab_account: &AccountInfo,
+    side: Side,
+    order_id: u128,
+) -> Result<Option<CallBackInfo>, ProgramError> {
+    let mut slab_guard = slab_account.data.borrow_mut();
+    let expected_tag = match side {
+        Side::Bid => AccountTag::Bids,
+        Side::Ask => AccountTag::Asks,
+    };
+    let slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, expected_tag)?;
+    match slab.find_by_key(order_id) {
+        Some(h) => Ok(Some(CallBackInfo::read(&slab.callback_infos[h as usize])?)),
+        None => Ok(None),
+    }
+}
+
+/// Whether a quote budget (scaled) can't buy a single base unit (scaled) at the given price anymore.
+///
+/// A bid is considered filled once its base quantity is matched or its quote budget is exhausted against the best
+/// crossing ask, both when simulating a match and when checking the actual match of a fill-or-kill order.
+pub(crate) fn is_quote_budget_exhausted(quote_left: u64, price: u64) -> bool {
+    fp32_div(quote_left, price).unwrap_or(u64::MAX) == 0
+}
+
+/// The outcome of a simulated match against one side of the book
+pub(crate) struct MatchSimulation {
+    /// The base quantity which would be matched (scaled)
+    pub base_qty: u64,
+    /// The quote quantity which would be matched (scaled), fees excluded
+    pub quote_qty: u64,
+    /// Whether matching would stop because the base quantity or quote budget was fully consumed
+    pub is_filled: bool,
+}
+
+/// Walks the maker side of the book as the orderbook matching engine would, without modifying it.
+///
+/// All quantities are expressed in scaled units. Self-trading is not taken into account, which means that the result
+/// can be optimistic for orders using `CancelProvide`.
+pub(crate) fn simulate_match(
+    maker_slab_account: &AccountInfo,
+    taker_side: Side,
+    limit_price: u64,
+    max_base_qty: u64,
+    max_quote_qty: u64,
+    match_limit: u64,
+) -> Result<MatchSimulation, ProgramError> {
+    let mut slab_guard = maker_slab_account.data.borrow_mut();
+    let (expected_tag, price_ascending) = match taker_side {
+        Side::Bid => (AccountTag::Asks, true),
+        Side::Ask => (AccountTag::Bids, false),
+    };
+    let slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, expected_tag)?;
+
+    let mut base_left = max_base_qty;
+    let mut quote_left = max_quote_qty;
+    let mut budget_exhausted = false;
+    let mut matches = 0;
+
+    for leaf in slab.into_iter(price_ascending) {
+        if base_left == 0 {
+            break;
+        }
+        let price = leaf.price();
+        let crosses = match taker_side {
+            Side::Bid => price <= limit_price,
+            Side::Ask => price >= limit_price,
+        };
+        if !crosses {
+            break;
+        }
+        if is_quote_budget_exhausted(quote_left, price) {
+            budget_exhausted = true;
+            break;
+        }
+        if matches == match_limit {
+            break;
+        }
+        let base_trade_qty = leaf
+            .base_quantity
+            .min(base_left)
+            .min(fp32_div(quote_left, price).unwrap_or(u64::MAX));
+        let quote_trade_qty = fp32_mul(base_trade_qty, price).ok_or(DexError::NumericalOverflow)?;
+        base_left -= base_trade_qty;
+        quote_left = quote_left.saturating_sub(quote_trade_qty);
+        matches += 1;
+    }
+
+    Ok(MatchSimulation {
+        base_qty: max_base_qty - base_left,
+        quote_qty: max_quote_qty - quote_left,
+        is_filled: base_left == 0 || budget_exhausted,
+    })
+}
+
+/// Finds the first order of the given user account which a taker would match against, without modifying the book.
+///
+/// Returns the order id and callback information of that order, along with the base quantity (scaled) resting in
+/// front of it, which the taker can match before reaching it.
+pub(crate) fn find_self_trade(
+    maker_slab_account: &AccountInfo,
+    taker_side: Side,
+    limit_price: u64,
+    match_limit: u64,
+    user_account: &Pubkey,
+) -> Result<Option<(u128, u64, CallBackInfo)>, ProgramError> {
+    let mut slab_guard = maker_slab_account.data.borrow_mut();
+    let (expected_tag, price_ascending) = match taker_side {
+        Side::Bid => (AccountTag::Asks, true),
+        Side::Ask => (AccountTag::Bids, false),
+    };
+    let slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, expected_tag)?;
+
+    let mut base_qty_ahead: u64 = 0;
+
+    for (matches, leaf) in slab.into_iter(price_ascending).enumerate() {
+        if matches as u64 == match_limit {
+            break;
+        }
+        let crosses = match taker_side {
+            Side::Bid => leaf.price() <= limit_price,
+            Side::Ask => leaf.price() >= limit_price,
+        };
+        if !crosses {
+            break;
+        }
+        let h = slab.find_by_key(leaf.key).unwrap();
+        let callback_info = CallBackInfo::read(&slab.callback_infos[h as usize])?;
+        if &callback_info.user_account == user_account {
+            return Ok(Some((leaf.key, base_qty_ahead, callback_info)));
+        }
+        base_qty_ahead = base_qty_ahead.saturating_add(leaf.base_quantity);
+    }
+
+    Ok(None)
+}
+
+/// Computes the quote quantity (scaled) which a taker would self-match under `DecrementTake`, walking the
+/// maker side of the book as the orderbook matching engine would, without modifying it.
+///
+/// The quote budget of bids is not taken into account, which means that the result can be pessimistic.
+pub(crate) fn simulate_self_match(
+    maker_slab_account: &AccountInfo,
+    taker_side: Side,
+    limit_price: u64,
+    max_base_qty: u64,
+    match_limit: u64,
+    user_account: &Pubkey,
+) -> Result<u64, ProgramError> {
+    let mut slab_guard = maker_slab_account.data.borrow_mut();
+    let (expected_tag, price_ascending) = match taker_side {
+        Side::Bid => (AccountTag::Asks, true),
+        Side::Ask => (AccountTag::Bids, false),
+    };
+    let slab = Slab::<CallBackInfo>::from_buffer(&mut slab_guard, expected_tag)?;
+
+    let mut base_left = max_base_qty;
+    let mut self_quote_qty: u64 = 0;
+
+    for (matches, leaf) in slab.into_iter(price_ascending).enumerate() {
+        if base_left == 0 || matches as u64 == match_limit {
+            break;
+        }
+        let crosses = match taker_side {
+            Side::Bid => leaf.price() <= limit_price,
+            Side::Ask => leaf.price() >= limit_price,
+        };
+        if !crosses {
+            break;
+        }
+        let base_trade_qty = leaf.base_quantity.min(base_left);
+        base_left -= base_trade_qty;
+        let h = slab.find_by_key(leaf.key).unwrap();
+        if &CallBackInfo::read(&slab.callback_infos[h as usize])?.user_account == user_account {
+            self_quote_qty = fp32_mul(base_trade_qty, leaf.price())
+                .and_then(|q| self_quote_qty.checked_add(q))
+                .ok_or(DexError::NumericalOverflow)?;
+        }
+    }
+
+    Ok(self_quote_qty)
+}
+
 pub fn check_metadata_account(account: &AccountInfo, mint: &Pubkey) -> ProgramResult {
     let expected = find_metadata_account(mint).0;
     check_account_key(account, &expected, DexError::InvalidMetadataKey)?;
@@ -0,0 +1,76 @@
+use borsh::BorshSerialize;
+use bytemuck::Zeroable;
+use dex_v4::error::DexError;
+use dex_v4::state::{AccessGrant, AccountTag, DexState, ACCESS_GRANT_LEN};
+use solana_program::{account_info::AccountInfo, pubkey::Pubkey};
+
+#[test]
+fn test_access_grant() {
+    let program_id = Pubkey::new_unique();
+    let market = Pubkey::new_unique();
+    let wallet = Pubkey::new_unique();
+    let mut dex_state = DexState::zeroed();
+
+    // Open markets don't need an access grant
+    dex_state
+        .check_access(&program_id, &market, &wallet, None)
+        .unwrap();
+
+    dex_state.access_authority = Pubkey::new_unique();
+    assert_eq!(
+        dex_state.check_access(&program_id, &market, &wallet, None),
+        Err(DexError::Unauthorized.into())
+    );
+
+    let grant = AccessGrant {
+        tag: AccountTag::AccessGrant as u64,
+        market,
+        wallet,
+    };
+    let mut data = grant.try_to_vec().unwrap();
+    assert_eq!(data.len(), ACCESS_GRANT_LEN);
+    let key = Pubkey::new_unique();
+    let mut lamports = 0;
+    let account = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &program_id,
+        false,
+        0,
+    );
+    dex_state
+        .check_access(&program_id, &market, &wallet, Some(&account))
+        .unwrap();
+
+    // The grant of another wallet or on another market is rejected
+    assert_eq!(
+        dex_state.check_access(&program_id, &market, &Pubkey::new_unique(), Some(&account)),
+        Err(DexError::Unauthorized.into())
+    );
+    assert_eq!(
+        dex_state.check_access(&program_id, &Pubkey::new_unique(), &wallet, Some(&account)),
+        Err(DexError::Unauthorized.into())
+    );
+
+    // As is a grant which isn't owned by the DEX program
+    let other_program_id = Pubkey::new_unique();
+    let mut other_lamports = 0;
+    let mut other_data = grant.try_to_vec().unwrap();
+    let foreign_account = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut other_lamports,
+        &mut other_data,
+        &other_program_id,
+        false,
+        0,
+    );
+    assert_eq!(
+        dex_state.check_access(&program_id, &market, &wallet, Some(&foreign_account)),
+        Err(DexError::Unauthorized.into())
+    );
+}
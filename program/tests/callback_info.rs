@@ -0,0 +1,99 @@
+use borsh::BorshSerialize;
+use bytemuck::bytes_of;
+use dex_v4::state::{CallBackInfo, LegacyCallBackInfo, CALLBACK_INFO_VERSION};
+use dex_v4::CALLBACK_INFO_LEN;
+use solana_program::pubkey::Pubkey;
+
+#[test]
+fn test_callback_info_layout() {
+    assert_eq!(CallBackInfo::LEN as u64, CALLBACK_INFO_LEN);
+    // The records of orderbooks created by the initial program version, which migrate_market moves over
+    assert_eq!(LegacyCallBackInfo::LEN, 33);
+
+    let callback_info = CallBackInfo {
+        user_account: Pubkey::new_unique(),
+        fee_tier: 2,
+        self_trade_behavior: 3,
+        version: CALLBACK_INFO_VERSION,
+        _padding: [0; 5],
+        expiry_timestamp: 1_700_000_000,
+        display_base_qty: 1_000,
+        hidden_base_qty: 9_000,
+    };
+    // The borsh encoding of the record matches its memory layout
+    assert_eq!(
+        callback_info.try_to_vec().unwrap(),
+        bytes_of(&callback_info)
+    );
+}
+
+#[test]
+fn test_callback_info_legacy_layout() {
+    let legacy = LegacyCallBackInfo {
+        user_account: Pubkey::new_unique(),
+        fee_tier: 3,
+    };
+    let data = bytes_of(&legacy);
+    assert_eq!(data.len(), LegacyCallBackInfo::LEN);
+
+    let callback_info = CallBackInfo::from_versioned_bytes(data).unwrap();
+    assert_eq!(callback_info.user_account, legacy.user_account);
+    assert_eq!(callback_info.fee_tier, 3);
+    assert_eq!(callback_info.self_trade_behavior, 0);
+    assert_eq!(callback_info.version, 0);
+    assert_eq!(callback_info.expiry_timestamp, 0);
+    assert_eq!(callback_info.display_base_qty, 0);
+    assert_eq!(callback_info.hidden_base_qty, 0);
+}
+
+#[test]
+fn test_callback_info_current_layout() {
+    let callback_info = CallBackInfo {
+        user_account: Pubkey::new_unique(),
+        fee_tier: 2,
+        self_trade_behavior: 3,
+        version: CALLBACK_INFO_VERSION,
+        _padding: [0; 5],
+        expiry_timestamp: 1_700_000_000,
+        display_base_qty: 1_000,
+        hidden_base_qty: 9_000,
+    };
+    assert_eq!(
+        CallBackInfo::from_versioned_bytes(bytes_of(&callback_info)).unwrap(),
+        callback_info
+    );
+    assert_eq!(
+        CallBackInfo::from_versioned_bytes(&callback_info.try_to_vec().unwrap()).unwrap(),
+        callback_info
+    );
+    assert_eq!(CallBackInfo::read(&callback_info).unwrap(), callback_info);
+
+    // Records written before the version field was introduced
+    let unversioned = CallBackInfo {
+        version: 0,
+        ..callback_info
+    };
+    assert_eq!(
+        CallBackInfo::from_versioned_bytes(bytes_of(&unversioned)).unwrap(),
+        unversioned
+    );
+    assert_eq!(CallBackInfo::read(&unversioned).unwrap(), unversioned);
+}
+
+#[test]
+fn test_callback_info_invalid_layouts() {
+    let callback_info = CallBackInfo {
+        user_account: Pubkey::new_unique(),
+        fee_tier: 0,
+        self_trade_behavior: 0,
+        version: CALLBACK_INFO_VERSION + 1,
+        _padding: [0; 5],
+        expiry_timestamp: 0,
+        display_base_qty: 0,
+        hidden_base_qty: 0,
+    };
+    assert!(CallBackInfo::from_versioned_bytes(bytes_of(&callback_info)).is_err());
+    assert!(CallBackInfo::read(&callback_info).is_err());
+    assert!(CallBackInfo::from_versioned_bytes(&[0; CallBackInfo::LEN - 1]).is_err());
+    assert!(CallBackInfo::from_versioned_bytes(&[0; LegacyCallBackInfo::LEN + 1]).is_err());
+}
@@ -5,7 +5,8 @@ use asset_agnostic_orderbook::state::AccountTag;
 use bytemuck::try_from_bytes;
 use dex_v4::instruction_auto::initialize_account;
 use dex_v4::instruction_auto::new_order;
-use dex_v4::state::{DexState, DEX_STATE_LEN};
+use dex_v4::instruction_auto::set_program_config::get_program_config_address;
+use dex_v4::state::{market_signer, DexState, DEX_STATE_LEN};
 use mpl_token_metadata::pda::find_metadata_account;
 use serum_dex::state::gen_vault_signer_key;
 use solana_program::instruction::Instruction;
@@ -89,6 +90,23 @@ pub async fn create_aob_dex(
     .await
     .unwrap();
 
+    // Create the trade log account
+    let trade_log_account = Keypair::new();
+    let create_trade_log_account_instruction = create_account(
+        &pgr_test_ctx.payer.pubkey(),
+        &trade_log_account.pubkey(),
+        1_000_000_000_000,
+        1_000_000,
+        &dex_v4::ID,
+    );
+    sign_send_instructions(
+        &mut pgr_test_ctx,
+        vec![create_trade_log_account_instruction],
+        vec![&trade_log_account],
+    )
+    .await
+    .unwrap();
+
     // Define the market signer
     let (market_signer, signer_nonce) =
         Pubkey::find_program_address(&[&market_account.pubkey().to_bytes()], &dex_v4::ID);
@@ -118,6 +136,13 @@ pub async fn create_aob_dex(
             asks: &aaob_accounts.asks,
             bids: &aaob_accounts.bids,
             token_metadata: &find_metadata_account(&base_mint_key).0,
+            trade_log: &trade_log_account.pubkey(),
+            base_mint: &base_mint_key,
+            quote_mint: &quote_mint_key,
+            program_config: &get_program_config_address(&dex_v4::ID).0,
+            system_program: None,
+            creation_fee_payer: None,
+            creation_fee_treasury: None,
         },
         dex_v4::instruction_auto::create_market::Params {
             signer_nonce: signer_nonce as u64,
@@ -125,6 +150,9 @@ pub async fn create_aob_dex(
             tick_size: 1,
             base_currency_multiplier: 1,
             quote_currency_multiplier: 1,
+            base_taker_rate: 0,
+            base_maker_rebate: 0,
+            base_lot_size: 0,
         },
     );
     sign_send_instructions(&mut pgr_test_ctx, vec![create_market_instruction], vec![])
@@ -171,6 +199,7 @@ pub async fn create_aob_dex(
             initialize_account::Params {
                 market: market_account.pubkey(),
                 max_orders: 100,
+                referrer: Pubkey::default(),
             },
         );
         sign_send_instructions(
@@ -405,6 +434,12 @@ pub async fn aob_dex_new_order(
     max_quote_qty: u64,
     user_account_index: usize,
 ) {
+    let market_signer = market_signer(
+        &dex_test_ctx.dex_market_key,
+        dex_test_ctx.dex_market.signer_nonce,
+        &dex_v4::ID,
+    )
+    .unwrap();
     // New Order on AOB DEX
     let new_order_instruction = new_order(
         dex_v4::ID,
@@ -418,6 +453,9 @@ pub async fn aob_dex_new_order(
             asks: &dex_test_ctx.aob_market.asks,
             base_vault: &dex_test_ctx.dex_market.base_vault,
             quote_vault: &dex_test_ctx.dex_market.quote_vault,
+            market_signer: &market_signer,
+            base_mint: &dex_test_ctx.dex_market.base_mint,
+            quote_mint: &dex_test_ctx.dex_market.quote_mint,
             user: &dex_test_ctx.user_account_keys[user_account_index],
             user_token_account: &match side {
                 asset_agnostic_orderbook::state::Side::Ask => {
@@ -429,6 +467,10 @@ pub async fn aob_dex_new_order(
             },
             user_owner: &dex_test_ctx.user_owners[user_account_index].pubkey(),
             discount_token_account: None,
+            oracle: None,
+            liquidity_metrics: None,
+            market_stats: None,
+            access_grant: None,
             fee_referral_account: None,
         },
         new_order::Params {
@@ -440,12 +482,23 @@ pub async fn aob_dex_new_order(
             self_trade_behavior: asset_agnostic_orderbook::state::SelfTradeBehavior::DecrementTake
                 as u8,
             match_limit: 10,
+            expiry_timestamp: 0,
+            display_base_qty: 0,
+            max_taker_fee: 0,
+            min_base_to_fill: 0,
             #[cfg(not(any(feature = "aarch64-test", target_arch = "aarch64")))]
             client_order_id: 0,
             #[cfg(any(feature = "aarch64-test", target_arch = "aarch64"))]
             client_order_id: bytemuck::cast(0u128),
             has_discount_token_account: false as u8,
-            _padding: 0,
+            reduce_only: 0,
+            wrap_native_quote: 0,
+            has_oracle_account: 0,
+            has_liquidity_metrics_account: 0,
+            has_market_stats_account: 0,
+            size_in_quote: 0,
+            has_access_grant_account: 0,
+            _padding: [0; 5],
         },
     );
     sign_send_instructions(
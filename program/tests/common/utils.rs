@@ -1,6 +1,8 @@
 use asset_agnostic_orderbook::state::event_queue::EventQueue;
 use asset_agnostic_orderbook::state::market_state::MarketState;
-use dex_v4::state::CallBackInfo;
+use bytemuck::cast_slice_mut;
+use dex_v4::instruction_auto::create_account::user_account_size;
+use dex_v4::state::{AccountTag, CallBackInfo, UserAccount};
 use solana_program::instruction::Instruction;
 use solana_program::program_pack::Pack;
 use solana_program::pubkey::Pubkey;
@@ -172,3 +174,39 @@ pub async fn create_aob_market_and_accounts(
         bids: bids_account.pubkey(),
     }
 }
+
+/// Zeroed account data for the state objects which are loaded from a buffer.
+///
+/// The data is backed by u64 words, which gives it the alignment of on-chain account data.
+pub struct AccountBuffer {
+    words: Vec<u64>,
+}
+
+impl AccountBuffer {
+    pub fn new(len: usize) -> Self {
+        assert_eq!(
+            len % 8,
+            0,
+            "The account length should be a multiple of 8 bytes"
+        );
+        Self {
+            words: vec![0; len / 8],
+        }
+    }
+
+    /// The data of a user account which can hold the given number of orders
+    pub fn for_user_account(max_orders: usize) -> Self {
+        Self::new(user_account_size(max_orders as u32))
+    }
+
+    pub fn bytes(&mut self) -> &mut [u8] {
+        cast_slice_mut(&mut self.words)
+    }
+
+    /// Loads the data as a user account, tagged as such
+    pub fn user_account(&mut self) -> UserAccount<'_> {
+        let mut user_account = UserAccount::from_buffer_unchecked(self.bytes()).unwrap();
+        user_account.header.tag = AccountTag::UserAccount as u64;
+        user_account
+    }
+}
@@ -0,0 +1,23 @@
+use dex_v4::instruction_auto::consume_events::fifo_batch_len;
+use solana_program::pubkey::Pubkey;
+
+#[test]
+fn test_interleaved_fills_are_consumed_in_order() {
+    let alice = Pubkey::new_unique();
+    let bob = Pubkey::new_unique();
+    // The makers credited by the queued fills, from the head of the queue
+    let event_user_accounts = [alice, bob, alice, bob, alice];
+
+    let mut both = [alice, bob];
+    both.sort();
+    assert_eq!(fifo_batch_len(&event_user_accounts, &both), 5);
+
+    // A crank which only provides one of the makers stops at the first fill of the other one, it cannot cherry-pick
+    // the later fills of the maker it provided
+    assert_eq!(fifo_batch_len(&event_user_accounts, &[alice]), 1);
+    assert_eq!(fifo_batch_len(&event_user_accounts, &[bob]), 0);
+    assert_eq!(fifo_batch_len(&event_user_accounts, &[]), 0);
+
+    // The batch size is applied to the queued events beforehand
+    assert_eq!(fifo_batch_len(&event_user_accounts[..3], &both), 3);
+}
@@ -0,0 +1,57 @@
+use borsh::BorshSerialize;
+use dex_v4::instruction_auto::get_depth::{DepthLevel, OrderbookDepth, MAX_DEPTH_LEVELS};
+
+#[test]
+fn test_depth_aggregates_price_levels() {
+    let asks = vec![(10, 1), (10, 2), (11, 5), (12, 1), (12, 1), (13, 7)];
+    let depth = DepthLevel::aggregate(asks.into_iter(), 3);
+    assert_eq!(
+        depth,
+        vec![
+            DepthLevel {
+                price: 10,
+                base_qty: 3
+            },
+            DepthLevel {
+                price: 11,
+                base_qty: 5
+            },
+            DepthLevel {
+                price: 12,
+                base_qty: 2
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_depth_pads_empty_levels() {
+    let bids = vec![(20, 4), (19, 1), (19, 1)];
+    let depth = DepthLevel::aggregate(bids.into_iter(), 4);
+    assert_eq!(depth.len(), 4);
+    assert_eq!(
+        depth[1],
+        DepthLevel {
+            price: 19,
+            base_qty: 2
+        }
+    );
+    assert_eq!(depth[2], DepthLevel::default());
+    assert_eq!(depth[3], DepthLevel::default());
+
+    assert!(DepthLevel::aggregate(std::iter::empty(), 0).is_empty());
+    assert_eq!(
+        DepthLevel::aggregate(std::iter::empty(), 2),
+        vec![DepthLevel::default(); 2]
+    );
+}
+
+#[test]
+fn test_depth_fits_return_data() {
+    let levels = MAX_DEPTH_LEVELS as usize;
+    let depth = OrderbookDepth {
+        bids: vec![DepthLevel::default(); levels],
+        asks: vec![DepthLevel::default(); levels],
+    };
+    assert!(depth.try_to_vec().unwrap().len() <= solana_program::program::MAX_RETURN_DATA);
+}
@@ -0,0 +1,41 @@
+use bytemuck::cast_slice_mut;
+use dex_v4::error::DexError;
+use dex_v4::state::AccountTag;
+use dex_v4::state::FeeRecipient;
+use dex_v4::state::FeeRecipients;
+use dex_v4::state::FEE_RECIPIENTS_LEN;
+use dex_v4::state::MAX_FEE_RECIPIENTS;
+use solana_program::pubkey::Pubkey;
+
+fn recipient(weight_bps: u16) -> FeeRecipient {
+    FeeRecipient {
+        token_account: Pubkey::new_unique(),
+        weight_bps,
+        _padding: [0; 6],
+    }
+}
+
+#[test]
+fn test_fee_recipients_split() {
+    let mut words = vec![0u64; FEE_RECIPIENTS_LEN / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+    let mut fee_recipients = FeeRecipients::from_buffer_unchecked(buf).unwrap();
+    fee_recipients.header.tag = AccountTag::FeeRecipients as u64;
+
+    let res = fee_recipients.set_recipients(&[]);
+    assert!(matches!(res, Err(DexError::InvalidFeeRecipients)));
+    let res = fee_recipients.set_recipients(&[recipient(5_000), recipient(4_000)]);
+    assert!(matches!(res, Err(DexError::InvalidFeeRecipients)));
+    let res = fee_recipients.set_recipients(&vec![recipient(0); MAX_FEE_RECIPIENTS + 1]);
+    assert!(matches!(res, Err(DexError::InvalidFeeRecipients)));
+    assert!(fee_recipients.recipients().is_empty());
+
+    let recipients = [recipient(5_000), recipient(3_000), recipient(2_000)];
+    fee_recipients.set_recipients(&recipients).unwrap();
+    assert_eq!(fee_recipients.recipients(), &recipients);
+
+    assert_eq!(fee_recipients.split(1_000), vec![500, 300, 200]);
+    // The rounding remainder goes to the last recipient
+    assert_eq!(fee_recipients.split(7), vec![3, 2, 2]);
+    assert_eq!(fee_recipients.split(u64::MAX).iter().sum::<u64>(), u64::MAX);
+}
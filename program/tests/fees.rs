@@ -0,0 +1,315 @@
+use bytemuck::Zeroable;
+use dex_v4::error::DexError;
+use dex_v4::state::{DexState, FeeRounding, FeeTier};
+use dex_v4::REFERRAL_MASK;
+use rand::Rng;
+
+const FEE_TIERS: [FeeTier; 8] = [
+    FeeTier::Base,
+    FeeTier::Srm2,
+    FeeTier::Srm3,
+    FeeTier::Srm4,
+    FeeTier::Srm5,
+    FeeTier::Srm6,
+    FeeTier::MSrm,
+    FeeTier::Stable,
+];
+
+fn expected_fp32_mul(a: u64, b_fp32: u64) -> Option<u64> {
+    let r = ((a as u128) * (b_fp32 as u128)) >> 32;
+    (r <= u64::MAX as u128).then(|| r as u64)
+}
+
+fn random_amount(rng: &mut impl Rng) -> u64 {
+    // Amounts are spread over all orders of magnitude rather than clustering near u64::MAX
+    rng.gen::<u64>() >> rng.gen_range(0..64)
+}
+
+#[test]
+fn test_fee_math_never_wraps() {
+    let mut rng = rand::thread_rng();
+    for _ in 0..100_000 {
+        let mut dex_state = DexState::zeroed();
+        dex_state.base_taker_rate = random_amount(&mut rng);
+        dex_state.base_maker_rebate = random_amount(&mut rng);
+        dex_state.referral_fee_share_bps = rng.gen_range(0..=10_000);
+        let quote_qty = random_amount(&mut rng);
+
+        for fee_tier in FEE_TIERS {
+            let taker_rate = fee_tier.taker_rate(&dex_state);
+            match fee_tier.taker_fee(&dex_state, quote_qty) {
+                Ok(fee) => assert_eq!(Some(fee), expected_fp32_mul(quote_qty, taker_rate)),
+                Err(e) => {
+                    assert!(matches!(e, DexError::NumericalOverflow));
+                    assert_eq!(expected_fp32_mul(quote_qty, taker_rate), None);
+                }
+            }
+
+            let maker_rate = fee_tier.maker_rate(&dex_state);
+            match fee_tier.maker_rebate(&dex_state, quote_qty) {
+                Ok(rebate) => assert_eq!(Some(rebate), expected_fp32_mul(quote_qty, maker_rate)),
+                Err(e) => {
+                    assert!(matches!(e, DexError::NumericalOverflow));
+                    assert_eq!(expected_fp32_mul(quote_qty, maker_rate), None);
+                }
+            }
+
+            let referral_rate = fee_tier.referral_rate(&dex_state);
+            assert!(referral_rate <= taker_rate);
+            match fee_tier.referral_fee(&dex_state, quote_qty) {
+                Ok(fee) => assert_eq!(Some(fee), expected_fp32_mul(quote_qty, referral_rate)),
+                Err(e) => {
+                    assert!(matches!(e, DexError::NumericalOverflow));
+                    assert_eq!(expected_fp32_mul(quote_qty, referral_rate), None);
+                }
+            }
+
+            // Removing the taker fee can never increase the quantity
+            match fee_tier.remove_taker_fee(&dex_state, quote_qty) {
+                Ok(qty) => assert!(qty <= quote_qty),
+                Err(e) => {
+                    assert!(matches!(e, DexError::NumericalOverflow));
+                    assert!(taker_rate.checked_add(1 << 32).is_none());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_maker_rebate_boost() {
+    let mut dex_state = DexState::zeroed();
+    dex_state.rebate_boost_rate = 1 << 31;
+    dex_state.rebate_boost_end = 1_000;
+    dex_state.accumulated_fees = 300;
+
+    assert_eq!(
+        FeeTier::Base
+            .maker_rebate_boost(&dex_state, 400, 999)
+            .unwrap(),
+        200
+    );
+    // The boost is capped by the accumulated fees which fund it
+    assert_eq!(
+        FeeTier::Base
+            .maker_rebate_boost(&dex_state, 1_000, 999)
+            .unwrap(),
+        300
+    );
+    // The boost expires on its own
+    assert_eq!(
+        FeeTier::Base
+            .maker_rebate_boost(&dex_state, 400, 1_000)
+            .unwrap(),
+        0
+    );
+}
+
+#[test]
+fn test_exact_taker_fees() {
+    let mut dex_state = DexState::zeroed();
+    // A 1% taker rate, which isn't exactly representable as an FP32
+    dex_state.base_taker_rate = (1 << 32) / 100;
+
+    // Fees are rounded down
+    assert_eq!(
+        FeeTier::Base.taker_fee(&dex_state, 1_000_000).unwrap(),
+        9_999
+    );
+    assert_eq!(FeeTier::MSrm.taker_rate(&dex_state), 32_212_254);
+    assert_eq!(
+        FeeTier::MSrm.taker_fee(&dex_state, 1_000_000).unwrap(),
+        7_499
+    );
+
+    assert_eq!(
+        FeeTier::from_srm_and_msrm_balances(&dex_state, 0, 1),
+        FeeTier::MSrm
+    );
+    assert_eq!(
+        FeeTier::from_srm_and_msrm_balances(&dex_state, 100_000_000, 0),
+        FeeTier::Srm2
+    );
+    assert_eq!(FeeTier::from_u8(6 | REFERRAL_MASK), (FeeTier::MSrm, true));
+    assert_eq!(FeeTier::from_u8(0), (FeeTier::Base, false));
+}
+
+#[test]
+fn test_fee_rounding_boundaries() {
+    let mut dex_state = DexState::zeroed();
+    // A 50% taker rate, so that odd quantities land exactly on half a unit
+    dex_state.base_taker_rate = 1 << 31;
+    dex_state.base_maker_rebate = 1 << 31;
+    assert_eq!(dex_state.fee_rounding(), FeeRounding::Down);
+
+    assert_eq!(FeeTier::Base.taker_fee(&dex_state, 1).unwrap(), 0);
+    assert_eq!(FeeTier::Base.taker_fee(&dex_state, 3).unwrap(), 1);
+
+    dex_state.fee_rounding = FeeRounding::HalfUp as u8;
+    assert_eq!(FeeTier::Base.taker_fee(&dex_state, 1).unwrap(), 1);
+    assert_eq!(FeeTier::Base.taker_fee(&dex_state, 3).unwrap(), 2);
+    assert_eq!(FeeTier::Base.taker_fee(&dex_state, 4).unwrap(), 2);
+    // Maker rebates are always rounded down
+    assert_eq!(FeeTier::Base.maker_rebate(&dex_state, 1).unwrap(), 0);
+    assert_eq!(FeeTier::Base.maker_rebate(&dex_state, 3).unwrap(), 1);
+
+    // Just below and just above half a unit
+    dex_state.base_taker_rate = (1 << 31) - 1;
+    assert_eq!(FeeTier::Base.taker_fee(&dex_state, 1).unwrap(), 0);
+    dex_state.base_taker_rate = (1 << 31) + 1;
+    assert_eq!(FeeTier::Base.taker_fee(&dex_state, 1).unwrap(), 1);
+
+    // Unknown rounding modes fall back to rounding down
+    dex_state.fee_rounding = 2;
+    assert_eq!(dex_state.fee_rounding(), FeeRounding::Down);
+    assert_eq!(FeeTier::Base.taker_fee(&dex_state, 1).unwrap(), 0);
+}
+
+#[test]
+fn test_fee_rounding_never_pays_out_more_than_collected() {
+    let mut rng = rand::thread_rng();
+    for _ in 0..100_000 {
+        let mut dex_state = DexState::zeroed();
+        dex_state.base_taker_rate = rng.gen_range(0..=1 << 32);
+        dex_state.base_maker_rebate = rng.gen_range(0..=dex_state.base_taker_rate);
+        dex_state.referral_fee_share_bps = rng.gen_range(0..=10_000);
+        dex_state.fee_rounding = rng.gen_range(0..=1);
+        let quote_qty = rng.gen::<u32>() as u64;
+
+        let fee_tier = FeeTier::Base;
+        let taker_fee = fee_tier.taker_fee(&dex_state, quote_qty).unwrap();
+        let maker_rebate = fee_tier.maker_rebate(&dex_state, quote_qty).unwrap();
+        let referral_fee = fee_tier.referral_fee(&dex_state, quote_qty).unwrap();
+        assert!(maker_rebate + referral_fee <= taker_fee);
+
+        // A bid never spends more than the quote amount it was given
+        let matched_qty = fee_tier.remove_taker_fee(&dex_state, quote_qty).unwrap();
+        assert!(matched_qty + fee_tier.taker_fee(&dex_state, matched_qty).unwrap() <= quote_qty);
+    }
+}
+
+#[test]
+fn test_fee_rounding_multi_fill_credits() {
+    let mut dex_state = DexState::zeroed();
+    // A 50% taker rate, so that each fill of 3 lands exactly on one and a half unit
+    dex_state.base_taker_rate = 1 << 31;
+    dex_state.fee_rounding = FeeRounding::HalfUp as u8;
+
+    let fills = [3u64; 3];
+    let charged = FeeTier::Base
+        .taker_fee(&dex_state, fills.iter().sum())
+        .unwrap();
+    assert_eq!(charged, 5);
+    // Rounding each fill half up would credit 6
+    assert_eq!(FeeTier::Base.taker_fee(&dex_state, 3).unwrap(), 2);
+    let credited: u64 = fills
+        .iter()
+        .map(|q| FeeTier::Base.taker_fee_credit(&dex_state, *q).unwrap())
+        .sum();
+    assert_eq!(credited, 3);
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..10_000 {
+        dex_state.base_taker_rate = rng.gen_range(0..=1 << 32);
+        dex_state.fee_rounding = rng.gen_range(0..=1);
+        let fills: Vec<u64> = (0..rng.gen_range(1..10))
+            .map(|_| rng.gen::<u32>() as u64)
+            .collect();
+        let charged = FeeTier::Base
+            .taker_fee(&dex_state, fills.iter().sum())
+            .unwrap();
+        let credited: u64 = fills
+            .iter()
+            .map(|q| FeeTier::Base.taker_fee_credit(&dex_state, *q).unwrap())
+            .sum();
+        assert!(credited <= charged);
+    }
+}
+
+#[test]
+fn test_fee_rounding_multi_fill_referral_credits() {
+    let mut dex_state = DexState::zeroed();
+    // A 50% taker rate of which 70% goes to the referrer, which is a 35% referral rate
+    dex_state.base_taker_rate = 1 << 31;
+    dex_state.referral_fee_share_bps = 7_000;
+    dex_state.fee_rounding = FeeRounding::HalfUp as u8;
+
+    let fills = [2u64; 2];
+    let quote_qty = fills.iter().sum();
+    let charged = FeeTier::Base.taker_fee(&dex_state, quote_qty).unwrap();
+    let referral_fee = FeeTier::Base.referral_fee(&dex_state, quote_qty).unwrap();
+    assert_eq!((charged, referral_fee), (2, 1));
+    // Subtracting the rounded referral fee of each fill would credit 1 per fill, one more than the market keeps
+    assert_eq!(
+        FeeTier::Base.taker_fee_credit(&dex_state, 2).unwrap()
+            - FeeTier::Base.referral_fee(&dex_state, 2).unwrap(),
+        1
+    );
+    let credited: u64 = fills
+        .iter()
+        .map(|q| {
+            FeeTier::Base
+                .net_taker_fee_credit(&dex_state, *q, true)
+                .unwrap()
+        })
+        .sum();
+    assert_eq!(credited, 0);
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..10_000 {
+        dex_state.base_taker_rate = rng.gen_range(0..=1 << 32);
+        dex_state.base_maker_rebate = rng.gen_range(0..=dex_state.base_taker_rate);
+        dex_state.referral_fee_share_bps = rng.gen_range(1..=10_000);
+        dex_state.fee_rounding = rng.gen_range(0..=1);
+        let fills: Vec<u64> = (0..rng.gen_range(1..10))
+            .map(|_| rng.gen::<u32>() as u64)
+            .collect();
+        let quote_qty = fills.iter().sum();
+        let retained = FeeTier::Base.taker_fee(&dex_state, quote_qty).unwrap()
+            - FeeTier::Base.referral_fee(&dex_state, quote_qty).unwrap();
+        let credited: u64 = fills
+            .iter()
+            .map(|q| {
+                FeeTier::Base
+                    .net_taker_fee_credit(&dex_state, *q, true)
+                    .unwrap()
+            })
+            .sum();
+        assert!(credited <= retained);
+    }
+}
+
+#[test]
+fn test_maker_rebates_fee_reserve() {
+    let mut dex_state = DexState::zeroed();
+    dex_state.accumulated_fees = 100;
+
+    // The rebates are covered exactly by the reserve and the fill's net taker fee
+    assert_eq!(dex_state.pay_maker_rebates(20, 90, 30), (90, 30));
+    assert_eq!(dex_state.accumulated_fees, 0);
+    assert_eq!(dex_state.accumulated_boosted_rebates, 30);
+
+    // One unit short, the rebates are reduced proportionally and rounded down
+    dex_state.accumulated_fees = 99;
+    assert_eq!(dex_state.pay_maker_rebates(20, 90, 30), (89, 29));
+    assert_eq!(dex_state.accumulated_fees, 1);
+    assert_eq!(dex_state.accumulated_boosted_rebates, 59);
+
+    // An empty reserve pays out nothing beyond the fill's own fee
+    dex_state.accumulated_fees = 0;
+    assert_eq!(dex_state.pay_maker_rebates(10, 20, 0), (10, 0));
+    assert_eq!(dex_state.accumulated_fees, 0);
+
+    // A strict reserve withholds the whole rebate instead, the fill's fee still being credited
+    dex_state.strict_fee_reserve = 1;
+    dex_state.accumulated_fees = 79;
+    assert_eq!(dex_state.pay_maker_rebates(20, 90, 30), (0, 0));
+    assert_eq!(dex_state.accumulated_fees, 99);
+    assert_eq!(dex_state.accumulated_boosted_rebates, 59);
+    assert_eq!(dex_state.pay_maker_rebates(21, 90, 30), (90, 30));
+    assert_eq!(dex_state.accumulated_fees, 0);
+
+    // Regular fills keep the difference between the taker fee and the rebate
+    assert_eq!(dex_state.pay_maker_rebates(50, 20, 0), (20, 0));
+    assert_eq!(dex_state.accumulated_fees, 30);
+}
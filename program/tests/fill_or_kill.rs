@@ -0,0 +1,119 @@
+use asset_agnostic_orderbook::state::Side;
+use dex_v4::instruction_auto::new_order;
+use dex_v4::state::market_signer;
+use solana_program::system_program;
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::signature::Signer;
+pub mod common;
+use crate::common::performance_test_utils::{aob_dex_new_order, create_aob_dex, AobDexTestContext};
+use crate::common::utils::sign_send_instructions;
+
+const PRICE: u64 = 1 << 32;
+const BASE_QTY: u64 = 10_000_000;
+
+const MAKER: usize = 0;
+const TAKER: usize = 1;
+
+fn fill_or_kill_bid(
+    dex_test_ctx: &AobDexTestContext,
+    max_base_qty: u64,
+) -> solana_program::instruction::Instruction {
+    let market_signer = market_signer(
+        &dex_test_ctx.dex_market_key,
+        dex_test_ctx.dex_market.signer_nonce,
+        &dex_v4::ID,
+    )
+    .unwrap();
+    new_order(
+        dex_v4::ID,
+        new_order::Accounts {
+            spl_token_program: &spl_token::ID,
+            system_program: &system_program::ID,
+            market: &dex_test_ctx.dex_market_key,
+            orderbook: &dex_test_ctx.dex_market.orderbook,
+            event_queue: &dex_test_ctx.aob_market.event_queue,
+            bids: &dex_test_ctx.aob_market.bids,
+            asks: &dex_test_ctx.aob_market.asks,
+            base_vault: &dex_test_ctx.dex_market.base_vault,
+            quote_vault: &dex_test_ctx.dex_market.quote_vault,
+            market_signer: &market_signer,
+            base_mint: &dex_test_ctx.dex_market.base_mint,
+            quote_mint: &dex_test_ctx.dex_market.quote_mint,
+            user: &dex_test_ctx.user_account_keys[TAKER],
+            user_token_account: &dex_test_ctx.user_quotes[TAKER],
+            user_owner: &dex_test_ctx.user_owners[TAKER].pubkey(),
+            discount_token_account: None,
+            oracle: None,
+            liquidity_metrics: None,
+            market_stats: None,
+            access_grant: None,
+            fee_referral_account: None,
+        },
+        new_order::Params {
+            #[cfg(not(any(feature = "aarch64-test", target_arch = "aarch64")))]
+            client_order_id: 0,
+            #[cfg(any(feature = "aarch64-test", target_arch = "aarch64"))]
+            client_order_id: bytemuck::cast(0u128),
+            side: Side::Bid as u8,
+            limit_price: PRICE,
+            max_base_qty,
+            max_quote_qty: u64::MAX,
+            order_type: new_order::OrderType::FillOrKill as u8,
+            self_trade_behavior: asset_agnostic_orderbook::state::SelfTradeBehavior::DecrementTake
+                as u8,
+            match_limit: 10,
+            expiry_timestamp: 0,
+            display_base_qty: 0,
+            max_taker_fee: 0,
+            min_base_to_fill: 0,
+            has_discount_token_account: false as u8,
+            reduce_only: 0,
+            wrap_native_quote: 0,
+            has_oracle_account: 0,
+            has_liquidity_metrics_account: 0,
+            has_market_stats_account: 0,
+            size_in_quote: 0,
+            has_access_grant_account: 0,
+            _padding: [0; 5],
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_fill_or_kill_bid_base_capped() {
+    let program_test = ProgramTest::new(
+        "dex_v4",
+        dex_v4::ID,
+        processor!(dex_v4::entrypoint::process_instruction),
+    );
+    let (dex_test_ctx, mut prg_test_ctx) = create_aob_dex(program_test).await;
+
+    aob_dex_new_order(
+        &mut prg_test_ctx,
+        &dex_test_ctx,
+        Side::Ask,
+        PRICE,
+        2 * BASE_QTY,
+        u64::MAX,
+        MAKER,
+    )
+    .await;
+
+    // The bid is filled once its base quantity is matched, even though its quote budget isn't spent
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![fill_or_kill_bid(&dex_test_ctx, BASE_QTY)],
+        vec![&dex_test_ctx.user_owners[TAKER]],
+    )
+    .await
+    .unwrap();
+
+    // Only half of the requested base quantity is left on the book
+    assert!(sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![fill_or_kill_bid(&dex_test_ctx, 2 * BASE_QTY)],
+        vec![&dex_test_ctx.user_owners[TAKER]],
+    )
+    .await
+    .is_err());
+}
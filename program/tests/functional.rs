@@ -6,11 +6,14 @@ use dex_v4::instruction_auto::consume_events;
 use dex_v4::instruction_auto::create_market;
 use dex_v4::instruction_auto::initialize_account;
 use dex_v4::instruction_auto::new_order;
+use dex_v4::instruction_auto::set_program_config::get_program_config_address;
 use dex_v4::instruction_auto::settle;
 use dex_v4::instruction_auto::swap;
 use dex_v4::instruction_auto::sweep_fees;
 use dex_v4::state::UserAccountHeader;
+use dex_v4::state::FillEvent;
 use dex_v4::state::DEX_STATE_LEN;
+use dex_v4::state::TRADE_LOG_HEADER_LEN;
 use dex_v4::state::USER_ACCOUNT_HEADER_LEN;
 use mpl_token_metadata::pda::find_metadata_account;
 use solana_program::account_info::AccountInfo;
@@ -120,6 +123,24 @@ async fn test_dex() {
     .await
     .unwrap();
 
+    // Create the trade log account
+    let trade_log_len = TRADE_LOG_HEADER_LEN + 100 * FillEvent::LEN;
+    let trade_log_account = Keypair::new();
+    let create_trade_log_account_instruction = create_account(
+        &prg_test_ctx.payer.pubkey(),
+        &trade_log_account.pubkey(),
+        rent.minimum_balance(trade_log_len),
+        trade_log_len as u64,
+        &dex_program_id,
+    );
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![create_trade_log_account_instruction],
+        vec![&trade_log_account],
+    )
+    .await
+    .unwrap();
+
     // Define the market signer
     let (market_signer, signer_nonce) =
         Pubkey::find_program_address(&[&market_account.pubkey().to_bytes()], &dex_program_id);
@@ -135,8 +156,46 @@ async fn test_dex() {
         .await
         .unwrap();
 
-    // Create the dex market
+    // Markets which trade a token against itself are rejected
     let market_admin = Keypair::new();
+    let create_market_instruction = create_market(
+        dex_program_id,
+        dex_v4::instruction_auto::create_market::Accounts {
+            base_vault: &base_vault,
+            quote_vault: &base_vault,
+            market: &market_account.pubkey(),
+            orderbook: &aaob_accounts.market,
+            market_admin: &market_admin.pubkey(),
+            event_queue: &aaob_accounts.event_queue,
+            asks: &aaob_accounts.asks,
+            bids: &aaob_accounts.bids,
+            token_metadata: &find_metadata_account(&base_mint_key).0,
+            trade_log: &trade_log_account.pubkey(),
+            base_mint: &base_mint_key,
+            quote_mint: &base_mint_key,
+            program_config: &get_program_config_address(&dex_program_id).0,
+            system_program: None,
+            creation_fee_payer: None,
+            creation_fee_treasury: None,
+        },
+        create_market::Params {
+            signer_nonce: signer_nonce as u64,
+            min_base_order_size: 1,
+            tick_size: 42949672,
+            base_currency_multiplier: 1,
+            quote_currency_multiplier: 10000,
+            base_taker_rate: 0,
+            base_maker_rebate: 0,
+            base_lot_size: 0,
+        },
+    );
+    assert!(
+        sign_send_instructions(&mut prg_test_ctx, vec![create_market_instruction], vec![])
+            .await
+            .is_err()
+    );
+
+    // Create the dex market
     let create_market_instruction = create_market(
         dex_program_id,
         dex_v4::instruction_auto::create_market::Accounts {
@@ -149,6 +208,13 @@ async fn test_dex() {
             asks: &aaob_accounts.asks,
             bids: &aaob_accounts.bids,
             token_metadata: &find_metadata_account(&base_mint_key).0,
+            trade_log: &trade_log_account.pubkey(),
+            base_mint: &base_mint_key,
+            quote_mint: &quote_mint_key,
+            program_config: &get_program_config_address(&dex_program_id).0,
+            system_program: None,
+            creation_fee_payer: None,
+            creation_fee_treasury: None,
         },
         create_market::Params {
             signer_nonce: signer_nonce as u64,
@@ -156,6 +222,9 @@ async fn test_dex() {
             tick_size: 42949672,
             base_currency_multiplier: 1,
             quote_currency_multiplier: 10000,
+            base_taker_rate: 0,
+            base_maker_rebate: 0,
+            base_lot_size: 0,
         },
     );
     sign_send_instructions(&mut prg_test_ctx, vec![create_market_instruction], vec![])
@@ -221,6 +290,7 @@ async fn test_dex() {
         initialize_account::Params {
             market: market_account.pubkey(),
             max_orders: 10,
+            referrer: Pubkey::default(),
         },
     );
     sign_send_instructions(
@@ -309,10 +379,17 @@ async fn test_dex() {
             asks: &aaob_market_state.asks,
             base_vault: &base_vault,
             quote_vault: &quote_vault,
+            market_signer: &market_signer,
+            base_mint: &base_mint_key,
+            quote_mint: &quote_mint_key,
             user: &user_account,
             user_token_account: &user_base_token_account,
             user_owner: &user_account_owner.pubkey(),
             discount_token_account: None,
+            oracle: None,
+            liquidity_metrics: None,
+            market_stats: None,
+            access_grant: None,
             fee_referral_account: None,
         },
         new_order::Params {
@@ -328,8 +405,19 @@ async fn test_dex() {
             self_trade_behavior: asset_agnostic_orderbook::state::SelfTradeBehavior::DecrementTake
                 as u8,
             match_limit: 10,
+            expiry_timestamp: 0,
+            display_base_qty: 0,
+            max_taker_fee: 0,
+            min_base_to_fill: 0,
             has_discount_token_account: false as u8,
-            _padding: 0,
+            reduce_only: 0,
+            wrap_native_quote: 0,
+            has_oracle_account: 0,
+            has_liquidity_metrics_account: 0,
+            has_market_stats_account: 0,
+            size_in_quote: 0,
+            has_access_grant_account: 0,
+            _padding: [0; 5],
         },
     );
     sign_send_instructions(
@@ -394,10 +482,17 @@ async fn test_dex() {
             asks: &aaob_market_state.asks,
             base_vault: &base_vault,
             quote_vault: &quote_vault,
+            market_signer: &market_signer,
+            base_mint: &base_mint_key,
+            quote_mint: &quote_mint_key,
             user: &user_account,
             user_token_account: &user_base_token_account,
             user_owner: &user_account_owner.pubkey(),
             discount_token_account: None,
+            oracle: None,
+            liquidity_metrics: None,
+            market_stats: None,
+            access_grant: None,
             fee_referral_account: None,
         },
         new_order::Params {
@@ -413,8 +508,19 @@ async fn test_dex() {
             self_trade_behavior: asset_agnostic_orderbook::state::SelfTradeBehavior::DecrementTake
                 as u8,
             match_limit: 10,
+            expiry_timestamp: 0,
+            display_base_qty: 0,
+            max_taker_fee: 0,
+            min_base_to_fill: 0,
             has_discount_token_account: false as u8,
-            _padding: 0,
+            reduce_only: 0,
+            wrap_native_quote: 0,
+            has_oracle_account: 0,
+            has_liquidity_metrics_account: 0,
+            has_market_stats_account: 0,
+            size_in_quote: 0,
+            has_access_grant_account: 0,
+            _padding: [0; 5],
         },
     );
     sign_send_instructions(
@@ -438,10 +544,16 @@ async fn test_dex() {
     //         asks: &aaob_market_state.asks,
     //         base_vault: &base_vault,
     //         quote_vault: &quote_vault,
+    //         market_signer: &market_signer,
+    //         base_mint: &base_mint_key,
+    //         quote_mint: &quote_mint_key,
     //         user: &user_account,
     //         user_token_account: &user_quote_token_account,
     //         user_owner: &user_account_owner.pubkey(),
     //         discount_token_account: None,
+    //         oracle: None,
+    //         liquidity_metrics: None,
+    //         market_stats: None,
     //         fee_referral_account: None,
     //     },
     //     new_order::Params {
@@ -457,8 +569,14 @@ async fn test_dex() {
     //         self_trade_behavior: asset_agnostic_orderbook::state::SelfTradeBehavior::DecrementTake
     //             as u8,
     //         match_limit: 10,
+    //         expiry_timestamp: 0,
+    //         display_base_qty: 0,
     //         has_discount_token_account: false as u8,
-    //         _padding: 0,
+    //         reduce_only: 0,
+    //         wrap_native_quote: 0,
+    //         has_oracle_account: 0,
+    //         has_liquidity_metrics_account: 0,
+    //         has_market_stats_account: 0,
     //     },
     // );
     // sign_send_instructions(
@@ -478,12 +596,18 @@ async fn test_dex() {
     //         market: &market_account.pubkey(),
     //         orderbook: &aaob_accounts.market,
     //         event_queue: &aaob_market_state.event_queue,
+    //         bids: &aaob_market_state.bids,
+    //         asks: &aaob_market_state.asks,
     //         reward_target: &reward_target.pubkey(),
+    //         liquidity_metrics: None,
+    //         market_stats: None,
     //         user_accounts: &[user_account],
     //     },
     //     consume_events::Params {
     //         max_iterations: 10,
     //         no_op_err: 1,
+    //         has_liquidity_metrics_account: 0,
+    //         has_market_stats_account: 0,
     //     },
     // );
     // sign_send_instructions(&mut prg_test_ctx, vec![consume_events_instruction], vec![])
@@ -503,8 +627,13 @@ async fn test_dex() {
             user_owner: &user_account_owner.pubkey(),
             destination_base_account: &user_base_token_account,
             destination_quote_account: &user_quote_token_account,
+            base_mint: &base_mint_key,
+            quote_mint: &quote_mint_key,
+        },
+        settle::Params {
+            unwrap_native_quote: 0,
+            settle_destination: 0,
         },
-        settle::Params {},
     );
     sign_send_instructions(
         &mut prg_test_ctx,
@@ -528,6 +657,8 @@ async fn test_dex() {
     //         base_vault: &base_vault,
     //         quote_vault: &quote_vault,
     //         market_signer: &market_signer,
+    //         base_mint: &base_mint_key,
+    //         quote_mint: &quote_mint_key,
     //         user_base_account: &user_base_token_account,
     //         user_quote_account: &user_quote_token_account,
     //         user_owner: &user_account_owner.pubkey(),
@@ -558,12 +689,17 @@ async fn test_dex() {
             market: &market_account.pubkey(),
             market_signer: &market_signer,
             quote_vault: &quote_vault,
+            quote_mint: &quote_mint_key,
             destination_token_account: &sweep_fees_ata,
             spl_token_program: &spl_token::ID,
             token_metadata: &find_metadata_account(&base_mint_key).0,
+            fee_recipients: None,
+            fee_recipient_token_accounts: &[],
             creators_token_accounts: &[user_quote_token_account, base_mint_auth_token_account],
         },
-        sweep_fees::Params {},
+        sweep_fees::Params {
+            has_fee_recipients_account: 0,
+        },
     );
     sign_send_instructions(&mut prg_test_ctx, vec![ix], vec![])
         .await
@@ -576,12 +712,19 @@ async fn test_dex() {
             market: &market_account.pubkey(),
             orderbook: &aaob_accounts.market,
             event_queue: &aaob_market_state.event_queue,
+            bids: &aaob_market_state.bids,
+            asks: &aaob_market_state.asks,
+            trade_log: &trade_log_account.pubkey(),
             reward_target: &reward_target.pubkey(),
+            liquidity_metrics: None,
+            market_stats: None,
             user_accounts: &[user_account],
         },
         consume_events::Params {
             max_iterations: 11,
             no_op_err: 1,
+            has_liquidity_metrics_account: 0,
+            has_market_stats_account: 0,
         },
     );
     sign_send_instructions(&mut prg_test_ctx, vec![consume_events_instruction], vec![])
@@ -610,12 +753,19 @@ async fn test_dex() {
             market: &market_account.pubkey(),
             orderbook: &aaob_accounts.market,
             event_queue: &aaob_market_state.event_queue,
+            bids: &aaob_market_state.bids,
+            asks: &aaob_market_state.asks,
+            trade_log: &trade_log_account.pubkey(),
             reward_target: &reward_target.pubkey(),
+            liquidity_metrics: None,
+            market_stats: None,
             user_accounts: &[user_account],
         },
         consume_events::Params {
             max_iterations: 10,
             no_op_err: 0,
+            has_liquidity_metrics_account: 0,
+            has_market_stats_account: 0,
         },
     );
     sign_send_instructions(&mut prg_test_ctx, vec![consume_events_instruction], vec![])
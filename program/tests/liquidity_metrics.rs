@@ -0,0 +1,37 @@
+use bytemuck::cast_slice_mut;
+use dex_v4::state::AccountTag;
+use dex_v4::state::LiquidityMetrics;
+use dex_v4::state::LIQUIDITY_METRICS_HEADER_LEN;
+use solana_program::pubkey::Pubkey;
+
+const BITMAP_LEN: usize = 32;
+
+#[test]
+fn test_liquidity_metrics_bitmap() {
+    let mut words = vec![0u64; (LIQUIDITY_METRICS_HEADER_LEN + BITMAP_LEN) / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+    assert!(LiquidityMetrics::from_buffer(buf).is_err());
+    let mut liquidity_metrics = LiquidityMetrics::from_buffer_unchecked(buf).unwrap();
+    liquidity_metrics.header.tag = AccountTag::LiquidityMetrics as u64;
+
+    assert_eq!(liquidity_metrics.bit_count(), BITMAP_LEN * 8);
+
+    let maker = Pubkey::new_unique();
+    assert!(!liquidity_metrics.contains(&maker));
+    assert!(liquidity_metrics.insert(&maker));
+    assert!(liquidity_metrics.contains(&maker));
+    // A maker is only counted once per epoch
+    assert!(!liquidity_metrics.insert(&maker));
+    assert_eq!(liquidity_metrics.set_bit_count(), 1);
+
+    // Makers sharing the low bits of their pubkey share a bit, and only the first one is counted
+    let mut colliding_maker = maker.to_bytes();
+    colliding_maker[31] ^= 1;
+    let colliding_maker = Pubkey::new_from_array(colliding_maker);
+    assert!(liquidity_metrics.contains(&colliding_maker));
+    assert!(!liquidity_metrics.insert(&colliding_maker));
+
+    liquidity_metrics.clear();
+    assert_eq!(liquidity_metrics.set_bit_count(), 0);
+    assert!(liquidity_metrics.insert(&colliding_maker));
+}
@@ -0,0 +1,57 @@
+use bytemuck::Zeroable;
+use dex_v4::error::DexError;
+use dex_v4::state::DexState;
+
+#[test]
+fn test_base_lots_round_trip() {
+    let mut dex_state = DexState::zeroed();
+    dex_state.base_lot_size = 1_000;
+
+    assert_eq!(dex_state.base_lots_to_raw(0).unwrap(), 0);
+    assert_eq!(dex_state.base_lots_to_raw(7).unwrap(), 7_000);
+    assert_eq!(dex_state.raw_to_base_lots(7_000).unwrap(), 7);
+    assert!(matches!(
+        dex_state.raw_to_base_lots(7_001),
+        Err(DexError::InvalidLotSize)
+    ));
+
+    // Markets without a lot size have lots of a single base token unit
+    dex_state.base_lot_size = 0;
+    assert_eq!(dex_state.base_lots_to_raw(123).unwrap(), 123);
+    assert_eq!(dex_state.raw_to_base_lots(123).unwrap(), 123);
+}
+
+#[test]
+fn test_base_lots_boundaries() {
+    let mut dex_state = DexState::zeroed();
+    dex_state.base_lot_size = 1_000;
+
+    let max_lots = u64::MAX / 1_000;
+    let max_raw = max_lots * 1_000;
+    assert_eq!(dex_state.base_lots_to_raw(max_lots).unwrap(), max_raw);
+    assert_eq!(dex_state.raw_to_base_lots(max_raw).unwrap(), max_lots);
+    assert!(matches!(
+        dex_state.base_lots_to_raw(max_lots + 1),
+        Err(DexError::NumericalOverflow)
+    ));
+    assert!(matches!(
+        dex_state.raw_to_base_lots(u64::MAX),
+        Err(DexError::InvalidLotSize)
+    ));
+
+    dex_state.base_lot_size = 1;
+    assert_eq!(dex_state.base_lots_to_raw(u64::MAX).unwrap(), u64::MAX);
+    assert_eq!(dex_state.raw_to_base_lots(u64::MAX).unwrap(), u64::MAX);
+
+    dex_state.base_lot_size = u64::MAX;
+    assert_eq!(dex_state.base_lots_to_raw(1).unwrap(), u64::MAX);
+    assert!(matches!(
+        dex_state.base_lots_to_raw(2),
+        Err(DexError::NumericalOverflow)
+    ));
+    assert_eq!(dex_state.raw_to_base_lots(u64::MAX).unwrap(), 1);
+    assert!(matches!(
+        dex_state.raw_to_base_lots(u64::MAX - 1),
+        Err(DexError::InvalidLotSize)
+    ));
+}
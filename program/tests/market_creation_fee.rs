@@ -0,0 +1,324 @@
+use dex_v4::instruction_auto::create_market;
+use dex_v4::instruction_auto::set_program_config;
+use dex_v4::instruction_auto::set_program_config::get_program_config_address;
+use dex_v4::state::{FillEvent, DEX_STATE_LEN, TRADE_LOG_HEADER_LEN};
+use mpl_token_metadata::pda::find_metadata_account;
+use solana_program::bpf_loader_upgradeable;
+use solana_program::instruction::Instruction;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_instruction::create_account;
+use solana_program::system_program;
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::signature::{Keypair, Signer};
+pub mod common;
+use crate::common::utils::{
+    create_aob_market_and_accounts, create_associated_token, mint_bootstrap, sign_send_instructions,
+};
+
+/// Adds the program data account of the DEX program, which records its upgrade authority
+fn add_program_data(program_test: &mut ProgramTest, upgrade_authority: &Pubkey) -> Pubkey {
+    let (program_data, _) =
+        Pubkey::find_program_address(&[&dex_v4::ID.to_bytes()], &bpf_loader_upgradeable::ID);
+    // The bincode encoding of `UpgradeableLoaderState::ProgramData`
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.push(1);
+    data.extend_from_slice(&upgrade_authority.to_bytes());
+    program_test.add_account(
+        program_data,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: bpf_loader_upgradeable::ID,
+            ..Account::default()
+        },
+    );
+    program_data
+}
+
+struct MarketAccounts {
+    market: Pubkey,
+    orderbook: Pubkey,
+    event_queue: Pubkey,
+    bids: Pubkey,
+    asks: Pubkey,
+    trade_log: Pubkey,
+    base_vault: Pubkey,
+    quote_vault: Pubkey,
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+    signer_nonce: u8,
+}
+
+/// Allocates the accounts of a new market
+async fn create_market_accounts(
+    prg_test_ctx: &mut ProgramTestContext,
+    base_mint: Pubkey,
+    quote_mint: Pubkey,
+) -> MarketAccounts {
+    let rent = prg_test_ctx.banks_client.get_rent().await.unwrap();
+    let market_account = Keypair::new();
+    let trade_log_account = Keypair::new();
+    let trade_log_len = TRADE_LOG_HEADER_LEN + 10 * FillEvent::LEN;
+    let payer = prg_test_ctx.payer.pubkey();
+    sign_send_instructions(
+        prg_test_ctx,
+        vec![
+            create_account(
+                &payer,
+                &market_account.pubkey(),
+                rent.minimum_balance(DEX_STATE_LEN),
+                DEX_STATE_LEN as u64,
+                &dex_v4::ID,
+            ),
+            create_account(
+                &payer,
+                &trade_log_account.pubkey(),
+                rent.minimum_balance(trade_log_len),
+                trade_log_len as u64,
+                &dex_v4::ID,
+            ),
+        ],
+        vec![&market_account, &trade_log_account],
+    )
+    .await
+    .unwrap();
+
+    let (market_signer, signer_nonce) =
+        Pubkey::find_program_address(&[&market_account.pubkey().to_bytes()], &dex_v4::ID);
+    let aaob_accounts = create_aob_market_and_accounts(prg_test_ctx, dex_v4::ID).await;
+    let base_vault = create_associated_token(prg_test_ctx, &base_mint, &market_signer)
+        .await
+        .unwrap();
+    let quote_vault = create_associated_token(prg_test_ctx, &quote_mint, &market_signer)
+        .await
+        .unwrap();
+
+    MarketAccounts {
+        market: market_account.pubkey(),
+        orderbook: aaob_accounts.market,
+        event_queue: aaob_accounts.event_queue,
+        bids: aaob_accounts.bids,
+        asks: aaob_accounts.asks,
+        trade_log: trade_log_account.pubkey(),
+        base_vault,
+        quote_vault,
+        base_mint,
+        quote_mint,
+        signer_nonce,
+    }
+}
+
+/// Builds the create_market instruction of the market, paying the creation fee from the given account to the given
+/// treasury if any
+fn create_market_instruction(
+    accounts: &MarketAccounts,
+    creation_fee: Option<(&Pubkey, &Pubkey)>,
+) -> Instruction {
+    let market_admin = Pubkey::new_unique();
+    create_market(
+        dex_v4::ID,
+        create_market::Accounts {
+            market: &accounts.market,
+            orderbook: &accounts.orderbook,
+            base_vault: &accounts.base_vault,
+            quote_vault: &accounts.quote_vault,
+            market_admin: &market_admin,
+            event_queue: &accounts.event_queue,
+            asks: &accounts.asks,
+            bids: &accounts.bids,
+            token_metadata: &find_metadata_account(&accounts.base_mint).0,
+            trade_log: &accounts.trade_log,
+            base_mint: &accounts.base_mint,
+            quote_mint: &accounts.quote_mint,
+            program_config: &get_program_config_address(&dex_v4::ID).0,
+            system_program: creation_fee.map(|_| &system_program::ID),
+            creation_fee_payer: creation_fee.map(|(payer, _)| payer),
+            creation_fee_treasury: creation_fee.map(|(_, treasury)| treasury),
+        },
+        create_market::Params {
+            signer_nonce: accounts.signer_nonce as u64,
+            min_base_order_size: 1,
+            tick_size: 42949672,
+            base_currency_multiplier: 1,
+            quote_currency_multiplier: 10000,
+            base_taker_rate: 0,
+            base_maker_rebate: 0,
+            base_lot_size: 0,
+        },
+    )
+}
+
+fn set_program_config_instruction(
+    authority: &Pubkey,
+    fee_payer: &Pubkey,
+    program_data: Option<&Pubkey>,
+    params: set_program_config::Params,
+) -> Instruction {
+    set_program_config(
+        dex_v4::ID,
+        set_program_config::Accounts {
+            system_program: &system_program::ID,
+            program_config: &get_program_config_address(&dex_v4::ID).0,
+            authority,
+            fee_payer,
+            program_data,
+        },
+        params,
+    )
+}
+
+#[tokio::test]
+async fn test_market_creation_fee() {
+    let mut program_test = ProgramTest::new(
+        "dex_v4",
+        dex_v4::ID,
+        processor!(dex_v4::entrypoint::process_instruction),
+    );
+    let upgrade_authority = Keypair::new();
+    let program_data = add_program_data(&mut program_test, &upgrade_authority.pubkey());
+    let mint_authority = Pubkey::new_unique();
+    let (base_mint, _) = mint_bootstrap(None, 0, &mut program_test, &mint_authority);
+    let (quote_mint, _) = mint_bootstrap(None, 6, &mut program_test, &mint_authority);
+
+    let mut prg_test_ctx = program_test.start_with_context().await;
+    let payer = prg_test_ctx.payer.pubkey();
+
+    // No fee is charged while the program config doesn't exist
+    let free_market = create_market_accounts(&mut prg_test_ctx, base_mint, quote_mint).await;
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![create_market_instruction(&free_market, None)],
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    // Only the program's upgrade authority can create the program config
+    let treasury = Pubkey::new_unique();
+    let creation_fee = 100_000_000;
+    let config_params = set_program_config::Params {
+        market_creation_fee: creation_fee,
+        market_creation_fee_treasury: treasury,
+        new_authority: Pubkey::default(),
+    };
+    let impostor = Keypair::new();
+    assert!(sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![set_program_config_instruction(
+            &impostor.pubkey(),
+            &payer,
+            Some(&program_data),
+            config_params,
+        )],
+        vec![&impostor],
+    )
+    .await
+    .is_err());
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![set_program_config_instruction(
+            &upgrade_authority.pubkey(),
+            &payer,
+            Some(&program_data),
+            config_params,
+        )],
+        vec![&upgrade_authority],
+    )
+    .await
+    .unwrap();
+
+    // The fee must be paid to the configured treasury
+    let paid_market = create_market_accounts(&mut prg_test_ctx, base_mint, quote_mint).await;
+    assert!(sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![create_market_instruction(&paid_market, None)],
+        vec![],
+    )
+    .await
+    .is_err());
+    let other_treasury = Pubkey::new_unique();
+    assert!(sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![create_market_instruction(
+            &paid_market,
+            Some((&payer, &other_treasury))
+        )],
+        vec![],
+    )
+    .await
+    .is_err());
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![create_market_instruction(
+            &paid_market,
+            Some((&payer, &treasury)),
+        )],
+        vec![],
+    )
+    .await
+    .unwrap();
+    let treasury_account = prg_test_ctx
+        .banks_client
+        .get_account(treasury)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(treasury_account.lamports, creation_fee);
+
+    // The config's authority can hand it over and disable the fee
+    let new_authority = Keypair::new();
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![set_program_config_instruction(
+            &upgrade_authority.pubkey(),
+            &payer,
+            None,
+            set_program_config::Params {
+                new_authority: new_authority.pubkey(),
+                ..config_params
+            },
+        )],
+        vec![&upgrade_authority],
+    )
+    .await
+    .unwrap();
+    let disable_fee_params = set_program_config::Params {
+        market_creation_fee: 0,
+        ..config_params
+    };
+    assert!(sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![set_program_config_instruction(
+            &upgrade_authority.pubkey(),
+            &payer,
+            None,
+            disable_fee_params,
+        )],
+        vec![&upgrade_authority],
+    )
+    .await
+    .is_err());
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![set_program_config_instruction(
+            &new_authority.pubkey(),
+            &payer,
+            None,
+            disable_fee_params,
+        )],
+        vec![&new_authority],
+    )
+    .await
+    .unwrap();
+
+    let free_market = create_market_accounts(&mut prg_test_ctx, base_mint, quote_mint).await;
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![create_market_instruction(&free_market, None)],
+        vec![],
+    )
+    .await
+    .unwrap();
+}
@@ -0,0 +1,497 @@
+use asset_agnostic_orderbook::state::critbit::Slab;
+use asset_agnostic_orderbook::state::event_queue::EventQueue;
+use asset_agnostic_orderbook::state::market_state::MarketState;
+use asset_agnostic_orderbook::state::{SelfTradeBehavior, Side};
+use bytemuck::{cast_slice_mut, try_from_bytes, try_from_bytes_mut, Zeroable};
+use dex_v4::instruction_auto::{consume_events, initialize_account, migrate_market, new_order};
+use dex_v4::state::{
+    AccountTag, DexState, FillEvent, LegacyCallBackInfo, LegacyDexState, MarketStatus, TradeLog,
+    DEX_STATE_LEN, DEX_STATE_VERSION, LEGACY_DEX_STATE_LEN, TRADE_LOG_HEADER_LEN,
+};
+use solana_program::account_info::AccountInfo;
+use solana_program::pubkey::Pubkey;
+use solana_program::rent::Rent;
+use solana_program::system_instruction::create_account;
+use solana_program::system_program;
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::account::Account;
+use solana_sdk::signature::{Keypair, Signer};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::instruction::mint_to;
+pub mod common;
+use crate::common::utils::{
+    create_aob_market_and_accounts, create_associated_token, mint_bootstrap,
+    sign_send_instructions, AccountBuffer,
+};
+
+#[test]
+fn test_market_legacy_migration() {
+    assert_eq!(LEGACY_DEX_STATE_LEN, 280);
+    // The state has no u128 field, so that its layout is the same on-chain and off-chain
+    assert_eq!(std::mem::align_of::<DexState>(), 8);
+    assert_eq!(DEX_STATE_LEN % 8, 0);
+    assert!(DexState::is_legacy_len(LEGACY_DEX_STATE_LEN));
+    assert!(!DexState::is_legacy_len(DEX_STATE_LEN));
+
+    let mut words = vec![0u64; DEX_STATE_LEN / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+
+    let base_mint = Pubkey::new_unique();
+    let quote_mint = Pubkey::new_unique();
+    let orderbook = Pubkey::new_unique();
+    let admin = Pubkey::new_unique();
+    {
+        let legacy: &mut LegacyDexState =
+            try_from_bytes_mut(&mut buf[..LEGACY_DEX_STATE_LEN]).unwrap();
+        legacy.tag = AccountTag::DexState as u64;
+        legacy.base_mint = base_mint;
+        legacy.quote_mint = quote_mint;
+        legacy.orderbook = orderbook;
+        legacy.admin = admin;
+        legacy.creation_timestamp = 1_600_000_000;
+        legacy.base_volume = 1;
+        legacy.quote_volume = 2;
+        legacy.accumulated_fees = 3;
+        legacy.min_base_order_size = 4;
+        legacy.royalties_bps = 5;
+        legacy.accumulated_royalties = 6;
+        legacy.base_currency_multiplier = 7;
+        legacy.quote_currency_multiplier = 8;
+        legacy.signer_nonce = 254;
+        legacy.fee_type = 1;
+    }
+
+    DexState::migrate_legacy_buffer(buf, LEGACY_DEX_STATE_LEN, 1_650_000_000).unwrap();
+
+    let market_state: &DexState = try_from_bytes(&buf[..DEX_STATE_LEN]).unwrap();
+    assert_eq!(market_state.tag, AccountTag::DexState as u64);
+    assert_eq!(market_state.base_mint, base_mint);
+    assert_eq!(market_state.quote_mint, quote_mint);
+    assert_eq!(market_state.orderbook, orderbook);
+    assert_eq!(market_state.admin, admin);
+    assert_eq!(market_state.creation_timestamp, 1_600_000_000);
+    assert_eq!(market_state.base_volume, 1);
+    assert_eq!(market_state.quote_volume, 2);
+    assert_eq!(market_state.accumulated_fees, 3);
+    assert_eq!(market_state.min_base_order_size, 4);
+    assert_eq!(market_state.royalties_bps, 5);
+    assert_eq!(market_state.accumulated_royalties, 6);
+    assert_eq!(market_state.base_currency_multiplier, 7);
+    assert_eq!(market_state.quote_currency_multiplier, 8);
+    assert_eq!(market_state.signer_nonce, 254);
+    assert_eq!(market_state.fee_type, 1);
+
+    // The new fields start from the values of a newly created market
+    assert_eq!(market_state.version, DEX_STATE_VERSION);
+    assert_eq!(market_state.status, MarketStatus::Active as u8);
+    assert_eq!(market_state.token_program, spl_token::ID);
+    assert_eq!(market_state.base_lot_size, 1);
+    assert_eq!(market_state.best_bid_price, 0);
+    assert_eq!(market_state.best_ask_price, u64::MAX);
+    assert_eq!(market_state.twap_last_update, 1_650_000_000);
+    assert_eq!(market_state.access_authority, Pubkey::default());
+    assert_eq!(market_state.max_orders_per_account, 0);
+    // migrate_market records the legacy orderbook once it created the new one
+    assert_eq!(market_state.legacy_orderbook, Pubkey::default());
+
+    // The state can't be relocated twice from a buffer which doesn't hold a legacy state
+    assert!(DexState::migrate_legacy_buffer(buf, DEX_STATE_LEN, 1_650_000_000).is_err());
+}
+
+/// Creates the accounts of a legacy market, whose orderbook stores the 33 byte callback informations, and returns the
+/// keys of its orderbook, event queue, bids and asks
+fn add_legacy_market(
+    program_test: &mut ProgramTest,
+    market: &Pubkey,
+    legacy_state: LegacyDexState,
+) -> [Pubkey; 4] {
+    let keys = [
+        legacy_state.orderbook,
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    ];
+    let mut buffers = [
+        AccountBuffer::new(8 + MarketState::LEN),
+        AccountBuffer::new(EventQueue::<LegacyCallBackInfo>::compute_allocation_size(
+            10,
+        )),
+        AccountBuffer::new(Slab::<LegacyCallBackInfo>::compute_allocation_size(100)),
+        AccountBuffer::new(Slab::<LegacyCallBackInfo>::compute_allocation_size(100)),
+    ];
+    {
+        let mut lamports = [1_000_000_000; 4];
+        let mut account_infos = keys
+            .iter()
+            .zip(buffers.iter_mut())
+            .zip(lamports.iter_mut())
+            .map(|((key, buffer), lamports)| {
+                AccountInfo::new(
+                    key,
+                    false,
+                    true,
+                    lamports,
+                    buffer.bytes(),
+                    &dex_v4::ID,
+                    false,
+                    0,
+                )
+            })
+            .collect::<Vec<_>>()
+            .into_iter();
+        let orderbook = account_infos.next().unwrap();
+        let event_queue = account_infos.next().unwrap();
+        let bids = account_infos.next().unwrap();
+        let asks = account_infos.next().unwrap();
+        asset_agnostic_orderbook::instruction::create_market::process::<LegacyCallBackInfo>(
+            &dex_v4::ID,
+            asset_agnostic_orderbook::instruction::create_market::Accounts {
+                market: &orderbook,
+                event_queue: &event_queue,
+                bids: &bids,
+                asks: &asks,
+            },
+            asset_agnostic_orderbook::instruction::create_market::Params {
+                min_base_order_size: legacy_state.min_base_order_size,
+                tick_size: 42949672,
+            },
+        )
+        .unwrap();
+    }
+    for (key, buffer) in keys.iter().zip(buffers.iter_mut()) {
+        program_test.add_account(
+            *key,
+            Account {
+                lamports: 1_000_000_000,
+                data: buffer.bytes().to_vec(),
+                owner: dex_v4::ID,
+                ..Account::default()
+            },
+        );
+    }
+
+    let mut market_data = vec![0; LEGACY_DEX_STATE_LEN];
+    *try_from_bytes_mut::<LegacyDexState>(&mut market_data).unwrap() = legacy_state;
+    program_test.add_account(
+        *market,
+        Account {
+            lamports: Rent::default().minimum_balance(LEGACY_DEX_STATE_LEN),
+            data: market_data,
+            owner: dex_v4::ID,
+            ..Account::default()
+        },
+    );
+
+    keys
+}
+
+#[tokio::test]
+async fn test_migrate_market_then_consume_events() {
+    let mut program_test = ProgramTest::new(
+        "dex_v4",
+        dex_v4::ID,
+        processor!(dex_v4::entrypoint::process_instruction),
+    );
+
+    let base_mint_auth = Keypair::new();
+    let (base_mint, _) = mint_bootstrap(None, 0, &mut program_test, &base_mint_auth.pubkey());
+    let quote_mint_auth = Keypair::new();
+    let (quote_mint, _) = mint_bootstrap(None, 6, &mut program_test, &quote_mint_auth.pubkey());
+
+    let market = Pubkey::new_unique();
+    let (market_signer, signer_nonce) =
+        Pubkey::find_program_address(&[&market.to_bytes()], &dex_v4::ID);
+    let base_vault = get_associated_token_address(&market_signer, &base_mint);
+    let quote_vault = get_associated_token_address(&market_signer, &quote_mint);
+    let market_admin = Keypair::new();
+
+    let mut legacy_state = LegacyDexState::zeroed();
+    legacy_state.tag = AccountTag::DexState as u64;
+    legacy_state.base_mint = base_mint;
+    legacy_state.quote_mint = quote_mint;
+    legacy_state.base_vault = base_vault;
+    legacy_state.quote_vault = quote_vault;
+    legacy_state.orderbook = Pubkey::new_unique();
+    legacy_state.admin = market_admin.pubkey();
+    legacy_state.min_base_order_size = 1;
+    legacy_state.base_currency_multiplier = 1;
+    legacy_state.quote_currency_multiplier = 10000;
+    legacy_state.signer_nonce = signer_nonce;
+    let [legacy_orderbook, legacy_event_queue, legacy_bids, legacy_asks] =
+        add_legacy_market(&mut program_test, &market, legacy_state);
+
+    let mut prg_test_ctx = program_test.start_with_context().await;
+    let rent = prg_test_ctx.banks_client.get_rent().await.unwrap();
+
+    create_associated_token(&mut prg_test_ctx, &base_mint, &market_signer)
+        .await
+        .unwrap();
+    create_associated_token(&mut prg_test_ctx, &quote_mint, &market_signer)
+        .await
+        .unwrap();
+
+    // The new orderbook and the trade log, which legacy markets lack
+    let aaob_accounts = create_aob_market_and_accounts(&mut prg_test_ctx, dex_v4::ID).await;
+    let trade_log_len = TRADE_LOG_HEADER_LEN + 100 * FillEvent::LEN;
+    let trade_log_account = Keypair::new();
+    let create_trade_log_account_instruction = create_account(
+        &prg_test_ctx.payer.pubkey(),
+        &trade_log_account.pubkey(),
+        rent.minimum_balance(trade_log_len),
+        trade_log_len as u64,
+        &dex_v4::ID,
+    );
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![create_trade_log_account_instruction],
+        vec![&trade_log_account],
+    )
+    .await
+    .unwrap();
+
+    let payer = prg_test_ctx.payer.pubkey();
+    let migrate_market_instruction = |admin: &Pubkey| {
+        migrate_market(
+            dex_v4::ID,
+            migrate_market::Accounts {
+                system_program: &system_program::ID,
+                market: &market,
+                base_vault: &base_vault,
+                quote_vault: &quote_vault,
+                fee_payer: &payer,
+                market_admin: admin,
+                legacy_orderbook: &legacy_orderbook,
+                legacy_event_queue: &legacy_event_queue,
+                legacy_bids: &legacy_bids,
+                legacy_asks: &legacy_asks,
+                orderbook: &aaob_accounts.market,
+                event_queue: &aaob_accounts.event_queue,
+                bids: &aaob_accounts.bids,
+                asks: &aaob_accounts.asks,
+                trade_log: &trade_log_account.pubkey(),
+            },
+            migrate_market::Params {},
+        )
+    };
+
+    // Only the market admin can migrate the market
+    let impostor = Keypair::new();
+    assert!(sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![migrate_market_instruction(&impostor.pubkey())],
+        vec![&impostor],
+    )
+    .await
+    .is_err());
+
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![migrate_market_instruction(&market_admin.pubkey())],
+        vec![&market_admin],
+    )
+    .await
+    .unwrap();
+
+    let market_data = prg_test_ctx
+        .banks_client
+        .get_account(market)
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+    assert_eq!(market_data.len(), DEX_STATE_LEN);
+    let market_state: &DexState = try_from_bytes(&market_data[..DEX_STATE_LEN]).unwrap();
+    assert_eq!(market_state.orderbook, aaob_accounts.market);
+    assert_eq!(market_state.trade_log, trade_log_account.pubkey());
+    // The legacy orderbook was empty, so that the migration completed at once
+    assert_eq!(market_state.legacy_orderbook, Pubkey::default());
+    let mut trade_log_data = prg_test_ctx
+        .banks_client
+        .get_account(trade_log_account.pubkey())
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+    let trade_log = TradeLog::from_buffer(&mut trade_log_data).unwrap();
+    assert_eq!(trade_log.header.market, market);
+    assert_eq!(trade_log.header.count, 0);
+
+    // A user trades on the migrated market
+    let user_owner = Keypair::new();
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![create_account(
+            &payer,
+            &user_owner.pubkey(),
+            1_000_000,
+            0,
+            &system_program::ID,
+        )],
+        vec![&user_owner],
+    )
+    .await
+    .unwrap();
+    let (user_account, _) = Pubkey::find_program_address(
+        &[&market.to_bytes(), &user_owner.pubkey().to_bytes()],
+        &dex_v4::ID,
+    );
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![initialize_account(
+            dex_v4::ID,
+            initialize_account::Accounts {
+                system_program: &system_program::ID,
+                user: &user_account,
+                user_owner: &user_owner.pubkey(),
+                fee_payer: &payer,
+            },
+            initialize_account::Params {
+                market,
+                max_orders: 10,
+                referrer: Pubkey::default(),
+            },
+        )],
+        vec![&user_owner],
+    )
+    .await
+    .unwrap();
+    let user_base_token_account =
+        create_associated_token(&mut prg_test_ctx, &base_mint, &user_owner.pubkey())
+            .await
+            .unwrap();
+    let user_quote_token_account =
+        create_associated_token(&mut prg_test_ctx, &quote_mint, &user_owner.pubkey())
+            .await
+            .unwrap();
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![mint_to(
+            &spl_token::ID,
+            &base_mint,
+            &user_base_token_account,
+            &base_mint_auth.pubkey(),
+            &[],
+            1 << 25,
+        )
+        .unwrap()],
+        vec![&base_mint_auth],
+    )
+    .await
+    .unwrap();
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![mint_to(
+            &spl_token::ID,
+            &quote_mint,
+            &user_quote_token_account,
+            &quote_mint_auth.pubkey(),
+            &[],
+            1 << 25,
+        )
+        .unwrap()],
+        vec![&quote_mint_auth],
+    )
+    .await
+    .unwrap();
+
+    for (side, limit_price, user_token_account) in [
+        (Side::Ask, 9 * 42949672, user_base_token_account),
+        (Side::Bid, 11 * 42949672, user_quote_token_account),
+    ] {
+        let new_order_instruction = new_order(
+            dex_v4::ID,
+            new_order::Accounts {
+                spl_token_program: &spl_token::ID,
+                system_program: &system_program::ID,
+                market: &market,
+                orderbook: &aaob_accounts.market,
+                event_queue: &aaob_accounts.event_queue,
+                bids: &aaob_accounts.bids,
+                asks: &aaob_accounts.asks,
+                base_vault: &base_vault,
+                quote_vault: &quote_vault,
+                market_signer: &market_signer,
+                base_mint: &base_mint,
+                quote_mint: &quote_mint,
+                user: &user_account,
+                user_token_account: &user_token_account,
+                user_owner: &user_owner.pubkey(),
+                discount_token_account: None,
+                oracle: None,
+                liquidity_metrics: None,
+                market_stats: None,
+                access_grant: None,
+                fee_referral_account: None,
+            },
+            new_order::Params {
+                #[cfg(not(any(feature = "aarch64-test", target_arch = "aarch64")))]
+                client_order_id: 0,
+                #[cfg(any(feature = "aarch64-test", target_arch = "aarch64"))]
+                client_order_id: bytemuck::cast(0u128),
+                side: side as u8,
+                limit_price,
+                max_base_qty: 1,
+                max_quote_qty: u64::MAX,
+                order_type: new_order::OrderType::Limit as u8,
+                self_trade_behavior: SelfTradeBehavior::DecrementTake as u8,
+                match_limit: 10,
+                expiry_timestamp: 0,
+                display_base_qty: 0,
+                max_taker_fee: 0,
+                min_base_to_fill: 0,
+                has_discount_token_account: false as u8,
+                reduce_only: 0,
+                wrap_native_quote: 0,
+                has_oracle_account: 0,
+                has_liquidity_metrics_account: 0,
+                has_market_stats_account: 0,
+                size_in_quote: 0,
+                has_access_grant_account: 0,
+                _padding: [0; 5],
+            },
+        );
+        sign_send_instructions(
+            &mut prg_test_ctx,
+            vec![new_order_instruction],
+            vec![&user_owner],
+        )
+        .await
+        .unwrap();
+    }
+
+    // The events of the migrated market can be consumed, which fails without a trade log
+    let reward_target = Keypair::new();
+    let consume_events_instruction = consume_events(
+        dex_v4::ID,
+        consume_events::Accounts {
+            market: &market,
+            orderbook: &aaob_accounts.market,
+            event_queue: &aaob_accounts.event_queue,
+            bids: &aaob_accounts.bids,
+            asks: &aaob_accounts.asks,
+            trade_log: &trade_log_account.pubkey(),
+            reward_target: &reward_target.pubkey(),
+            liquidity_metrics: None,
+            market_stats: None,
+            user_accounts: &[user_account],
+        },
+        consume_events::Params {
+            max_iterations: 10,
+            no_op_err: 1,
+            has_liquidity_metrics_account: 0,
+            has_market_stats_account: 0,
+        },
+    );
+    sign_send_instructions(&mut prg_test_ctx, vec![consume_events_instruction], vec![])
+        .await
+        .unwrap();
+
+    // The market is already up to date
+    assert!(sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![migrate_market_instruction(&market_admin.pubkey())],
+        vec![&market_admin],
+    )
+    .await
+    .is_err());
+}
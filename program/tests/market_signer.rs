@@ -0,0 +1,24 @@
+use dex_v4::state::{find_market_signer, market_signer};
+use solana_program::pubkey::Pubkey;
+
+#[test]
+fn test_market_signer_derivation() {
+    for _ in 0..100 {
+        let market = Pubkey::new_unique();
+        let (signer, signer_nonce) = find_market_signer(&market, &dex_v4::ID);
+
+        // The nonce stored by create_market rederives the same signer
+        assert_eq!(
+            market_signer(&market, signer_nonce, &dex_v4::ID).unwrap(),
+            signer
+        );
+        assert_eq!(
+            Pubkey::find_program_address(&[&market.to_bytes()], &dex_v4::ID),
+            (signer, signer_nonce)
+        );
+        assert_ne!(
+            find_market_signer(&Pubkey::new_unique(), &dex_v4::ID).0,
+            signer
+        );
+    }
+}
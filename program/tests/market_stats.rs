@@ -0,0 +1,53 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::Zeroable;
+use dex_v4::state::{AccountTag, DexState, MarketStats, MARKET_STATS_LEN};
+use solana_program::pubkey::Pubkey;
+
+#[test]
+fn test_market_stats_encoding() {
+    let stats = MarketStats {
+        tag: AccountTag::MarketStats as u64,
+        market: Pubkey::new_unique(),
+        base_volume: 1,
+        quote_volume: 2,
+        last_trade_price: 3,
+        last_trade_timestamp: 4,
+        best_bid_price: 5,
+        best_ask_price: u64::MAX,
+        total_bid_base: 7,
+        total_ask_base: 8,
+        last_update_timestamp: -9,
+    };
+    let data = stats.try_to_vec().unwrap();
+    assert_eq!(data.len(), MARKET_STATS_LEN);
+    assert_eq!(MarketStats::try_from_slice(&data).unwrap(), stats);
+}
+
+#[test]
+fn test_twap_accumulator() {
+    let mut dex_state = DexState::zeroed();
+    dex_state.best_ask_price = u64::MAX;
+
+    // Time during which a side of the book is empty isn't accumulated
+    dex_state.accumulate_twap(10);
+    assert_eq!(dex_state.twap_price_cumulative(), 0);
+    assert_eq!(dex_state.twap_last_update, 10);
+
+    dex_state.best_bid_price = 3 << 32;
+    dex_state.best_ask_price = 5 << 32;
+    dex_state.accumulate_twap(20);
+    assert_eq!(dex_state.twap_price_cumulative(), 10 * (4 << 32));
+
+    // The accumulator carries over into its high word
+    dex_state.twap_price_cumulative = [u64::MAX, 0];
+    dex_state.accumulate_twap(21);
+    assert_eq!(
+        dex_state.twap_price_cumulative(),
+        u64::MAX as u128 + (4 << 32)
+    );
+    assert_eq!(dex_state.twap_price_cumulative, [(4 << 32) - 1, 1]);
+
+    // Going back in time never decreases the accumulator
+    dex_state.accumulate_twap(15);
+    assert_eq!(dex_state.twap_price_cumulative, [(4 << 32) - 1, 1]);
+}
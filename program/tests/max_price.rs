@@ -0,0 +1,36 @@
+use bytemuck::Zeroable;
+use dex_v4::error::DexError;
+use dex_v4::state::{DexState, MAX_PRICE};
+
+#[test]
+fn test_max_price_boundary() {
+    let mut dex_state = DexState::zeroed();
+
+    assert!(dex_state.check_max_price(MAX_PRICE).is_ok());
+    assert!(matches!(
+        dex_state.check_max_price(MAX_PRICE + 1),
+        Err(DexError::PriceTooLarge)
+    ));
+    assert!(matches!(
+        dex_state.check_max_price(u64::MAX),
+        Err(DexError::PriceTooLarge)
+    ));
+
+    // A market may lower the bound, but never raise it
+    dex_state.max_price = 1 << 40;
+    assert!(dex_state.check_max_price(1 << 40).is_ok());
+    assert!(matches!(
+        dex_state.check_max_price((1 << 40) + 1),
+        Err(DexError::PriceTooLarge)
+    ));
+    dex_state.max_price = u64::MAX;
+    assert_eq!(dex_state.effective_max_price(), MAX_PRICE);
+}
+
+#[test]
+fn test_max_price_quote_fits() {
+    // The quote value of any quantity below 2^40 lots at the maximum price fits in a u64
+    let max_base_qty = (1u128 << 40) - 1;
+    assert!((max_base_qty * MAX_PRICE as u128) >> 32 <= u64::MAX as u128);
+    assert!(((max_base_qty + 1) * (MAX_PRICE as u128 + 1)) >> 32 > u64::MAX as u128);
+}
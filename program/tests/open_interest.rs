@@ -0,0 +1,56 @@
+use asset_agnostic_orderbook::state::Side;
+use bytemuck::Zeroable;
+use dex_v4::state::DexState;
+use rand::Rng;
+
+#[test]
+fn test_resting_base_matches_book() {
+    let mut rng = rand::thread_rng();
+    let mut dex_state = DexState::zeroed();
+    // The quantities resting on (bids, asks), one entry per order
+    let mut book: (Vec<u64>, Vec<u64>) = (Vec::new(), Vec::new());
+
+    for _ in 0..100_000 {
+        let side = if rng.gen() { Side::Bid } else { Side::Ask };
+        let orders = match side {
+            Side::Bid => &mut book.0,
+            Side::Ask => &mut book.1,
+        };
+        match rng.gen_range(0..4) {
+            // Posting, possibly after a partial match
+            0 | 1 => {
+                let base_qty = rng.gen_range(1..1_000_000);
+                orders.push(base_qty);
+                dex_state.add_resting_base(side, base_qty);
+            }
+            // Partial fill, or reduction of the order size
+            2 if !orders.is_empty() => {
+                let i = rng.gen_range(0..orders.len());
+                let base_qty = rng.gen_range(0..orders[i]);
+                orders[i] -= base_qty;
+                dex_state.remove_resting_base(side, base_qty);
+            }
+            // Complete fill or cancellation, including self-trade cancels
+            _ if !orders.is_empty() => {
+                let base_qty = orders.swap_remove(rng.gen_range(0..orders.len()));
+                dex_state.remove_resting_base(side, base_qty);
+            }
+            _ => {}
+        }
+        assert_eq!(dex_state.total_bid_base, book.0.iter().sum::<u64>());
+        assert_eq!(dex_state.total_ask_base, book.1.iter().sum::<u64>());
+    }
+}
+
+#[test]
+fn test_resting_base_saturates() {
+    let mut dex_state = DexState::zeroed();
+    dex_state.add_resting_base(Side::Bid, 10);
+    dex_state.remove_resting_base(Side::Bid, 11);
+    assert_eq!(dex_state.total_bid_base, 0);
+
+    dex_state.add_resting_base(Side::Ask, u64::MAX);
+    dex_state.add_resting_base(Side::Ask, 1);
+    assert_eq!(dex_state.total_ask_base, u64::MAX);
+    assert_eq!(dex_state.total_bid_base, 0);
+}
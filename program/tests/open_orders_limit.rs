@@ -0,0 +1,25 @@
+use bytemuck::Zeroable;
+use dex_v4::error::DexError;
+use dex_v4::state::DexState;
+
+#[test]
+fn test_open_orders_limit() {
+    let mut dex_state = DexState::zeroed();
+
+    // Markets are unlimited by default
+    dex_state.check_open_orders_limit(0).unwrap();
+    dex_state.check_open_orders_limit(u32::MAX).unwrap();
+
+    dex_state.max_orders_per_account = 2;
+    dex_state.check_open_orders_limit(0).unwrap();
+    dex_state.check_open_orders_limit(1).unwrap();
+    assert!(matches!(
+        dex_state.check_open_orders_limit(2),
+        Err(DexError::TooManyOrders)
+    ));
+    // Accounts above a lowered limit can't post until they are back below it
+    assert!(matches!(
+        dex_state.check_open_orders_limit(5),
+        Err(DexError::TooManyOrders)
+    ));
+}
@@ -0,0 +1,161 @@
+use asset_agnostic_orderbook::state::Side;
+use dex_v4::instruction_auto::new_order;
+use dex_v4::instruction_auto::register_referrer;
+use dex_v4::state::{market_signer, FeeTier};
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_program;
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::signature::Signer;
+pub mod common;
+use crate::common::performance_test_utils::{aob_dex_new_order, create_aob_dex, AobDexTestContext};
+use crate::common::utils::sign_send_instructions;
+
+const PRICE: u64 = 1 << 32;
+const BASE_QTY: u64 = 10_000_000;
+
+const MAKER: usize = 0;
+const TAKER: usize = 1;
+const REFERRER: usize = 2;
+
+async fn token_balance(prg_test_ctx: &mut ProgramTestContext, token_account: Pubkey) -> u64 {
+    let account = prg_test_ctx
+        .banks_client
+        .get_account(token_account)
+        .await
+        .unwrap()
+        .unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
+fn referred_bid(
+    dex_test_ctx: &AobDexTestContext,
+    fee_referral_account: Option<&Pubkey>,
+) -> solana_program::instruction::Instruction {
+    let market_signer = market_signer(
+        &dex_test_ctx.dex_market_key,
+        dex_test_ctx.dex_market.signer_nonce,
+        &dex_v4::ID,
+    )
+    .unwrap();
+    new_order(
+        dex_v4::ID,
+        new_order::Accounts {
+            spl_token_program: &spl_token::ID,
+            system_program: &system_program::ID,
+            market: &dex_test_ctx.dex_market_key,
+            orderbook: &dex_test_ctx.dex_market.orderbook,
+            event_queue: &dex_test_ctx.aob_market.event_queue,
+            bids: &dex_test_ctx.aob_market.bids,
+            asks: &dex_test_ctx.aob_market.asks,
+            base_vault: &dex_test_ctx.dex_market.base_vault,
+            quote_vault: &dex_test_ctx.dex_market.quote_vault,
+            market_signer: &market_signer,
+            base_mint: &dex_test_ctx.dex_market.base_mint,
+            quote_mint: &dex_test_ctx.dex_market.quote_mint,
+            user: &dex_test_ctx.user_account_keys[TAKER],
+            user_token_account: &dex_test_ctx.user_quotes[TAKER],
+            user_owner: &dex_test_ctx.user_owners[TAKER].pubkey(),
+            discount_token_account: None,
+            oracle: None,
+            liquidity_metrics: None,
+            market_stats: None,
+            access_grant: None,
+            fee_referral_account,
+        },
+        new_order::Params {
+            #[cfg(not(any(feature = "aarch64-test", target_arch = "aarch64")))]
+            client_order_id: 0,
+            #[cfg(any(feature = "aarch64-test", target_arch = "aarch64"))]
+            client_order_id: bytemuck::cast(0u128),
+            side: Side::Bid as u8,
+            limit_price: PRICE,
+            max_base_qty: BASE_QTY,
+            max_quote_qty: u64::MAX,
+            order_type: new_order::OrderType::ImmediateOrCancel as u8,
+            self_trade_behavior: asset_agnostic_orderbook::state::SelfTradeBehavior::DecrementTake
+                as u8,
+            match_limit: 10,
+            expiry_timestamp: 0,
+            display_base_qty: 0,
+            max_taker_fee: 0,
+            min_base_to_fill: 0,
+            has_discount_token_account: false as u8,
+            reduce_only: 0,
+            wrap_native_quote: 0,
+            has_oracle_account: 0,
+            has_liquidity_metrics_account: 0,
+            has_market_stats_account: 0,
+            size_in_quote: 0,
+            has_access_grant_account: 0,
+            _padding: [0; 5],
+        },
+    )
+}
+
+#[tokio::test]
+async fn test_referred_order() {
+    let program_test = ProgramTest::new(
+        "dex_v4",
+        dex_v4::ID,
+        processor!(dex_v4::entrypoint::process_instruction),
+    );
+    let (dex_test_ctx, mut prg_test_ctx) = create_aob_dex(program_test).await;
+
+    let referrer = dex_test_ctx.user_owners[REFERRER].pubkey();
+    let referral_account = dex_test_ctx.user_quotes[REFERRER];
+    let register_referrer_instruction = register_referrer(
+        dex_v4::ID,
+        register_referrer::Accounts {
+            user: &dex_test_ctx.user_account_keys[TAKER],
+            user_owner: &dex_test_ctx.user_owners[TAKER].pubkey(),
+        },
+        register_referrer::Params { referrer },
+    );
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![register_referrer_instruction],
+        vec![&dex_test_ctx.user_owners[TAKER]],
+    )
+    .await
+    .unwrap();
+
+    aob_dex_new_order(
+        &mut prg_test_ctx,
+        &dex_test_ctx,
+        Side::Ask,
+        PRICE,
+        BASE_QTY,
+        u64::MAX,
+        MAKER,
+    )
+    .await;
+
+    // A referred user account can't take liquidity without paying its referrer
+    assert!(sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![referred_bid(&dex_test_ctx, None)],
+        vec![&dex_test_ctx.user_owners[TAKER]],
+    )
+    .await
+    .is_err());
+
+    // The referral fee is paid out of the quote vault, which only the market signer can authorize
+    let balance_before = token_balance(&mut prg_test_ctx, referral_account).await;
+    sign_send_instructions(
+        &mut prg_test_ctx,
+        vec![referred_bid(&dex_test_ctx, Some(&referral_account))],
+        vec![&dex_test_ctx.user_owners[TAKER]],
+    )
+    .await
+    .unwrap();
+    let balance_after = token_balance(&mut prg_test_ctx, referral_account).await;
+
+    let expected_referral_fee = FeeTier::Base
+        .referral_fee(&dex_test_ctx.dex_market, BASE_QTY)
+        .unwrap();
+    assert_ne!(expected_referral_fee, 0);
+    assert_eq!(balance_after - balance_before, expected_referral_fee);
+}
@@ -0,0 +1,57 @@
+use bytemuck::Zeroable;
+use dex_v4::instruction_auto::new_order::SelfTradeBehavior;
+use dex_v4::state::{DexState, FeeTier};
+
+#[test]
+fn test_self_trade_fee_per_behavior() {
+    let mut dex_state = DexState::zeroed();
+    // A 10% self-trade fee, which isn't exactly representable as an FP32
+    dex_state.self_trade_fee_rate = (1 << 32) / 10;
+    let self_matched_quote_qty = 1_000_000;
+
+    // Only the self-matched quantity which counts as filled is charged, rounded down
+    assert_eq!(
+        SelfTradeBehavior::DecrementTake
+            .self_trade_fee(&dex_state, self_matched_quote_qty)
+            .unwrap(),
+        99_999
+    );
+    for behavior in [
+        SelfTradeBehavior::CancelProvide,
+        SelfTradeBehavior::AbortTransaction,
+        SelfTradeBehavior::CancelBoth,
+    ] {
+        assert_eq!(
+            behavior
+                .self_trade_fee(&dex_state, self_matched_quote_qty)
+                .unwrap(),
+            0
+        );
+    }
+
+    // Markets without a self-trade fee never charge one
+    dex_state.self_trade_fee_rate = 0;
+    assert_eq!(
+        SelfTradeBehavior::DecrementTake
+            .self_trade_fee(&dex_state, self_matched_quote_qty)
+            .unwrap(),
+        0
+    );
+}
+
+#[test]
+fn test_taker_fee_after_cancelled_self_match() {
+    let mut dex_state = DexState::zeroed();
+    dex_state.self_trade_fee_rate = (1 << 32) / 10;
+
+    // A bid cancels its own resting ask of 1 000 000 quote then fills 2 000 000 quote against another maker. Only the
+    // fill against the other maker pays the default 0.04% taker fee
+    let other_makers_quote_qty = 2_000_000;
+    let fees = FeeTier::Base
+        .taker_fee(&dex_state, other_makers_quote_qty)
+        .unwrap()
+        + SelfTradeBehavior::CancelProvide
+            .self_trade_fee(&dex_state, 1_000_000)
+            .unwrap();
+    assert_eq!(fees, 799);
+}
@@ -0,0 +1,35 @@
+use bytemuck::{cast_slice_mut, Zeroable};
+use dex_v4::state::{DexState, FeeTier, Order, UserAccount, USER_ACCOUNT_HEADER_LEN};
+use dex_v4::time::{FixedTime, TimeProvider};
+
+fn heartbeat_expired(user_account: &UserAccount, clock: &impl TimeProvider) -> bool {
+    user_account
+        .header
+        .is_heartbeat_expired(clock.now().unwrap())
+}
+
+#[test]
+fn test_fixed_time() {
+    let clock = FixedTime(1_650_000_000);
+    assert_eq!(clock.now().unwrap(), 1_650_000_000);
+    assert_eq!(clock.now().unwrap(), clock.now().unwrap());
+
+    let mut words = vec![0u64; (USER_ACCOUNT_HEADER_LEN + Order::LEN) / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+    let mut user_account = UserAccount::from_buffer_unchecked(buf).unwrap();
+    user_account.header.heartbeat_expiry = 1_650_000_060;
+    assert!(!heartbeat_expired(&user_account, &clock));
+    assert!(heartbeat_expired(&user_account, &FixedTime(1_650_000_061)));
+
+    let mut dex_state = DexState::zeroed();
+    dex_state.rebate_boost_rate = 1 << 31;
+    dex_state.rebate_boost_end = 1_650_000_001;
+    dex_state.accumulated_fees = u64::MAX;
+    let boost = |clock: FixedTime| {
+        FeeTier::Base
+            .maker_rebate_boost(&dex_state, 100, clock.now().unwrap())
+            .unwrap()
+    };
+    assert_eq!(boost(clock), 50);
+    assert_eq!(boost(FixedTime(1_650_000_001)), 0);
+}
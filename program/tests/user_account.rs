@@ -0,0 +1,299 @@
+use bytemuck::{cast_slice, cast_slice_mut, try_from_bytes_mut};
+use dex_v4::error::DexError;
+use dex_v4::instruction_auto::create_account::user_account_size;
+use dex_v4::state::AccountTag;
+use dex_v4::state::LegacyUserAccountHeader;
+use dex_v4::state::Order;
+use dex_v4::state::UserAccount;
+use dex_v4::state::LEGACY_USER_ACCOUNT_HEADER_LEN;
+use dex_v4::state::MAX_ORDERS_PER_ACCOUNT;
+use dex_v4::state::MIN_HEARTBEAT_INTERVAL;
+use dex_v4::state::USER_ACCOUNT_HEADER_LEN;
+use solana_program::pubkey::Pubkey;
+pub mod common;
+use crate::common::utils::AccountBuffer;
+
+const MAX_ORDERS: usize = 4;
+
+#[test]
+fn test_user_account_capacity() {
+    let mut buffer = AccountBuffer::for_user_account(MAX_ORDERS);
+    let mut user_account = buffer.user_account();
+
+    assert_eq!(user_account.capacity(), MAX_ORDERS);
+    assert_eq!(user_account.remaining_slots(), MAX_ORDERS);
+
+    for i in 0..MAX_ORDERS {
+        user_account
+            .add_order(Order {
+                id: i as u128,
+                client_id: 0,
+            })
+            .unwrap();
+        assert_eq!(user_account.remaining_slots(), MAX_ORDERS - i - 1);
+    }
+    assert_eq!(user_account.capacity(), MAX_ORDERS);
+
+    let res = user_account.add_order(Order {
+        id: MAX_ORDERS as u128,
+        client_id: 0,
+    });
+    assert!(matches!(res, Err(DexError::UserAccountFull)));
+    assert_eq!(user_account.header.number_of_orders as usize, MAX_ORDERS);
+    assert_eq!(user_account.remaining_slots(), 0);
+
+    assert_eq!(user_account.remove_order(0).unwrap(), 0);
+    assert_eq!(user_account.remaining_slots(), 1);
+}
+
+#[test]
+fn test_user_account_max_orders() {
+    // The account data has room for one order past the maximum
+    let mut words =
+        vec![0u64; (USER_ACCOUNT_HEADER_LEN + (MAX_ORDERS_PER_ACCOUNT + 1) * Order::LEN) / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+    let mut user_account = UserAccount::from_buffer_unchecked(buf).unwrap();
+    user_account.header.tag = AccountTag::UserAccount as u64;
+
+    assert_eq!(user_account.capacity(), MAX_ORDERS_PER_ACCOUNT);
+
+    for i in 0..MAX_ORDERS_PER_ACCOUNT {
+        user_account
+            .add_order(Order {
+                id: i as u128,
+                client_id: 0,
+            })
+            .unwrap();
+    }
+    assert_eq!(user_account.remaining_slots(), 0);
+
+    let res = user_account.add_order(Order {
+        id: MAX_ORDERS_PER_ACCOUNT as u128,
+        client_id: 0,
+    });
+    assert!(matches!(res, Err(DexError::UserAccountFull)));
+    assert_eq!(
+        user_account.header.number_of_orders as usize,
+        MAX_ORDERS_PER_ACCOUNT
+    );
+}
+
+#[test]
+fn test_user_account_duplicate_order() {
+    let mut words = vec![0u64; (USER_ACCOUNT_HEADER_LEN + MAX_ORDERS * Order::LEN) / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+    let mut user_account = UserAccount::from_buffer_unchecked(buf).unwrap();
+    user_account.header.tag = AccountTag::UserAccount as u64;
+
+    user_account
+        .add_order(Order {
+            id: 1,
+            client_id: 0,
+        })
+        .unwrap();
+    user_account
+        .add_order(Order {
+            id: 2,
+            client_id: 0,
+        })
+        .unwrap();
+
+    // Storing the same id twice would leave the second copy orphaned once the first one is cancelled
+    let res = user_account.add_order(Order {
+        id: 1,
+        client_id: 3,
+    });
+    assert!(matches!(res, Err(DexError::DuplicateOrder)));
+    assert_eq!(user_account.header.number_of_orders, 2);
+
+    let index = user_account.find_order_index(1).unwrap();
+    user_account.remove_order(index).unwrap();
+    assert!(matches!(
+        user_account.find_order_index(1),
+        Err(DexError::OrderNotFound)
+    ));
+
+    // The id is free again once the order is removed
+    user_account
+        .add_order(Order {
+            id: 1,
+            client_id: 0,
+        })
+        .unwrap();
+    assert_eq!(user_account.header.number_of_orders, 2);
+}
+
+#[test]
+fn test_user_account_size() {
+    assert_eq!(
+        user_account_size(MAX_ORDERS as u32),
+        USER_ACCOUNT_HEADER_LEN + MAX_ORDERS * Order::LEN
+    );
+
+    let mut words = vec![0u64; user_account_size(MAX_ORDERS as u32) / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+    let user_account = UserAccount::from_buffer_unchecked(buf).unwrap();
+    assert_eq!(user_account.capacity(), MAX_ORDERS);
+}
+
+#[test]
+fn test_user_account_bid_price_improvement() {
+    let mut words = vec![0u64; (USER_ACCOUNT_HEADER_LEN + MAX_ORDERS * Order::LEN) / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+    let mut user_account = UserAccount::from_buffer_unchecked(buf).unwrap();
+
+    // A bid for 100 base tokens with a limit price of 11 fully matches an ask resting at 9
+    let limit_quote_qty = 100 * 11;
+    let matched_quote_qty = 100 * 9;
+    user_account.header.quote_token_free = limit_quote_qty;
+
+    let deposit_qty = user_account.header.debit_bid_quote(matched_quote_qty, 0);
+    assert_eq!(deposit_qty, 0);
+    assert_eq!(
+        user_account.header.quote_token_free,
+        limit_quote_qty - matched_quote_qty
+    );
+    assert_eq!(user_account.header.quote_token_locked, 0);
+
+    // Without a free balance, only the quote at the maker's price is deposited
+    user_account.header.quote_token_free = 0;
+    let deposit_qty = user_account.header.debit_bid_quote(matched_quote_qty, 0);
+    assert_eq!(deposit_qty, matched_quote_qty);
+    assert_eq!(user_account.header.quote_token_free, 0);
+
+    // A posted remainder is locked at the limit price
+    user_account.header.quote_token_free = 500;
+    let deposit_qty = user_account
+        .header
+        .debit_bid_quote(matched_quote_qty + 50 * 11, 50 * 11);
+    assert_eq!(deposit_qty, matched_quote_qty + 50 * 11 - 500);
+    assert_eq!(user_account.header.quote_token_free, 0);
+    assert_eq!(user_account.header.quote_token_locked, 50 * 11);
+}
+
+#[test]
+fn test_user_account_heartbeat_expiry() {
+    let mut words = vec![0u64; (USER_ACCOUNT_HEADER_LEN + MAX_ORDERS * Order::LEN) / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+    let mut user_account = UserAccount::from_buffer_unchecked(buf).unwrap();
+
+    // The dead-man switch is disarmed by default
+    assert!(!user_account.header.is_heartbeat_expired(i64::MAX));
+
+    let now = 1_650_000_000;
+    user_account.header.heartbeat_expiry = now + MIN_HEARTBEAT_INTERVAL;
+    assert!(!user_account.header.is_heartbeat_expired(now));
+    assert!(!user_account
+        .header
+        .is_heartbeat_expired(now + MIN_HEARTBEAT_INTERVAL));
+    assert!(user_account
+        .header
+        .is_heartbeat_expired(now + MIN_HEARTBEAT_INTERVAL + 1));
+}
+
+#[test]
+fn test_user_account_locked_balance_underflow() {
+    let mut words = vec![0u64; (USER_ACCOUNT_HEADER_LEN + MAX_ORDERS * Order::LEN) / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+    let mut user_account = UserAccount::from_buffer_unchecked(buf).unwrap();
+
+    user_account.header.base_token_locked = 100;
+    user_account.header.quote_token_locked = 1_000;
+    user_account.header.unlock_base(40).unwrap();
+    user_account.header.unlock_quote(1_000).unwrap();
+    assert_eq!(user_account.header.base_token_locked, 60);
+    assert_eq!(user_account.header.base_token_free, 40);
+    assert_eq!(user_account.header.quote_token_locked, 0);
+    assert_eq!(user_account.header.quote_token_free, 1_000);
+
+    // An order releasing more than the tracked locked balance is reported instead of wrapping the balance
+    assert!(matches!(
+        user_account.header.unlock_base(61),
+        Err(DexError::LockedBalanceUnderflow)
+    ));
+    assert!(matches!(
+        user_account.header.unlock_quote(1),
+        Err(DexError::LockedBalanceUnderflow)
+    ));
+    assert_eq!(user_account.header.base_token_locked, 60);
+    assert_eq!(user_account.header.base_token_free, 40);
+    assert_eq!(user_account.header.quote_token_locked, 0);
+    assert_eq!(user_account.header.quote_token_free, 1_000);
+}
+
+#[test]
+fn test_user_account_legacy_migration() {
+    let orders = [
+        Order {
+            id: (1u128 << 64) | 7,
+            client_id: 42,
+        },
+        Order {
+            id: (2u128 << 64) | 9,
+            client_id: 0,
+        },
+    ];
+    let legacy_len = LEGACY_USER_ACCOUNT_HEADER_LEN + 3 * Order::LEN;
+    let migrated_len = USER_ACCOUNT_HEADER_LEN + 3 * Order::LEN;
+    let mut words = vec![0u64; migrated_len / 8];
+    let buf: &mut [u8] = cast_slice_mut(&mut words);
+
+    let market = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    {
+        let (hd, tl) = buf.split_at_mut(LEGACY_USER_ACCOUNT_HEADER_LEN);
+        let legacy_header: &mut LegacyUserAccountHeader = try_from_bytes_mut(hd).unwrap();
+        legacy_header.tag = AccountTag::UserAccount as u64;
+        legacy_header.market = market;
+        legacy_header.owner = owner;
+        legacy_header.base_token_free = 1;
+        legacy_header.base_token_locked = 2;
+        legacy_header.quote_token_free = 3;
+        legacy_header.quote_token_locked = 4;
+        legacy_header.accumulated_rebates = 5;
+        legacy_header.accumulated_maker_quote_volume = 6;
+        legacy_header.accumulated_maker_base_volume = 7;
+        legacy_header.accumulated_taker_quote_volume = 8;
+        legacy_header.accumulated_taker_base_volume = 9;
+        legacy_header.number_of_orders = orders.len() as u32;
+        tl[..2 * Order::LEN].copy_from_slice(cast_slice(&orders));
+    }
+
+    assert!(UserAccount::is_legacy_layout(&buf[..legacy_len]));
+    assert!(!UserAccount::is_legacy_layout(buf));
+    // Legacy accounts can't be loaded until they are migrated
+    assert_eq!(
+        UserAccount::from_buffer(&mut buf[..legacy_len]).err(),
+        Some(DexError::UnsupportedStateVersion.into())
+    );
+    UserAccount::migrate_legacy_buffer(buf, legacy_len).unwrap();
+
+    let user_account = UserAccount::from_buffer(buf).unwrap();
+    let header = &user_account.header;
+    assert_eq!(header.market, market);
+    assert_eq!(header.owner, owner);
+    assert_eq!(header.base_token_free, 1);
+    assert_eq!(header.base_token_locked, 2);
+    assert_eq!(header.quote_token_free, 3);
+    assert_eq!(header.quote_token_locked, 4);
+    assert_eq!(header.accumulated_rebates, 5);
+    assert_eq!(header.accumulated_maker_quote_volume, 6);
+    assert_eq!(header.accumulated_maker_base_volume, 7);
+    assert_eq!(header.accumulated_taker_quote_volume, 8);
+    assert_eq!(header.accumulated_taker_base_volume, 9);
+    assert_eq!(header.number_of_orders, 2);
+    assert_eq!(header.accumulated_fees_paid, 0);
+    assert_eq!(header.referrer, Pubkey::default());
+    assert_eq!(header.delegate, Pubkey::default());
+    assert_eq!(header.creation_timestamp, 0);
+    assert_eq!(header.heartbeat_expiry, 0);
+    assert_eq!(user_account.capacity(), 3);
+    for (i, order) in orders.iter().enumerate() {
+        let migrated_order = user_account.read_order(i).unwrap();
+        assert_eq!(migrated_order.id, order.id);
+        assert_eq!(migrated_order.client_id, order.client_id);
+    }
+
+    // The length of a migrated account no longer matches the legacy layout, so that it can't be migrated twice
+    assert!(!UserAccount::is_legacy_layout(buf));
+}
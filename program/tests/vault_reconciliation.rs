@@ -0,0 +1,51 @@
+use bytemuck::Zeroable;
+use dex_v4::error::DexError;
+use dex_v4::instruction_auto::reconcile::VaultReconciliation;
+use dex_v4::state::DexState;
+
+#[test]
+fn test_vault_flows_balance() {
+    let mut dex_state = DexState::zeroed();
+
+    // A deposit, a bid which pays out a referral fee and a settlement
+    dex_state.record_vault_inflow(1_000, 5_000).unwrap();
+    dex_state.record_vault_inflow(0, 2_200).unwrap();
+    dex_state.record_vault_outflow(0, 20);
+    dex_state.record_vault_outflow(400, 3_000);
+
+    assert_eq!(dex_state.total_base_in_vault, 600);
+    assert_eq!(dex_state.total_quote_in_vault, 4_180);
+
+    let reconciliation = VaultReconciliation::new(&dex_state, 600, 4_180);
+    assert!(reconciliation.is_balanced());
+}
+
+#[test]
+fn test_vault_discrepancy() {
+    let mut dex_state = DexState::zeroed();
+    dex_state.record_vault_inflow(1_000, 1_000).unwrap();
+
+    // Tokens sent straight to the vault and tokens missing from it
+    let reconciliation = VaultReconciliation::new(&dex_state, 1_250, 900);
+    assert!(!reconciliation.is_balanced());
+    assert_eq!(reconciliation.base_discrepancy, 250);
+    assert_eq!(reconciliation.quote_discrepancy, -100);
+
+    // Outflows saturate instead of failing, the discrepancy is reported instead
+    dex_state.record_vault_outflow(1_500, 0);
+    assert_eq!(dex_state.total_base_in_vault, 0);
+    assert_eq!(
+        VaultReconciliation::new(&dex_state, 0, 1_000).base_discrepancy,
+        0
+    );
+
+    dex_state.total_quote_in_vault = u64::MAX;
+    assert!(matches!(
+        dex_state.record_vault_inflow(0, 1),
+        Err(DexError::NumericalOverflow)
+    ));
+    assert_eq!(
+        VaultReconciliation::new(&dex_state, 0, 0).quote_discrepancy,
+        -(u64::MAX as i128)
+    );
+}